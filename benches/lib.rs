@@ -49,6 +49,28 @@ fn spawn_bench(b: &mut Bencher) {
     });
 }
 
+#[bench]
+fn spawn_batch_bench(b: &mut Bencher) {
+    may::config().set_workers(4);
+    b.iter(|| {
+        let hs = unsafe { coroutine::spawn_batch((0..1000).map(|_| || {})) };
+        for h in hs {
+            h.join().unwrap();
+        }
+    });
+}
+
+#[bench]
+fn spawn_loop_bench(b: &mut Bencher) {
+    may::config().set_workers(4);
+    b.iter(|| {
+        let hs = (0..1000).map(|_| go!(|| {})).collect::<Vec<_>>();
+        for h in hs {
+            h.join().unwrap();
+        }
+    });
+}
+
 #[bench]
 fn spawn_bench_1(b: &mut Bencher) {
     may::config().set_workers(4);
@@ -72,6 +94,19 @@ fn spawn_bench_1(b: &mut Bencher) {
     });
 }
 
+#[bench]
+fn spawn_stack_size_bench(b: &mut Bencher) {
+    may::config().set_workers(4);
+    b.iter(|| {
+        let v = (0..1000)
+            .map(|_| go!(Builder::new().stack_size(0x8000), || {}).unwrap())
+            .collect::<Vec<_>>();
+        for h in v {
+            h.join().unwrap();
+        }
+    });
+}
+
 #[bench]
 fn smoke_bench(b: &mut Bencher) {
     may::config().set_workers(4).set_pool_capacity(10000);
@@ -173,3 +208,112 @@ fn smoke_bench_3(b: &mut Bencher) {
         }
     });
 }
+
+#[cfg(unix)]
+fn response_pair() -> (may::os::unix::net::UnixStream, std::thread::JoinHandle<()>) {
+    use std::io::Read;
+
+    let (s1, mut s2) = may::os::unix::net::UnixStream::pair().unwrap();
+    let drain = std::thread::spawn(move || {
+        let mut buf = [0u8; 8192];
+        while s2.read(&mut buf).unwrap() > 0 {}
+    });
+    (s1, drain)
+}
+
+#[cfg(unix)]
+#[bench]
+fn write_vectored_bench(b: &mut Bencher) {
+    use std::io::{IoSlice, Write};
+
+    let header = [b'h'; 128];
+    let body = [b'b'; 4096];
+    let (mut s, drain) = response_pair();
+    scope(|scope| {
+        go!(scope, || {
+            b.iter(|| {
+                let bufs = [IoSlice::new(&header), IoSlice::new(&body)];
+                let n = s.write_vectored(&bufs).unwrap();
+                if n < header.len() {
+                    s.write_all(&header[n..]).unwrap();
+                    s.write_all(&body).unwrap();
+                } else {
+                    s.write_all(&body[n - header.len()..]).unwrap();
+                }
+            });
+        });
+    });
+    drop(s);
+    drain.join().unwrap();
+}
+
+#[cfg(unix)]
+#[bench]
+fn write_copy_bench(b: &mut Bencher) {
+    use std::io::Write;
+
+    let header = [b'h'; 128];
+    let body = [b'b'; 4096];
+    let (mut s, drain) = response_pair();
+    scope(|scope| {
+        go!(scope, || {
+            let mut buf = Vec::with_capacity(header.len() + body.len());
+            b.iter(|| {
+                buf.clear();
+                buf.extend_from_slice(&header);
+                buf.extend_from_slice(&body);
+                s.write_all(&buf).unwrap();
+            });
+        });
+    });
+    drop(s);
+    drain.join().unwrap();
+}
+
+// copy 4M bytes from the source to the sink through a proxy coroutine
+#[cfg(unix)]
+fn proxy_bench(b: &mut Bencher, splice: bool) {
+    use std::io::{Read, Write};
+    use may::net::{TcpListener, TcpStream};
+
+    let data = vec![b'p'; 4 * 1024 * 1024];
+    b.iter(|| {
+        let src = TcpListener::bind("127.0.0.1:0").unwrap();
+        let dst = TcpListener::bind("127.0.0.1:0").unwrap();
+        let src_addr = src.local_addr().unwrap();
+        let dst_addr = dst.local_addr().unwrap();
+        scope(|scope| {
+            go!(scope, || {
+                let mut s = TcpStream::connect(src_addr).unwrap();
+                s.write_all(&data).unwrap();
+            });
+            go!(scope, || {
+                let (mut s, _) = dst.accept().unwrap();
+                let mut buf = Vec::with_capacity(data.len());
+                s.read_to_end(&mut buf).unwrap();
+                assert_eq!(buf.len(), data.len());
+            });
+            go!(scope, || {
+                let (mut from, _) = src.accept().unwrap();
+                let mut to = TcpStream::connect(dst_addr).unwrap();
+                if splice {
+                    may::io::copy(&mut from, &mut to).unwrap();
+                } else {
+                    std::io::copy(&mut from, &mut to).unwrap();
+                }
+            });
+        });
+    });
+}
+
+#[cfg(unix)]
+#[bench]
+fn proxy_std_copy_bench(b: &mut Bencher) {
+    proxy_bench(b, false);
+}
+
+#[cfg(unix)]
+#[bench]
+fn proxy_may_copy_bench(b: &mut Bencher) {
+    proxy_bench(b, true);
+}
@@ -0,0 +1,35 @@
+#[macro_use]
+extern crate may;
+
+use std::time::Duration;
+use may::coroutine;
+
+// print the scheduler metrics in the prometheus text format
+fn print_metrics() {
+    let m = may::scheduler::metrics();
+    println!("may_workers {}", m.workers);
+    println!("may_parked_workers {}", m.parked_workers);
+    println!("may_live_coroutines {}", m.live_coroutines);
+    println!("may_spawned_coroutines_total {}", m.spawned_coroutines);
+    println!("may_finished_coroutines_total {}", m.finished_coroutines);
+    println!("may_ready_coroutines {}", m.ready_coroutines);
+    println!("may_running_coroutines {}", m.running_coroutines);
+    println!("may_suspended_coroutines {}", m.suspended_coroutines);
+    println!("may_resumes_total {}", m.resumes);
+    println!("may_io_events_total {}", m.io_events);
+    println!("may_timers {}", m.timers);
+    println!();
+}
+
+fn main() {
+    // some load that comes and goes
+    for i in 0..1000 {
+        go!(move || coroutine::sleep(Duration::from_millis(i * 3)));
+    }
+
+    // poll once a second
+    for _ in 0..4 {
+        print_metrics();
+        coroutine::sleep(Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,23 @@
+#[macro_use]
+extern crate may;
+
+use std::time::Duration;
+use may::coroutine::{self, Builder};
+use may::PanicPolicy;
+
+fn main() {
+    // fail fast, a bug in any coroutine stops the whole process
+    may::config().set_panic_policy(PanicPolicy::Abort);
+
+    let items = vec![1, 2, 3];
+    // the detached worker has an off by one bug, without the policy the
+    // panic would be lost together with the dropped join handle
+    go!(Builder::new().name("worker".to_owned()), move || {
+        for i in 0..items.len() + 1 {
+            println!("item = {}", items[i]);
+        }
+    }).unwrap();
+
+    coroutine::sleep(Duration::from_millis(100));
+    println!("not reached, the process is aborted by the worker panic");
+}
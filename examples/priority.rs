@@ -0,0 +1,46 @@
+#[macro_use]
+extern crate may;
+
+use std::time::{Duration, Instant};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use may::coroutine::{self, Builder, Priority};
+
+// measure how long a coroutine waits in the ready list before it runs
+fn latency(priority: Priority, rounds: u32) -> Duration {
+    let mut total = Duration::from_secs(0);
+    for _ in 0..rounds {
+        let start = Instant::now();
+        let h = go!(Builder::new().priority(priority), move || start.elapsed()).unwrap();
+        total += h.join().unwrap();
+    }
+    total / rounds
+}
+
+fn main() {
+    may::config().set_workers(2);
+
+    // the background load that always has something ready to run
+    let stop = Arc::new(AtomicBool::new(false));
+    let load = (0..1000)
+        .map(|_| {
+            let stop = stop.clone();
+            go!(move || while !stop.load(Ordering::Relaxed) {
+                coroutine::yield_now();
+            })
+        })
+        .collect::<Vec<_>>();
+
+    // the high priority coroutines skip the normal load. the low ready list
+    // is short here, and it's looked at first once in a while, so the low
+    // priority coroutines are delayed but not stuck behind the load
+    let rounds = 200;
+    for &p in &[Priority::High, Priority::Normal, Priority::Low] {
+        println!("{:?}: scheduling latency {:?}", p, latency(p, rounds));
+    }
+
+    stop.store(true, Ordering::Relaxed);
+    for h in load {
+        h.join().unwrap();
+    }
+}
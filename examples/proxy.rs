@@ -0,0 +1,90 @@
+#[macro_use]
+extern crate may;
+
+use std::io::{self, Read, Write};
+use may::{coroutine, cqueue};
+use may::net::{TcpListener, TcpStream};
+use may::sync::mpsc::{channel, Receiver};
+
+// copy the data that is ready on `from` to `to`, return false on EOF
+fn forward(from: &TcpStream, to: &TcpStream) -> io::Result<bool> {
+    let mut buf = [0u8; 4096];
+    let n = (&*from).read(&mut buf)?;
+    if n == 0 {
+        return Ok(false);
+    }
+    (&*to).write_all(&buf[0..n])?;
+    Ok(true)
+}
+
+// the proxy waits the readiness of the two streams and the control channel
+// in one cqueue. each arm is one select coroutine for the whole proxy, it
+// reads its stream in the bottom half and writes to a clone of the other
+// one, because the other arm keeps waiting on the stream itself
+#[cfg(unix)]
+fn proxy(client: TcpStream, upstream: TcpStream, ctrl: Receiver<&'static str>) -> io::Result<()> {
+    let (client_w, upstream_w) = (client.try_clone()?, upstream.try_clone()?);
+    cqueue::scope(|q| {
+        let (client_ready, upstream_ready) = (client.read_ready_event(), upstream.read_ready_event());
+        cqueue_add!(q, 0, _ = client_ready.wait() => if !forward(&client, &upstream_w).unwrap_or(false) {
+            break;
+        });
+        cqueue_add!(q, 1, _ = upstream_ready.wait() => if !forward(&upstream, &client_w).unwrap_or(false) {
+            break;
+        });
+        cqueue_add_oneshot!(q, 2, cmd = ctrl.recv() => println!("proxy got {:?}", cmd));
+
+        // quit on the control message, the drop of the cqueue cancels the
+        // arms of the streams
+        loop {
+            match q.poll(None) {
+                Ok(ref ev) if ev.token != 2 => {}
+                _ => return Ok(()),
+            }
+        }
+    })
+}
+
+#[cfg(not(unix))]
+fn main() {
+    println!("the stream readiness events are only supported on unix");
+}
+
+#[cfg(unix)]
+fn main() {
+    // the upstream echo server
+    let server = TcpListener::bind("127.0.0.1:0").unwrap();
+    let server_addr = server.local_addr().unwrap();
+    go!(move || {
+        let (mut stream, _) = server.accept().unwrap();
+        let mut buf = [0u8; 64];
+        loop {
+            match stream.read(&mut buf).unwrap() {
+                0 => break,
+                n => stream.write_all(&buf[0..n]).unwrap(),
+            }
+        }
+    });
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (tx, rx) = channel();
+    let h = go!(move || {
+        let (client, _) = listener.accept().unwrap();
+        let upstream = TcpStream::connect(server_addr).unwrap();
+        proxy(client, upstream, rx).unwrap();
+        println!("proxy closed");
+    });
+
+    let mut client = TcpStream::connect(addr).unwrap();
+    for msg in &["hello", "world"] {
+        client.write_all(msg.as_bytes()).unwrap();
+        let mut buf = vec![0u8; msg.len()];
+        client.read_exact(&mut buf).unwrap();
+        println!("echo: {}", String::from_utf8_lossy(&buf));
+        coroutine::sleep(::std::time::Duration::from_millis(10));
+    }
+
+    tx.send("stop").unwrap();
+    h.join().unwrap();
+}
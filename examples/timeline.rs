@@ -0,0 +1,82 @@
+#[macro_use]
+extern crate may;
+
+use std::io::{Read, Write};
+use std::time::{Duration, Instant};
+use may::coroutine::{self, Builder, CoroutineEventListener, ExitKind, ParkReason};
+use may::net::{TcpListener, TcpStream};
+
+// print each event with the time since the start
+struct Timeline {
+    start: Instant,
+}
+
+impl Timeline {
+    fn print(&self, id: usize, event: std::fmt::Arguments) {
+        let t = self.start.elapsed();
+        let ms = t.as_secs() as f64 * 1000.0 + t.subsec_nanos() as f64 / 1_000_000.0;
+        println!("{:>9.3}ms  co {:<3} {}", ms, id, event);
+    }
+}
+
+impl CoroutineEventListener for Timeline {
+    fn on_spawn(&self, id: usize, name: Option<&str>) {
+        self.print(id, format_args!("spawn {}", name.unwrap_or("<unnamed>")));
+    }
+    fn on_resume(&self, id: usize, worker: Option<usize>) {
+        match worker {
+            Some(w) => self.print(id, format_args!("resume on worker {}", w)),
+            None => self.print(id, format_args!("resume")),
+        }
+    }
+    fn on_park(&self, id: usize, reason: ParkReason) {
+        self.print(id, format_args!("park {:?}", reason));
+    }
+    fn on_exit(&self, id: usize, kind: ExitKind) {
+        self.print(id, format_args!("exit {:?}", kind));
+    }
+}
+
+fn main() {
+    may::config().set_event_listener(Timeline {
+        start: Instant::now(),
+    });
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = go!(Builder::new().name("server".to_owned()), move || {
+        // serve the two clients
+        for _ in 0..2 {
+            let (mut stream, _) = listener.accept().unwrap();
+            go!(Builder::new().name("echo".to_owned()), move || {
+                let mut buf = [0u8; 64];
+                loop {
+                    let n = stream.read(&mut buf).unwrap();
+                    if n == 0 {
+                        break;
+                    }
+                    stream.write_all(&buf[0..n]).unwrap();
+                }
+            }).unwrap();
+        }
+    }).unwrap();
+
+    let clients = (0..2)
+        .map(|i| {
+            go!(Builder::new().name(format!("client {}", i)), move || {
+                let mut stream = TcpStream::connect(addr).unwrap();
+                let mut buf = [0u8; 5];
+                stream.write_all(b"hello").unwrap();
+                stream.read_exact(&mut buf).unwrap();
+                coroutine::sleep(Duration::from_millis(10));
+            }).unwrap()
+        })
+        .collect::<Vec<_>>();
+
+    for c in clients {
+        c.join().unwrap();
+    }
+    server.join().unwrap();
+    // let the echo coroutines see the closed connections
+    coroutine::sleep(Duration::from_millis(10));
+}
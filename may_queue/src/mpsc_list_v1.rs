@@ -249,6 +249,70 @@ impl<T> Queue<T> {
         }
     }
 
+    /// remove the entry from the list and return the contained value
+    ///
+    /// unlike `Entry::remove` it also unlinks the last pushed node, only
+    /// the node that is the only one in the list is left for `pop()`.
+    /// it's only safe for the consumer that call pop()
+    pub fn remove(&self, entry: Entry<T>) -> Option<T> {
+        unsafe {
+            let node = &mut *entry.node;
+
+            // already removed or popped
+            if node.refs & !REF_COUNT_MASK == 0 || node.prev.is_null() {
+                return None;
+            }
+
+            let prev = node.prev;
+            let mut next = node.next.load(Ordering::Acquire);
+            if next.is_null() {
+                // the only node, keep the list not empty so that the
+                // consumer's view of the head doesn't change
+                if prev == *self.tail.get() {
+                    return None;
+                }
+
+                // move the head back to prev, the pushers that come later
+                // would link their nodes to prev. only the consumer reads
+                // the next of a node that is not the tail
+                (*prev).next.store(ptr::null_mut(), Ordering::Release);
+                if self.head
+                    .compare_exchange(node, prev, Ordering::AcqRel, Ordering::Acquire)
+                    .is_ok()
+                {
+                    node.refs &= REF_COUNT_MASK;
+                    let ret = node.value.take();
+                    // the entry ref is released when it's dropped
+                    node.refs -= 1;
+                    return ret;
+                }
+
+                // a node is pushed after it, wait for the link
+                (*prev).next.store(node, Ordering::Release);
+                let mut i = 0;
+                loop {
+                    next = node.next.load(Ordering::Acquire);
+                    if !next.is_null() {
+                        break;
+                    }
+                    i += 1;
+                    if i > 100 {
+                        thread::yield_now();
+                        i = 0;
+                    }
+                }
+            }
+
+            // clear the link bit
+            node.refs &= REF_COUNT_MASK;
+            (*next).prev = prev;
+            (*prev).next.store(next, Ordering::Release);
+            let ret = node.value.take();
+            node.refs -= 1;
+            ret
+        }
+    }
+
     /// Pops some data from this queue.
     pub fn pop(&self) -> Option<T> {
         unsafe {
@@ -338,6 +402,68 @@ mod tests {
         assert_eq!(q.pop(), Some(7));
     }
 
+    #[test]
+    fn test_remove() {
+        let q: Queue<usize> = Queue::new();
+        // the only node is left for pop
+        let a = q.push(1).0;
+        assert_eq!(q.remove(a), None);
+        assert_eq!(q.pop(), Some(1));
+
+        q.push(2);
+        let b = q.push(3).0;
+        let c = q.push(4).0;
+        // the last node
+        assert_eq!(q.remove(c), Some(4));
+        q.push(5);
+        // in the middle
+        assert_eq!(q.remove(b), Some(3));
+        assert_eq!(q.pop(), Some(2));
+        assert_eq!(q.pop(), Some(5));
+        assert_eq!(q.is_empty(), true);
+
+        // the popped node
+        let d = q.push(6).0;
+        q.push(7);
+        assert_eq!(q.pop(), Some(6));
+        assert_eq!(q.remove(d), None);
+        assert_eq!(q.pop(), Some(7));
+    }
+
+    #[test]
+    fn test_remove_push() {
+        let nthreads = 4;
+        let nmsgs = 10000;
+        let q = Arc::new(Queue::new());
+        let (tx, rx) = channel();
+        for _ in 0..nthreads {
+            let tx = tx.clone();
+            let q = q.clone();
+            thread::spawn(move || {
+                for i in 0..nmsgs {
+                    tx.send(q.push(i).0).unwrap();
+                }
+            });
+        }
+        drop(tx);
+
+        // remove what the pushers send, the removes of the last nodes race
+        // with the pushes
+        let mut removed = 0;
+        for e in rx.iter() {
+            if q.remove(e).is_some() {
+                removed += 1;
+            }
+        }
+        let mut left = 0;
+        while let Some(_) = q.pop() {
+            left += 1;
+        }
+        assert_eq!(removed + left, nthreads * nmsgs);
+        // only the first one that is left alone is kept
+        assert!(left <= 1);
+    }
+
     #[test]
     fn test() {
         let nthreads = 8;
@@ -0,0 +1,203 @@
+//! run the blocking code on a dedicated thread pool
+//!
+//! a coroutine that calls a blocking function parks the whole worker thread
+//! and starves the other coroutines on it. `spawn_blocking` sends the closure
+//! to the blocking pool and the result is delivered back through a channel,
+//! so only the calling coroutine waits for it
+
+use std::fmt;
+use std::thread;
+use std::collections::VecDeque;
+use std::sync::{Condvar, Mutex, Once, ONCE_INIT};
+use std::panic::{self, AssertUnwindSafe};
+
+use config::config;
+use sync::mpsc::{channel, Receiver};
+
+type Task = Box<FnOnce() + Send>;
+
+struct State {
+    tasks: VecDeque<Task>,
+    // number of the threads that wait for a task
+    idle: usize,
+    // number of the wakeups that are not consumed by the idle threads
+    notified: usize,
+    // number of the threads that are spawned
+    threads: usize,
+}
+
+struct BlockingPool {
+    state: Mutex<State>,
+    cond: Condvar,
+}
+
+static mut POOL: *const BlockingPool = 0 as *const _;
+
+#[cold]
+#[inline(never)]
+fn init_pool() {
+    let pool = Box::new(BlockingPool {
+        state: Mutex::new(State {
+            tasks: VecDeque::new(),
+            idle: 0,
+            notified: 0,
+            threads: 0,
+        }),
+        cond: Condvar::new(),
+    });
+    unsafe { POOL = Box::into_raw(pool) };
+}
+
+fn get_pool() -> &'static BlockingPool {
+    static ONCE: Once = ONCE_INIT;
+    ONCE.call_once(init_pool);
+    unsafe { &*POOL }
+}
+
+impl BlockingPool {
+    fn execute(&'static self, task: Task) {
+        let mut state = self.state.lock().unwrap();
+        state.tasks.push_back(task);
+        if state.idle > 0 {
+            state.idle -= 1;
+            state.notified += 1;
+            self.cond.notify_one();
+            return;
+        }
+
+        // all the threads are busy, grow the pool if it's not full
+        if state.threads < config().get_blocking_workers() {
+            let id = state.threads;
+            let ret = thread::Builder::new()
+                .name(format!("may-blocking-{}", id))
+                .spawn(move || self.run());
+            match ret {
+                Ok(_) => state.threads += 1,
+                Err(e) => error!("failed to spawn the blocking thread: {}", e),
+            }
+        }
+    }
+
+    fn run(&self) {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            match state.tasks.pop_front() {
+                Some(task) => {
+                    drop(state);
+                    task();
+                    state = self.state.lock().unwrap();
+                }
+                None => {
+                    // the idle count is decreased by the notifier
+                    state.idle += 1;
+                    while state.notified == 0 {
+                        state = self.cond.wait(state).unwrap();
+                    }
+                    state.notified -= 1;
+                }
+            }
+        }
+    }
+}
+
+/// A handle to the result of the closure passed to `spawn_blocking`
+pub struct BlockingJoinHandle<T> {
+    rx: Receiver<thread::Result<T>>,
+}
+
+impl<T> BlockingJoinHandle<T> {
+    /// wait for the closure to finish, returning the result it produced
+    ///
+    /// only the current coroutine is blocked, a panic in the closure is
+    /// returned as the `Err`
+    pub fn join(self) -> thread::Result<T> {
+        self.rx.recv().expect("the blocking task is lost")
+    }
+}
+
+impl<T> fmt::Debug for BlockingJoinHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("BlockingJoinHandle { .. }")
+    }
+}
+
+/// run the blocking closure on the blocking thread pool
+///
+/// the pool starts with no thread and grows up to the number set by
+/// `config().set_blocking_workers()`, when all the threads are busy
+/// the closures are queued
+///
+/// # Examples
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate may;
+///
+/// use std::thread;
+/// use std::time::Duration;
+/// use may::coroutine;
+///
+/// fn main() {
+///     let h = go!(|| {
+///         let task = coroutine::spawn_blocking(|| {
+///             // the blocking call doesn't hold the worker thread
+///             thread::sleep(Duration::from_millis(10));
+///             42
+///         });
+///         task.join().unwrap()
+///     });
+///     assert_eq!(h.join().unwrap(), 42);
+/// }
+/// ```
+pub fn spawn_blocking<F, T>(f: F) -> BlockingJoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let (tx, rx) = channel();
+    get_pool().execute(Box::new(move || {
+        let ret = panic::catch_unwind(AssertUnwindSafe(f));
+        // the handle may be already dropped
+        tx.send(ret).ok();
+    }));
+    BlockingJoinHandle { rx: rx }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn not_block_workers() {
+        // the workers keep running the other coroutines
+        let start = Instant::now();
+        let handles = (0..4)
+            .map(|_| {
+                go!(|| {
+                    spawn_blocking(|| thread::sleep(Duration::from_millis(200)))
+                        .join()
+                        .unwrap()
+                })
+            })
+            .collect::<Vec<_>>();
+        go!(|| ()).join().unwrap();
+        assert!(start.elapsed() < Duration::from_millis(200));
+        for h in handles {
+            h.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn panic_in_task() {
+        let h = go!(|| spawn_blocking(|| panic!("blocking panic")).join());
+        let err = h.join().unwrap().unwrap_err();
+        assert_eq!(err.downcast_ref::<&str>(), Some(&"blocking panic"));
+    }
+
+    #[test]
+    fn join_in_thread() {
+        let ret = spawn_blocking(|| 1 + 1).join().unwrap();
+        assert_eq!(ret, 2);
+    }
+}
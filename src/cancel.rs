@@ -1,13 +1,15 @@
-use std::io;
+use std::{error, fmt, io};
+use std::any::Any;
 use std::thread;
 use std::sync::Arc;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use generator::Error;
 use sync::AtomicOption;
 use yield_now::set_co_para;
 use io::cancel::CancelIoImpl;
 use scheduler::get_scheduler;
-use coroutine_impl::CoroutineImpl;
+use coroutine_impl::{current_cancel_data, is_coroutine, CoroutineImpl};
 
 // the cancel is implemented by triggering a Cancel panic
 // if drop is called due to a Cancel panic, it's not safe
@@ -27,11 +29,180 @@ pub fn trigger_cancel_panic() -> ! {
     panic!(Error::Cancel);
 }
 
+/// The error payload of an operation that is interrupted by a cancel
+///
+/// it's wrapped in an `io::Error` of `ErrorKind::Other` kind, use
+/// `is_cancel_err` to tell it from the other errors, a timeout is always
+/// reported as `ErrorKind::TimedOut` instead
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CancelError;
+
+impl fmt::Display for CancelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "coroutine canceled")
+    }
+}
+
+impl error::Error for CancelError {
+    fn description(&self) -> &str {
+        "coroutine canceled"
+    }
+}
+
+/// the io error returned to the canceled operation
+pub(crate) fn cancel_err() -> io::Error {
+    io::Error::new(io::ErrorKind::Other, CancelError)
+}
+
+/// return true if the error is caused by a coroutine cancel
+///
+/// # Examples
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate may;
+///
+/// use std::io::Read;
+/// use may::coroutine;
+/// use may::net::{TcpListener, TcpStream};
+///
+/// fn main() {
+///     let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+///     let addr = listener.local_addr().unwrap();
+///     let h = go!(move || {
+///         let mut s = TcpStream::connect(addr).unwrap();
+///         let mut buf = [0u8; 4];
+///         // the peer sends nothing, the read is interrupted by the cancel
+///         let e = s.read(&mut buf).unwrap_err();
+///         coroutine::is_cancel_err(&e)
+///     });
+///     let _s = listener.accept().unwrap();
+///     coroutine::sleep(std::time::Duration::from_millis(20));
+///     unsafe { h.coroutine().cancel() };
+///     assert!(h.join().unwrap());
+/// }
+/// ```
+pub fn is_cancel_err(e: &io::Error) -> bool {
+    e.get_ref()
+        .map_or(false, |e| e.downcast_ref::<CancelError>().is_some())
+}
+
+/// return true if the panic payload returned by `JoinHandle::join` is
+/// caused by a coroutine cancel rather than a panic in the coroutine
+///
+/// an unwind after a canceled io returned the `CancelError` counts as the
+/// cancel, e.g. the panic of unwrapping that error. a panic before the
+/// coroutine sees the cancel is still a panic
+///
+/// # Examples
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate may;
+///
+/// use may::coroutine;
+///
+/// fn main() {
+///     let h = go!(|| coroutine::park());
+///     unsafe { h.coroutine().cancel() };
+///     assert!(coroutine::is_cancel_panic(&*h.join().unwrap_err()));
+///
+///     let h = go!(|| panic!("boom"));
+///     let e = h.join().unwrap_err();
+///     assert!(!coroutine::is_cancel_panic(&*e));
+///     assert_eq!(e.downcast_ref::<&str>(), Some(&"boom"));
+/// }
+/// ```
+pub fn is_cancel_panic(panic: &(Any + Send)) -> bool {
+    match panic.downcast_ref::<Error>() {
+        Some(&Error::Cancel) => true,
+        _ => false,
+    }
+}
+
+/// A guard that defers the cancel of the current coroutine while it's held
+///
+/// a cancel that comes while the guard is alive doesn't interrupt the
+/// blocking calls in the region, it's delivered at the next cancellation
+/// point after the last guard is dropped. the guards could be nested, and
+/// it does nothing in thread context
+///
+/// # Examples
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate may;
+///
+/// use std::time::Duration;
+/// use may::coroutine::{self, CancelGuard};
+///
+/// fn main() {
+///     let h = go!(|| {
+///         {
+///             let _guard = CancelGuard::new();
+///             // the sleep is not interrupted by the cancel
+///             coroutine::sleep(Duration::from_millis(50));
+///         }
+///         // the cancel is delivered here
+///         coroutine::sleep(Duration::from_secs(100));
+///     });
+///     coroutine::sleep(Duration::from_millis(10));
+///     unsafe { h.coroutine().cancel() };
+///     assert!(coroutine::is_cancel_panic(&*h.join().unwrap_err()));
+/// }
+/// ```
+pub struct CancelGuard {
+    cancel: Option<&'static Cancel>,
+    // the guard belongs to the current coroutine
+    _marker: PhantomData<*const ()>,
+}
+
+impl CancelGuard {
+    /// defer the cancel of the current coroutine until the guard is dropped
+    pub fn new() -> CancelGuard {
+        let cancel = if is_coroutine() {
+            let cancel = current_cancel_data();
+            cancel.disable_cancel();
+            Some(cancel)
+        } else {
+            None
+        };
+        CancelGuard {
+            cancel: cancel,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl Drop for CancelGuard {
+    fn drop(&mut self) {
+        self.cancel.map(|c| c.enable_cancel());
+    }
+}
+
+impl fmt::Debug for CancelGuard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("CancelGuard { .. }")
+    }
+}
+
+/// a cancellation point for the code that never blocks
+///
+/// the current coroutine exits by a cancel panic here if it's canceled and
+/// the cancel is not deferred by a `CancelGuard`. it does nothing in thread
+/// context
+pub fn check_cancel() {
+    if is_coroutine() {
+        current_cancel_data().check_cancel();
+    }
+}
+
 pub trait CancelIo {
     type Data;
     fn new() -> Self;
     fn set(&self, Self::Data);
     fn clear(&self);
+    fn is_set(&self) -> bool;
     unsafe fn cancel(&self);
 }
 
@@ -40,6 +211,8 @@ pub struct CancelImpl<T: CancelIo> {
     // first bit is used when need to cancel the coroutine
     // higher bits are used to disable the cancel
     state: AtomicUsize,
+    // the cancel is already returned as an io error
+    io_reported: AtomicBool,
     // the io data when the coroutine is suspended
     io: T,
     // other suspended type would register the co itself
@@ -53,6 +226,7 @@ impl<T: CancelIo> CancelImpl<T> {
     pub fn new() -> Self {
         CancelImpl {
             state: AtomicUsize::new(0),
+            io_reported: AtomicBool::new(false),
             io: T::new(),
             co: AtomicOption::none(),
         }
@@ -85,15 +259,33 @@ impl<T: CancelIo> CancelImpl<T> {
         }
     }
 
+    // return true if the cancel is returned as the error of an io
+    pub fn is_io_reported(&self) -> bool {
+        self.is_canceled() && self.io_reported.load(Ordering::Acquire)
+    }
+
+    // the io operation that is interrupted by the cancel returns the
+    // cancel error, the following blocking calls panic as usual so that
+    // the coroutine could still exit if the error is ignored
+    pub fn check_cancel_io(&self) {
+        if self.is_canceled() && self.io_reported.swap(true, Ordering::AcqRel) {
+            trigger_cancel_panic();
+        }
+    }
+
     // async cancel for a coroutine
     pub unsafe fn cancel(&self) {
-        self.state.fetch_or(1, Ordering::Release);
+        if self.state.fetch_or(1, Ordering::AcqRel) >= 2 {
+            // the cancel is disabled, the coroutine is not waked and it
+            // checks the cancel at the blocking calls after enabled
+            return;
+        }
         match self.co.take(Ordering::Acquire) {
             Some(co) => {
                 co.take(Ordering::Acquire)
                     .map(|mut co| {
                         // set the cancel result for the coroutine
-                        set_co_para(&mut co, io::Error::new(io::ErrorKind::Other, "Canceled"));
+                        set_co_para(&mut co, cancel_err());
                         get_scheduler().schedule(co);
                     })
                     .unwrap_or(())
@@ -114,6 +306,11 @@ impl<T: CancelIo> CancelImpl<T> {
         self.co.swap(co, Ordering::Release);
     }
 
+    // return true if the coroutine is suspended on an io request
+    pub fn is_io_waiting(&self) -> bool {
+        self.io.is_set()
+    }
+
     // clear the cancel io data
     // should be called after io completion
     pub fn clear(&self) {
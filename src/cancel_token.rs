@@ -0,0 +1,264 @@
+use std::fmt;
+use std::mem;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use coroutine_impl::{current, current_cancel_token, is_coroutine, Coroutine};
+
+struct State {
+    // the live coroutines that are bound to the token
+    coroutines: HashMap<usize, Coroutine>,
+    // the child tokens that are cancelled together
+    children: Vec<Weak<Inner>>,
+}
+
+struct Inner {
+    cancelled: AtomicBool,
+    state: Mutex<State>,
+}
+
+/// A token that cancels a whole tree of coroutines
+///
+/// the coroutines spawned by a `Builder` with the token are bound to it, and
+/// so are all the coroutines spawned by them unless they are given another
+/// token. cancelling the token cancels all the bound coroutines that are
+/// still alive, the same way as `Coroutine::cancel`, and the cancelled ones
+/// that are blocked in a park or an io wait are waked up immediately
+///
+/// the coroutines could also check `is_cancelled` at the yield points to
+/// exit cooperatively
+///
+/// # Examples
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate may;
+///
+/// use may::coroutine::{self, Builder, CancelToken};
+///
+/// fn main() {
+///     let token = CancelToken::new();
+///     let h = go!(Builder::new().cancel_token(token.clone()), || {
+///         // the child is bound to the same token
+///         let child = go!(|| coroutine::park());
+///         child.join().ok();
+///         coroutine::park();
+///     }).unwrap();
+///
+///     coroutine::sleep(std::time::Duration::from_millis(10));
+///     unsafe { token.cancel() };
+///     assert!(h.join().is_err());
+/// }
+/// ```
+#[derive(Clone)]
+pub struct CancelToken {
+    inner: Arc<Inner>,
+}
+
+impl CancelToken {
+    /// create a new token that is not cancelled
+    pub fn new() -> CancelToken {
+        CancelToken {
+            inner: Arc::new(Inner {
+                cancelled: AtomicBool::new(false),
+                state: Mutex::new(State {
+                    coroutines: HashMap::new(),
+                    children: Vec::new(),
+                }),
+            }),
+        }
+    }
+
+    /// return the token of the current coroutine if it's bound to one
+    pub fn current() -> Option<CancelToken> {
+        if !is_coroutine() {
+            return None;
+        }
+        current_cancel_token()
+    }
+
+    /// create a child token that is cancelled when this one is cancelled,
+    /// cancelling the child doesn't affect this one
+    pub fn child_token(&self) -> CancelToken {
+        let child = CancelToken::new();
+        {
+            let mut state = self.inner.state.lock().unwrap();
+            if !self.is_cancelled() {
+                // drop the dead children before adding a new one
+                state.children.retain(|c| c.upgrade().is_some());
+                state.children.push(Arc::downgrade(&child.inner));
+                return child;
+            }
+        }
+        child.inner.cancelled.store(true, Ordering::Release);
+        child
+    }
+
+    /// return true if the token is cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::Acquire)
+    }
+
+    /// cancel all the coroutines bound to this token and its children
+    ///
+    /// the coroutines spawned with the token after this call are cancelled
+    /// immediately. the calling coroutine itself is not cancelled even if
+    /// it's bound to the token, it could check `is_cancelled` instead
+    ///
+    /// # Safety
+    ///
+    /// the same as `Coroutine::cancel`, the cancelled coroutines unwind from
+    /// where they are blocked, or get the cancel error from the blocked io
+    pub unsafe fn cancel(&self) {
+        if self.inner.cancelled.swap(true, Ordering::AcqRel) {
+            return;
+        }
+
+        let (coroutines, children) = {
+            let mut state = self.inner.state.lock().unwrap();
+            (
+                mem::replace(&mut state.coroutines, HashMap::new()),
+                mem::replace(&mut state.children, Vec::new()),
+            )
+        };
+
+        let me = if is_coroutine() {
+            Some(current().id())
+        } else {
+            None
+        };
+        for (id, co) in coroutines {
+            if Some(id) != me {
+                co.cancel();
+            }
+        }
+        for child in children {
+            if let Some(inner) = child.upgrade() {
+                CancelToken { inner: inner }.cancel();
+            }
+        }
+    }
+
+    // bind the newly spawned coroutine to the token
+    pub(crate) fn register(&self, co: &Coroutine) {
+        {
+            let mut state = self.inner.state.lock().unwrap();
+            if !self.is_cancelled() {
+                state.coroutines.insert(co.id(), co.clone());
+                return;
+            }
+        }
+        unsafe { co.cancel() };
+    }
+
+    // unbind the finished coroutine
+    pub(crate) fn unregister(&self, co: &Coroutine) {
+        let mut state = self.inner.state.lock().unwrap();
+        state.coroutines.remove(&co.id());
+    }
+}
+
+impl Default for CancelToken {
+    fn default() -> CancelToken {
+        CancelToken::new()
+    }
+}
+
+impl fmt::Debug for CancelToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CancelToken")
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Duration;
+    use sync::mpsc::channel;
+    use coroutine_impl::Builder;
+    use net::{TcpListener, TcpStream};
+
+    #[test]
+    fn cancel_tree() {
+        let token = CancelToken::new();
+        let (tx, rx) = channel();
+        let h = go!(Builder::new().cancel_token(token.clone()), move || {
+            assert!(CancelToken::current().is_some());
+            // the grand children inherit the token
+            let children = (0..4)
+                .map(|_| {
+                    let tx = tx.clone();
+                    go!(move || {
+                        let h = go!(|| ::coroutine::park());
+                        tx.send(()).unwrap();
+                        h.join().unwrap_err();
+                        ::coroutine::park();
+                    })
+                })
+                .collect::<Vec<_>>();
+            for c in children {
+                c.join().unwrap_err();
+            }
+        }).unwrap();
+
+        for _ in 0..4 {
+            rx.recv().unwrap();
+        }
+        unsafe { token.cancel() };
+        assert!(token.is_cancelled());
+        // the parent is waiting for the children, it's cancelled as well
+        assert!(h.join().is_err());
+        assert!(token.inner.state.lock().unwrap().coroutines.is_empty());
+    }
+
+    #[test]
+    fn cancel_io_wait() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let token = CancelToken::new();
+        let child = token.child_token();
+
+        let h = go!(Builder::new().cancel_token(child), move || {
+            use std::io::Read;
+            let mut s = TcpStream::connect(addr).unwrap();
+            let mut buf = [0u8; 4];
+            // nothing is sent, the read blocks until cancelled
+            s.read(&mut buf).unwrap();
+        }).unwrap();
+
+        let _s = listener.accept().unwrap();
+        thread::sleep(Duration::from_millis(20));
+        unsafe { token.cancel() };
+        assert!(h.join().is_err());
+    }
+
+    #[test]
+    fn spawn_after_cancel() {
+        let token = CancelToken::new();
+        unsafe { token.cancel() };
+        assert!(token.child_token().is_cancelled());
+
+        let h = go!(Builder::new().cancel_token(token), || {
+            ::coroutine::park();
+        }).unwrap();
+        assert!(h.join().is_err());
+    }
+
+    #[test]
+    fn cancel_in_bound_coroutine() {
+        let token = CancelToken::new();
+        let h = go!(Builder::new().cancel_token(token), || {
+            let token = CancelToken::current().unwrap();
+            let child = go!(|| ::coroutine::park());
+            unsafe { token.cancel() };
+            // the caller is not cancelled itself
+            assert!(token.is_cancelled());
+            child.join().is_err()
+        }).unwrap();
+        assert!(h.join().unwrap());
+    }
+}
@@ -0,0 +1,241 @@
+//! Interop with the `futures` 0.3 world
+//!
+//! this module is enabled by the `futures` feature. `block_on` runs a future
+//! to completion in a coroutine, and `Compat` lets the futures based code do
+//! the async io over the may streams
+
+use std::sync::Arc;
+use std::pin::Pin;
+use std::future::Future;
+use std::task::{Context, Poll};
+
+use futures::task::{waker, ArcWake};
+use sync::Blocker;
+
+// wake the blocked coroutine or thread from any thread
+struct BlockerWaker(Arc<Blocker>);
+
+impl ArcWake for BlockerWaker {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        arc_self.0.unpark();
+    }
+}
+
+/// run the future to completion
+///
+/// the current coroutine is parked while the future is pending, and it's
+/// unparked by the future's `Waker`, which could be waked from any thread.
+/// in thread context the thread is parked instead
+///
+/// # Examples
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate may;
+/// extern crate futures;
+///
+/// use may::compat::block_on;
+///
+/// fn main() {
+///     let h = go!(|| block_on(futures::future::ready(42)));
+///     assert_eq!(h.join().unwrap(), 42);
+/// }
+/// ```
+pub fn block_on<F: Future>(mut fut: F) -> F::Output {
+    let blocker = Blocker::current();
+    let waker = waker(Arc::new(BlockerWaker(blocker.clone())));
+    let mut cx = Context::from_waker(&waker);
+    // the future is never moved after pinned on the stack
+    let mut fut = unsafe { Pin::new_unchecked(&mut fut) };
+    loop {
+        if let Poll::Ready(ret) = fut.as_mut().poll(&mut cx) {
+            return ret;
+        }
+        // a wake before the park makes it return at once
+        blocker.park(None).ok();
+    }
+}
+
+#[cfg(unix)]
+pub use self::stream::Compat;
+
+#[cfg(unix)]
+mod stream {
+    use std::io::{self, Read, Write};
+    use std::net::Shutdown;
+    use std::pin::Pin;
+    use std::sync::{Arc, Mutex};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::task::{Context, Poll, Waker};
+
+    use futures::io::{AsyncRead, AsyncWrite};
+    use coroutine_impl::Coroutine;
+    use net::TcpStream;
+
+    // the waker that waits for one kind of readiness
+    struct Waiter {
+        // there is a coroutine waiting for the readiness
+        waiting: AtomicBool,
+        // the waker of the latest pending poll
+        waker: Mutex<Option<Waker>>,
+        // the waiting coroutine, cancelled when the adapter is dropped
+        co: Mutex<Option<Coroutine>>,
+    }
+
+    impl Waiter {
+        fn new() -> Arc<Waiter> {
+            Arc::new(Waiter {
+                waiting: AtomicBool::new(false),
+                waker: Mutex::new(None),
+                co: Mutex::new(None),
+            })
+        }
+
+        // register the waker and wait for the readiness in a coroutine
+        fn register<F>(self: &Arc<Self>, waker: &Waker, wait: F)
+        where
+            F: FnOnce() -> io::Result<()> + Send + 'static,
+        {
+            *self.waker.lock().unwrap() = Some(waker.clone());
+            if self.waiting.swap(true, Ordering::AcqRel) {
+                // the waiting coroutine would wake the new waker
+                return;
+            }
+
+            let me = self.clone();
+            let h = go!(move || {
+                // an error is reported by the next poll
+                wait().ok();
+                me.waiting.store(false, Ordering::Release);
+                let waker = me.waker.lock().unwrap().take();
+                waker.map(|w| w.wake());
+            });
+            *self.co.lock().unwrap() = Some(h.coroutine().clone());
+        }
+
+        fn cancel(&self) {
+            if let Some(co) = self.co.lock().unwrap().take() {
+                unsafe { co.cancel() };
+            }
+        }
+    }
+
+    /// An adapter that implements the `futures` io traits for a stream
+    ///
+    /// the stream is put in nonblocking mode, a read or write that would
+    /// block returns `Poll::Pending`, and the task is waked when the stream
+    /// becomes ready. the readiness is waited on a clone of the stream by a
+    /// coroutine, so it works under any executor without a busy loop
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// #[macro_use]
+    /// extern crate may;
+    /// extern crate futures;
+    ///
+    /// use futures::io::{AsyncReadExt, AsyncWriteExt};
+    /// use may::compat::{block_on, Compat};
+    /// use may::net::{TcpListener, TcpStream};
+    ///
+    /// fn main() {
+    ///     let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    ///     let addr = listener.local_addr().unwrap();
+    ///     go!(move || {
+    ///         let (mut s, _) = listener.accept().unwrap();
+    ///         std::io::copy(&mut s.try_clone().unwrap(), &mut s).unwrap();
+    ///     });
+    ///
+    ///     let mut s = Compat::new(TcpStream::connect(addr).unwrap()).unwrap();
+    ///     let mut buf = [0u8; 4];
+    ///     block_on(s.write_all(b"ping")).unwrap();
+    ///     block_on(s.read_exact(&mut buf)).unwrap();
+    ///     assert_eq!(&buf, b"ping");
+    /// }
+    /// ```
+    pub struct Compat<T> {
+        inner: T,
+        // the clone that is used by the waiting coroutines
+        ready: Arc<T>,
+        read: Arc<Waiter>,
+        write: Arc<Waiter>,
+    }
+
+    impl Compat<TcpStream> {
+        /// wrap the stream, it's set to nonblocking mode
+        pub fn new(s: TcpStream) -> io::Result<Compat<TcpStream>> {
+            let ready = Arc::new(s.try_clone()?);
+            s.set_nonblocking(true)?;
+            Ok(Compat {
+                inner: s,
+                ready: ready,
+                read: Waiter::new(),
+                write: Waiter::new(),
+            })
+        }
+    }
+
+    impl<T> Compat<T> {
+        /// get a reference to the wrapped stream
+        pub fn get_ref(&self) -> &T {
+            &self.inner
+        }
+
+        /// get a mutable reference to the wrapped stream
+        pub fn get_mut(&mut self) -> &mut T {
+            &mut self.inner
+        }
+    }
+
+    impl<T> Drop for Compat<T> {
+        fn drop(&mut self) {
+            // don't leave the waiting coroutines behind
+            self.read.cancel();
+            self.write.cancel();
+        }
+    }
+
+    impl AsyncRead for Compat<TcpStream> {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            let me = self.get_mut();
+            match me.inner.read(buf) {
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    let ready = me.ready.clone();
+                    me.read.register(cx.waker(), move || ready.wait_read());
+                    Poll::Pending
+                }
+                ret => Poll::Ready(ret),
+            }
+        }
+    }
+
+    impl AsyncWrite for Compat<TcpStream> {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            let me = self.get_mut();
+            match me.inner.write(buf) {
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    let ready = me.ready.clone();
+                    me.write.register(cx.waker(), move || ready.wait_write());
+                    Poll::Pending
+                }
+                ret => Poll::Ready(ret),
+            }
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+            Poll::Ready(self.inner.shutdown(Shutdown::Write))
+        }
+    }
+}
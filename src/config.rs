@@ -1,8 +1,13 @@
 //! `May` Configuration interface
 //!
 
+use std::cmp;
+use std::time::Duration;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
+use scheduler;
+use listener::{self, CoroutineEventListener};
+
 // default configs
 const DEFAULT_WORKERS: usize = 2;
 const DEFAULT_IO_WORKERS: usize = 2;
@@ -10,11 +15,43 @@ const DEFAULT_IO_WORKERS: usize = 2;
 // windows has a minimal size as 0x4a8!!!!
 const DEFAULT_STACK_SIZE: usize = 0x1000;
 const DEFAULT_POOL_CAPACITY: usize = 100;
+const DEFAULT_STACK_POOL_CAPACITY: usize = 16;
+const DEFAULT_BLOCKING_WORKERS: usize = 32;
+// default connect timeout, in ms
+const DEFAULT_CONNECT_TIMEOUT: usize = 10_000;
+// default timer resolution, in us
+const DEFAULT_TIMER_RESOLUTION: usize = 1_000;
 
 static WORKERS: AtomicUsize = AtomicUsize::new(DEFAULT_WORKERS);
 static IO_WORKERS: AtomicUsize = AtomicUsize::new(DEFAULT_IO_WORKERS);
 static STACK_SIZE: AtomicUsize = AtomicUsize::new(DEFAULT_STACK_SIZE);
 static POOL_CAPACITY: AtomicUsize = AtomicUsize::new(DEFAULT_POOL_CAPACITY);
+// the coroutines created for the pool at startup, MAX to fill the pool
+static PREALLOC_STACKS: AtomicUsize = AtomicUsize::new(::std::usize::MAX);
+static STACK_POOL_CAPACITY: AtomicUsize = AtomicUsize::new(DEFAULT_STACK_POOL_CAPACITY);
+// the idle time in ms before the pages of a pooled stack are released, 0 for never
+static STACK_IDLE_RELEASE: AtomicUsize = AtomicUsize::new(0);
+static BLOCKING_WORKERS: AtomicUsize = AtomicUsize::new(DEFAULT_BLOCKING_WORKERS);
+static CONNECT_TIMEOUT: AtomicUsize = AtomicUsize::new(DEFAULT_CONNECT_TIMEOUT);
+static TIMER_RESOLUTION: AtomicUsize = AtomicUsize::new(DEFAULT_TIMER_RESOLUTION);
+static TRACK_STACK: AtomicUsize = AtomicUsize::new(0);
+static STACK_PROTECTION: AtomicUsize = AtomicUsize::new(0);
+static COROUTINE_REGISTRY: AtomicUsize = AtomicUsize::new(0);
+static PANIC_POLICY: AtomicUsize = AtomicUsize::new(PanicPolicy::Propagate as usize);
+
+/// What to do when a coroutine panics
+///
+/// the cancel of a coroutine also unwinds it by a panic, that's not treated
+/// as a panic here
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PanicPolicy {
+    /// return the panic from `join`, this is the default
+    Propagate,
+    /// log the panic with the coroutine name, and return it from `join`
+    LogAndContinue,
+    /// print the panic with the coroutine name and abort the process
+    Abort,
+}
 
 /// `May` Configuration type
 pub struct Config;
@@ -64,6 +101,10 @@ impl Config {
 
     /// set cached coroutine pool number
     ///
+    /// it's the max number of the cached coroutines of the default stack
+    /// size, the stack of a finished coroutine is given back to the system
+    /// when the pool is full. it's read when the scheduler starts
+    ///
     /// if you pass 0 to it, will use internal default
     pub fn set_pool_capacity(&self, capacity: usize) -> &Self {
         info!("set pool capacity={:?}", capacity);
@@ -81,6 +122,94 @@ impl Config {
         }
     }
 
+    /// set the number of the coroutines that are created with their stacks
+    /// for the pool when the scheduler starts
+    ///
+    /// the default is to fill the pool up to its capacity, so that the first
+    /// spawns don't allocate. 0 leaves the pool to be filled by the finished
+    /// coroutines. it's capped by the pool capacity
+    pub fn set_prealloc_stacks(&self, stacks: usize) -> &Self {
+        info!("set prealloc stacks={:?}", stacks);
+        PREALLOC_STACKS.store(stacks, Ordering::Release);
+        self
+    }
+
+    /// get the number of the coroutines created for the pool at startup
+    pub fn get_prealloc_stacks(&self) -> usize {
+        let stacks = PREALLOC_STACKS.load(Ordering::Acquire);
+        cmp::min(stacks, self.get_pool_capacity())
+    }
+
+    /// set the cached coroutine number of each stack size other than the
+    /// default one
+    ///
+    /// the coroutines spawned with `Builder::stack_size` reuse the cached
+    /// stack of the same size. this can be changed at any time
+    /// if you pass 0 to it, will use internal default
+    pub fn set_stack_pool_capacity(&self, capacity: usize) -> &Self {
+        info!("set stack pool capacity={:?}", capacity);
+        STACK_POOL_CAPACITY.store(capacity, Ordering::Release);
+        self
+    }
+
+    /// get the cached coroutine number of each stack size
+    pub fn get_stack_pool_capacity(&self) -> usize {
+        let size = STACK_POOL_CAPACITY.load(Ordering::Acquire);
+        if size != 0 {
+            size
+        } else {
+            DEFAULT_STACK_POOL_CAPACITY
+        }
+    }
+
+    /// set the max thread number of the blocking pool used by `spawn_blocking`
+    ///
+    /// the threads are spawned on demand, this can be changed at any time
+    /// if you pass 0 to it, will use internal default
+    pub fn set_blocking_workers(&self, workers: usize) -> &Self {
+        info!("set blocking workers={:?}", workers);
+        BLOCKING_WORKERS.store(workers, Ordering::Release);
+        self
+    }
+
+    /// get the max thread number of the blocking pool
+    pub fn get_blocking_workers(&self) -> usize {
+        let workers = BLOCKING_WORKERS.load(Ordering::Acquire);
+        if workers != 0 {
+            workers
+        } else {
+            DEFAULT_BLOCKING_WORKERS
+        }
+    }
+
+    /// give the pages of the cached coroutine stacks back to the system
+    /// after they are idle in the pool for the time, it's off by default
+    ///
+    /// the stacks keep their mappings and the pages are mapped again with
+    /// zeros when they are used, so the spawns still reuse the cached
+    /// coroutines. it trims the memory of a service that idles after a burst
+    /// of spawns. the pool is checked once each time, so a stack could stay
+    /// idle for up to twice the time before it's released. it's read when
+    /// the scheduler starts, pass 0 to turn it off. this is only supported
+    /// on unix
+    pub fn set_stack_idle_release(&self, idle: Duration) -> &Self {
+        info!("set stack idle release={:?}", idle);
+        let ms = (idle.as_secs() as usize)
+            .saturating_mul(1_000)
+            .saturating_add((idle.subsec_nanos() as usize + 999_999) / 1_000_000);
+        STACK_IDLE_RELEASE.store(ms, Ordering::Release);
+        self
+    }
+
+    /// get the idle time before the pages of a cached stack are released,
+    /// `None` if they are never released
+    pub fn get_stack_idle_release(&self) -> Option<Duration> {
+        match STACK_IDLE_RELEASE.load(Ordering::Acquire) {
+            0 => None,
+            ms => Some(Duration::from_millis(ms as u64)),
+        }
+    }
+
     /// set default coroutine stack size in usize
     ///
     /// if you pass 0 to it, will use internal default
@@ -94,4 +223,136 @@ impl Config {
     pub fn get_stack_size(&self) -> usize {
         STACK_SIZE.load(Ordering::Acquire)
     }
+
+    /// set the default connect timeout in ms
+    ///
+    /// this is used by the `TcpStream` and `UnixStream` connect APIs that
+    /// don't take a timeout parameter, when called in coroutine context
+    /// if you pass 0 to it, will use internal default
+    pub fn set_connect_timeout(&self, ms: usize) -> &Self {
+        info!("set connect timeout={:?}ms", ms);
+        CONNECT_TIMEOUT.store(ms, Ordering::Release);
+        self
+    }
+
+    /// get the default connect timeout
+    pub fn get_connect_timeout(&self) -> Duration {
+        let ms = CONNECT_TIMEOUT.load(Ordering::Acquire);
+        let ms = if ms != 0 { ms } else { DEFAULT_CONNECT_TIMEOUT };
+        Duration::from_millis(ms as u64)
+    }
+
+    /// set the resolution of the timers, the default is 1ms
+    ///
+    /// the timeouts of sleeps, parks and io are rounded up to the multiple of
+    /// the resolution, so that the timers in the same slot expire together.
+    /// a timer never fires before its deadline, and no later than the
+    /// deadline plus the resolution, not counting the time to wake up the
+    /// timer thread and to run the coroutine. a coarse resolution saves
+    /// wakeups at the cost of the latency. the resolution is in us at least
+    /// if you pass 0 to it, will use internal default. it's read when the
+    /// scheduler starts
+    pub fn set_timer_resolution(&self, resolution: Duration) -> &Self {
+        info!("set timer resolution={:?}", resolution);
+        let us = (resolution.as_secs() as usize)
+            .saturating_mul(1_000_000)
+            .saturating_add((resolution.subsec_nanos() as usize + 999) / 1_000);
+        TIMER_RESOLUTION.store(us, Ordering::Release);
+        self
+    }
+
+    /// set the policy for the coroutine panics
+    ///
+    /// this can be changed at any time
+    pub fn set_panic_policy(&self, policy: PanicPolicy) -> &Self {
+        info!("set panic policy={:?}", policy);
+        PANIC_POLICY.store(policy as usize, Ordering::Release);
+        self
+    }
+
+    /// get the policy for the coroutine panics
+    pub fn get_panic_policy(&self) -> PanicPolicy {
+        match PANIC_POLICY.load(Ordering::Acquire) {
+            1 => PanicPolicy::LogAndContinue,
+            2 => PanicPolicy::Abort,
+            _ => PanicPolicy::Propagate,
+        }
+    }
+
+    /// measure the peak stack usage of all the spawned coroutines, it's off
+    /// by default
+    ///
+    /// this is the default of `Builder::track_stack`, the usage is reported
+    /// by `JoinHandle::stack_high_water` and `coroutine::stack_stats`. this
+    /// can be changed at any time, it only affects the coroutines spawned
+    /// after that
+    pub fn set_track_stack(&self, track: bool) -> &Self {
+        info!("set track stack={:?}", track);
+        TRACK_STACK.store(track as usize, Ordering::Release);
+        self
+    }
+
+    /// return true if the spawned coroutines measure their stack usage
+    pub fn get_track_stack(&self) -> bool {
+        TRACK_STACK.load(Ordering::Acquire) != 0
+    }
+
+    /// report the coroutine that overflows its stack and abort, it's off by
+    /// default
+    ///
+    /// the coroutine stacks always have a guard page below them, so an
+    /// overflow faults instead of corrupting the memory. with this on a
+    /// `SIGSEGV` handler prints the name, the id and the stack size of the
+    /// coroutine that hits its guard page. the handler is installed when the
+    /// scheduler starts, so set it before spawning the first coroutine. this
+    /// is only supported on unix
+    pub fn set_stack_protection(&self, enable: bool) -> &Self {
+        info!("set stack protection={:?}", enable);
+        STACK_PROTECTION.store(enable as usize, Ordering::Release);
+        self
+    }
+
+    /// return true if the stack overflow of coroutines is reported
+    pub fn get_stack_protection(&self) -> bool {
+        STACK_PROTECTION.load(Ordering::Acquire) != 0
+    }
+
+    /// list the spawned coroutines for `coroutine::dump`, it's off by default
+    ///
+    /// this can be changed at any time, only the coroutines spawned while
+    /// it's on are listed. the listed coroutines update their state each
+    /// time they are scheduled
+    pub fn set_coroutine_registry(&self, enable: bool) -> &Self {
+        info!("set coroutine registry={:?}", enable);
+        COROUTINE_REGISTRY.store(enable as usize, Ordering::Release);
+        self
+    }
+
+    /// return true if the spawned coroutines are listed for `coroutine::dump`
+    pub fn get_coroutine_registry(&self) -> bool {
+        COROUTINE_REGISTRY.load(Ordering::Acquire) != 0
+    }
+
+    /// install the listener of the coroutine lifecycle events
+    ///
+    /// the listener is installed only once and only before the scheduler
+    /// starts, that is before the first spawn. the later calls are ignored
+    /// with an error log. see `coroutine::CoroutineEventListener`
+    pub fn set_event_listener<L: CoroutineEventListener>(&self, listener: L) -> &Self {
+        if scheduler::is_started() {
+            error!("the event listener must be set before the scheduler starts");
+        } else if !listener::install(Box::new(listener)) {
+            error!("the event listener is already set");
+        } else {
+            info!("set event listener");
+        }
+        self
+    }
+
+    /// get the resolution of the timers
+    pub fn get_timer_resolution(&self) -> Duration {
+        let us = TIMER_RESOLUTION.load(Ordering::Acquire);
+        let us = if us != 0 { us } else { DEFAULT_TIMER_RESOLUTION };
+        Duration::from_micros(us as u64)
+    }
 }
@@ -1,8 +1,17 @@
 // re-export coroutine interface
-pub use sleep::sleep;
-pub use scoped::scope;
+pub use sleep::{interval, interval_at, sleep, sleep_until, Interval, MissedTickBehavior};
+pub use scoped::{scope, scope_collect};
 pub use park::ParkError;
-pub use join::JoinHandle;
+pub use join::{with_timeout, JoinHandle, TimeoutError};
+pub use blocking::{spawn_blocking, BlockingJoinHandle};
 pub use yield_now::yield_now;
-pub use cancel::trigger_cancel_panic;
-pub use coroutine_impl::{current, park, park_timeout, spawn, Builder};
+pub use local::{AccessError, LocalKey};
+pub use cancel::{check_cancel, is_cancel_err, is_cancel_panic, trigger_cancel_panic, CancelError,
+                 CancelGuard};
+pub use cancel_token::CancelToken;
+pub use scheduler::{current_worker_id, Priority};
+pub use registry::{dump, CoroutineInfo, CoroutineState};
+pub use listener::{CoroutineEventListener, ExitKind, ParkReason};
+pub use stack_stats::{stack_stats, StackStats};
+pub use sync::mpsc::{ticker, Ticker};
+pub use coroutine_impl::{current, park, park_timeout, spawn, spawn_batch, spawn_on, Builder};
@@ -1,17 +1,22 @@
 use std::io;
 use std::fmt;
+use std::mem;
+use std::any::Any;
 use std::sync::Arc;
 use std::time::Duration;
 use std::cell::UnsafeCell;
-use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use park::Park;
-use cancel::Cancel;
+use cancel::{is_cancel_panic, Cancel};
+use cancel_token::CancelToken;
 use sync::AtomicOption;
 use local::CoroutineLocal;
-use scheduler::get_scheduler;
-use config::config;
+use registry::{CoroutineInfo, CoroutineState};
+use listener::{listener, ExitKind, ParkReason};
+use scheduler::{self, get_scheduler, worker_id, Priority};
+use config::{config, PanicPolicy};
 use join::{make_join_handle, Join, JoinHandle};
-use generator::{get_local_data, Generator, Gn};
+use generator::{get_local_data, Error, Generator, Gn};
 
 /// /////////////////////////////////////////////////////////////////////////////
 /// Coroutine framework types
@@ -32,15 +37,32 @@ impl EventSubscriber {
         let resource = unsafe { &mut *self.resource };
         resource.subscribe(c);
     }
+
+    #[inline]
+    fn park_reason(&self) -> Option<ParkReason> {
+        let resource = unsafe { &*self.resource };
+        resource.park_reason()
+    }
 }
 
 pub trait EventSource {
     /// kernel handler of the event
     fn subscribe(&mut self, _c: CoroutineImpl);
+    /// why the coroutine is suspended, reported to the event listener.
+    /// most of the event sources are io requests, `None` if the coroutine
+    /// is finished
+    fn park_reason(&self) -> Option<ParkReason> {
+        Some(ParkReason::Io)
+    }
     /// after yield back process
     fn yield_back(&self, cancel: &'static Cancel) {
-        // after return back we should re-check the panic and clear it
-        cancel.check_cancel();
+        // after return back we should re-check the panic and clear it.
+        // the io requests return the cancel as the error of the operation
+        // instead of panic
+        match self.park_reason() {
+            Some(ParkReason::Io) => cancel.check_cancel_io(),
+            _ => cancel.check_cancel(),
+        }
     }
 }
 
@@ -51,13 +73,22 @@ pub trait EventSource {
 pub struct Done;
 
 impl Done {
-    fn drop_coroutine(co: CoroutineImpl) {
+    fn drop_coroutine(co: CoroutineImpl, kind: ExitKind) {
+        scheduler::coroutine_done();
         // println!("co is dropped. done={:?}", co.is_done());
         // assert!(co.is_done(), "unfinished coroutine detected");
         // just consume the coroutine
         // destroy the local storage
         let local = unsafe { Box::from_raw(co.get_local_data() as *mut CoroutineLocal) };
+        if let Some(l) = listener() {
+            l.on_exit(local.get_co().id(), kind);
+        }
+        if let Some(token) = local.get_token() {
+            token.unregister(local.get_co());
+        }
+        get_scheduler().registry.remove(local.get_co());
         let name = local.get_co().name();
+        let join = unsafe { &mut *local.get_join().get() };
 
         // recycle the coroutine
         let (size, used) = co.stack_usage();
@@ -65,8 +96,14 @@ impl Done {
             eprintln!("stack overflow detected, size={}", size);
             ::std::process::exit(1);
         }
-        // show the actual used stack size in debug log
-        if size & 1 == 1 {
+        if join.is_stack_tracked() {
+            // the join is delayed until the usage is recorded
+            let bytes = used * mem::size_of::<usize>();
+            get_scheduler().stack_stats.record(bytes);
+            join.set_stack_used(bytes);
+            join.trigger();
+        } else if size & 1 == 1 {
+            // show the actual used stack size in debug log
             println!(
                 "coroutine name = {:?}, stack size = {},  used size = {}",
                 name, size, used
@@ -74,14 +111,21 @@ impl Done {
         }
 
         if size == config().get_stack_size() {
-            get_scheduler().pool.put(co);
+            get_scheduler().pool.put(co, local.get_stack());
+        } else if size & 1 == 0 {
+            // the odd sizes are only painted when the stack is allocated
+            get_scheduler().pool.put_sized(size, local.get_stack(), co);
         }
     }
 }
 
 impl EventSource for Done {
     fn subscribe(&mut self, co: CoroutineImpl) {
-        Self::drop_coroutine(co);
+        Self::drop_coroutine(co, ExitKind::Finished);
+    }
+
+    fn park_reason(&self) -> Option<ParkReason> {
+        None
     }
 }
 
@@ -93,11 +137,23 @@ pub type CoroutineImpl = Generator<'static, EventResult, EventSubscriber>;
 /// Coroutine
 /// /////////////////////////////////////////////////////////////////////////////
 
+// the id of the next spawned coroutine
+static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+
+// the worker of a coroutine that is never run
+const NO_WORKER: usize = ::std::usize::MAX;
+
 /// The internal representation of a `Coroutine` handle
 struct Inner {
+    id: usize,
     name: Option<String>,
     park: Park,
     cancel: Cancel,
+    // the coroutine is shown by `dump`, only then the state is updated
+    listed: bool,
+    state: AtomicUsize,
+    // the worker that runs the coroutine last time
+    worker: AtomicUsize,
 }
 
 #[derive(Clone)]
@@ -113,9 +169,13 @@ impl Coroutine {
     fn new(name: Option<String>) -> Coroutine {
         Coroutine {
             inner: Arc::new(Inner {
+                id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
                 name: name,
                 park: Park::new(),
                 cancel: Cancel::new(),
+                listed: config().get_coroutine_registry(),
+                state: AtomicUsize::new(CoroutineState::Ready as usize),
+                worker: AtomicUsize::new(NO_WORKER),
             }),
         }
     }
@@ -126,6 +186,10 @@ impl Coroutine {
     }
 
     /// cancel a coroutine
+    ///
+    /// the io operation that the coroutine is blocked in returns an error
+    /// that satisfies `coroutine::is_cancel_err`, the following blocking
+    /// calls and all the other blocking APIs unwind the coroutine
     pub unsafe fn cancel(&self) {
         self.inner.cancel.cancel();
     }
@@ -134,6 +198,48 @@ impl Coroutine {
     pub fn name(&self) -> Option<&str> {
         self.inner.name.as_ref().map(|s| &**s)
     }
+
+    /// Gets the unique id of the coroutine, the ids are assigned from 1 in
+    /// the order of spawning
+    pub fn id(&self) -> usize {
+        self.inner.id
+    }
+
+    // return true if the coroutine is shown by `dump`
+    pub(crate) fn is_listed(&self) -> bool {
+        self.inner.listed
+    }
+
+    // update the state shown by `coroutine::dump`
+    #[inline]
+    pub(crate) fn set_state(&self, state: CoroutineState) {
+        if !self.inner.listed {
+            return;
+        }
+        if state == CoroutineState::Running {
+            let worker = worker_id().unwrap_or(NO_WORKER);
+            self.inner.worker.store(worker, Ordering::Relaxed);
+        }
+        self.inner.state.store(state as usize, Ordering::Relaxed);
+    }
+
+    // a snapshot of the coroutine for `coroutine::dump`
+    pub(crate) fn info(&self) -> CoroutineInfo {
+        let state = match self.inner.state.load(Ordering::Relaxed) {
+            0 => CoroutineState::Ready,
+            1 => CoroutineState::Running,
+            // the io waits register the io data for the cancel
+            _ if self.inner.cancel.is_io_waiting() => CoroutineState::Io,
+            _ => CoroutineState::Parked,
+        };
+        let worker = self.inner.worker.load(Ordering::Relaxed);
+        CoroutineInfo {
+            id: self.id(),
+            name: self.name().map(|s| s.to_owned()),
+            state: state,
+            worker: if worker == NO_WORKER { None } else { Some(worker) },
+        }
+    }
 }
 
 impl fmt::Debug for Coroutine {
@@ -151,10 +257,13 @@ impl fmt::Debug for Coroutine {
 ///
 /// Methods can be chained on it in order to configure it.
 ///
-/// The two configurations available are:
+/// The configurations available are:
 ///
 /// - [`name`]: specifies an [associated name for the coroutine][naming-coroutines]
 /// - [`stack_size`]: specifies the [desired stack size for the coroutine][stack-size]
+/// - [`cancel_token`]: binds the coroutine to a cancel token
+/// - [`priority`]: specifies the scheduling priority of the coroutine
+/// - [`track_stack`]: measures the peak stack usage of the coroutine
 ///
 /// The [`spawn`] method will take ownership of the builder and create an
 /// `io::Result` to the coroutine handle with the given configuration.
@@ -182,6 +291,9 @@ impl fmt::Debug for Coroutine {
 /// [`coroutine::spawn`]: ./fn.spawn.html
 /// [`stack_size`]: ./struct.Builder.html#method.stack_size
 /// [`name`]: ./struct.Builder.html#method.name
+/// [`cancel_token`]: ./struct.Builder.html#method.cancel_token
+/// [`priority`]: ./struct.Builder.html#method.priority
+/// [`track_stack`]: ./struct.Builder.html#method.track_stack
 /// [`spawn`]: ./struct.Builder.html#method.spawn
 /// [naming-coroutines]: ./index.html#naming-coroutine
 /// [stack-size]: ./index.html#stack-siz
@@ -190,6 +302,14 @@ pub struct Builder {
     name: Option<String>,
     // The size of the stack for the spawned coroutine
     stack_size: Option<usize>,
+    // The cancel token that the coroutine is bound to
+    token: Option<CancelToken>,
+    // The scheduling priority of the coroutine
+    priority: Priority,
+    // The worker that the coroutine is pinned to
+    pin: Option<usize>,
+    // Measure the peak stack usage of the coroutine
+    track_stack: Option<bool>,
 }
 
 impl Builder {
@@ -199,6 +319,10 @@ impl Builder {
         Builder {
             name: None,
             stack_size: None,
+            token: None,
+            priority: Priority::Normal,
+            pin: None,
+            track_stack: None,
         }
     }
 
@@ -215,10 +339,67 @@ impl Builder {
         self
     }
 
+    /// Binds the new coroutine to the cancel token, it's cancelled when the
+    /// token is cancelled. Without this the new coroutine inherits the token
+    /// of the spawning coroutine.
+    pub fn cancel_token(mut self, token: CancelToken) -> Builder {
+        self.token = Some(token);
+        self
+    }
+
+    /// Sets the scheduling priority of the new coroutine, the workers pick
+    /// the ready coroutines of higher priority first. The default is
+    /// `Priority::Normal`, it's not inherited from the spawning coroutine.
+    pub fn priority(mut self, priority: Priority) -> Builder {
+        self.priority = priority;
+        self
+    }
+
+    /// Pins the new coroutine to the worker thread of the index, it's only
+    /// run by that worker and is never picked by the others, including
+    /// after the io and timer wake ups. The index must be less than the
+    /// number of workers that the scheduler is started with.
+    ///
+    /// the pinned coroutines are run before the shared ready list, it's
+    /// meant for the coroutines that keep their state in the thread locals
+    /// of the worker, or that should stay close to their data, such as the
+    /// coroutine of a shard. `spawn` returns an `InvalidInput` error for an
+    /// index out of range. the coroutines spawned by a pinned coroutine are
+    /// not pinned unless they are asked to be
+    pub fn pin_to(mut self, worker: usize) -> Builder {
+        self.pin = Some(worker);
+        self
+    }
+
+    /// Measures the peak stack usage of the new coroutine, which is then
+    /// returned by `JoinHandle::stack_high_water` when it's done.
+    ///
+    /// the whole stack is painted before the coroutine runs and scanned
+    /// after it's done, so the coroutine doesn't reuse a pooled stack. it's
+    /// meant for sizing the stack with `stack_size` in tests, not for the
+    /// production use. the default is `Config::get_track_stack`
+    pub fn track_stack(mut self, track: bool) -> Builder {
+        self.track_stack = Some(track);
+        self
+    }
+
     /// Spawns a new coroutine, and returns a join handle for it.
     /// The join handle can be used to block on
     /// termination of the child coroutine, including recovering its panics.
-    fn spawn_impl<F, T>(self, f: F) -> io::Result<JoinHandle<T>>
+    fn spawn_impl<F, T>(self, f: F, detached: bool) -> io::Result<JoinHandle<T>>
+    where
+        F: FnOnce() -> T,
+        F: Send + 'static,
+        T: Send + 'static,
+    {
+        let (co, handle) = self.prepare(f, detached)?;
+        // put the coroutine to ready list
+        get_scheduler().schedule(co);
+        Ok(handle)
+    }
+
+    // create the coroutine that is not scheduled yet
+    fn prepare<F, T>(self, f: F, detached: bool) -> io::Result<(CoroutineImpl, JoinHandle<T>)>
     where
         F: FnOnce() -> T,
         F: Send + 'static,
@@ -226,15 +407,50 @@ impl Builder {
     {
         static DONE: Done = Done {};
         let sched = get_scheduler();
-        let _co = sched.pool.get();
-        _co.prefetch();
-
         let done = &DONE as &EventSource as *const _ as *mut EventSource;
-        let Builder { name, stack_size } = self;
-        let stack_size = stack_size.unwrap_or(config().get_stack_size());
+        let Builder {
+            name,
+            stack_size,
+            token,
+            priority,
+            pin,
+            track_stack,
+        } = self;
+        if sched.is_closing() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "the scheduler is shut down",
+            ));
+        }
+        if let Some(worker) = pin {
+            if worker >= sched.worker_count() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "no such worker to pin the coroutine to",
+                ));
+            }
+        }
+        let track_stack = track_stack.unwrap_or_else(|| config().get_track_stack());
+        let mut stack_size = stack_size.unwrap_or(config().get_stack_size());
+        let stack_bytes = stack_size * mem::size_of::<usize>();
+        if track_stack {
+            // an odd size makes the generator paint the whole stack
+            stack_size |= 1;
+        }
+        // the tracked coroutines need a freshly painted stack
+        let pooled = if track_stack {
+            None
+        } else if stack_size == config().get_stack_size() {
+            Some(sched.pool.get())
+        } else {
+            sched.pool.get_sized(stack_size)
+        };
+        if let Some(ref co) = pooled {
+            co.prefetch();
+        }
         // create a join resource, shared by waited coroutine and *this* coroutine
         let panic = Arc::new(UnsafeCell::new(None));
-        let join = Arc::new(UnsafeCell::new(Join::new(panic.clone())));
+        let join = Arc::new(UnsafeCell::new(Join::new(panic.clone(), track_stack)));
         let packet = Arc::new(AtomicOption::none());
         let their_join = join.clone();
         let their_packet = packet.clone();
@@ -246,32 +462,68 @@ impl Builder {
             // coroutine local data so that can return from the packet variable
             let join = unsafe { &mut *their_join.get() };
 
+            // drop the para left over by the last user of the pooled coroutine
+            ::yield_now::get_co_para();
+
+            // the stack top is about where this frame is, the overflow
+            // handler looks for the faults below the stack bottom
+            let top = 0u8;
+            let local = unsafe { &*(get_local_data() as *mut CoroutineLocal) };
+            local.set_stack(&top as *const u8 as usize, stack_bytes);
+
             // set the return packet
             their_packet.swap(f(), Ordering::Release);
 
-            join.trigger();
+            if !join.is_stack_tracked() {
+                join.trigger();
+            }
             EventSubscriber { resource: done }
         };
 
-        let mut co;
-        if stack_size == config().get_stack_size() {
-            co = _co;
-            // re-init the closure
-            co.init(closure);
-        } else {
-            sched.pool.put(_co);
-            co = Gn::new_opt(stack_size, closure);
-        }
+        let mut co = match pooled {
+            Some(mut co) => {
+                // re-init the closure
+                co.init(closure);
+                co
+            }
+            None => Gn::new_opt(stack_size, closure),
+        };
 
         let handle = Coroutine::new(name);
+        // inherit the cancel token of the parent coroutine
+        let token = token.or_else(|| {
+            if is_coroutine() {
+                current_cancel_token()
+            } else {
+                None
+            }
+        });
+        if let Some(ref token) = token {
+            token.register(&handle);
+        }
+        sched.registry.add(&handle);
+        if sched.is_closing() {
+            // the shutdown started after the check above, its snapshot of the
+            // registry may miss this one, so it's canceled here
+            unsafe { handle.cancel() };
+        }
+        if let Some(l) = listener() {
+            l.on_spawn(handle.id(), handle.name());
+        }
         // create the local storage
-        let local = CoroutineLocal::new(handle.clone(), join.clone());
+        let local = CoroutineLocal::new(
+            handle.clone(),
+            join.clone(),
+            token,
+            priority,
+            pin,
+            detached,
+        );
         // attache the local storage to the coroutine
         co.set_local_data(Box::into_raw(local) as *mut u8);
 
-        // put the coroutine to ready list
-        sched.schedule(co);
-        Ok(make_join_handle(handle, join, packet, panic))
+        scheduler::coroutine_spawned();
+        Ok((co, make_join_handle(handle, join, packet, panic)))
     }
 
     /// Spawns a new coroutine by taking ownership of the `Builder`, and returns an
@@ -321,7 +573,39 @@ impl Builder {
         T: Send + 'static,
     {
         // we will still get optimizations in spawn_impl
-        self.spawn_impl(f)
+        self.spawn_impl(f, false)
+    }
+
+    /// Spawns a new coroutine that is never joined
+    ///
+    /// the panic of a detached coroutine would otherwise get lost with the
+    /// dropped `JoinHandle`, so it's reported to stderr by the panic hook,
+    /// together with the coroutine name, before the coroutine unwinds. the
+    /// backtrace is printed as well if `RUST_BACKTRACE` is set
+    ///
+    /// # Unsafety
+    ///
+    /// the same as [`spawn`](#method.spawn)
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use may::coroutine;
+    ///
+    /// unsafe {
+    ///     coroutine::Builder::new()
+    ///         .name("worker".to_owned())
+    ///         .spawn_detached(|| {
+    ///             // a panic here is logged as from coroutine 'worker'
+    ///         })
+    ///         .unwrap()
+    /// };
+    /// ```
+    pub unsafe fn spawn_detached<F>(self, f: F) -> io::Result<()>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.spawn_impl(f, true).map(|_| ())
     }
 }
 
@@ -383,6 +667,89 @@ where
     Builder::new().spawn(f).unwrap()
 }
 
+/// Spawns a batch of new coroutines, returning the [`JoinHandle`]s in the
+/// same order
+///
+/// the coroutines are put to the ready list in chunks, and the parked
+/// workers are waked once for each chunk to share it, instead of once for
+/// each coroutine. it's cheaper than calling `spawn` in a loop for a large
+/// fan out. the unsafety is the same as `spawn`
+///
+/// # Panics
+///
+/// panics if the scheduler is shut down, the coroutines that are created
+/// before that are still run
+///
+/// # Examples
+///
+/// ```
+/// use may::coroutine;
+///
+/// let hs = unsafe { coroutine::spawn_batch((0..100).map(|i| move || i * 2)) };
+/// let sum: usize = hs.into_iter().map(|h| h.join().unwrap()).sum();
+/// assert_eq!(sum, 9900);
+/// ```
+///
+/// [`JoinHandle`]: struct.JoinHandle.html
+pub unsafe fn spawn_batch<I, F, T>(fs: I) -> Vec<JoinHandle<T>>
+where
+    I: IntoIterator<Item = F>,
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    // the ones scheduled in a chunk, they are run and give their stacks
+    // back to the pool while the rest are being created
+    const CHUNK: usize = 64;
+
+    let sched = get_scheduler();
+    let fs = fs.into_iter();
+    let mut cos = Vec::with_capacity(CHUNK);
+    let mut handles = Vec::with_capacity(fs.size_hint().0);
+    for f in fs {
+        match Builder::new().prepare(f, false) {
+            Ok((co, handle)) => {
+                cos.push(co);
+                handles.push(handle);
+            }
+            Err(e) => {
+                sched.schedule_batch(cos);
+                panic!("failed to spawn the coroutine: {}", e);
+            }
+        }
+        if cos.len() == CHUNK {
+            sched.schedule_batch(mem::replace(&mut cos, Vec::with_capacity(CHUNK)));
+        }
+    }
+    sched.schedule_batch(cos);
+    handles
+}
+
+/// Spawns a new coroutine that is pinned to the worker thread of the index
+///
+/// it's the same as `spawn` with `Builder::pin_to`, the coroutine is only
+/// run by that worker. the TLS of the worker is stable for the pinned
+/// coroutine, the other unsafety of `spawn` still applies
+///
+/// # Panics
+///
+/// panics if the index is not less than the number of the workers
+///
+/// # Examples
+///
+/// ```
+/// use may::coroutine;
+///
+/// let h = unsafe { coroutine::spawn_on(0, coroutine::current_worker_id) };
+/// assert_eq!(h.join().unwrap(), Some(0));
+/// ```
+pub unsafe fn spawn_on<F, T>(worker: usize, f: F) -> JoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    Builder::new().pin_to(worker).spawn(f).unwrap()
+}
+
 /// Gets a handle to the thread that invokes it.
 #[inline]
 pub fn current() -> Coroutine {
@@ -390,6 +757,13 @@ pub fn current() -> Coroutine {
     local.get_co().clone()
 }
 
+/// get the cancel token of the current coroutine
+#[inline]
+pub(crate) fn current_cancel_token() -> Option<CancelToken> {
+    let local = unsafe { &*(get_local_data() as *mut CoroutineLocal) };
+    local.get_token().cloned()
+}
+
 /// if current context is coroutine
 #[inline]
 pub fn is_coroutine() -> bool {
@@ -399,6 +773,21 @@ pub fn is_coroutine() -> bool {
     !get_local_data().is_null()
 }
 
+/// describe the current coroutine for the panic message
+pub(crate) fn current_description() -> Option<String> {
+    if !is_coroutine() {
+        return None;
+    }
+    let local = unsafe { &*(get_local_data() as *mut CoroutineLocal) };
+    let co = local.get_co();
+    Some(format!(
+        "{}coroutine '{}' (id {})",
+        if local.is_detached() { "detached " } else { "" },
+        co.name().unwrap_or("<unnamed>"),
+        co.id()
+    ))
+}
+
 /// get current coroutine cancel registration
 #[inline]
 pub fn current_cancel_data() -> &'static Cancel {
@@ -434,20 +823,94 @@ pub fn park_timeout(dur: Duration) {
     park_timeout_impl(Some(dur));
 }
 
+// the message of the panic payload
+fn panic_message(panic: &(Any + Send)) -> &str {
+    if let Some(s) = panic.downcast_ref::<&'static str>() {
+        return s;
+    }
+    if let Some(s) = panic.downcast_ref::<String>() {
+        return s;
+    }
+    "Box<Any>"
+}
+
+// log or abort for the panic according to the config
+fn apply_panic_policy(co: &Coroutine, panic: &(Any + Send)) {
+    // the cancel is not a bug
+    if is_cancel_panic(panic) {
+        return;
+    }
+
+    let name = co.name().unwrap_or("<unnamed>");
+    match config().get_panic_policy() {
+        PanicPolicy::Propagate => {}
+        PanicPolicy::LogAndContinue => {
+            error!("coroutine '{}' panicked at '{}'", name, panic_message(panic));
+        }
+        PanicPolicy::Abort => {
+            eprintln!(
+                "coroutine '{}' panicked at '{}', aborting",
+                name,
+                panic_message(panic)
+            );
+            ::std::process::abort();
+        }
+    }
+}
+
 /// run the coroutine
 #[inline]
 pub fn run_coroutine(mut co: CoroutineImpl) {
-    match co.resume() {
-        Some(ev) => ev.subscribe(co),
+    // the local data is valid until the coroutine is handed out
+    let local = unsafe { &*(co.get_local_data() as *mut CoroutineLocal) };
+    if let Some(pin) = local.get_pin() {
+        // the io workers and the wakers run it directly, send it home
+        if worker_id() != Some(pin) {
+            return get_scheduler().schedule(co);
+        }
+    }
+    local.get_co().set_state(CoroutineState::Running);
+    let listener = listener();
+    if let Some(l) = listener {
+        l.on_resume(local.get_co().id(), worker_id());
+    }
+    scheduler::coroutine_resumed();
+    let ev = co.resume();
+    scheduler::coroutine_suspended();
+    match ev {
+        Some(ev) => {
+            local.get_co().set_state(CoroutineState::Parked);
+            if let Some(l) = listener {
+                ev.park_reason().map(|r| l.on_park(local.get_co().id(), r));
+            }
+            ev.subscribe(co)
+        }
         None => {
             // panic happened here
-            let local = unsafe { &*(co.get_local_data() as *mut CoroutineLocal) };
             let join = unsafe { &mut *local.get_join().get() };
             // set the panic data
-            co.get_panic_data().map(|panic| join.set_panic_data(panic));
+            // no panic data is left for a cancel, the same as the join
+            let mut kind = ExitKind::Canceled;
+            let canceled = local.get_co().inner.cancel.is_io_reported();
+            co.get_panic_data().map(|panic| {
+                // a canceled io returns the cancel error, and the caller may
+                // unwrap it into a panic of its own. it's still the cancel
+                let panic: Box<Any + Send> = if canceled && !is_cancel_panic(&*panic) {
+                    Box::new(Error::Cancel)
+                } else {
+                    panic
+                };
+                if !is_cancel_panic(&*panic) {
+                    kind = ExitKind::Panicked;
+                }
+                apply_panic_policy(local.get_co(), &*panic);
+                join.set_panic_data(panic)
+            });
             // trigger the join here
-            join.trigger();
-            Done::drop_coroutine(co);
+            if !join.is_stack_tracked() {
+                join.trigger();
+            }
+            Done::drop_coroutine(co, kind);
         }
     }
 }
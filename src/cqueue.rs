@@ -1,5 +1,6 @@
 use std::panic;
 use std::sync::Arc;
+use std::collections::{BTreeMap, VecDeque};
 use std::time::{Duration, Instant};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use cancel::Cancel;
@@ -9,6 +10,7 @@ use scoped::spawn_unsafe;
 use yield_now::yield_with;
 use sync::{AtomicOption, Blocker};
 use coroutine_impl::{current_cancel_data, run_coroutine, Coroutine, CoroutineImpl, EventSource};
+use listener::ParkReason;
 
 /// This enumeration is the list of the possible reasons that `poll`
 /// could not return Event when called.
@@ -46,6 +48,8 @@ pub struct Event {
     pub extra: usize,
     /// id of the select coroutine, used internally to locate the JoinHandle
     id: usize,
+    /// priority of the select coroutine
+    prio: usize,
     /// the event type
     kind: EventKind,
     // the async coroutine that work on an select
@@ -81,6 +85,8 @@ pub struct EventSender<'a> {
     id: usize,
     // associated token, passed from `add`
     token: usize,
+    // associated priority, passed from `add_with_priority`
+    prio: usize,
     // the select coroutine can use it to pass extra data to the caller
     extra: usize,
     // the mpsc event queue to collect the events
@@ -107,6 +113,7 @@ impl<'a> EventSource for EventSender<'a> {
     fn subscribe(&mut self, co: CoroutineImpl) {
         self.cqueue.ev_queue.push(Event {
             id: self.id,
+            prio: self.prio,
             token: self.token,
             extra: self.extra,
             kind: EventKind::Normal,
@@ -118,6 +125,10 @@ impl<'a> EventSource for EventSender<'a> {
             .map(|w| w.unpark());
     }
 
+    fn park_reason(&self) -> Option<ParkReason> {
+        Some(ParkReason::Sync)
+    }
+
     fn yield_back(&self, _cancel: &'static Cancel) {
         // ignore the cancel to let the bottom half get processed
     }
@@ -128,6 +139,7 @@ impl<'a> Drop for EventSender<'a> {
     fn drop(&mut self) {
         self.cqueue.ev_queue.push(Event {
             id: self.id,
+            prio: self.prio,
             token: self.token,
             extra: self.extra,
             kind: EventKind::Done,
@@ -142,9 +154,16 @@ impl<'a> Drop for EventSender<'a> {
 }
 
 /// cqueue interface for general select model
+///
+/// `poll` returns the ready events of the higher priority first. the events
+/// of the same priority are returned in the order they are ready, a select
+/// coroutine is not ready again until its event is polled, so the sources
+/// that are always ready take turns
 pub struct Cqueue {
     // the mpsc queue that transfer event
     ev_queue: mpsc_list::Queue<Event>,
+    // the events got from the queue but not polled yet, by priority
+    ready: BTreeMap<usize, VecDeque<Event>>,
     // thread/coroutine for wake up
     to_wake: AtomicOption<Arc<Blocker>>,
     // track how many coroutines left
@@ -163,13 +182,14 @@ impl Cqueue {
     /// register a select coroutine with the cqueue
     /// should use `cqueue_add` and `cqueue_add_oneshot` macros to
     /// create select coroutines correctly
-    fn add_impl<'a, F>(&self, token: usize, f: F) -> Selector
+    fn add_impl<'a, F>(&self, token: usize, prio: usize, f: F) -> Selector
     where
         F: FnOnce(EventSender) + Send + 'a,
     {
         let sender = EventSender {
             id: self.total,
             token: token,
+            prio: prio,
             extra: 0,
             cqueue: self,
         };
@@ -190,7 +210,44 @@ impl Cqueue {
     where
         F: FnOnce(EventSender) + Send + 'a,
     {
-        self.add_impl(token, f)
+        self.add_impl(token, 0, f)
+    }
+
+    /// register a select coroutine with a priority, the default is 0
+    ///
+    /// the ready events of the higher priority are polled first, so a source
+    /// that is always ready starves the lower ones
+    pub unsafe fn add_with_priority<'a, F>(&self, token: usize, prio: usize, f: F) -> Selector
+    where
+        F: FnOnce(EventSender) + Send + 'a,
+    {
+        self.add_impl(token, prio, f)
+    }
+
+    // put the event to the ready list of its priority
+    fn push_ready(&self, ev: Event) {
+        let me = unsafe { &mut *(self as *const _ as *mut Self) };
+        me.ready
+            .entry(ev.prio)
+            .or_insert_with(VecDeque::new)
+            .push_back(ev);
+    }
+
+    // move the events from the queue to the ready lists
+    fn collect_events(&self) {
+        while let Some(ev) = self.ev_queue.pop() {
+            self.push_ready(ev);
+        }
+    }
+
+    // get the first ready event of the highest priority
+    fn pop_ready(&self) -> Option<Event> {
+        let me = unsafe { &mut *(self as *const _ as *mut Self) };
+        me.ready
+            .values_mut()
+            .rev()
+            .find(|list| !list.is_empty())
+            .and_then(|list| list.pop_front())
     }
 
     // when the select coroutine is done, check the panic status
@@ -226,6 +283,9 @@ impl Cqueue {
     /// the API is "completion" mode
     /// if any panic in select coroutine detected during the poll
     /// it will propagate the panic to the caller
+    ///
+    /// the timeout bounds the whole poll, `PollError::Timeout` is returned
+    /// when no event comes before it
     pub fn poll(&self, timeout: Option<Duration>) -> Result<Event, PollError> {
         macro_rules! run_ev {
             ($ev:ident) => ({
@@ -240,12 +300,32 @@ impl Cqueue {
 
         let deadline = timeout.map(|dur| Instant::now() + dur);
         loop {
-            match self.ev_queue.pop() {
+            self.collect_events();
+            match self.pop_ready() {
                 Some(mut ev) => run_ev!(ev),
-                None if self.cnt.load(Ordering::Relaxed) == 0 => return Err(PollError::Finished),
+                None if self.cnt.load(Ordering::Relaxed) == 0 => {
+                    // the last done event could be pushed after the collect
+                    if self.ev_queue.is_empty() {
+                        return Err(PollError::Finished);
+                    }
+                    continue;
+                }
                 _ => {}
             }
 
+            // the rest of the timeout, the park timer is removed when an
+            // event wakes us before it and a new one is set for the next wait
+            let timeout = match deadline {
+                Some(d) => {
+                    let now = Instant::now();
+                    if now >= d {
+                        return Err(PollError::Timeout);
+                    }
+                    Some(d - now)
+                }
+                None => None,
+            };
+
             let cur = Blocker::current();
             // register the waiter
             self.to_wake.swap(cur.clone(), Ordering::Release);
@@ -254,18 +334,13 @@ impl Cqueue {
                 None => {
                     cur.park(timeout).ok();
                 }
-                Some(mut ev) => {
+                Some(ev) => {
                     self.to_wake.take(Ordering::Relaxed).map(|w| w.unpark());
                     cur.park(timeout).ok();
-                    run_ev!(ev);
+                    // it's polled by its priority
+                    self.push_ready(ev);
                 }
             }
-
-            // check the timeout
-            match deadline {
-                Some(d) if Instant::now() >= d => return Err(PollError::Timeout),
-                _ => {}
-            }
         }
     }
 }
@@ -309,6 +384,7 @@ where
 {
     let cqueue = Cqueue {
         ev_queue: mpsc_list::Queue::new(),
+        ready: BTreeMap::new(),
         to_wake: AtomicOption::none(),
         cnt: AtomicUsize::new(0),
         selectors: Vec::new(),
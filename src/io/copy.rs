@@ -0,0 +1,267 @@
+//! coroutine aware `io::copy`
+//!
+//! on linux the data is moved by `splice(2)` through an intermediate pipe,
+//! so it never goes through a user space buffer. elsewhere, or when the fds
+//! don't support splice, it falls back to the buffered copy loop
+
+use std::io::{self, Read, Write};
+use std::os::unix::io::AsRawFd;
+
+use io::AsIoData;
+
+const BUF_SIZE: usize = 64 * 1024;
+
+// tell the read errors from the write errors
+fn copy_error(e: io::Error, side: &str) -> io::Error {
+    io::Error::new(e.kind(), format!("copy failed to {}: {}", side, e))
+}
+
+/// Copies the entire content of a reader into a writer
+///
+/// both ends must be the may io objects, e.g. `TcpStream`, `UnixStream` or
+/// the pipes, only the current coroutine is blocked when either side would
+/// block. the read timeout of the reader and the write timeout of the
+/// writer bound each wait, the same as they do for the normal read/write.
+/// return the total number of bytes copied, the error message tells
+/// whether it's the read or the write that failed
+///
+/// # Examples
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate may;
+///
+/// use std::io::{Read, Write};
+/// use may::os::unix::pipe;
+///
+/// fn main() {
+///     let (mut r1, mut w1) = pipe().unwrap();
+///     let (mut r2, mut w2) = pipe().unwrap();
+///
+///     go!(move || {
+///         w1.write_all(b"hello").unwrap();
+///     });
+///     let h = go!(move || may::io::copy(&mut r1, &mut w2).unwrap());
+///     assert_eq!(h.join().unwrap(), 5);
+///
+///     let mut s = String::new();
+///     r2.read_to_string(&mut s).unwrap();
+///     assert_eq!(s, "hello");
+/// }
+/// ```
+pub fn copy<R, W>(reader: &mut R, writer: &mut W) -> io::Result<u64>
+where
+    R: Read + AsRawFd + AsIoData,
+    W: Write + AsRawFd + AsIoData,
+{
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        use coroutine_impl::is_coroutine;
+
+        if is_coroutine() {
+            // a zero length io makes sure the fd is in the coroutine non
+            // blocking mode, the same as the normal read/write does
+            reader.read(&mut []).map_err(|e| copy_error(e, "read"))?;
+            writer.write(&[]).map_err(|e| copy_error(e, "write"))?;
+            if let Some(ret) = splice::copy(reader, writer) {
+                return ret;
+            }
+        }
+    }
+
+    buffered_copy(reader, writer)
+}
+
+fn buffered_copy<R: Read, W: Write>(reader: &mut R, writer: &mut W) -> io::Result<u64> {
+    let mut buf = vec![0u8; BUF_SIZE];
+    let mut total = 0;
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => return Ok(total),
+            Ok(n) => n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(copy_error(e, "read")),
+        };
+        writer
+            .write_all(&buf[..n])
+            .map_err(|e| copy_error(e, "write"))?;
+        total += n as u64;
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod splice {
+    use std::io;
+    use std::ptr;
+    use std::time::Duration;
+    use std::os::unix::io::{AsRawFd, RawFd};
+
+    use libc;
+    use io::AsIoData;
+    use io::sys::WaitIo;
+    use yield_now::yield_with;
+    use super::{copy_error, BUF_SIZE};
+
+    struct Pipe([RawFd; 2]);
+
+    impl Drop for Pipe {
+        fn drop(&mut self) {
+            unsafe {
+                libc::close(self.0[0]);
+                libc::close(self.0[1]);
+            }
+        }
+    }
+
+    fn splice(from: RawFd, to: RawFd, len: usize) -> io::Result<usize> {
+        let flags = libc::SPLICE_F_MOVE | libc::SPLICE_F_NONBLOCK;
+        let n = unsafe { libc::splice(from, ptr::null_mut(), to, ptr::null_mut(), len, flags) };
+        if n == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(n as usize)
+    }
+
+    // wait for the next event of the io
+    fn wait<T: AsIoData>(io: &T, timeout: Option<Duration>) -> io::Result<()> {
+        let waiter = WaitIo::new(io, timeout);
+        yield_with(&waiter);
+        waiter.done()
+    }
+
+    // return None if splice is not supported by the fds before anything is
+    // copied, so that the caller can fall back to the buffered copy
+    pub fn copy<R, W>(reader: &R, writer: &W) -> Option<io::Result<u64>>
+    where
+        R: AsRawFd + AsIoData,
+        W: AsRawFd + AsIoData,
+    {
+        let mut fds = [0; 2];
+        let flags = libc::O_NONBLOCK | libc::O_CLOEXEC;
+        if unsafe { libc::pipe2(fds.as_mut_ptr(), flags) } == -1 {
+            return None;
+        }
+        let pipe = Pipe(fds);
+        let (rfd, wfd) = (reader.as_raw_fd(), writer.as_raw_fd());
+        let mut total = 0;
+
+        loop {
+            // move the data from the reader into the pipe
+            reader.as_io_data().reset();
+            let n = match splice(rfd, pipe.0[1], BUF_SIZE) {
+                Ok(0) => return Some(Ok(total)),
+                Ok(n) => n,
+                Err(ref e) if e.raw_os_error() == Some(libc::EINVAL) && total == 0 => {
+                    return None
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    if let Err(e) = wait(reader, reader.io_read_timeout()) {
+                        return Some(Err(copy_error(e, "read")));
+                    }
+                    continue;
+                }
+                Err(e) => return Some(Err(copy_error(e, "read"))),
+            };
+
+            // drain the pipe into the writer
+            let mut left = n;
+            while left > 0 {
+                writer.as_io_data().reset();
+                match splice(pipe.0[0], wfd, left) {
+                    Ok(m) => {
+                        left -= m;
+                        total += m as u64;
+                    }
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        if let Err(e) = wait(writer, writer.io_write_timeout()) {
+                            return Some(Err(copy_error(e, "write")));
+                        }
+                    }
+                    Err(e) => return Some(Err(copy_error(e, "write"))),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use net::{TcpListener, TcpStream};
+    use os::unix::pipe;
+
+    #[test]
+    fn copy_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let data = (0..1024 * 1024).map(|i| i as u8).collect::<Vec<_>>();
+
+        let data1 = data.clone();
+        let client = go!(move || {
+            let mut s = TcpStream::connect(addr).unwrap();
+            s.write_all(&data1).unwrap();
+        });
+
+        let (mut src, _) = listener.accept().unwrap();
+        let (mut r, mut w) = pipe().unwrap();
+        let reader = go!(move || {
+            let mut buf = Vec::new();
+            r.read_to_end(&mut buf).unwrap();
+            buf
+        });
+
+        let n = go!(move || copy(&mut src, &mut w).unwrap()).join().unwrap();
+        client.join().unwrap();
+        assert_eq!(n, data.len() as u64);
+        assert_eq!(reader.join().unwrap(), data);
+    }
+
+    #[test]
+    fn copy_idle_peer_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        // the peer never sends anything
+        let _client = TcpStream::connect(addr).unwrap();
+        let (mut src, _) = listener.accept().unwrap();
+        src.set_read_timeout(Some(Duration::from_millis(100))).unwrap();
+        let (_r, mut w) = pipe().unwrap();
+
+        let err = go!(move || copy(&mut src, &mut w).unwrap_err())
+            .join()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(err.to_string().contains("read"));
+    }
+
+    #[test]
+    fn copy_in_thread() {
+        let (mut r1, mut w1) = pipe().unwrap();
+        let (mut r2, mut w2) = pipe().unwrap();
+        w1.write_all(b"hello").unwrap();
+        drop(w1);
+        assert_eq!(copy(&mut r1, &mut w2).unwrap(), 5);
+        drop(w2);
+        let mut s = String::new();
+        r2.read_to_string(&mut s).unwrap();
+        assert_eq!(s, "hello");
+    }
+
+    #[test]
+    fn copy_write_error() {
+        let (mut r1, mut w1) = pipe().unwrap();
+        let (r2, mut w2) = pipe().unwrap();
+        drop(r2);
+
+        let h = go!(move || {
+            let err = copy(&mut r1, &mut w2).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+            assert!(err.to_string().contains("write"));
+        });
+        ::coroutine::sleep(Duration::from_millis(10));
+        w1.write_all(b"hello").unwrap();
+        h.join().unwrap();
+    }
+}
@@ -0,0 +1,151 @@
+//! the deadline of a single io operation
+//!
+//! the remaining time of the deadline is used as the timeout of the wait that
+//! the operation yields on, the read/write timeout of the io object is not
+//! touched. so the clones of the same socket could run the operations with
+//! different deadlines at the same time
+
+use std::error;
+use std::fmt;
+use std::io;
+use std::os::unix::io::RawFd;
+use std::time::{Duration, Instant};
+
+use libc;
+use io::sys::poll_fd;
+
+/// The error payload of an operation that runs out of its deadline
+///
+/// it's wrapped in an `io::Error` of `ErrorKind::TimedOut` kind, the number
+/// of bytes already transferred before the deadline could be recovered by
+/// `DeadlineExceeded::from_io_error`
+///
+/// # Examples
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate may;
+///
+/// use std::time::{Duration, Instant};
+/// use may::io::DeadlineExceeded;
+/// use may::net::{TcpListener, TcpStream};
+///
+/// fn main() {
+///     let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+///     let addr = listener.local_addr().unwrap();
+///     let h = go!(move || {
+///         let s = TcpStream::connect(addr).unwrap();
+///         let mut buf = [0u8; 16];
+///         let deadline = Instant::now() + Duration::from_millis(20);
+///         // the peer sends nothing
+///         let e = s.read_exact_deadline(&mut buf, deadline).unwrap_err();
+///         DeadlineExceeded::from_io_error(&e).unwrap().transferred()
+///     });
+///     let _s = listener.accept().unwrap();
+///     assert_eq!(h.join().unwrap(), 0);
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeadlineExceeded {
+    transferred: usize,
+}
+
+impl DeadlineExceeded {
+    /// the number of bytes transferred before the deadline
+    pub fn transferred(&self) -> usize {
+        self.transferred
+    }
+
+    /// return the payload if the error is caused by a deadline
+    pub fn from_io_error(e: &io::Error) -> Option<&DeadlineExceeded> {
+        e.get_ref().and_then(|e| e.downcast_ref::<DeadlineExceeded>())
+    }
+}
+
+impl fmt::Display for DeadlineExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "deadline exceeded after {} bytes transferred",
+            self.transferred
+        )
+    }
+}
+
+impl error::Error for DeadlineExceeded {
+    fn description(&self) -> &str {
+        "deadline exceeded"
+    }
+}
+
+/// convert the timeout error to the deadline error
+pub(crate) fn exceeded(e: io::Error, transferred: usize) -> io::Error {
+    if e.kind() != io::ErrorKind::TimedOut {
+        return e;
+    }
+    io::Error::new(
+        io::ErrorKind::TimedOut,
+        DeadlineExceeded {
+            transferred: transferred,
+        },
+    )
+}
+
+/// the time left before the deadline, it's an error if already passed
+pub(crate) fn remaining(deadline: Instant) -> io::Result<Duration> {
+    let now = Instant::now();
+    if deadline <= now {
+        return Err(exceeded(io::ErrorKind::TimedOut.into(), 0));
+    }
+    Ok(deadline - now)
+}
+
+/// wait for the fd to be ready in thread context
+pub(crate) fn wait_fd(fd: RawFd, events: libc::c_short, deadline: Instant) -> io::Result<()> {
+    while !poll_fd(fd, events, Some(remaining(deadline)?))? {}
+    Ok(())
+}
+
+/// fill the whole buffer by the single reads
+pub(crate) fn read_exact<F>(buf: &mut [u8], mut read: F) -> io::Result<()>
+where
+    F: FnMut(&mut [u8]) -> io::Result<usize>,
+{
+    let mut n = 0;
+    while n < buf.len() {
+        match read(&mut buf[n..]) {
+            Ok(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "failed to fill whole buffer",
+                ))
+            }
+            Ok(m) => n += m,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(exceeded(e, n)),
+        }
+    }
+    Ok(())
+}
+
+/// write the whole buffer by the single writes
+pub(crate) fn write_all<F>(buf: &[u8], mut write: F) -> io::Result<()>
+where
+    F: FnMut(&[u8]) -> io::Result<usize>,
+{
+    let mut n = 0;
+    while n < buf.len() {
+        match write(&buf[n..]) {
+            Ok(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::WriteZero,
+                    "failed to write whole buffer",
+                ))
+            }
+            Ok(m) => n += m,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(exceeded(e, n)),
+        }
+    }
+    Ok(())
+}
@@ -1,4 +1,5 @@
 use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
 use super::sys::{Selector, SysEvent};
 use scheduler::get_scheduler;
 use coroutine_impl::{run_coroutine, CoroutineImpl};
@@ -27,12 +28,12 @@ impl EventLoop {
         Selector::new(io_workers, schedule_policy).map(|selector| EventLoop { selector: selector })
     }
 
-    /// Keep spinning the event loop indefinitely, and notify the handler whenever
-    /// any of the registered handles are ready.
-    pub fn run(&self, id: usize) -> io::Result<()> {
+    /// Keep spinning the event loop until `stop` is set, and notify the handler
+    /// whenever any of the registered handles are ready.
+    pub fn run(&self, id: usize, stop: &AtomicBool) -> io::Result<()> {
         let mut events_buf: [SysEvent; 1024] = unsafe { ::std::mem::uninitialized() };
         let mut next_expire = None;
-        loop {
+        while !stop.load(Ordering::Acquire) {
             next_expire = match self.selector.select(id, &mut events_buf, next_expire) {
                 Ok(v) => v,
                 Err(e) => {
@@ -41,6 +42,12 @@ impl EventLoop {
                 }
             }
         }
+        Ok(())
+    }
+
+    /// wake up all the event loops so that they could see the `stop` flag
+    pub fn wakeup_all(&self) {
+        self.selector.wakeup_all();
     }
 
     // get the internal selector
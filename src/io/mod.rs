@@ -11,6 +11,7 @@ pub(crate) mod sys;
 mod event_loop;
 
 use std::io;
+use std::time::Duration;
 use std::sync::atomic::{AtomicBool, Ordering};
 use coroutine_impl::is_coroutine;
 
@@ -19,6 +20,17 @@ pub(crate) use self::sys::{add_socket, cancel, net, IoData, Selector};
 
 pub trait AsIoData {
     fn as_io_data(&self) -> &IoData;
+
+    /// the read timeout for the helpers that wait on the io data directly,
+    /// such as `io::copy`
+    fn io_read_timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    /// the write timeout for the helpers that wait on the io data directly
+    fn io_write_timeout(&self) -> Option<Duration> {
+        None
+    }
 }
 
 #[derive(Debug)]
@@ -95,3 +107,16 @@ impl IoContext {
 // export the generic IO wrapper
 pub mod co_io_err;
 pub use self::sys::co_io::CoIo;
+
+#[cfg(unix)]
+mod copy;
+#[cfg(unix)]
+pub use self::copy::copy;
+
+#[cfg(unix)]
+pub use self::sys::ReadyEvent;
+
+#[cfg(unix)]
+pub(crate) mod deadline;
+#[cfg(unix)]
+pub use self::deadline::DeadlineExceeded;
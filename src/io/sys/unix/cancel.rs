@@ -22,6 +22,10 @@ impl CancelIo for CancelIoImpl {
         self.0.take_fast(Ordering::Relaxed);
     }
 
+    fn is_set(&self) -> bool {
+        !self.0.is_none()
+    }
+
     unsafe fn cancel(&self) {
         self.0.take(Ordering::Acquire).map(|e| {
             e.co
@@ -2,6 +2,11 @@
 //! `CoIo` is a generic wrapper type that can be used in coroutine
 //! context with non blocking operations
 //!
+//! any type that implements `AsRawFd` can be wrapped, e.g. pipes, ttys,
+//! serial ports or ptys. regular files are not supported, `epoll` and
+//! `kqueue` always report them ready so the read/write would still block
+//! the worker thread, `CoIo::new` returns an `InvalidInput` error for them
+//!
 
 use libc;
 use io as io_impl;
@@ -10,7 +15,7 @@ use self::io_impl::net as net_impl;
 use self::io_impl::co_io_err::Error;
 
 use std::time::Duration;
-use std::io::{self, Read, Write};
+use std::io::{self, IoSlice, IoSliceMut, Read, Write};
 use std::os::unix::io::{AsRawFd, IntoRawFd, RawFd};
 
 fn set_nonblocking<T: AsRawFd>(fd: &T, nb: bool) -> io::Result<()> {
@@ -34,12 +39,30 @@ fn set_nonblocking<T: AsRawFd>(fd: &T, nb: bool) -> io::Result<()> {
     }
 }
 
+// regular files are always ready for the selector
+fn check_not_regular_file<T: AsRawFd>(fd: &T) -> io::Result<()> {
+    let mut stat: libc::stat = unsafe { ::std::mem::zeroed() };
+    if unsafe { libc::fstat(fd.as_raw_fd(), &mut stat) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    if stat.st_mode & libc::S_IFMT == libc::S_IFREG {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "regular files are not supported by CoIo",
+        ));
+    }
+    Ok(())
+}
+
 /// Generic wrapper for any type that can be converted to raw `fd/HANDLE`
 /// this type can be used in coroutine context without blocking the thread
 #[derive(Debug)]
 pub struct CoIo<T: AsRawFd> {
-    inner: T,
+    // io must be dropped before inner, so that the fd is removed
+    // from the selector before it's closed
     io: io_impl::IoData,
+    inner: T,
     ctx: io_impl::IoContext,
     read_timeout: Option<Duration>,
     write_timeout: Option<Duration>,
@@ -49,6 +72,14 @@ impl<T: AsRawFd> io_impl::AsIoData for CoIo<T> {
     fn as_io_data(&self) -> &io_impl::IoData {
         &self.io
     }
+
+    fn io_read_timeout(&self) -> Option<Duration> {
+        self.read_timeout
+    }
+
+    fn io_write_timeout(&self) -> Option<Duration> {
+        self.write_timeout
+    }
 }
 
 impl<T: AsRawFd> AsRawFd for CoIo<T> {
@@ -65,7 +96,15 @@ impl<T: AsRawFd + IntoRawFd> IntoRawFd for CoIo<T> {
 
 impl<T: AsRawFd> CoIo<T> {
     /// create `CoIo` instance from `T`
+    ///
+    /// the fd is set to non blocking mode and registered to the selector.
+    /// return an `InvalidInput` error if `T` is a regular file, the
+    /// original io object can be taken back by `Error::into_data`
     pub fn new(io: T) -> Result<Self, Error<T>> {
+        if let Err(e) = check_not_regular_file(&io) {
+            return Err(Error::new(e, io));
+        }
+
         let io_data = match io_impl::add_socket(&io) {
             Ok(o) => o,
             Err(e) => return Err(Error::new(e, io)),
@@ -113,7 +152,7 @@ impl<T: AsRawFd> CoIo<T> {
     }
 
     /// get inner mut ref
-    pub fn inner_mut(&mut self) -> &T {
+    pub fn inner_mut(&mut self) -> &mut T {
         &mut self.inner
     }
 
@@ -151,6 +190,38 @@ impl<T: AsRawFd> CoIo<T> {
         self.ctx.set_nonblocking(nb);
         Ok(())
     }
+
+    /// wait until the io is readable without reading anything
+    ///
+    /// it's for the code that does the io on the raw fd by itself, only the
+    /// current coroutine is blocked and the wait is limited by the read
+    /// timeout. the readiness may be already gone when the caller reads,
+    /// e.g. taken by another reader of the same fd, so a following read
+    /// could still return `WouldBlock` and the caller should wait again
+    pub fn wait_read(&self) -> io::Result<()> {
+        self.read_ready_event().wait()
+    }
+
+    /// wait until the io is writable without writing anything
+    ///
+    /// the same as `wait_read`, but for the write and limited by the
+    /// write timeout
+    pub fn wait_write(&self) -> io::Result<()> {
+        self.write_ready_event().wait()
+    }
+
+    /// return the event that the io is readable, for `cqueue_add!`
+    ///
+    /// see `TcpStream::read_ready_event`, the io should be read in the
+    /// bottom half of the arm
+    pub fn read_ready_event(&self) -> io_impl::ReadyEvent<Self> {
+        io_impl::ReadyEvent::new(self, libc::POLLIN, self.read_timeout, CoIo::ctx_check)
+    }
+
+    /// return the event that the io is writable, for `cqueue_add!`
+    pub fn write_ready_event(&self) -> io_impl::ReadyEvent<Self> {
+        io_impl::ReadyEvent::new(self, libc::POLLOUT, self.write_timeout, CoIo::ctx_check)
+    }
 }
 
 impl<T: AsRawFd + Read> Read for CoIo<T> {
@@ -172,6 +243,24 @@ impl<T: AsRawFd + Read> Read for CoIo<T> {
         yield_with(&reader);
         reader.done()
     }
+
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut]) -> io::Result<usize> {
+        if !self.ctx_check()? {
+            // this can't be nonblocking!!
+            return self.inner.read_vectored(bufs);
+        }
+
+        self.io.reset();
+        // this is an earlier return try for nonblocking read
+        match self.inner.read_vectored(bufs) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            ret => return ret,
+        }
+
+        let reader = net_impl::SocketReadVectored::new(self, bufs, self.read_timeout);
+        yield_with(&reader);
+        reader.done()
+    }
 }
 
 impl<T: AsRawFd + Write> Write for CoIo<T> {
@@ -193,6 +282,24 @@ impl<T: AsRawFd + Write> Write for CoIo<T> {
         writer.done()
     }
 
+    fn write_vectored(&mut self, bufs: &[IoSlice]) -> io::Result<usize> {
+        if !self.ctx_check()? {
+            // this can't be nonblocking!!
+            return self.inner.write_vectored(bufs);
+        }
+
+        self.io.reset();
+        // this is an earlier return try for nonblocking write
+        match self.inner.write_vectored(bufs) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            ret => return ret,
+        }
+
+        let writer = net_impl::SocketWriteVectored::new(self, bufs, self.write_timeout);
+        yield_with(&writer);
+        writer.done()
+    }
+
     fn flush(&mut self) -> io::Result<()> {
         self.inner.flush()
     }
@@ -203,6 +310,11 @@ impl<'a, T: AsRawFd + Read> Read for &'a CoIo<T> {
         let s = unsafe { &mut *(*self as *const _ as *mut _) };
         CoIo::<T>::read(s, buf)
     }
+
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut]) -> io::Result<usize> {
+        let s = unsafe { &mut *(*self as *const _ as *mut _) };
+        CoIo::<T>::read_vectored(s, bufs)
+    }
 }
 
 impl<'a, T: AsRawFd + Write> Write for &'a CoIo<T> {
@@ -211,6 +323,11 @@ impl<'a, T: AsRawFd + Write> Write for &'a CoIo<T> {
         CoIo::<T>::write(s, buf)
     }
 
+    fn write_vectored(&mut self, bufs: &[IoSlice]) -> io::Result<usize> {
+        let s = unsafe { &mut *(*self as *const _ as *mut _) };
+        CoIo::<T>::write_vectored(s, bufs)
+    }
+
     fn flush(&mut self) -> io::Result<()> {
         let s = unsafe { &mut *(*self as *const _ as *mut _) };
         CoIo::<T>::flush(s)
@@ -220,6 +337,8 @@ impl<'a, T: AsRawFd + Write> Write for &'a CoIo<T> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::fs::File;
+    use std::os::unix::io::FromRawFd;
 
     #[allow(dead_code)]
     fn compile_co_io() {
@@ -245,4 +364,135 @@ mod tests {
         let mut buf = [0u8; 100];
         io.read(&mut buf).unwrap();
     }
+
+    fn pipe() -> (File, File) {
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) }
+    }
+
+    #[test]
+    fn pipe_read_write() {
+        let (r, w) = pipe();
+        let mut r = CoIo::new(r).unwrap();
+        let mut w = CoIo::new(w).unwrap();
+
+        let reader = go!(move || {
+            // block on the empty pipe until the writer sends the data
+            let mut buf = [0u8; 5];
+            r.read_exact(&mut buf).unwrap();
+            assert_eq!(&buf, b"hello");
+            let mut rest = Vec::new();
+            r.read_to_end(&mut rest).unwrap();
+            assert_eq!(rest, b" world");
+        });
+
+        let writer = go!(move || {
+            ::coroutine::sleep(Duration::from_millis(10));
+            w.write_all(b"hello").unwrap();
+            ::coroutine::sleep(Duration::from_millis(10));
+            w.write_all(b" world").unwrap();
+            // close the write end so the reader sees EOF
+        });
+
+        writer.join().unwrap();
+        reader.join().unwrap();
+    }
+
+    #[test]
+    fn pipe_read_timeout() {
+        let (r, w) = pipe();
+        let mut r = CoIo::new(r).unwrap();
+        r.set_read_timeout(Some(Duration::from_millis(10))).unwrap();
+
+        let h = go!(move || {
+            let mut buf = [0u8; 1];
+            let err = r.read(&mut buf).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+            drop(w);
+        });
+        h.join().unwrap();
+    }
+
+    #[test]
+    fn regular_file() {
+        let file = File::open(file!()).unwrap();
+        let fd = file.as_raw_fd();
+        let err = CoIo::new(file).unwrap_err();
+        assert_eq!(err.to_string(), "regular files are not supported by CoIo");
+        // the file is given back on error
+        let file = err.into_data();
+        assert_eq!(file.as_raw_fd(), fd);
+        let err: io::Error = CoIo::new(file).unwrap_err().into();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    // read to EOF by the raw read(2) that is gated on wait_read
+    fn raw_read_to_end<T: AsRawFd>(io: &CoIo<T>) -> io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        let mut buf = [0u8; 1024];
+        loop {
+            io.wait_read()?;
+            let n = unsafe {
+                libc::read(io.as_raw_fd(), buf.as_mut_ptr() as *mut libc::c_void, buf.len())
+            };
+            match n {
+                0 => return Ok(data),
+                n if n > 0 => data.extend_from_slice(&buf[..n as usize]),
+                _ => {
+                    let e = io::Error::last_os_error();
+                    if e.kind() != io::ErrorKind::WouldBlock {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn wait_read_raw_fd() {
+        let expected = (0..64 * 1024).map(|i| i as u8).collect::<Vec<_>>();
+        let send = |w: File| {
+            let data = expected.clone();
+            let mut w = CoIo::new(w).unwrap();
+            go!(move || for chunk in data.chunks(10 * 1024) {
+                ::coroutine::sleep(Duration::from_millis(5));
+                w.write_all(chunk).unwrap();
+            })
+        };
+
+        // the raw read loop gets the same data as the built-in read
+        let (r, w) = pipe();
+        let r = CoIo::new(r).unwrap();
+        let writer = send(w);
+        let raw = go!(move || raw_read_to_end(&r).unwrap());
+        writer.join().unwrap();
+        assert_eq!(raw.join().unwrap(), expected);
+
+        let (r, w) = pipe();
+        let mut r = CoIo::new(r).unwrap();
+        let writer = send(w);
+        let builtin = go!(move || {
+            let mut data = Vec::new();
+            r.read_to_end(&mut data).unwrap();
+            data
+        });
+        writer.join().unwrap();
+        assert_eq!(builtin.join().unwrap(), expected);
+    }
+
+    #[test]
+    fn wait_write_timeout() {
+        let (_r, w) = pipe();
+        let w = CoIo::new(w).unwrap();
+        let j = go!(move || {
+            w.set_write_timeout(Some(Duration::from_millis(50))).unwrap();
+            // the pipe is writable until it's filled up
+            w.wait_write().unwrap();
+            let buf = [0u8; 4096];
+            while (&w).write(&buf).is_ok() {}
+            w.wait_write().unwrap_err().kind()
+        });
+        assert_eq!(j.join().unwrap(), io::ErrorKind::TimedOut);
+    }
 }
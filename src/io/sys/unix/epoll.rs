@@ -4,14 +4,15 @@ use std::sync::Arc;
 use std::time::Duration;
 use std::{cmp, io, isize, ptr};
 
-use super::{from_nix_error, timeout_handler, EventData, IoData, TimerList};
+use super::{from_nix_error, timeout_handler, EventData, IoData, TimerHandle, TimerList};
 use coroutine_impl::CoroutineImpl;
+use scheduler::count_io_events;
 use libc::{eventfd, EFD_NONBLOCK};
 use may_queue::mpsc_list::Queue as mpsc;
 use nix::sys::epoll::*;
 use nix::unistd::{close, read, write};
 use smallvec::SmallVec;
-use timeout_list::{now, ns_to_ms};
+use timeout_list::{now, ns_to_ms, remove_timer};
 
 fn create_eventfd() -> io::Result<RawFd> {
     let fd = unsafe { eventfd(0, EFD_NONBLOCK) };
@@ -28,6 +29,8 @@ struct SingleSelector {
     evfd: RawFd,
     timer_list: TimerList,
     free_ev: mpsc<Arc<EventData>>,
+    // the io timers removed by the workers
+    free_timer: mpsc<TimerHandle>,
 }
 
 impl SingleSelector {
@@ -45,6 +48,7 @@ impl SingleSelector {
             epfd: epfd,
             evfd: evfd,
             free_ev: mpsc::new(),
+            free_timer: mpsc::new(),
             timer_list: TimerList::new(),
         })
     }
@@ -100,6 +104,7 @@ impl Selector {
         epoll_ctl(epfd, EpollOp::EpollCtlDel, evfd, &mut ev).ok();
         epoll_ctl(epfd, EpollOp::EpollCtlAdd, evfd, &mut ev).ok();
 
+        let mut got = 0;
         for event in events[..n].iter() {
             if event.data() == 0 {
                 // this is just a wakeup event, ignore it
@@ -109,6 +114,7 @@ impl Selector {
                 info!("got wakeup event in select, id={}", id);
                 continue;
             }
+            got += 1;
             let data = unsafe { &mut *(event.data() as *mut EventData) };
             // info!("select got event, data={:p}", data);
             data.io_flag.store(true, Ordering::Relaxed);
@@ -127,16 +133,24 @@ impl Selector {
                     // it's not always true that you can really remove the timer entry
                     h.get_data().data.event_data = ptr::null_mut();
                 }
-                h.remove()
+                remove_timer(h)
             });
 
             // schedule the coroutine
             (self.schedule_policy)(co);
         }
 
+        count_io_events(got);
+
         // free the unused event_data
         self.free_unused_event_data(id);
 
+        // remove the timers that the workers give up
+        let free_timer = &self.vec[id].free_timer;
+        while let Some(h) = free_timer.pop() {
+            remove_timer(h);
+        }
+
         // deal with the timer list
         let next_expire = self.vec[id]
             .timer_list
@@ -144,6 +158,13 @@ impl Selector {
         Ok(next_expire)
     }
 
+    // wake up all the event loops
+    pub fn wakeup_all(&self) {
+        for id in 0..self.vec.len() {
+            self.wakeup(id);
+        }
+    }
+
     // this will post an os event so that we can wake up the event loop
     #[inline]
     fn wakeup(&self, id: usize) {
@@ -203,6 +224,14 @@ impl Selector {
         while let Some(_) = free_ev.pop() {}
     }
 
+    // the timer list can only be changed by the selector thread that runs
+    // it, so the timer removed by a worker is sent to that thread
+    #[inline]
+    pub fn del_io_timer(&self, io: &EventData, h: TimerHandle) {
+        let id = io.fd as usize % self.vec.len();
+        self.vec[id].free_timer.push(h);
+    }
+
     // register the io request to the timeout list
     #[inline]
     pub fn add_io_timer(&self, io: &IoData, timeout: Option<Duration>) {
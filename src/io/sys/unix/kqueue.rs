@@ -7,10 +7,11 @@ use std::sync::atomic::Ordering;
 use libc;
 use smallvec::SmallVec;
 use coroutine_impl::CoroutineImpl;
-use timeout_list::{now, ns_to_dur};
+use scheduler::count_io_events;
+use timeout_list::{now, ns_to_dur, remove_timer};
 use may_queue::mpsc_list::Queue as mpsc;
 
-use super::{timeout_handler, EventData, IoData, TimerList};
+use super::{timeout_handler, EventData, IoData, TimerHandle, TimerList};
 
 pub type SysEvent = libc::kevent;
 
@@ -34,6 +35,8 @@ struct SingleSelector {
     kqfd: RawFd,
     timer_list: TimerList,
     free_ev: mpsc<Arc<EventData>>,
+    // the io timers removed by the workers
+    free_timer: mpsc<TimerHandle>,
 }
 
 impl SingleSelector {
@@ -60,6 +63,7 @@ impl SingleSelector {
         Ok(SingleSelector {
             kqfd: kqfd,
             free_ev: mpsc::new(),
+            free_timer: mpsc::new(),
             timer_list: TimerList::new(),
         })
     }
@@ -130,6 +134,7 @@ impl Selector {
 
         let n = n as usize;
 
+        let mut got = 0;
         for event in events[..n].iter() {
             if event.udata == ptr::null_mut() {
                 // this is just a wakeup event, ignore it
@@ -139,6 +144,7 @@ impl Selector {
                 info!("got wakeup event in select, id={}", id);
                 continue;
             }
+            got += 1;
             let data = unsafe { &mut *(event.udata as *mut EventData) };
             // info!("select got event, data={:p}", data);
             data.io_flag.store(true, Ordering::Relaxed);
@@ -157,16 +163,24 @@ impl Selector {
                     // it's not always true that you can really remove the timer entry
                     h.get_data().data.event_data = ptr::null_mut();
                 }
-                h.remove()
+                remove_timer(h)
             });
 
             // schedule the coroutine
             (self.schedule_policy)(co);
         }
 
+        count_io_events(got);
+
         // free the unused event_data
         self.free_unused_event_data(id);
 
+        // remove the timers that the workers give up
+        let free_timer = &self.vec[id].free_timer;
+        while let Some(h) = free_timer.pop() {
+            remove_timer(h);
+        }
+
         // deal with the timer list
         let next_expire = self.vec[id]
             .timer_list
@@ -174,6 +188,13 @@ impl Selector {
         Ok(next_expire)
     }
 
+    // wake up all the event loops
+    pub fn wakeup_all(&self) {
+        for id in 0..self.vec.len() {
+            self.wakeup(id);
+        }
+    }
+
     // this will post an os event so that we can wakeup the event loop
     #[inline]
     fn wakeup(&self, id: usize) {
@@ -272,6 +293,14 @@ impl Selector {
         while let Some(_) = free_ev.pop() {}
     }
 
+    // the timer list can only be changed by the selector thread that runs
+    // it, so the timer removed by a worker is sent to that thread
+    #[inline]
+    pub fn del_io_timer(&self, io: &EventData, h: TimerHandle) {
+        let id = io.fd as usize % self.vec.len();
+        self.vec[id].free_timer.push(h);
+    }
+
     // register the io request to the timeout list
     #[inline]
     pub fn add_io_timer(&self, io: &IoData, timeout: Option<Duration>) {
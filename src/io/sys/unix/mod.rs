@@ -10,6 +10,7 @@ mod select;
 pub mod net;
 pub mod co_io;
 pub mod cancel;
+mod wait_io;
 
 use std::sync::Arc;
 use std::ops::Deref;
@@ -20,11 +21,13 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use nix;
 use sync::AtomicOption;
 use scheduler::get_scheduler;
+use cancel::cancel_err;
 use yield_now::{get_co_para, set_co_para};
-use coroutine_impl::{run_coroutine, CoroutineImpl};
+use coroutine_impl::{current_cancel_data, run_coroutine, CoroutineImpl};
 use timeout_list::{TimeOutList, TimeoutHandle};
 
 pub use self::select::{Selector, SysEvent};
+pub use self::wait_io::{poll_fd, ReadyEvent, WaitIo};
 
 #[inline]
 pub fn add_socket<T: AsRawFd + ?Sized>(t: &T) -> io::Result<IoData> {
@@ -37,11 +40,13 @@ fn del_socket(io: &IoData) {
     get_scheduler().get_selector().del_fd(io.clone());
 }
 
-// deal with the io result
+// deal with the io result, the timeout is reported as `TimedOut` and
+// the cancel as the `CancelError` payload
 #[inline]
 fn co_io_result() -> io::Result<()> {
     match get_co_para() {
         Some(err) => return Err(err),
+        None if current_cancel_data().is_canceled() => return Err(cancel_err()),
         None => return Ok(()),
     }
 }
@@ -130,14 +135,14 @@ impl EventData {
             Some(co) => co,
         };
 
-        // it's safe to remove the timer since we are running the timer_list in the same thread
+        // the timer is removed by the selector thread that runs the timer_list
         self.timer.borrow_mut().take().map(|h| {
             unsafe {
                 // tell the timer function not to cancel the io
                 // it's not always true that you can really remove the timer entry
                 h.get_data().data.event_data = ptr::null_mut();
             }
-            h.remove()
+            get_scheduler().get_selector().del_io_timer(self, h)
         });
 
         // schedule the coroutine
@@ -1,5 +1,11 @@
 mod socket_read;
 mod socket_write;
+mod socket_peek;
+mod socket_read_vectored;
+mod socket_write_vectored;
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "ios",
+          target_os = "freebsd"))]
+mod socket_send_file;
 mod tcp_stream_connect;
 mod tcp_listener_accpet;
 mod udp_send_to;
@@ -8,9 +14,21 @@ mod unix_stream_connect;
 mod unix_listener_accpet;
 mod unix_send_to;
 mod unix_recv_from;
+mod unix_send_fds;
+mod unix_recv_fds;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod unix_seqpacket_accept;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod unix_seqpacket_recv;
 
 pub use self::socket_read::SocketRead;
 pub use self::socket_write::SocketWrite;
+pub use self::socket_peek::{peek, SocketPeek};
+pub use self::socket_read_vectored::SocketReadVectored;
+pub use self::socket_write_vectored::SocketWriteVectored;
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "ios",
+          target_os = "freebsd"))]
+pub use self::socket_send_file::{send_file, SocketSendFile};
 pub use self::tcp_stream_connect::TcpStreamConnect;
 pub use self::tcp_listener_accpet::TcpListenerAccept;
 pub use self::udp_send_to::UdpSendTo;
@@ -19,3 +37,9 @@ pub use self::unix_stream_connect::UnixStreamConnect;
 pub use self::unix_listener_accpet::UnixListenerAccept;
 pub use self::unix_send_to::UnixSendTo;
 pub use self::unix_recv_from::UnixRecvFrom;
+pub use self::unix_send_fds::{send_with_fds, UnixSendFds};
+pub use self::unix_recv_fds::{recv_with_fds, UnixRecvFds};
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use self::unix_seqpacket_accept::UnixSeqpacketAccept;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use self::unix_seqpacket_recv::{recv_packet, UnixSeqpacketRecv};
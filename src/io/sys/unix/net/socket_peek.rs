@@ -0,0 +1,96 @@
+use std::io;
+use std::ops::Deref;
+use std::time::Duration;
+use std::sync::atomic::Ordering;
+use std::os::unix::io::RawFd;
+use libc;
+use io::AsIoData;
+use yield_now::yield_with;
+use scheduler::get_scheduler;
+use sync::delay_drop::DelayDrop;
+use super::super::{co_io_result, IoData};
+use coroutine_impl::{co_cancel_data, CoroutineImpl, EventSource};
+
+// read from the socket without removing the data from the queue
+pub fn peek(fd: RawFd, buf: &mut [u8]) -> io::Result<usize> {
+    let ret = unsafe {
+        libc::recv(
+            fd,
+            buf.as_mut_ptr() as *mut libc::c_void,
+            buf.len(),
+            libc::MSG_PEEK,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(ret as usize)
+}
+
+pub struct SocketPeek<'a> {
+    io_data: &'a IoData,
+    buf: &'a mut [u8],
+    timeout: Option<Duration>,
+    can_drop: DelayDrop,
+}
+
+impl<'a> SocketPeek<'a> {
+    pub fn new<T: AsIoData>(s: &'a T, buf: &'a mut [u8], timeout: Option<Duration>) -> Self {
+        SocketPeek {
+            io_data: s.as_io_data(),
+            buf: buf,
+            timeout: timeout,
+            can_drop: DelayDrop::new(),
+        }
+    }
+
+    #[inline]
+    pub fn done(self) -> io::Result<usize> {
+        loop {
+            co_io_result()?;
+
+            // clear the io_flag
+            self.io_data.io_flag.store(false, Ordering::Relaxed);
+
+            // the data is left in the socket, a following read would get it
+            // without waiting for another event
+            match peek(self.io_data.fd, self.buf) {
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                ret => return ret,
+            }
+
+            if self.io_data.io_flag.swap(false, Ordering::Relaxed) {
+                continue;
+            }
+
+            // the result is still WouldBlock, need to try again
+            self.can_drop.reset();
+            yield_with(&self);
+        }
+    }
+}
+
+impl<'a> EventSource for SocketPeek<'a> {
+    fn subscribe(&mut self, co: CoroutineImpl) {
+        // when exit the scope the `can_drop` will be set to true
+        let _g = self.can_drop.delay_drop();
+
+        let cancel = co_cancel_data(&co);
+        get_scheduler()
+            .get_selector()
+            .add_io_timer(self.io_data, self.timeout);
+        self.io_data.co.swap(co, Ordering::Release);
+
+        // there is event, re-run the coroutine
+        if self.io_data.io_flag.load(Ordering::Relaxed) {
+            return self.io_data.schedule();
+        }
+
+        // register the cancel io data
+        cancel.set_io(self.io_data.deref().clone());
+        // re-check the cancel status
+        if cancel.is_canceled() {
+            unsafe { cancel.cancel() };
+        }
+    }
+}
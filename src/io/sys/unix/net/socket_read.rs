@@ -1,8 +1,9 @@
 use std::io;
 use std::ops::Deref;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::sync::atomic::Ordering;
 use io::AsIoData;
+use io::deadline;
 use nix::unistd::read;
 use yield_now::yield_with;
 use scheduler::get_scheduler;
@@ -14,6 +15,8 @@ pub struct SocketRead<'a> {
     io_data: &'a IoData,
     buf: &'a mut [u8],
     timeout: Option<Duration>,
+    // each wait is limited by the time left before it
+    deadline: Option<Instant>,
     can_drop: DelayDrop,
 }
 
@@ -23,12 +26,19 @@ impl<'a> SocketRead<'a> {
             io_data: s.as_io_data(),
             buf: buf,
             timeout: timeout,
+            deadline: None,
             can_drop: DelayDrop::new(),
         }
     }
 
+    pub fn new_until<T: AsIoData>(s: &'a T, buf: &'a mut [u8], deadline: Instant) -> io::Result<Self> {
+        let mut io = SocketRead::new(s, buf, Some(deadline::remaining(deadline)?));
+        io.deadline = Some(deadline);
+        Ok(io)
+    }
+
     #[inline]
-    pub fn done(self) -> io::Result<usize> {
+    pub fn done(mut self) -> io::Result<usize> {
         loop {
             co_io_result()?;
 
@@ -46,6 +56,9 @@ impl<'a> SocketRead<'a> {
             }
 
             // the result is still WouldBlock, need to try again
+            if let Some(deadline) = self.deadline {
+                self.timeout = Some(deadline::remaining(deadline)?);
+            }
             self.can_drop.reset();
             yield_with(&self);
         }
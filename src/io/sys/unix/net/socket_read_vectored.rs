@@ -0,0 +1,92 @@
+use std::io::{self, IoSliceMut};
+use std::ops::Deref;
+use std::time::Duration;
+use std::sync::atomic::Ordering;
+use libc;
+use io::AsIoData;
+use yield_now::yield_with;
+use scheduler::get_scheduler;
+use sync::delay_drop::DelayDrop;
+use super::super::{co_io_result, IoData};
+use coroutine_impl::{co_cancel_data, CoroutineImpl, EventSource};
+
+pub struct SocketReadVectored<'a, 'b: 'a> {
+    io_data: &'a IoData,
+    bufs: &'a mut [IoSliceMut<'b>],
+    timeout: Option<Duration>,
+    can_drop: DelayDrop,
+}
+
+impl<'a, 'b> SocketReadVectored<'a, 'b> {
+    pub fn new<T: AsIoData>(
+        s: &'a T,
+        bufs: &'a mut [IoSliceMut<'b>],
+        timeout: Option<Duration>,
+    ) -> Self {
+        SocketReadVectored {
+            io_data: s.as_io_data(),
+            bufs: bufs,
+            timeout: timeout,
+            can_drop: DelayDrop::new(),
+        }
+    }
+
+    #[inline]
+    pub fn done(self) -> io::Result<usize> {
+        loop {
+            co_io_result()?;
+
+            // clear the io_flag
+            self.io_data.io_flag.store(false, Ordering::Relaxed);
+
+            // finish the read operation
+            match readv(self.io_data.fd, self.bufs) {
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                ret => return ret,
+            }
+
+            if self.io_data.io_flag.swap(false, Ordering::Relaxed) {
+                continue;
+            }
+
+            // the result is still WouldBlock, need to try again
+            self.can_drop.reset();
+            yield_with(&self);
+        }
+    }
+}
+
+// `IoSliceMut` is guaranteed to be ABI compatible with `iovec` on unix
+fn readv(fd: libc::c_int, bufs: &mut [IoSliceMut]) -> io::Result<usize> {
+    let cnt = ::std::cmp::min(bufs.len(), libc::c_int::max_value() as usize);
+    let ret = unsafe { libc::readv(fd, bufs.as_ptr() as *const libc::iovec, cnt as libc::c_int) };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(ret as usize)
+}
+
+impl<'a, 'b> EventSource for SocketReadVectored<'a, 'b> {
+    fn subscribe(&mut self, co: CoroutineImpl) {
+        // when exit the scope the `can_drop` will be set to true
+        let _g = self.can_drop.delay_drop();
+
+        let cancel = co_cancel_data(&co);
+        get_scheduler()
+            .get_selector()
+            .add_io_timer(self.io_data, self.timeout);
+        self.io_data.co.swap(co, Ordering::Release);
+
+        // there is event, re-run the coroutine
+        if self.io_data.io_flag.load(Ordering::Relaxed) {
+            return self.io_data.schedule();
+        }
+
+        // register the cancel io data
+        cancel.set_io(self.io_data.deref().clone());
+        // re-check the cancel status
+        if cancel.is_canceled() {
+            unsafe { cancel.cancel() };
+        }
+    }
+}
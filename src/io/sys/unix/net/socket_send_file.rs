@@ -0,0 +1,139 @@
+use std::io;
+use std::ops::Deref;
+use std::time::Duration;
+use std::sync::atomic::Ordering;
+use std::os::unix::io::RawFd;
+use libc;
+use io::AsIoData;
+use yield_now::yield_with;
+use scheduler::get_scheduler;
+use sync::delay_drop::DelayDrop;
+use super::super::{co_io_result, IoData};
+use coroutine_impl::{co_cancel_data, CoroutineImpl, EventSource};
+
+// send at most len bytes of the file from the offset, the file offset is
+// not changed. return 0 if the offset is at or beyond the EOF
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn send_file(fd: RawFd, file: RawFd, offset: u64, len: usize) -> io::Result<usize> {
+    let mut off = offset as libc::off_t;
+    let ret = unsafe { libc::sendfile(fd, file, &mut off, len) };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(ret as usize)
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+pub fn send_file(fd: RawFd, file: RawFd, offset: u64, len: usize) -> io::Result<usize> {
+    let mut sent = len as libc::off_t;
+    let ret = unsafe {
+        libc::sendfile(
+            file,
+            fd,
+            offset as libc::off_t,
+            &mut sent,
+            ::std::ptr::null_mut(),
+            0,
+        )
+    };
+    // a partial send is reported together with EAGAIN or EINTR
+    if ret < 0 && sent == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(sent as usize)
+}
+
+#[cfg(target_os = "freebsd")]
+pub fn send_file(fd: RawFd, file: RawFd, offset: u64, len: usize) -> io::Result<usize> {
+    let mut sent: libc::off_t = 0;
+    let ret = unsafe {
+        libc::sendfile(
+            file,
+            fd,
+            offset as libc::off_t,
+            len,
+            ::std::ptr::null_mut(),
+            &mut sent,
+            0,
+        )
+    };
+    // a partial send is reported together with EAGAIN or EINTR
+    if ret < 0 && sent == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(sent as usize)
+}
+
+pub struct SocketSendFile<'a> {
+    io_data: &'a IoData,
+    file: RawFd,
+    offset: u64,
+    len: usize,
+    timeout: Option<Duration>,
+    can_drop: DelayDrop,
+}
+
+impl<'a> SocketSendFile<'a> {
+    pub fn new<T: AsIoData>(
+        s: &'a T,
+        file: RawFd,
+        offset: u64,
+        len: usize,
+        timeout: Option<Duration>,
+    ) -> Self {
+        SocketSendFile {
+            io_data: s.as_io_data(),
+            file: file,
+            offset: offset,
+            len: len,
+            timeout: timeout,
+            can_drop: DelayDrop::new(),
+        }
+    }
+
+    #[inline]
+    pub fn done(self) -> io::Result<usize> {
+        loop {
+            co_io_result()?;
+
+            // clear the io_flag
+            self.io_data.io_flag.store(false, Ordering::Relaxed);
+
+            match send_file(self.io_data.fd, self.file, self.offset, self.len) {
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                ret => return ret,
+            }
+
+            if self.io_data.io_flag.swap(false, Ordering::Relaxed) {
+                continue;
+            }
+
+            // the result is still WouldBlock, need to try again
+            self.can_drop.reset();
+            yield_with(&self);
+        }
+    }
+}
+
+impl<'a> EventSource for SocketSendFile<'a> {
+    fn subscribe(&mut self, co: CoroutineImpl) {
+        let _g = self.can_drop.delay_drop();
+        let cancel = co_cancel_data(&co);
+        get_scheduler()
+            .get_selector()
+            .add_io_timer(self.io_data, self.timeout);
+        self.io_data.co.swap(co, Ordering::Release);
+
+        // there is event, re-run the coroutine
+        if self.io_data.io_flag.load(Ordering::Relaxed) {
+            return self.io_data.schedule();
+        }
+
+        // register the cancel io data
+        cancel.set_io(self.io_data.deref().clone());
+        // re-check the cancel status
+        if cancel.is_canceled() {
+            unsafe { cancel.cancel() };
+        }
+    }
+}
@@ -1,18 +1,22 @@
 use std::io;
-use std::time::Duration;
+use std::ops::Deref;
+use std::time::{Duration, Instant};
 use std::sync::atomic::Ordering;
 use io::AsIoData;
+use io::deadline;
 use nix::unistd::write;
 use yield_now::yield_with;
 use scheduler::get_scheduler;
 use sync::delay_drop::DelayDrop;
-use coroutine_impl::{CoroutineImpl, EventSource};
+use coroutine_impl::{co_cancel_data, CoroutineImpl, EventSource};
 use super::super::{co_io_result, from_nix_error, IoData};
 
 pub struct SocketWrite<'a> {
     io_data: &'a IoData,
     buf: &'a [u8],
     timeout: Option<Duration>,
+    // each wait is limited by the time left before it
+    deadline: Option<Instant>,
     can_drop: DelayDrop,
 }
 
@@ -22,12 +26,19 @@ impl<'a> SocketWrite<'a> {
             io_data: s.as_io_data(),
             buf: buf,
             timeout: timeout,
+            deadline: None,
             can_drop: DelayDrop::new(),
         }
     }
 
+    pub fn new_until<T: AsIoData>(s: &'a T, buf: &'a [u8], deadline: Instant) -> io::Result<Self> {
+        let mut io = SocketWrite::new(s, buf, Some(deadline::remaining(deadline)?));
+        io.deadline = Some(deadline);
+        Ok(io)
+    }
+
     #[inline]
-    pub fn done(self) -> io::Result<usize> {
+    pub fn done(mut self) -> io::Result<usize> {
         loop {
             co_io_result()?;
 
@@ -44,6 +55,9 @@ impl<'a> SocketWrite<'a> {
             }
 
             // the result is still WouldBlock, need to try again
+            if let Some(deadline) = self.deadline {
+                self.timeout = Some(deadline::remaining(deadline)?);
+            }
             self.can_drop.reset();
             yield_with(&self);
         }
@@ -53,6 +67,7 @@ impl<'a> SocketWrite<'a> {
 impl<'a> EventSource for SocketWrite<'a> {
     fn subscribe(&mut self, co: CoroutineImpl) {
         let _g = self.can_drop.delay_drop();
+        let cancel = co_cancel_data(&co);
         get_scheduler()
             .get_selector()
             .add_io_timer(self.io_data, self.timeout);
@@ -60,7 +75,14 @@ impl<'a> EventSource for SocketWrite<'a> {
 
         // there is event, re-run the coroutine
         if self.io_data.io_flag.load(Ordering::Relaxed) {
-            self.io_data.schedule();
+            return self.io_data.schedule();
+        }
+
+        // register the cancel io data
+        cancel.set_io(self.io_data.deref().clone());
+        // re-check the cancel status
+        if cancel.is_canceled() {
+            unsafe { cancel.cancel() };
         }
     }
 }
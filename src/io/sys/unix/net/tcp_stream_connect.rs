@@ -22,7 +22,12 @@ pub struct TcpStreamConnect {
 }
 
 impl TcpStreamConnect {
-    pub fn new<A: ToSocketAddrs>(addr: A, timeout: Option<Duration>) -> io::Result<Self> {
+    // bind the socket to `local` before connect if it's given
+    pub fn new<A: ToSocketAddrs>(
+        addr: A,
+        local: Option<SocketAddr>,
+        timeout: Option<Duration>,
+    ) -> io::Result<Self> {
         use socket2::{Domain, Type};
 
         let err = io::Error::new(io::ErrorKind::Other, "no socket addresses resolved");
@@ -31,12 +36,16 @@ impl TcpStreamConnect {
                 prev.or_else(|_| {
                     let stream = match addr {
                         SocketAddr::V4(..) => Socket::new(Domain::ipv4(), Type::stream(), None)?,
-                        SocketAddr::V6(..) => Socket::new(Domain::ipv4(), Type::stream(), None)?,
+                        SocketAddr::V6(..) => Socket::new(Domain::ipv6(), Type::stream(), None)?,
                     };
                     Ok((stream, addr))
                 })
             })
             .and_then(|(stream, addr)| {
+                if let Some(local) = local {
+                    stream.bind(&local.into())?;
+                }
+
                 // before yield we must set the socket to nonblocking mode and registe to selector
                 stream.set_nonblocking(true)?;
 
@@ -84,13 +93,16 @@ impl TcpStreamConnect {
             // clear the io_flag
             self.io_data.io_flag.store(false, Ordering::Relaxed);
 
-            match self.stream.connect(&self.addr.into()) {
+            // once writable the connect result is stored in SO_ERROR
+            // calling connect again would give a misleading errno
+            if let Some(e) = self.stream.take_error()? {
+                return Err(e);
+            }
+
+            // no pending error, but the event may not come from the connect
+            match self.stream.peer_addr() {
                 Ok(_) => return Ok(convert_to_stream(self)),
-                Err(ref e) if e.raw_os_error() == Some(libc::EINPROGRESS) => {}
-                Err(ref e) if e.raw_os_error() == Some(libc::EALREADY) => {}
-                Err(ref e) if e.raw_os_error() == Some(libc::EISCONN) => {
-                    return Ok(convert_to_stream(self));
-                }
+                Err(ref e) if e.raw_os_error() == Some(libc::ENOTCONN) => {}
                 Err(e) => return Err(e),
             }
 
@@ -98,7 +110,7 @@ impl TcpStreamConnect {
                 continue;
             }
 
-            // the result is still EINPROGRESS, need to try again
+            // the connect is still in progress, need to wait again
             self.can_drop.reset();
             yield_with(&self);
         }
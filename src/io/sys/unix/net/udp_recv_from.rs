@@ -17,6 +17,8 @@ pub struct UdpRecvFrom<'a> {
     socket: &'a std::net::UdpSocket,
     timeout: Option<Duration>,
     can_drop: DelayDrop,
+    // leave the datagram in the queue
+    peek: bool,
 }
 
 impl<'a> UdpRecvFrom<'a> {
@@ -27,9 +29,16 @@ impl<'a> UdpRecvFrom<'a> {
             socket: socket.inner(),
             timeout: socket.read_timeout().unwrap(),
             can_drop: DelayDrop::new(),
+            peek: false,
         }
     }
 
+    pub fn new_peek(socket: &'a UdpSocket, buf: &'a mut [u8]) -> Self {
+        let mut me = UdpRecvFrom::new(socket, buf);
+        me.peek = true;
+        me
+    }
+
     #[inline]
     pub fn done(self) -> io::Result<(usize, SocketAddr)> {
         loop {
@@ -38,7 +47,13 @@ impl<'a> UdpRecvFrom<'a> {
             // clear the io_flag
             self.io_data.io_flag.store(false, Ordering::Relaxed);
 
-            match self.socket.recv_from(self.buf) {
+            let ret = if self.peek {
+                self.socket.peek_from(self.buf)
+            } else {
+                self.socket.recv_from(self.buf)
+            };
+
+            match ret {
                 Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
                 ret => return ret,
             }
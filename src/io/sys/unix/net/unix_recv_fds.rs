@@ -0,0 +1,154 @@
+use std::{io, mem, ptr};
+use std::ops::Deref;
+use std::time::Duration;
+use std::sync::atomic::Ordering;
+use std::os::unix::io::RawFd;
+use libc;
+use io::AsIoData;
+use yield_now::yield_with;
+use scheduler::get_scheduler;
+use os::unix::net::UnixStream;
+use sync::delay_drop::DelayDrop;
+use super::super::{co_io_result, IoData};
+use coroutine_impl::{co_cancel_data, CoroutineImpl, EventSource};
+
+// the received fds would be closed on exec
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const RECV_FLAGS: libc::c_int = libc::MSG_CMSG_CLOEXEC;
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+const RECV_FLAGS: libc::c_int = 0;
+
+// receive into buf and collect the fds from the SCM_RIGHTS control messages
+// return the number of bytes and the number of fds received
+pub fn recv_with_fds(fd: RawFd, buf: &mut [u8], fds: &mut [RawFd]) -> io::Result<(usize, usize)> {
+    let mut iov = libc::iovec {
+        iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+        iov_len: buf.len(),
+    };
+
+    let fds_len = fds.len() * mem::size_of::<RawFd>();
+    let space = unsafe { libc::CMSG_SPACE(fds_len as libc::c_uint) } as usize;
+    // use u64 to make sure the cmsghdr is properly aligned
+    let mut cmsg_buf = vec![0u64; (space + 7) / 8];
+
+    let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    if !fds.is_empty() {
+        msg.msg_control = cmsg_buf.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = space as _;
+    }
+
+    let ret = unsafe { libc::recvmsg(fd, &mut msg, RECV_FLAGS) };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut cnt = 0;
+    let mut truncated = msg.msg_flags & libc::MSG_CTRUNC != 0;
+    unsafe {
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                let data = libc::CMSG_DATA(cmsg);
+                let len = (*cmsg).cmsg_len as usize - (data as usize - cmsg as usize);
+                let n = len / mem::size_of::<RawFd>();
+                for i in 0..n {
+                    let mut raw: RawFd = 0;
+                    ptr::copy_nonoverlapping(
+                        data.offset((i * mem::size_of::<RawFd>()) as isize),
+                        &mut raw as *mut _ as *mut u8,
+                        mem::size_of::<RawFd>(),
+                    );
+                    if cnt < fds.len() {
+                        fds[cnt] = raw;
+                        cnt += 1;
+                    } else {
+                        libc::close(raw);
+                        truncated = true;
+                    }
+                }
+            }
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+    }
+
+    if truncated {
+        // don't leak the fds that we can't give back to the caller
+        for fd in &fds[..cnt] {
+            unsafe { libc::close(*fd) };
+        }
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            "control message truncated, the fds buffer is too small",
+        ));
+    }
+
+    Ok((ret as usize, cnt))
+}
+
+pub struct UnixRecvFds<'a> {
+    io_data: &'a IoData,
+    buf: &'a mut [u8],
+    fds: &'a mut [RawFd],
+    timeout: Option<Duration>,
+    can_drop: DelayDrop,
+}
+
+impl<'a> UnixRecvFds<'a> {
+    pub fn new(socket: &'a UnixStream, buf: &'a mut [u8], fds: &'a mut [RawFd]) -> Self {
+        UnixRecvFds {
+            io_data: socket.0.as_io_data(),
+            buf: buf,
+            fds: fds,
+            timeout: socket.read_timeout().unwrap(),
+            can_drop: DelayDrop::new(),
+        }
+    }
+
+    #[inline]
+    pub fn done(self) -> io::Result<(usize, usize)> {
+        loop {
+            co_io_result()?;
+
+            // clear the io_flag
+            self.io_data.io_flag.store(false, Ordering::Relaxed);
+
+            match recv_with_fds(self.io_data.fd, self.buf, self.fds) {
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                ret => return ret,
+            }
+
+            if self.io_data.io_flag.swap(false, Ordering::Relaxed) {
+                continue;
+            }
+
+            // the result is still WouldBlock, need to try again
+            self.can_drop.reset();
+            yield_with(&self);
+        }
+    }
+}
+
+impl<'a> EventSource for UnixRecvFds<'a> {
+    fn subscribe(&mut self, co: CoroutineImpl) {
+        let _g = self.can_drop.delay_drop();
+        let cancel = co_cancel_data(&co);
+        get_scheduler()
+            .get_selector()
+            .add_io_timer(self.io_data, self.timeout);
+        self.io_data.co.swap(co, Ordering::Release);
+
+        // there is event, re-run the coroutine
+        if self.io_data.io_flag.load(Ordering::Relaxed) {
+            return self.io_data.schedule();
+        }
+
+        // register the cancel io data
+        cancel.set_io(self.io_data.deref().clone());
+        // re-check the cancel status
+        if cancel.is_canceled() {
+            unsafe { cancel.cancel() };
+        }
+    }
+}
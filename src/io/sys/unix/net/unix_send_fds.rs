@@ -0,0 +1,118 @@
+use std::{io, mem, ptr};
+use std::ops::Deref;
+use std::time::Duration;
+use std::sync::atomic::Ordering;
+use std::os::unix::io::RawFd;
+use libc;
+use io::AsIoData;
+use yield_now::yield_with;
+use scheduler::get_scheduler;
+use os::unix::net::UnixStream;
+use sync::delay_drop::DelayDrop;
+use super::super::{co_io_result, IoData};
+use coroutine_impl::{co_cancel_data, CoroutineImpl, EventSource};
+
+// send the buf together with the fds as a SCM_RIGHTS control message
+pub fn send_with_fds(fd: RawFd, buf: &[u8], fds: &[RawFd]) -> io::Result<usize> {
+    let mut iov = libc::iovec {
+        iov_base: buf.as_ptr() as *mut libc::c_void,
+        iov_len: buf.len(),
+    };
+
+    let fds_len = fds.len() * mem::size_of::<RawFd>();
+    let space = unsafe { libc::CMSG_SPACE(fds_len as libc::c_uint) } as usize;
+    // use u64 to make sure the cmsghdr is properly aligned
+    let mut cmsg_buf = vec![0u64; (space + 7) / 8];
+
+    let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+
+    if !fds.is_empty() {
+        msg.msg_control = cmsg_buf.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = space as _;
+        unsafe {
+            let cmsg = libc::CMSG_FIRSTHDR(&msg);
+            (*cmsg).cmsg_level = libc::SOL_SOCKET;
+            (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+            (*cmsg).cmsg_len = libc::CMSG_LEN(fds_len as libc::c_uint) as _;
+            ptr::copy_nonoverlapping(
+                fds.as_ptr() as *const u8,
+                libc::CMSG_DATA(cmsg),
+                fds_len,
+            );
+        }
+    }
+
+    let ret = unsafe { libc::sendmsg(fd, &msg, 0) };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(ret as usize)
+}
+
+pub struct UnixSendFds<'a> {
+    io_data: &'a IoData,
+    buf: &'a [u8],
+    fds: &'a [RawFd],
+    timeout: Option<Duration>,
+    can_drop: DelayDrop,
+}
+
+impl<'a> UnixSendFds<'a> {
+    pub fn new(socket: &'a UnixStream, buf: &'a [u8], fds: &'a [RawFd]) -> Self {
+        UnixSendFds {
+            io_data: socket.0.as_io_data(),
+            buf: buf,
+            fds: fds,
+            timeout: socket.write_timeout().unwrap(),
+            can_drop: DelayDrop::new(),
+        }
+    }
+
+    #[inline]
+    pub fn done(self) -> io::Result<usize> {
+        loop {
+            co_io_result()?;
+
+            // clear the io_flag
+            self.io_data.io_flag.store(false, Ordering::Relaxed);
+
+            match send_with_fds(self.io_data.fd, self.buf, self.fds) {
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                ret => return ret,
+            }
+
+            if self.io_data.io_flag.swap(false, Ordering::Relaxed) {
+                continue;
+            }
+
+            // the result is still WouldBlock, need to try again
+            self.can_drop.reset();
+            yield_with(&self);
+        }
+    }
+}
+
+impl<'a> EventSource for UnixSendFds<'a> {
+    fn subscribe(&mut self, co: CoroutineImpl) {
+        let _g = self.can_drop.delay_drop();
+        let cancel = co_cancel_data(&co);
+        get_scheduler()
+            .get_selector()
+            .add_io_timer(self.io_data, self.timeout);
+        self.io_data.co.swap(co, Ordering::Release);
+
+        // there is event, re-run the coroutine
+        if self.io_data.io_flag.load(Ordering::Relaxed) {
+            return self.io_data.schedule();
+        }
+
+        // register the cancel io data
+        cancel.set_io(self.io_data.deref().clone());
+        // re-check the cancel status
+        if cancel.is_canceled() {
+            unsafe { cancel.cancel() };
+        }
+    }
+}
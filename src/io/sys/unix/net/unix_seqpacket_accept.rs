@@ -0,0 +1,71 @@
+use std::io;
+use std::ops::Deref;
+use std::sync::atomic::Ordering;
+
+use socket2::Socket;
+use io::AsIoData;
+use io::sys::{co_io_result, IoData};
+use yield_now::yield_with;
+use sync::delay_drop::DelayDrop;
+use os::unix::net::UnixSeqpacketListener;
+use coroutine_impl::{co_cancel_data, CoroutineImpl, EventSource};
+
+pub struct UnixSeqpacketAccept<'a> {
+    io_data: &'a IoData,
+    socket: &'a Socket,
+    can_drop: DelayDrop,
+}
+
+impl<'a> UnixSeqpacketAccept<'a> {
+    pub fn new(socket: &'a UnixSeqpacketListener) -> io::Result<Self> {
+        Ok(UnixSeqpacketAccept {
+            io_data: socket.0.as_io_data(),
+            socket: socket.0.inner(),
+            can_drop: DelayDrop::new(),
+        })
+    }
+
+    #[inline]
+    pub fn done(self) -> io::Result<Socket> {
+        loop {
+            co_io_result()?;
+
+            // clear the io_flag
+            self.io_data.io_flag.store(false, Ordering::Relaxed);
+
+            match self.socket.accept() {
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                ret => return ret.map(|(s, _)| s),
+            }
+
+            if self.io_data.io_flag.swap(false, Ordering::Relaxed) {
+                continue;
+            }
+
+            // the result is still WouldBlock, need to try again
+            self.can_drop.reset();
+            yield_with(&self);
+        }
+    }
+}
+
+impl<'a> EventSource for UnixSeqpacketAccept<'a> {
+    fn subscribe(&mut self, co: CoroutineImpl) {
+        let _g = self.can_drop.delay_drop();
+        let cancel = co_cancel_data(&co);
+        // if there is no timer we don't need to call add_io_timer
+        self.io_data.co.swap(co, Ordering::Release);
+
+        // there is event happened
+        if self.io_data.io_flag.load(Ordering::Relaxed) {
+            return self.io_data.schedule();
+        }
+
+        // register the cancel io data
+        cancel.set_io(self.io_data.deref().clone());
+        // re-check the cancel status
+        if cancel.is_canceled() {
+            unsafe { cancel.cancel() };
+        }
+    }
+}
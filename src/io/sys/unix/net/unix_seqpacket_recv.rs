@@ -0,0 +1,103 @@
+use std::{io, mem};
+use std::ops::Deref;
+use std::time::Duration;
+use std::sync::atomic::Ordering;
+use std::os::unix::io::RawFd;
+use libc;
+use io::AsIoData;
+use yield_now::yield_with;
+use scheduler::get_scheduler;
+use sync::delay_drop::DelayDrop;
+use super::super::{co_io_result, IoData};
+use coroutine_impl::{co_cancel_data, CoroutineImpl, EventSource};
+
+// receive one record, the part that doesn't fit in buf is discarded
+// by the system, report it as an error instead of a short read
+pub fn recv_packet(fd: RawFd, buf: &mut [u8]) -> io::Result<usize> {
+    let mut iov = libc::iovec {
+        iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+        iov_len: buf.len(),
+    };
+    let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+
+    let ret = unsafe { libc::recvmsg(fd, &mut msg, 0) };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    if msg.msg_flags & libc::MSG_TRUNC != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "message truncated, the buffer is too small",
+        ));
+    }
+    Ok(ret as usize)
+}
+
+pub struct UnixSeqpacketRecv<'a> {
+    io_data: &'a IoData,
+    buf: &'a mut [u8],
+    timeout: Option<Duration>,
+    can_drop: DelayDrop,
+}
+
+impl<'a> UnixSeqpacketRecv<'a> {
+    pub fn new<T: AsIoData>(s: &'a T, buf: &'a mut [u8], timeout: Option<Duration>) -> Self {
+        UnixSeqpacketRecv {
+            io_data: s.as_io_data(),
+            buf: buf,
+            timeout: timeout,
+            can_drop: DelayDrop::new(),
+        }
+    }
+
+    #[inline]
+    pub fn done(self) -> io::Result<usize> {
+        loop {
+            co_io_result()?;
+
+            // clear the io_flag
+            self.io_data.io_flag.store(false, Ordering::Relaxed);
+
+            match recv_packet(self.io_data.fd, self.buf) {
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                ret => return ret,
+            }
+
+            if self.io_data.io_flag.swap(false, Ordering::Relaxed) {
+                continue;
+            }
+
+            // the result is still WouldBlock, need to try again
+            self.can_drop.reset();
+            yield_with(&self);
+        }
+    }
+}
+
+impl<'a> EventSource for UnixSeqpacketRecv<'a> {
+    fn subscribe(&mut self, co: CoroutineImpl) {
+        // when exit the scope the `can_drop` will be set to true
+        let _g = self.can_drop.delay_drop();
+
+        let cancel = co_cancel_data(&co);
+        get_scheduler()
+            .get_selector()
+            .add_io_timer(self.io_data, self.timeout);
+        self.io_data.co.swap(co, Ordering::Release);
+
+        // there is event, re-run the coroutine
+        if self.io_data.io_flag.load(Ordering::Relaxed) {
+            return self.io_data.schedule();
+        }
+
+        // register the cancel io data
+        cancel.set_io(self.io_data.deref().clone());
+        // re-check the cancel status
+        if cancel.is_canceled() {
+            unsafe { cancel.cancel() };
+        }
+    }
+}
@@ -18,20 +18,32 @@ pub struct UnixStreamConnect {
     io_data: IoData,
     stream: Socket,
     path: SockAddr,
+    timeout: Option<Duration>,
     can_drop: DelayDrop,
     is_connected: bool,
 }
 
 impl UnixStreamConnect {
-    pub fn new<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+    pub fn new<P: AsRef<Path>>(path: P, timeout: Option<Duration>) -> io::Result<Self> {
         let path = SockAddr::unix(path)?;
-        let socket = Socket::new(Domain::unix(), Type::stream(), None)?;
+        UnixStreamConnect::from_addr(path, timeout)
+    }
+
+    // connect to an already built unix address, e.g. an abstract one
+    pub fn from_addr(path: SockAddr, timeout: Option<Duration>) -> io::Result<Self> {
+        UnixStreamConnect::with_type(path, Type::stream(), timeout)
+    }
+
+    // the connect procedure is the same for connection oriented unix sockets
+    pub fn with_type(path: SockAddr, ty: Type, timeout: Option<Duration>) -> io::Result<Self> {
+        let socket = Socket::new(Domain::unix(), ty, None)?;
         // before yield we must set the socket to nonblocking mode and registe to selector
         socket.set_nonblocking(true)?;
         add_socket(&socket).map(|io| UnixStreamConnect {
             io_data: io,
             stream: socket,
             path: path,
+            timeout: timeout,
             can_drop: DelayDrop::new(),
             is_connected: false,
         })
@@ -54,9 +66,17 @@ impl UnixStreamConnect {
 
     #[inline]
     pub fn done(self) -> io::Result<UnixStream> {
-        fn convert_to_stream(s: UnixStreamConnect) -> UnixStream {
-            let stream = s.stream.into_unix_stream();
-            UnixStream::from_coio(CoIo::from_raw(stream, s.io_data))
+        self.done_socket().map(|(s, io)| {
+            let stream = s.into_unix_stream();
+            UnixStream::from_coio(CoIo::from_raw(stream, io))
+        })
+    }
+
+    // return the connected socket together with its registered io data
+    #[inline]
+    pub fn done_socket(self) -> io::Result<(Socket, IoData)> {
+        fn convert_to_stream(s: UnixStreamConnect) -> (Socket, IoData) {
+            (s.stream, s.io_data)
         }
 
         // first check if it's already connected
@@ -70,13 +90,16 @@ impl UnixStreamConnect {
             // clear the io_flag
             self.io_data.io_flag.store(false, Ordering::Relaxed);
 
-            match self.stream.connect(&self.path) {
+            // once writable the connect result is stored in SO_ERROR
+            // calling connect again would give a misleading errno
+            if let Some(e) = self.stream.take_error()? {
+                return Err(e);
+            }
+
+            // no pending error, but the event may not come from the connect
+            match self.stream.peer_addr() {
                 Ok(_) => return Ok(convert_to_stream(self)),
-                Err(ref e) if e.raw_os_error() == Some(libc::EINPROGRESS) => {}
-                Err(ref e) if e.raw_os_error() == Some(libc::EALREADY) => {}
-                Err(ref e) if e.raw_os_error() == Some(libc::EISCONN) => {
-                    return Ok(convert_to_stream(self));
-                }
+                Err(ref e) if e.raw_os_error() == Some(libc::ENOTCONN) => {}
                 Err(e) => return Err(e),
             }
 
@@ -84,7 +107,7 @@ impl UnixStreamConnect {
                 continue;
             }
 
-            // the result is still EINPROGRESS, need to try again
+            // the connect is still in progress, need to wait again
             self.can_drop.reset();
             yield_with(&self);
         }
@@ -98,7 +121,7 @@ impl EventSource for UnixStreamConnect {
         let io_data = &self.io_data;
         get_scheduler()
             .get_selector()
-            .add_io_timer(io_data, Some(Duration::from_secs(10)));
+            .add_io_timer(io_data, self.timeout);
         io_data.co.swap(co, Ordering::Release);
 
         // there is event, re-run the coroutine
@@ -0,0 +1,182 @@
+use std::{fmt, io};
+use std::cmp;
+use std::ops::Deref;
+use std::time::{Duration, Instant};
+use std::sync::atomic::Ordering;
+use std::os::unix::io::{AsRawFd, RawFd};
+use libc;
+use io::AsIoData;
+use yield_now::yield_with;
+use scheduler::get_scheduler;
+use sync::delay_drop::DelayDrop;
+use coroutine_impl::{co_cancel_data, CoroutineImpl, EventSource};
+use super::{co_io_result, IoData};
+
+// wait until there is an event on the io, without doing any io operation
+// the caller must reset the io before checking its state, then yield this
+pub struct WaitIo<'a> {
+    io_data: &'a IoData,
+    timeout: Option<Duration>,
+    can_drop: DelayDrop,
+}
+
+impl<'a> WaitIo<'a> {
+    pub fn new<T: AsIoData>(s: &'a T, timeout: Option<Duration>) -> Self {
+        WaitIo {
+            io_data: s.as_io_data(),
+            timeout: timeout,
+            can_drop: DelayDrop::new(),
+        }
+    }
+
+    #[inline]
+    pub fn done(self) -> io::Result<()> {
+        co_io_result()
+    }
+}
+
+impl<'a> EventSource for WaitIo<'a> {
+    fn subscribe(&mut self, co: CoroutineImpl) {
+        let _g = self.can_drop.delay_drop();
+        let cancel = co_cancel_data(&co);
+        get_scheduler()
+            .get_selector()
+            .add_io_timer(self.io_data, self.timeout);
+        self.io_data.co.swap(co, Ordering::Release);
+
+        // there is event, re-run the coroutine
+        if self.io_data.io_flag.load(Ordering::Relaxed) {
+            return self.io_data.schedule();
+        }
+
+        // register the cancel io data
+        cancel.set_io(self.io_data.deref().clone());
+        // re-check the cancel status
+        if cancel.is_canceled() {
+            unsafe { cancel.cancel() };
+        }
+    }
+}
+
+/// poll the fd for the events, return true if it's ready
+///
+/// the error and hang up conditions count as ready, so that the following
+/// io could report them. an interrupted poll returns false
+pub fn poll_fd(fd: RawFd, events: libc::c_short, timeout: Option<Duration>) -> io::Result<bool> {
+    let ms = match timeout {
+        None => -1,
+        Some(dur) => {
+            // round up the millis, or it would return before the timeout
+            let ms = dur.as_secs()
+                .saturating_mul(1000)
+                .saturating_add(u64::from((dur.subsec_nanos() + 999_999) / 1_000_000));
+            cmp::min(ms, libc::c_int::max_value() as u64) as libc::c_int
+        }
+    };
+
+    let mut pfd = libc::pollfd {
+        fd: fd,
+        events: events,
+        revents: 0,
+    };
+    match unsafe { libc::poll(&mut pfd, 1, ms) } {
+        -1 => {
+            let e = io::Error::last_os_error();
+            if e.kind() == io::ErrorKind::Interrupted {
+                return Ok(false);
+            }
+            Err(e)
+        }
+        0 => Ok(false),
+        _ => Ok(true),
+    }
+}
+
+/// the readiness of a stream as an event, it's waited by `wait`
+///
+/// it's got from `read_ready_event` or `write_ready_event` of a stream. each
+/// `wait` subscribes on the readiness of the io data and returns once the
+/// stream is ready, nothing is consumed. so it could be the top half of a
+/// `cqueue_add!` arm, whose select coroutine waits on it again after each
+/// bottom half for the whole life of the cqueue
+pub struct ReadyEvent<'a, T: 'a> {
+    io: &'a T,
+    events: libc::c_short,
+    timeout: Option<Duration>,
+    // return true if the wait should yield the coroutine
+    ctx: fn(&T) -> io::Result<bool>,
+}
+
+impl<'a, T: AsIoData + AsRawFd> ReadyEvent<'a, T> {
+    pub(crate) fn new(
+        io: &'a T,
+        events: libc::c_short,
+        timeout: Option<Duration>,
+        ctx: fn(&T) -> io::Result<bool>,
+    ) -> Self {
+        ReadyEvent {
+            io: io,
+            events: events,
+            timeout: timeout,
+            ctx: ctx,
+        }
+    }
+
+    /// block until the stream is ready
+    ///
+    /// only the current coroutine is blocked and each wait is limited by the
+    /// timeout of the stream, `TimedOut` is returned when it expires
+    pub fn wait(&self) -> io::Result<()> {
+        let co_ctx = (self.ctx)(self.io)?;
+        wait_ready(self.io, self.events, self.timeout, co_ctx)
+    }
+}
+
+impl<'a, T> fmt::Debug for ReadyEvent<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = if self.events == libc::POLLIN { "read" } else { "write" };
+        write!(f, "ReadyEvent {{ {} }}", kind)
+    }
+}
+
+/// wait until the io is ready for the events without doing any io operation
+///
+/// `co_ctx` tells if it's called in coroutine context, in thread context it
+/// just polls the fd. in coroutine context the selector is edge triggered and
+/// doesn't tell the kind of the event, so the fd is polled again after each
+/// wakeup and the coroutine keeps waiting if it's not ready yet
+pub fn wait_ready<T>(s: &T, events: libc::c_short, timeout: Option<Duration>, co_ctx: bool) -> io::Result<()>
+where
+    T: AsIoData + AsRawFd,
+{
+    let fd = s.as_raw_fd();
+    let deadline = timeout.map(|t| Instant::now() + t);
+    let remaining = || match deadline {
+        None => Ok(None),
+        Some(d) => {
+            let now = Instant::now();
+            if d <= now {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "timeout"));
+            }
+            Ok(Some(d - now))
+        }
+    };
+
+    if !co_ctx {
+        while !poll_fd(fd, events, remaining()?)? {}
+        return Ok(());
+    }
+
+    let io_data = s.as_io_data();
+    loop {
+        io_data.reset();
+        // check the state first, the event may be already consumed
+        if poll_fd(fd, events, Some(Duration::from_secs(0)))? {
+            return Ok(());
+        }
+
+        let waiter = WaitIo::new(s, remaining()?);
+        yield_with(&waiter);
+        waiter.done()?;
+    }
+}
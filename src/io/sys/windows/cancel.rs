@@ -52,6 +52,10 @@ impl CancelIo for CancelIoImpl {
         *self.0.lock().expect("failed to get CancelIo lock") = None;
     }
 
+    fn is_set(&self) -> bool {
+        self.0.lock().expect("failed to get CancelIo lock").is_some()
+    }
+
     unsafe fn cancel(&self) {
         self.0
             .lock()
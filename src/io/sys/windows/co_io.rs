@@ -74,7 +74,7 @@ impl<T: AsRawHandle> CoIo<T> {
     }
 
     /// get inner mut ref
-    pub fn inner_mut(&mut self) -> &T {
+    pub fn inner_mut(&mut self) -> &mut T {
         &mut self.inner
     }
 
@@ -4,7 +4,8 @@ use std::{io, ptr};
 use std::os::windows::io::AsRawSocket;
 use yield_now::set_co_para;
 use coroutine_impl::CoroutineImpl;
-use timeout_list::{now, ns_to_dur, TimeOutList, TimeoutHandle};
+use scheduler::count_io_events;
+use timeout_list::{now, ns_to_dur, remove_timer, TimeOutList, TimeoutHandle};
 
 use miow::iocp::{CompletionPort, CompletionStatus};
 use winapi::shared::ntdef::*;
@@ -66,15 +67,17 @@ pub type SysEvent = CompletionStatus;
 pub struct Selector {
     /// The actual completion port that's used to manage all I/O
     port: CompletionPort,
+    io_workers: usize,
     timer_list: TimerList,
     schedule_policy: fn(CoroutineImpl),
 }
 
 impl Selector {
-    pub fn new(_io_workers: usize, schedule_policy: fn(CoroutineImpl)) -> io::Result<Selector> {
+    pub fn new(io_workers: usize, schedule_policy: fn(CoroutineImpl)) -> io::Result<Selector> {
         // only let one thread working, other threads blocking, this is more efficient
         CompletionPort::new(1).map(|cp| Selector {
             port: cp,
+            io_workers: io_workers,
             timer_list: TimerList::new(),
             schedule_policy,
         })
@@ -94,6 +97,7 @@ impl Selector {
             Err(e) => return Err(e),
         };
 
+        let mut got = 0;
         for status in events[..n].iter() {
             // need to check the status for each io
             let overlapped = status.overlapped();
@@ -102,6 +106,7 @@ impl Selector {
                 continue;
             }
 
+            got += 1;
             let data = unsafe { &mut *(overlapped as *mut EventData) };
             // when cancel failed the coroutine will continue to finish
             // it's unsafe to ref any local stack value!
@@ -121,7 +126,7 @@ impl Selector {
                     h.get_data().data.event_data = ptr::null_mut();
                 }
                 // NOT SAFE for multi-thread!!
-                h.remove()
+                remove_timer(h)
             });
 
             let overlapped = unsafe { &*overlapped };
@@ -156,12 +161,20 @@ impl Selector {
             // schedule the coroutine
             (self.schedule_policy)(co);
         }
+        count_io_events(got);
 
         // deal with the timer list
         let next_expire = self.timer_list.schedule_timer(now(), &timeout_handler);
         Ok(next_expire)
     }
 
+    // wake up all the event loops, each takes one of the posted events
+    pub fn wakeup_all(&self) {
+        for _ in 0..self.io_workers {
+            self.wakeup();
+        }
+    }
+
     // this will post an os event so that we can wakeup the event loop
     #[inline]
     fn wakeup(&self) {
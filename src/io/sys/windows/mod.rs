@@ -18,7 +18,9 @@ pub mod cancel;
 
 use std::{fmt, io};
 use std::os::windows::io::AsRawSocket;
+use cancel::cancel_err;
 use yield_now::get_co_para;
+use coroutine_impl::current_cancel_data;
 use scheduler::get_scheduler;
 
 pub use self::iocp::{EventData, Selector, SysEvent};
@@ -54,9 +56,18 @@ pub fn add_socket<T: AsRawSocket + ?Sized>(t: &T) -> io::Result<IoData> {
 #[inline]
 fn co_io_result(io: &EventData) -> io::Result<usize> {
     match get_co_para() {
+        // the canceled io is aborted by the kernel, report it as the cancel
+        Some(ref err) if err.kind() != io::ErrorKind::TimedOut
+            && current_cancel_data().is_canceled() =>
+        {
+            return Err(cancel_err());
+        }
         Some(err) => {
             return Err(err);
         }
+        None if current_cancel_data().is_canceled() => {
+            return Err(cancel_err());
+        }
         None => {
             return Ok(io.get_io_size());
         }
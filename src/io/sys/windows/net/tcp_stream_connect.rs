@@ -21,7 +21,12 @@ pub struct TcpStreamConnect {
 }
 
 impl TcpStreamConnect {
-    pub fn new<A: ToSocketAddrs>(addr: A, timeout: Option<Duration>) -> io::Result<Self> {
+    // bind the socket to `local` instead of the any address if it's given
+    pub fn new<A: ToSocketAddrs>(
+        addr: A,
+        local: Option<SocketAddr>,
+        timeout: Option<Duration>,
+    ) -> io::Result<Self> {
         use socket2::{Domain, Socket, Type};
 
         let err = io::Error::new(io::ErrorKind::Other, "no socket addresses resolved");
@@ -33,7 +38,7 @@ impl TcpStreamConnect {
                 prev.or_else(|_| {
                     let socket = match addr {
                         SocketAddr::V4(..) => Socket::new(Domain::ipv4(), Type::stream(), None)?,
-                        SocketAddr::V6(..) => Socket::new(Domain::ipv4(), Type::stream(), None)?,
+                        SocketAddr::V6(..) => Socket::new(Domain::ipv6(), Type::stream(), None)?,
                     };
                     Ok((socket, addr))
                 })
@@ -54,7 +59,7 @@ impl TcpStreamConnect {
                 };
 
                 socket
-                    .bind(&any.into())
+                    .bind(&local.unwrap_or(any).into())
                     .and_then(|_| Ok(socket.into_tcp_stream()))
                     .and_then(|s| {
                         // must register io first
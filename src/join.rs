@@ -1,11 +1,14 @@
 use std::fmt;
+use std::error;
+use std::panic;
 use std::any::Any;
 use std::sync::Arc;
+use std::time::Duration;
 use std::thread::Result;
 use std::cell::UnsafeCell;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use generator::Error;
-use coroutine_impl::Coroutine;
+use coroutine_impl::{current_cancel_data, Builder, Coroutine};
 use sync::{AtomicOption, Blocker};
 
 pub struct Join {
@@ -20,18 +23,35 @@ pub struct Join {
     // we use to communicate with JoinHandle so that can return the panic info
     // this must be ready before the trigger
     panic: Arc<UnsafeCell<Option<Box<Any + Send>>>>,
+
+    // the whole stack is painted to measure the used size, the join is
+    // then triggered after the measurement when the coroutine is dropped
+    track_stack: bool,
+    // the used stack size in bytes, set when the coroutine is dropped
+    stack_used: AtomicUsize,
 }
 
 // this is the join resource type
 impl Join {
-    pub fn new(panic: Arc<UnsafeCell<Option<Box<Any + Send>>>>) -> Self {
+    pub fn new(panic: Arc<UnsafeCell<Option<Box<Any + Send>>>>, track_stack: bool) -> Self {
         Join {
             to_wake: AtomicOption::none(),
             state: AtomicBool::new(true),
             panic: panic,
+            track_stack: track_stack,
+            stack_used: AtomicUsize::new(0),
         }
     }
 
+    pub fn is_stack_tracked(&self) -> bool {
+        self.track_stack
+    }
+
+    // record the used stack size before the trigger
+    pub fn set_stack_used(&self, bytes: usize) {
+        self.stack_used.store(bytes, Ordering::Release);
+    }
+
     // the the panic for the coroutine
     pub fn set_panic_data(&mut self, panic: Box<Any + Send>) {
         let p = unsafe { &mut *self.panic.get() };
@@ -58,6 +78,47 @@ impl Join {
             cur.park(None).ok();
         }
     }
+
+    // return true if the coroutine is done within the timeout
+    fn wait_timeout(&mut self, dur: Duration) -> bool {
+        if self.state.load(Ordering::Acquire) {
+            let cur = Blocker::current();
+            // register the blocker first
+            self.to_wake.swap(cur.clone(), Ordering::Release);
+            // re-check the state
+            if self.state.load(Ordering::Acquire) {
+                cur.park(Some(dur)).ok();
+            }
+            // unregister the blocker if it's not consumed by the trigger
+            self.to_wake.take(Ordering::Acquire);
+        }
+        !self.state.load(Ordering::Acquire)
+    }
+}
+
+/// An error returned from `JoinHandle::join_timeout`
+///
+/// the coroutine is not finished within the timeout
+pub struct TimeoutError {
+    _private: (),
+}
+
+impl fmt::Debug for TimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TimeoutError").finish()
+    }
+}
+
+impl fmt::Display for TimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt("timed out waiting for the coroutine", f)
+    }
+}
+
+impl error::Error for TimeoutError {
+    fn description(&self) -> &str {
+        "timed out waiting for the coroutine"
+    }
 }
 
 /// A join handle to a coroutine
@@ -104,12 +165,58 @@ impl<T> JoinHandle<T> {
         join.wait();
     }
 
+    /// block until the coroutine is done, waiting at most `dur`
+    ///
+    /// return true if the coroutine is finished. a coroutine caller is parked
+    /// with a timer, a thread caller blocks the thread. on timeout the
+    /// coroutine keeps running, it could be canceled or waited again.
+    /// the handle is borrowed mutably because it has only one waiter slot
+    pub fn wait_timeout(&mut self, dur: Duration) -> bool {
+        let join = unsafe { &mut *self.join.get() };
+        join.wait_timeout(dur)
+    }
+
     /// Join the coroutine, returning the result it produced.
+    ///
+    /// like `std::thread::JoinHandle::join`, a panic in the coroutine is
+    /// returned as the `Err` with the panic payload. a canceled coroutine
+    /// returns a payload that makes `coroutine::is_cancel_panic` true
     pub fn join(self) -> Result<T> {
         let join = unsafe { &mut *self.join.get() };
         join.wait();
 
-        // take the result
+        self.take_result()
+    }
+
+    /// Join the coroutine, waiting at most `dur` for it to finish
+    ///
+    /// on timeout the coroutine keeps running and the handle can be joined
+    /// again later. once the result is returned, it's moved out of the handle
+    /// and the following joins would return an `Error::Cancel` panic data.
+    /// the handle is borrowed mutably because it has only one waiter slot
+    pub fn join_timeout(&mut self, dur: Duration) -> ::std::result::Result<Result<T>, TimeoutError> {
+        let join = unsafe { &mut *self.join.get() };
+        if !join.wait_timeout(dur) {
+            return Err(TimeoutError { _private: () });
+        }
+        Ok(self.take_result())
+    }
+
+    /// the peak stack usage of the coroutine in bytes
+    ///
+    /// it's only measured for the coroutines spawned with `track_stack`
+    /// set, on the `Builder` or by `Config::set_track_stack`, and is
+    /// available once the coroutine is done, otherwise `None` is returned
+    pub fn stack_high_water(&self) -> Option<usize> {
+        let join = unsafe { &*self.join.get() };
+        if !join.track_stack || join.state.load(Ordering::Acquire) {
+            return None;
+        }
+        Some(join.stack_used.load(Ordering::Acquire))
+    }
+
+    // take the result
+    fn take_result(&self) -> Result<T> {
         self.packet.take(Ordering::Acquire).ok_or_else(|| {
             let p = unsafe { &mut *self.panic.get() };
             p.take().unwrap_or_else(|| Box::new(Error::Cancel))
@@ -117,6 +224,70 @@ impl<T> JoinHandle<T> {
     }
 }
 
+// cancel the coroutine if the waiter unwinds
+struct CancelOnDrop(Option<Coroutine>);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.take().map(|co| unsafe { co.cancel() });
+    }
+}
+
+/// run the closure in a new coroutine, and cancel it if it's not done
+/// within `dur`
+///
+/// the canceled closure is unwound at the blocking call where it's waiting,
+/// or gets the cancel error back from a blocking io call, and its result is
+/// then dropped. either way its resources are dropped before `TimeoutError`
+/// is returned. a result that the closure returns before the cancel is set
+/// is still returned. a panic in the closure is propagated to the caller,
+/// unless it's after the cancel, e.g. by unwrapping the cancel error. a
+/// closure that never blocks is only canceled at `check_cancel`, and a
+/// `CancelGuard` in the closure delays the return until the guard is dropped
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::Duration;
+/// use may::coroutine;
+///
+/// let ret = coroutine::with_timeout(Duration::from_millis(10), || {
+///     coroutine::sleep(Duration::from_secs(10));
+/// });
+/// assert!(ret.is_err());
+///
+/// let ret = coroutine::with_timeout(Duration::from_secs(10), || 42);
+/// assert_eq!(ret.unwrap(), 42);
+/// ```
+pub fn with_timeout<F, T>(dur: Duration, f: F) -> ::std::result::Result<T, TimeoutError>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    // tell if the cancel is set before the closure returns
+    let mut h = unsafe {
+        Builder::new().spawn(move || {
+            let ret = f();
+            (ret, current_cancel_data().is_canceled())
+        })
+    }.unwrap();
+    let mut guard = CancelOnDrop(Some(h.coroutine().clone()));
+    let done = h.wait_timeout(dur);
+    guard.0 = None;
+    if !done {
+        unsafe { h.coroutine().cancel() };
+    }
+    match h.join() {
+        // the closure may return the cancel error of an io call
+        Ok((_, true)) if !done => Err(TimeoutError { _private: () }),
+        // it's finished before the cancel reaches it
+        Ok((ret, _)) => Ok(ret),
+        // any unwind of the canceled closure is caused by the cancel
+        Err(_) if !done => Err(TimeoutError { _private: () }),
+        Err(e) => panic::resume_unwind(e),
+    }
+}
+
 impl<T> fmt::Debug for JoinHandle<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.pad("JoinHandle { .. }")
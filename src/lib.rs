@@ -41,6 +41,9 @@ extern crate smallvec;
 #[cfg(test)]
 #[doc(hidden)]
 extern crate tempdir;
+#[cfg(feature = "futures")]
+#[doc(hidden)]
+extern crate futures;
 
 #[cfg(windows)]
 #[doc(hidden)]
@@ -66,8 +69,15 @@ mod config;
 #[macro_use]
 mod macros;
 mod scoped;
-mod scheduler;
+mod registry;
+mod listener;
+mod stack_stats;
+#[cfg(unix)]
+mod stack_guard;
+pub mod scheduler;
 mod yield_now;
+mod blocking;
+mod cancel_token;
 mod timeout_list;
 mod coroutine_impl;
 
@@ -77,5 +87,10 @@ pub mod net;
 pub mod sync;
 pub mod cqueue;
 pub mod coroutine;
-pub use local::LocalKey;
-pub use config::{config, Config};
+#[cfg(feature = "futures")]
+pub mod compat;
+#[cfg(unix)]
+pub mod process;
+pub use local::{AccessError, LocalKey};
+pub use config::{config, Config, PanicPolicy};
+pub use scheduler::shutdown;
@@ -0,0 +1,103 @@
+//! the hooks of the coroutine lifecycle for the tracing and profiling
+//!
+//! the listener is installed by `Config::set_event_listener`. without one
+//! each hook point only loads an atomic and takes a branch
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Why a coroutine is suspended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ParkReason {
+    /// waiting for an io request
+    Io,
+    /// sleeping
+    Timer,
+    /// waiting on a park, a join or the sync primitives
+    Sync,
+    /// yielded to the other coroutines, it's ready again at once
+    Yield,
+}
+
+/// How a coroutine is finished
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ExitKind {
+    /// returned from the closure
+    Finished,
+    /// unwound by a panic
+    Panicked,
+    /// unwound by a cancel
+    Canceled,
+}
+
+/// The callbacks of the coroutine lifecycle
+///
+/// the callbacks are called on the scheduler threads, and some of them are
+/// called for each context switch, so they should be quick and must not
+/// block. all of them do nothing by default
+///
+/// # Examples
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate may;
+///
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use may::coroutine::{CoroutineEventListener, ExitKind};
+///
+/// static FINISHED: AtomicUsize = AtomicUsize::new(0);
+///
+/// struct Counter;
+///
+/// impl CoroutineEventListener for Counter {
+///     fn on_exit(&self, _id: usize, kind: ExitKind) {
+///         if kind == ExitKind::Finished {
+///             FINISHED.fetch_add(1, Ordering::Relaxed);
+///         }
+///     }
+/// }
+///
+/// fn main() {
+///     may::config().set_event_listener(Counter);
+///     go!(|| {}).join().unwrap();
+///     // the exit is reported when the coroutine is dropped after the join
+///     while FINISHED.load(Ordering::Relaxed) == 0 {
+///         std::thread::yield_now();
+///     }
+/// }
+/// ```
+pub trait CoroutineEventListener: Send + Sync + 'static {
+    /// a coroutine is spawned, it's called by the spawning thread
+    fn on_spawn(&self, _id: usize, _name: Option<&str>) {}
+    /// a coroutine starts to run or runs again, `worker` is the same as
+    /// `scheduler::current_worker_id` of the running thread
+    fn on_resume(&self, _id: usize, _worker: Option<usize>) {}
+    /// a coroutine is suspended
+    fn on_park(&self, _id: usize, _reason: ParkReason) {}
+    /// a coroutine is finished, it's not resumed any more
+    fn on_exit(&self, _id: usize, _kind: ExitKind) {}
+}
+
+// the leaked `Box<Box<CoroutineEventListener>>`, 0 if there is none
+static LISTENER: AtomicUsize = AtomicUsize::new(0);
+
+// return false if there is one already
+pub(crate) fn install(listener: Box<CoroutineEventListener>) -> bool {
+    let ptr = Box::into_raw(Box::new(listener)) as usize;
+    match LISTENER.compare_exchange(0, ptr, Ordering::AcqRel, Ordering::Acquire) {
+        Ok(_) => true,
+        Err(_) => {
+            drop(unsafe { Box::from_raw(ptr as *mut Box<CoroutineEventListener>) });
+            false
+        }
+    }
+}
+
+/// get the installed listener
+#[inline]
+pub(crate) fn listener() -> Option<&'static CoroutineEventListener> {
+    match LISTENER.load(Ordering::Acquire) {
+        0 => None,
+        // it's never freed once installed
+        ptr => Some(unsafe { &**(ptr as *const Box<CoroutineEventListener>) }),
+    }
+}
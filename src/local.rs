@@ -1,10 +1,13 @@
+use std::{error, fmt};
 use std::sync::Arc;
 use std::any::TypeId;
 use std::collections::HashMap;
-use std::cell::{RefCell, UnsafeCell};
+use std::cell::{Cell, RefCell, UnsafeCell};
 use std::hash::{BuildHasherDefault, Hasher};
 use join::Join;
 use coroutine_impl::Coroutine;
+use cancel_token::CancelToken;
+use scheduler::Priority;
 use generator::get_local_data;
 
 // thread local map storage
@@ -18,15 +21,37 @@ pub struct CoroutineLocal {
     join: Arc<UnsafeCell<Join>>,
     // real local data hash map
     local_data: LocalMap,
+    // the cancel token that the coroutine is bound to
+    token: Option<CancelToken>,
+    // the scheduling priority of the coroutine
+    priority: Priority,
+    // the worker that the coroutine is pinned to
+    pin: Option<usize>,
+    // nobody joins the coroutine, its panic is reported by the panic hook
+    detached: bool,
+    // the top address and the size in bytes of the stack
+    stack: Cell<(usize, usize)>,
 }
 
 impl CoroutineLocal {
     /// create coroutine local storage
-    pub fn new(co: Coroutine, join: Arc<UnsafeCell<Join>>) -> Box<Self> {
+    pub fn new(
+        co: Coroutine,
+        join: Arc<UnsafeCell<Join>>,
+        token: Option<CancelToken>,
+        priority: Priority,
+        pin: Option<usize>,
+        detached: bool,
+    ) -> Box<Self> {
         Box::new(CoroutineLocal {
             co: co,
             join: join,
             local_data: RefCell::new(HashMap::default()),
+            token: token,
+            priority: priority,
+            pin: pin,
+            detached: detached,
+            stack: Cell::new((0, 0)),
         })
     }
 
@@ -39,6 +64,36 @@ impl CoroutineLocal {
     pub fn get_join(&self) -> Arc<UnsafeCell<Join>> {
         self.join.clone()
     }
+
+    // get the cancel token
+    pub fn get_token(&self) -> Option<&CancelToken> {
+        self.token.as_ref()
+    }
+
+    // get the scheduling priority
+    pub fn get_priority(&self) -> Priority {
+        self.priority
+    }
+
+    // get the worker that the coroutine is pinned to
+    pub fn get_pin(&self) -> Option<usize> {
+        self.pin
+    }
+
+    // return true if the coroutine is spawned detached
+    pub fn is_detached(&self) -> bool {
+        self.detached
+    }
+
+    // record the stack, it's set by the coroutine when it starts
+    pub fn set_stack(&self, top: usize, size: usize) {
+        self.stack.set((top, size));
+    }
+
+    // the top address and the size in bytes of the stack
+    pub fn get_stack(&self) -> (usize, usize) {
+        self.stack.get()
+    }
 }
 
 fn with<F: FnOnce(&LocalMap) -> R, R>(f: F) -> R {
@@ -62,7 +117,9 @@ impl<T> Opaque for T {}
 /// This type is generated by the `coroutine_local!` macro and performs very
 /// similarly to the `thread_local!` macro and `std::thread::LocalKey` types.
 /// Data associated with a `LocalKey<T>` is stored inside of a coroutine,
-/// and the data is destroyed when the coroutine is completed.
+/// and the data is destroyed when the coroutine is completed or cancelled.
+/// it moves together with the coroutine when the coroutine is resumed on
+/// another worker thread, which a `thread_local!` value can't do.
 ///
 /// coroutine-local data requires the `'static` bound to ensure it lives long
 /// enough. When a key is accessed for the first time the coroutine's data is
@@ -99,6 +156,30 @@ impl Hasher for IdHasher {
     }
 }
 
+/// An error returned by `LocalKey::try_with` when it's not accessed in a
+/// coroutine context
+pub struct AccessError {
+    _private: (),
+}
+
+impl fmt::Debug for AccessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AccessError").finish()
+    }
+}
+
+impl fmt::Display for AccessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt("not in a coroutine context", f)
+    }
+}
+
+impl error::Error for AccessError {
+    fn description(&self) -> &str {
+        "not in a coroutine context"
+    }
+}
+
 impl<T: 'static> LocalKey<T> {
     /// Access this coroutine-local key, running the provided closure with a
     /// reference to the value.
@@ -136,4 +217,17 @@ impl<T: 'static> LocalKey<T> {
             unsafe { f(&*raw_pointer) }
         })
     }
+
+    /// Access this coroutine-local key the same as `with`, except that it
+    /// returns an `AccessError` instead of using the thread local storage
+    /// when it's not called in a coroutine context
+    pub fn try_with<F, R>(&'static self, f: F) -> Result<R, AccessError>
+    where
+        F: FnOnce(&T) -> R,
+    {
+        if get_local_data().is_null() {
+            return Err(AccessError { _private: () });
+        }
+        Ok(self.with(f))
+    }
 }
@@ -81,10 +81,57 @@ macro_rules! cqueue_add_oneshot{
 
 /// macro used to select for only one event
 /// it will return the index of which event happens first
+///
+/// an optional `timeout(dur) => body` arm could be the last one, its body is
+/// run when no event happens within `dur`, and its index is returned. it uses
+/// the timer of the waiting instead of a select coroutine for the timeout
+///
+/// # Examples
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate may;
+///
+/// use std::time::Duration;
+/// use may::sync::mpsc::channel;
+///
+/// fn main() {
+///     let (_tx, rx) = channel::<u32>();
+///     let id = select!(
+///         _ = rx.recv() => {},
+///         timeout(Duration::from_millis(10)) => println!("timeout")
+///     );
+///     assert_eq!(id, 1);
+/// }
+/// ```
 #[macro_export]
 macro_rules! select {
+    // collect the arms one by one, so that the timeout arm is matched
+    // before it's parsed as a pattern
     (
-        $($name:pat = $top:expr => $bottom:expr),+
+        @arms [$([$name:pat] [$top:expr] [$bottom:expr])*]
+        timeout($dur:expr) => $timeout:expr $(,)*
+    ) => ({
+        use $crate::cqueue;
+        cqueue::scope(|cqueue| {
+            let mut _token = 0;
+            $(
+                cqueue_add_oneshot!(cqueue, _token, $name = $top => $bottom);
+                _token += 1;
+            )*
+            match cqueue.poll(Some($dur)) {
+                Ok(ev) => return ev.token,
+                #[allow(unreachable_code)]
+                Err(cqueue::PollError::Timeout) => {
+                    $timeout;
+                    return _token;
+                }
+                _ => unreachable!("select error"),
+            }
+        })
+    });
+    (
+        @arms [$([$name:pat] [$top:expr] [$bottom:expr])+] $(,)*
     ) => ({
         use $crate::cqueue;
         cqueue::scope(|cqueue| {
@@ -98,7 +145,18 @@ macro_rules! select {
                 _ => unreachable!("select error"),
             }
         })
-    })
+    });
+    (
+        @arms [$($arms:tt)*]
+        $name:pat = $top:expr => $bottom:expr, $($rest:tt)*
+    ) => (
+        select!(@arms [$($arms)* [$name] [$top] [$bottom]] $($rest)*)
+    );
+    (
+        $($arms:tt)+
+    ) => (
+        select!(@arms [] $($arms)+ ,)
+    );
 }
 
 /// macro used to join all scoped sub coroutines
@@ -120,13 +178,41 @@ macro_rules! join {
 ///
 /// This macro is intentionally similar to the `thread_local!`, and creates a
 /// `static` which has a `with` method to access the data on a coroutine.
+/// several keys could be declared at once, each with its own attributes and
+/// visibility.
 ///
 /// The data associated with each coroutine local is per-coroutine,
 /// so different coroutines will contain different values.
+///
+/// # Examples
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate may;
+///
+/// use std::cell::Cell;
+///
+/// coroutine_local! {
+///     static COUNT: Cell<u32> = Cell::new(0);
+///     pub static NAME: String = "may".to_owned();
+/// }
+///
+/// fn main() {
+///     let h = go!(|| {
+///         COUNT.with(|c| c.set(c.get() + 1));
+///         COUNT.with(|c| c.get())
+///     });
+///     assert_eq!(h.join().unwrap(), 1);
+///     // each coroutine has its own value
+///     assert_eq!(go!(|| COUNT.with(|c| c.get())).join().unwrap(), 0);
+///     NAME.with(|n| assert_eq!(n, "may"));
+/// }
+/// ```
 #[macro_export]
 macro_rules! coroutine_local {
-    (static $NAME:ident: $t:ty = $e:expr) => (
-        static $NAME: $crate::LocalKey<$t> = {
+    // the key of a single declaration
+    (@key $(#[$attr:meta])* $vis:vis $NAME:ident, $t:ty, $e:expr) => (
+        $(#[$attr])* $vis static $NAME: $crate::LocalKey<$t> = {
             fn __init() -> $t { $e }
             fn __key() -> ::std::any::TypeId {
                 struct __A;
@@ -137,5 +223,13 @@ macro_rules! coroutine_local {
                 __key: __key,
             }
         };
-    )
+    );
+    () => ();
+    ($(#[$attr:meta])* $vis:vis static $NAME:ident: $t:ty = $e:expr; $($rest:tt)*) => (
+        coroutine_local!(@key $(#[$attr])* $vis $NAME, $t, $e);
+        coroutine_local!($($rest)*);
+    );
+    ($(#[$attr:meta])* $vis:vis static $NAME:ident: $t:ty = $e:expr) => (
+        coroutine_local!(@key $(#[$attr])* $vis $NAME, $t, $e);
+    );
 }
@@ -3,6 +3,10 @@
 
 mod tcp;
 mod udp;
+mod resolve;
+pub(crate) mod sockopt;
 
-pub use self::tcp::{TcpListener, TcpStream};
+pub use self::tcp::{TcpListener, TcpListenerBuilder, TcpStream};
 pub use self::udp::UdpSocket;
+pub use self::resolve::resolve;
+pub use self::sockopt::TcpKeepalive;
@@ -0,0 +1,115 @@
+use std::io;
+use std::mem;
+use std::panic;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+
+use blocking::spawn_blocking;
+use cancel::CancelGuard;
+use coroutine_impl::is_coroutine;
+
+/// resolve the host name to the socket addresses
+///
+/// the system resolver blocks the calling thread, in coroutine context the
+/// lookup runs on the blocking thread pool of `spawn_blocking` instead, so
+/// only the calling coroutine waits for it while the worker keeps running
+/// the others. an ip address is returned as is without the lookup
+///
+/// `TcpStream::connect` and `TcpStream::connect_host` resolve through the
+/// pool as well, the other APIs that take `ToSocketAddrs` still resolve the
+/// host name on the worker, pass them the resolved addresses to avoid that
+///
+/// # Examples
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate may;
+///
+/// use may::net::{self, TcpListener, TcpStream};
+///
+/// fn main() {
+///     let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+///     let port = listener.local_addr().unwrap().port();
+///     let h = go!(move || {
+///         let addrs = net::resolve("localhost", port).unwrap();
+///         TcpStream::connect(&addrs[..]).is_ok()
+///     });
+///     let _s = listener.accept();
+///     assert!(h.join().unwrap());
+/// }
+/// ```
+pub fn resolve(host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+    resolve_with(host, port, lookup)
+}
+
+// resolve the host name by the given lookup, so that the tests could slow it
+pub(crate) fn resolve_with<F>(host: &str, port: u16, lookup: F) -> io::Result<Vec<SocketAddr>>
+where
+    F: FnOnce(&str, u16) -> io::Result<Vec<SocketAddr>> + Send + 'static,
+{
+    // the ipv6 address may be given in the bracket form
+    let ip = host.trim_left_matches('[').trim_right_matches(']');
+    if let Ok(ip) = ip.parse::<IpAddr>() {
+        return Ok(vec![SocketAddr::new(ip, port)]);
+    }
+
+    if !is_coroutine() {
+        return lookup(host, port);
+    }
+
+    let host = host.to_owned();
+    match spawn_blocking(move || lookup(&host, port)).join() {
+        Ok(ret) => ret,
+        Err(panic) => panic::resume_unwind(panic),
+    }
+}
+
+// resolve the borrowed addresses on the blocking thread pool in coroutine
+// context, the cancel is deferred until the lookup is done with the borrow
+pub(crate) fn resolve_addrs<A>(addr: &A) -> io::Result<Vec<SocketAddr>>
+where
+    A: ToSocketAddrs + Sync + ?Sized,
+{
+    if !is_coroutine() {
+        return addr.to_socket_addrs().map(|addrs| addrs.collect());
+    }
+
+    let _guard = CancelGuard::new();
+    let job: Box<FnOnce() -> io::Result<Vec<SocketAddr>> + Send> =
+        Box::new(move || addr.to_socket_addrs().map(|addrs| addrs.collect()));
+    // the job is always waited below, so it never outlives the borrow
+    let job: Box<FnOnce() -> io::Result<Vec<SocketAddr>> + Send + 'static> =
+        unsafe { mem::transmute(job) };
+    match spawn_blocking(job).join() {
+        Ok(ret) => ret,
+        Err(panic) => panic::resume_unwind(panic),
+    }
+}
+
+pub(crate) fn lookup(host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+    (host, port).to_socket_addrs().map(|addrs| addrs.collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_ip() {
+        let addrs = resolve("127.0.0.1", 80).unwrap();
+        assert_eq!(addrs, vec!["127.0.0.1:80".parse().unwrap()]);
+        let addrs = resolve("[::1]", 80).unwrap();
+        assert_eq!(addrs, vec!["[::1]:80".parse().unwrap()]);
+    }
+
+    #[test]
+    fn resolve_in_coroutine() {
+        let h = go!(|| resolve("localhost", 8080));
+        let addrs = h.join().unwrap().unwrap();
+        assert!(!addrs.is_empty());
+        assert!(addrs.iter().all(|a| a.port() == 8080 && a.ip().is_loopback()));
+
+        // the lookup error is returned to the caller
+        let h = go!(|| resolve("no-such-host.invalid", 80));
+        assert!(h.join().unwrap().is_err());
+    }
+}
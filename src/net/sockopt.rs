@@ -0,0 +1,269 @@
+//! the socket options that std doesn't expose, set through socket2 on the
+//! borrowed socket without taking the ownership
+
+use std::io;
+use std::time::Duration;
+use std::mem::ManuallyDrop;
+use socket2::Socket;
+
+/// The TCP keepalive parameters
+///
+/// the parameters left as `None` keep the system defaults. not every
+/// platform supports tuning the interval and the retries, setting them
+/// there returns an error of `ErrorKind::Other` kind
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::Duration;
+/// use may::net::{TcpKeepalive, TcpListener, TcpStream};
+///
+/// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+/// let s = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+/// let keepalive = TcpKeepalive::new().with_time(Duration::from_secs(60));
+/// s.set_keepalive(Some(&keepalive)).unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TcpKeepalive {
+    time: Option<Duration>,
+    interval: Option<Duration>,
+    retries: Option<u32>,
+}
+
+impl TcpKeepalive {
+    /// create the keepalive parameters with all the system defaults
+    pub fn new() -> TcpKeepalive {
+        TcpKeepalive::default()
+    }
+
+    /// set the idle time before the first keepalive probe is sent
+    pub fn with_time(self, time: Duration) -> TcpKeepalive {
+        TcpKeepalive {
+            time: Some(time),
+            ..self
+        }
+    }
+
+    /// set the interval between the keepalive probes
+    pub fn with_interval(self, interval: Duration) -> TcpKeepalive {
+        TcpKeepalive {
+            interval: Some(interval),
+            ..self
+        }
+    }
+
+    /// set the number of unacknowledged probes before the connection is dropped
+    pub fn with_retries(self, retries: u32) -> TcpKeepalive {
+        TcpKeepalive {
+            retries: Some(retries),
+            ..self
+        }
+    }
+
+    /// the idle time before the first keepalive probe
+    pub fn time(&self) -> Option<Duration> {
+        self.time
+    }
+
+    /// the interval between the keepalive probes
+    pub fn interval(&self) -> Option<Duration> {
+        self.interval
+    }
+
+    /// the number of unacknowledged probes before the connection is dropped
+    pub fn retries(&self) -> Option<u32> {
+        self.retries
+    }
+}
+
+pub(crate) fn unsupported(opt: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Other,
+        format!("{} is not supported on this platform", opt),
+    )
+}
+
+/// run the closure on the socket2 view of the socket, the socket is not closed
+#[cfg(unix)]
+pub(crate) fn with_socket<T, F, R>(t: &T, f: F) -> io::Result<R>
+where
+    T: ::std::os::unix::io::AsRawFd,
+    F: FnOnce(&Socket) -> io::Result<R>,
+{
+    use std::os::unix::io::FromRawFd;
+    let s = ManuallyDrop::new(unsafe { Socket::from_raw_fd(t.as_raw_fd()) });
+    f(&s)
+}
+
+/// run the closure on the socket2 view of the socket, the socket is not closed
+#[cfg(windows)]
+pub(crate) fn with_socket<T, F, R>(t: &T, f: F) -> io::Result<R>
+where
+    T: ::std::os::windows::io::AsRawSocket,
+    F: FnOnce(&Socket) -> io::Result<R>,
+{
+    use std::os::windows::io::FromRawSocket;
+    let s = ManuallyDrop::new(unsafe { Socket::from_raw_socket(t.as_raw_socket()) });
+    f(&s)
+}
+
+pub(crate) fn set_keepalive(s: &Socket, keepalive: Option<&TcpKeepalive>) -> io::Result<()> {
+    let keepalive = match keepalive {
+        Some(k) => k,
+        None => return s.set_keepalive(None),
+    };
+
+    match keepalive.time {
+        Some(time) => s.set_keepalive(Some(time))?,
+        None => enable_keepalive(s)?,
+    }
+    if let Some(interval) = keepalive.interval {
+        sys::set_interval(s, interval)?;
+    }
+    if let Some(retries) = keepalive.retries {
+        sys::set_retries(s, retries)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn keepalive(s: &Socket) -> io::Result<Option<TcpKeepalive>> {
+    let time = match s.keepalive()? {
+        Some(time) => time,
+        None => return Ok(None),
+    };
+    Ok(Some(TcpKeepalive {
+        time: Some(time),
+        interval: sys::interval(s)?,
+        retries: sys::retries(s)?,
+    }))
+}
+
+#[cfg(unix)]
+fn enable_keepalive(s: &Socket) -> io::Result<()> {
+    sys::setsockopt(s, ::libc::SOL_SOCKET, ::libc::SO_KEEPALIVE, 1)
+}
+
+// windows can't turn it on without the idle time, use the system default
+#[cfg(windows)]
+fn enable_keepalive(s: &Socket) -> io::Result<()> {
+    s.set_keepalive(Some(Duration::from_secs(2 * 60 * 60)))
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::io;
+    use std::mem;
+    use std::time::Duration;
+    use std::os::unix::io::AsRawFd;
+    use libc;
+    use socket2::Socket;
+
+    pub fn setsockopt(s: &Socket, level: libc::c_int, opt: libc::c_int, val: libc::c_int) -> io::Result<()> {
+        let ret = unsafe {
+            libc::setsockopt(
+                s.as_raw_fd(),
+                level,
+                opt,
+                &val as *const _ as *const libc::c_void,
+                mem::size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        };
+        if ret == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    #[allow(dead_code)]
+    fn getsockopt(s: &Socket, level: libc::c_int, opt: libc::c_int) -> io::Result<libc::c_int> {
+        let mut val: libc::c_int = 0;
+        let mut len = mem::size_of::<libc::c_int>() as libc::socklen_t;
+        let ret = unsafe {
+            libc::getsockopt(
+                s.as_raw_fd(),
+                level,
+                opt,
+                &mut val as *mut _ as *mut libc::c_void,
+                &mut len,
+            )
+        };
+        if ret == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(val)
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd",
+              target_os = "macos", target_os = "ios"))]
+    pub fn set_interval(s: &Socket, interval: Duration) -> io::Result<()> {
+        let secs = interval.as_secs() as libc::c_int;
+        setsockopt(s, libc::IPPROTO_TCP, libc::TCP_KEEPINTVL, secs)
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd",
+              target_os = "macos", target_os = "ios"))]
+    pub fn set_retries(s: &Socket, retries: u32) -> io::Result<()> {
+        setsockopt(s, libc::IPPROTO_TCP, libc::TCP_KEEPCNT, retries as libc::c_int)
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd",
+              target_os = "macos", target_os = "ios"))]
+    pub fn interval(s: &Socket) -> io::Result<Option<Duration>> {
+        let secs = getsockopt(s, libc::IPPROTO_TCP, libc::TCP_KEEPINTVL)?;
+        Ok(Some(Duration::from_secs(secs as u64)))
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd",
+              target_os = "macos", target_os = "ios"))]
+    pub fn retries(s: &Socket) -> io::Result<Option<u32>> {
+        let cnt = getsockopt(s, libc::IPPROTO_TCP, libc::TCP_KEEPCNT)?;
+        Ok(Some(cnt as u32))
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd",
+                  target_os = "macos", target_os = "ios")))]
+    pub fn set_interval(_s: &Socket, _interval: Duration) -> io::Result<()> {
+        Err(super::unsupported("TCP_KEEPINTVL"))
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd",
+                  target_os = "macos", target_os = "ios")))]
+    pub fn set_retries(_s: &Socket, _retries: u32) -> io::Result<()> {
+        Err(super::unsupported("TCP_KEEPCNT"))
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd",
+                  target_os = "macos", target_os = "ios")))]
+    pub fn interval(_s: &Socket) -> io::Result<Option<Duration>> {
+        Ok(None)
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd",
+                  target_os = "macos", target_os = "ios")))]
+    pub fn retries(_s: &Socket) -> io::Result<Option<u32>> {
+        Ok(None)
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use std::io;
+    use std::time::Duration;
+    use socket2::Socket;
+
+    pub fn set_interval(_s: &Socket, _interval: Duration) -> io::Result<()> {
+        Err(super::unsupported("the keepalive interval"))
+    }
+
+    pub fn set_retries(_s: &Socket, _retries: u32) -> io::Result<()> {
+        Err(super::unsupported("the keepalive retries"))
+    }
+
+    pub fn interval(_s: &Socket) -> io::Result<Option<Duration>> {
+        Ok(None)
+    }
+
+    pub fn retries(_s: &Socket) -> io::Result<Option<u32>> {
+        Ok(None)
+    }
+}
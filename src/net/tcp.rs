@@ -1,3 +1,5 @@
+use std::cmp;
+use std::fs::File;
 use std::time::Duration;
 use std::io::{self, Read, Write};
 use std::net::{self, Shutdown, SocketAddr, ToSocketAddrs};
@@ -5,6 +7,11 @@ use io as io_impl;
 use io::net as net_impl;
 use yield_now::yield_with;
 use coroutine_impl::is_coroutine;
+use config::config;
+use socket2::{Domain, Socket, Type};
+use super::sockopt::{self, TcpKeepalive};
+#[cfg(unix)]
+use std::time::Instant;
 
 // ===== TcpStream =====
 //
@@ -39,14 +46,101 @@ impl TcpStream {
         &self.sys
     }
 
-    pub fn connect<A: ToSocketAddrs>(addr: A) -> io::Result<TcpStream> {
+    /// register a std `TcpStream` so that it can be used in coroutines
+    pub fn from_std(s: net::TcpStream) -> io::Result<TcpStream> {
+        TcpStream::new(s)
+    }
+
+    /// remove the stream from the selector and put it back to blocking mode
+    ///
+    /// the stream is taken by value, so no coroutine could be parked on it
+    /// at the time of the conversion. dropping the io data deletes the fd
+    /// from the selector and removes the pending io timer if any, the fd is
+    /// not closed. the clones made by `try_clone` own their fds and keep
+    /// their registrations
+    ///
+    /// on windows the socket stays associated with the completion port,
+    /// which can't be undone, only the blocking mode is restored
+    pub fn into_std(self) -> io::Result<net::TcpStream> {
+        let TcpStream { io, sys, .. } = self;
+        drop(io);
+        sys.set_nonblocking(false)?;
+        Ok(sys)
+    }
+
+    /// connect to the address
+    ///
+    /// the resolved addresses are tried in order and the last error is
+    /// returned if all of them fail. in coroutine context the addresses are
+    /// resolved on the blocking thread pool like `net::resolve`, so a host
+    /// name lookup doesn't block the worker thread
+    pub fn connect<A: ToSocketAddrs + Sync>(addr: A) -> io::Result<TcpStream> {
         if !is_coroutine() {
             let s = net::TcpStream::connect(addr)?;
             let io = io_impl::IoData::new(&s);
             return Ok(TcpStream::from_stream(s, io));
         }
 
-        let mut c = net_impl::TcpStreamConnect::new(addr, None)?;
+        let addrs = super::resolve::resolve_addrs(&addr)?;
+        TcpStream::connect_each(&addrs)
+    }
+
+    /// connect to the host without blocking the worker on the name lookup
+    ///
+    /// the host name is resolved by `net::resolve` on the blocking thread
+    /// pool, then the addresses are tried in order like `connect`. an ip
+    /// address is connected directly without the lookup
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use may::net::TcpStream;
+    ///
+    /// let s = TcpStream::connect_host("example.com", 80).unwrap();
+    /// ```
+    pub fn connect_host(host: &str, port: u16) -> io::Result<TcpStream> {
+        TcpStream::connect_host_with(host, port, super::resolve::lookup)
+    }
+
+    // connect to the host that is resolved by the given lookup
+    fn connect_host_with<F>(host: &str, port: u16, lookup: F) -> io::Result<TcpStream>
+    where
+        F: FnOnce(&str, u16) -> io::Result<Vec<SocketAddr>> + Send + 'static,
+    {
+        let addrs = super::resolve::resolve_with(host, port, lookup)?;
+        if !is_coroutine() {
+            return TcpStream::connect(&addrs[..]);
+        }
+        TcpStream::connect_each(&addrs)
+    }
+
+    // connect to the addresses in order in coroutine context
+    // each of them is given the default connect timeout
+    fn connect_each(addrs: &[SocketAddr]) -> io::Result<TcpStream> {
+        let timeout = config().get_connect_timeout();
+        let mut last_err = None;
+        for addr in addrs {
+            match TcpStream::connect_one(addr, None, Some(timeout)) {
+                Ok(s) => return Ok(s),
+                Err(e) => last_err = Some(e),
+            }
+        }
+
+        Err(last_err.unwrap_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "could not resolve to any addresses",
+            )
+        }))
+    }
+
+    // the nonblocking connect in coroutine context
+    fn connect_one(
+        addr: &SocketAddr,
+        local: Option<SocketAddr>,
+        timeout: Option<Duration>,
+    ) -> io::Result<TcpStream> {
+        let mut c = net_impl::TcpStreamConnect::new(addr, local, timeout)?;
 
         if c.is_connected()? {
             return c.done();
@@ -56,21 +150,63 @@ impl TcpStream {
         c.done()
     }
 
+    /// connect with a per call timeout, `TimedOut` is returned when it expires
     pub fn connect_timeout(addr: &SocketAddr, timeout: Duration) -> io::Result<TcpStream> {
+        if timeout == Duration::from_secs(0) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "cannot set a 0 duration timeout",
+            ));
+        }
+
         if !is_coroutine() {
             let s = net::TcpStream::connect_timeout(addr, timeout)?;
             let io = io_impl::IoData::new(&s);
             return Ok(TcpStream::from_stream(s, io));
         }
 
-        let mut c = net_impl::TcpStreamConnect::new(addr, Some(timeout))?;
+        TcpStream::connect_one(addr, None, Some(timeout))
+    }
 
-        if c.is_connected()? {
-            return c.done();
+    /// connect to `remote` from the given local address
+    ///
+    /// the socket is bound to `local` before the connect, so the source
+    /// interface of the connection could be chosen on a multi-homed host.
+    /// a zero port in `local` picks an ephemeral port on the fixed ip
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use may::net::TcpStream;
+    ///
+    /// let local = "192.168.1.10:0".parse().unwrap();
+    /// let remote = "192.168.1.1:80".parse().unwrap();
+    /// let s = TcpStream::connect_from(local, remote).unwrap();
+    /// assert_eq!(s.local_addr().unwrap().ip(), local.ip());
+    /// ```
+    pub fn connect_from(local: SocketAddr, remote: SocketAddr) -> io::Result<TcpStream> {
+        if local.is_ipv4() != remote.is_ipv4() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the local and remote addresses are of different families",
+            ));
         }
 
-        yield_with(&c);
-        c.done()
+        if !is_coroutine() {
+            let domain = match remote {
+                SocketAddr::V4(..) => Domain::ipv4(),
+                SocketAddr::V6(..) => Domain::ipv6(),
+            };
+            let socket = Socket::new(domain, Type::stream(), None)?;
+            socket.bind(&local.into())?;
+            socket.connect(&remote.into())?;
+            let s = socket.into_tcp_stream();
+            let io = io_impl::IoData::new(&s);
+            return Ok(TcpStream::from_stream(s, io));
+        }
+
+        let timeout = config().get_connect_timeout();
+        TcpStream::connect_one(&remote, Some(local), Some(timeout))
     }
 
     pub fn peer_addr(&self) -> io::Result<SocketAddr> {
@@ -106,6 +242,124 @@ impl TcpStream {
         })
     }
 
+    /// receive data without removing it from the queue, a following
+    /// `read` would return the same data
+    #[cfg(unix)]
+    pub fn peek(&self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.ctx.check_nonblocking(|b| self.sys.set_nonblocking(b))?
+            || !self.ctx.check_context(|b| self.sys.set_nonblocking(b))?
+        {
+            return self.sys.peek(buf);
+        }
+
+        self.io.reset();
+        // this is an earlier return try for nonblocking read
+        match self.sys.peek(buf) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            ret => return ret,
+        }
+
+        let reader = net_impl::SocketPeek::new(self, buf, self.read_timeout);
+        yield_with(&reader);
+        reader.done()
+    }
+
+    /// wait until the stream is readable without reading anything
+    ///
+    /// it's for the code that does the io on the raw fd by itself, only the
+    /// current coroutine is blocked and the wait is limited by the read
+    /// timeout. the readiness may be already gone when the caller reads,
+    /// e.g. taken by another reader of the same fd, so a following read
+    /// could still return `WouldBlock` and the caller should wait again
+    ///
+    /// it could be an arm of `select!` along with the other events, when
+    /// another arm wins the wait is canceled and the data is left for the
+    /// next read. but `select!` spawns a select coroutine for each arm each
+    /// time, a loop that waits the stream should use `read_ready_event`
+    #[cfg(unix)]
+    pub fn wait_read(&self) -> io::Result<()> {
+        self.read_ready_event().wait()
+    }
+
+    /// wait until the stream is writable without writing anything
+    ///
+    /// the same as `wait_read`, but for the write and limited by the
+    /// write timeout
+    #[cfg(unix)]
+    pub fn wait_write(&self) -> io::Result<()> {
+        self.write_ready_event().wait()
+    }
+
+    /// return the event that the stream is readable, for `cqueue_add!`
+    ///
+    /// the select coroutine of the arm waits on the stream again right after
+    /// its bottom half, so the stream should be read in the bottom half, and
+    /// the other coroutines should do the io on it by a `try_clone`. the
+    /// read still gets the data after the event, it's not consumed
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// #[macro_use]
+    /// extern crate may;
+    ///
+    /// use std::io::{Read, Write};
+    /// use may::cqueue;
+    /// use may::net::{TcpListener, TcpStream};
+    /// use may::sync::mpsc::channel;
+    ///
+    /// fn main() {
+    ///     let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    ///     let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    ///     let (stream, _) = listener.accept().unwrap();
+    ///     let (tx, rx) = channel();
+    ///
+    ///     client.write_all(b"ping").unwrap();
+    ///     tx.send("stop").unwrap();
+    ///     cqueue::scope(|q| {
+    ///         let ready = stream.read_ready_event();
+    ///         let mut buf = [0u8; 4];
+    ///         // one select coroutine for all the reads
+    ///         cqueue_add!(q, 0, _ = ready.wait() => {
+    ///             (&stream).read_exact(&mut buf).unwrap();
+    ///             assert_eq!(&buf, b"ping");
+    ///         });
+    ///         cqueue_add_oneshot!(q, 1, cmd = rx.recv() => assert_eq!(cmd, Ok("stop")));
+    ///
+    ///         let mut tokens = vec![q.poll(None).unwrap().token, q.poll(None).unwrap().token];
+    ///         tokens.sort();
+    ///         assert_eq!(tokens, [0, 1]);
+    ///     });
+    /// }
+    /// ```
+    #[cfg(unix)]
+    pub fn read_ready_event(&self) -> io_impl::ReadyEvent<TcpStream> {
+        io_impl::ReadyEvent::new(self, ::libc::POLLIN, self.read_timeout, TcpStream::ready_ctx)
+    }
+
+    /// return the event that the stream is writable, for `cqueue_add!`
+    ///
+    /// the same as `read_ready_event`, but for the write and limited by the
+    /// write timeout
+    #[cfg(unix)]
+    pub fn write_ready_event(&self) -> io_impl::ReadyEvent<TcpStream> {
+        io_impl::ReadyEvent::new(self, ::libc::POLLOUT, self.write_timeout, TcpStream::ready_ctx)
+    }
+
+    // return true if the wait should yield the coroutine
+    #[cfg(unix)]
+    fn ready_ctx(&self) -> io::Result<bool> {
+        // the fd is kept nonblocking in the nonblocking mode
+        if self.ctx.check_nonblocking(|b| self.sys.set_nonblocking(b))? {
+            return Ok(is_coroutine());
+        }
+        self.ctx.check_context(|b| self.sys.set_nonblocking(b))
+    }
+
+    /// shut down the read, write, or both halves of the connection
+    ///
+    /// the coroutines blocked in the read or write of the stream or its
+    /// clones are waked up by the selector and see the EOF or `BrokenPipe`
     pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
         self.sys.shutdown(how)
     }
@@ -114,6 +368,66 @@ impl TcpStream {
         self.sys.set_nodelay(nodelay)
     }
 
+    pub fn nodelay(&self) -> io::Result<bool> {
+        self.sys.nodelay()
+    }
+
+    pub fn set_ttl(&self, ttl: u32) -> io::Result<()> {
+        self.sys.set_ttl(ttl)
+    }
+
+    pub fn ttl(&self) -> io::Result<u32> {
+        self.sys.ttl()
+    }
+
+    /// set the SO_LINGER option, `None` means the close returns immediately
+    /// and the pending data is sent in the background. a zero duration makes
+    /// the close discard the pending data and reset the connection
+    pub fn set_linger(&self, dur: Option<Duration>) -> io::Result<()> {
+        sockopt::with_socket(&self.sys, |s| s.set_linger(dur))
+    }
+
+    pub fn linger(&self) -> io::Result<Option<Duration>> {
+        sockopt::with_socket(&self.sys, |s| s.linger())
+    }
+
+    /// turn on the TCP keepalive with the parameters, `None` turns it off
+    pub fn set_keepalive(&self, keepalive: Option<&TcpKeepalive>) -> io::Result<()> {
+        sockopt::with_socket(&self.sys, |s| sockopt::set_keepalive(s, keepalive))
+    }
+
+    /// return the TCP keepalive parameters, `None` if it's off
+    ///
+    /// the parameters that can't be read on the platform are `None`
+    pub fn keepalive(&self) -> io::Result<Option<TcpKeepalive>> {
+        sockopt::with_socket(&self.sys, sockopt::keepalive)
+    }
+
+    /// set `SO_RCVBUF`, it bounds the receive window advertised to the peer
+    ///
+    /// the kernel may adjust the size, linux doubles it for the bookkeeping.
+    /// the window scale is agreed when connecting, so on a connected stream
+    /// it can't grow the window past what the scale allows
+    pub fn set_recv_buffer_size(&self, size: usize) -> io::Result<()> {
+        sockopt::with_socket(&self.sys, |s| s.set_recv_buffer_size(size))
+    }
+
+    /// return `SO_RCVBUF` as the kernel keeps it
+    pub fn recv_buffer_size(&self) -> io::Result<usize> {
+        sockopt::with_socket(&self.sys, |s| s.recv_buffer_size())
+    }
+
+    /// set `SO_SNDBUF`, the written data that is not acked by the peer yet
+    /// is kept in it, a write waits when it's full
+    pub fn set_send_buffer_size(&self, size: usize) -> io::Result<()> {
+        sockopt::with_socket(&self.sys, |s| s.set_send_buffer_size(size))
+    }
+
+    /// return `SO_SNDBUF` as the kernel keeps it
+    pub fn send_buffer_size(&self) -> io::Result<usize> {
+        sockopt::with_socket(&self.sys, |s| s.send_buffer_size())
+    }
+
     pub fn take_error(&self) -> io::Result<Option<io::Error>> {
         self.sys.take_error()
     }
@@ -145,6 +459,199 @@ impl TcpStream {
         Ok(())
     }
 
+    /// send `len` bytes of the file starting from `offset` to the stream
+    ///
+    /// the data is sent by `sendfile(2)` on linux, macos and freebsd without
+    /// going through a user space buffer, on the other platforms it's read
+    /// and written in a loop. the file offset is not changed. only the current
+    /// coroutine is blocked and each wait is limited by the write timeout
+    ///
+    /// return the number of bytes sent, which is less than `len` if the file
+    /// reaches EOF or an error happens after some of the data is sent, like
+    /// `write` the error is then returned by the next call that resumes from
+    /// `offset` plus the sent bytes. a peer that closes the connection gives
+    /// a `BrokenPipe` or `ConnectionReset` error
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "ios",
+            target_os = "freebsd"))]
+    pub fn send_file(&self, file: &File, offset: u64, len: u64) -> io::Result<u64> {
+        // the max bytes that linux sendfile could send at once
+        const MAX_CHUNK: u64 = 0x7fff_f000;
+
+        let mut sent = 0;
+        while sent < len {
+            let n = cmp::min(len - sent, MAX_CHUNK) as usize;
+            match self.send_file_chunk(file, offset + sent, n) {
+                // EOF reached
+                Ok(0) => break,
+                Ok(n) => sent += n as u64,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                // report what's already sent, the error comes again next time
+                Err(_) if sent > 0 => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(sent)
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "ios",
+            target_os = "freebsd"))]
+    fn send_file_chunk(&self, file: &File, offset: u64, len: usize) -> io::Result<usize> {
+        let (fd, file) = (self.sys.as_raw_fd(), file.as_raw_fd());
+        if self.ctx.check_nonblocking(|b| self.sys.set_nonblocking(b))?
+            || !self.ctx.check_context(|b| self.sys.set_nonblocking(b))?
+        {
+            return net_impl::send_file(fd, file, offset, len);
+        }
+
+        self.io.reset();
+        // this is an earlier return try for nonblocking send
+        match net_impl::send_file(fd, file, offset, len) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            ret => return ret,
+        }
+
+        let writer = net_impl::SocketSendFile::new(self, file, offset, len, self.write_timeout);
+        yield_with(&writer);
+        writer.done()
+    }
+
+    /// send `len` bytes of the file starting from `offset` to the stream
+    ///
+    /// the data is sent by `sendfile(2)` on linux, macos and freebsd without
+    /// going through a user space buffer, on the other platforms it's read
+    /// and written in a loop. the file offset is not changed. only the current
+    /// coroutine is blocked and each wait is limited by the write timeout
+    ///
+    /// return the number of bytes sent, which is less than `len` if the file
+    /// reaches EOF or an error happens after some of the data is sent, like
+    /// `write` the error is then returned by the next call that resumes from
+    /// `offset` plus the sent bytes. a peer that closes the connection gives
+    /// a `BrokenPipe` or `ConnectionReset` error
+    #[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos",
+                target_os = "ios", target_os = "freebsd")))]
+    pub fn send_file(&self, file: &File, offset: u64, len: u64) -> io::Result<u64> {
+        let mut buf = vec![0u8; cmp::min(len, 64 * 1024) as usize];
+        let mut sent = 0;
+        while sent < len {
+            let n = cmp::min(len - sent, buf.len() as u64) as usize;
+            let n = match read_at(file, &mut buf[..n], offset + sent) {
+                // EOF reached
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            let mut pos = 0;
+            while pos < n {
+                match (&*self).write(&buf[pos..n]) {
+                    Ok(0) => {
+                        let e = io::Error::new(io::ErrorKind::WriteZero, "failed to write");
+                        return if sent > 0 { Ok(sent) } else { Err(e) };
+                    }
+                    Ok(m) => {
+                        pos += m;
+                        sent += m as u64;
+                    }
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    // report what's already sent, the error comes again next time
+                    Err(_) if sent > 0 => return Ok(sent),
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        Ok(sent)
+    }
+
+    /// read some data from the stream, waiting no later than the deadline
+    ///
+    /// the deadline only applies to this call, the read timeout of the stream
+    /// is ignored and not changed. an expired deadline gives a `TimedOut`
+    /// error carrying `DeadlineExceeded`. in thread context the deadline
+    /// limits the wait for the stream to be readable
+    #[cfg(unix)]
+    pub fn read_deadline(&self, buf: &mut [u8], deadline: Instant) -> io::Result<usize> {
+        self.read_until(buf, deadline)
+            .map_err(|e| io_impl::deadline::exceeded(e, 0))
+    }
+
+    /// read the exact number of bytes to fill the buffer before the deadline
+    ///
+    /// when the deadline expires in the middle, the `DeadlineExceeded` of the
+    /// returned error tells how many bytes are already read into the buffer
+    #[cfg(unix)]
+    pub fn read_exact_deadline(&self, buf: &mut [u8], deadline: Instant) -> io::Result<()> {
+        io_impl::deadline::read_exact(buf, |buf| self.read_until(buf, deadline))
+    }
+
+    /// write some data to the stream, waiting no later than the deadline
+    ///
+    /// the deadline only applies to this call, the write timeout of the stream
+    /// is ignored and not changed. an expired deadline gives a `TimedOut`
+    /// error carrying `DeadlineExceeded`. in thread context the deadline
+    /// limits the wait for the stream to be writable
+    #[cfg(unix)]
+    pub fn write_deadline(&self, buf: &[u8], deadline: Instant) -> io::Result<usize> {
+        self.write_until(buf, deadline)
+            .map_err(|e| io_impl::deadline::exceeded(e, 0))
+    }
+
+    /// write the whole buffer to the stream before the deadline
+    ///
+    /// when the deadline expires in the middle, the `DeadlineExceeded` of the
+    /// returned error tells how many bytes are already written
+    #[cfg(unix)]
+    pub fn write_all_deadline(&self, buf: &[u8], deadline: Instant) -> io::Result<()> {
+        io_impl::deadline::write_all(buf, |buf| self.write_until(buf, deadline))
+    }
+
+    #[cfg(unix)]
+    fn read_until(&self, buf: &mut [u8], deadline: Instant) -> io::Result<usize> {
+        if self.ctx.check_nonblocking(|b| self.sys.set_nonblocking(b))? {
+            return (&self.sys).read(buf);
+        }
+        if !self.ctx.check_context(|b| self.sys.set_nonblocking(b))? {
+            io_impl::deadline::wait_fd(self.sys.as_raw_fd(), ::libc::POLLIN, deadline)?;
+            return (&self.sys).read(buf);
+        }
+
+        // an expired deadline fails before the io
+        io_impl::deadline::remaining(deadline)?;
+        self.io.reset();
+        // this is an earlier return try for nonblocking read
+        match (&self.sys).read(buf) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            ret => return ret,
+        }
+
+        let reader = net_impl::SocketRead::new_until(self, buf, deadline)?;
+        yield_with(&reader);
+        reader.done()
+    }
+
+    #[cfg(unix)]
+    fn write_until(&self, buf: &[u8], deadline: Instant) -> io::Result<usize> {
+        if self.ctx.check_nonblocking(|b| self.sys.set_nonblocking(b))? {
+            return (&self.sys).write(buf);
+        }
+        if !self.ctx.check_context(|b| self.sys.set_nonblocking(b))? {
+            io_impl::deadline::wait_fd(self.sys.as_raw_fd(), ::libc::POLLOUT, deadline)?;
+            return (&self.sys).write(buf);
+        }
+
+        // an expired deadline fails before the io
+        io_impl::deadline::remaining(deadline)?;
+        self.io.reset();
+        // this is an earlier return try for nonblocking write
+        match (&self.sys).write(buf) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            ret => return ret,
+        }
+
+        let writer = net_impl::SocketWrite::new_until(self, buf, deadline)?;
+        yield_with(&writer);
+        writer.done()
+    }
+
     // convert std::net::TcpStream to Self without add_socket
     pub(crate) fn from_stream(s: net::TcpStream, io: io_impl::IoData) -> Self {
         TcpStream {
@@ -178,6 +685,26 @@ impl Read for TcpStream {
         yield_with(&reader);
         reader.done()
     }
+
+    #[cfg(unix)]
+    fn read_vectored(&mut self, bufs: &mut [io::IoSliceMut]) -> io::Result<usize> {
+        if self.ctx.check_nonblocking(|b| self.sys.set_nonblocking(b))?
+            || !self.ctx.check_context(|b| self.sys.set_nonblocking(b))?
+        {
+            return self.sys.read_vectored(bufs);
+        }
+
+        self.io.reset();
+        // this is an earlier return try for nonblocking read
+        match self.sys.read_vectored(bufs) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            ret => return ret,
+        }
+
+        let reader = net_impl::SocketReadVectored::new(self, bufs, self.read_timeout);
+        yield_with(&reader);
+        reader.done()
+    }
 }
 
 impl Write for TcpStream {
@@ -201,6 +728,26 @@ impl Write for TcpStream {
         writer.done()
     }
 
+    #[cfg(unix)]
+    fn write_vectored(&mut self, bufs: &[io::IoSlice]) -> io::Result<usize> {
+        if self.ctx.check_nonblocking(|b| self.sys.set_nonblocking(b))?
+            || !self.ctx.check_context(|b| self.sys.set_nonblocking(b))?
+        {
+            return self.sys.write_vectored(bufs);
+        }
+
+        self.io.reset();
+        // this is an earlier return try for nonblocking write
+        match self.sys.write_vectored(bufs) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            ret => return ret,
+        }
+
+        let writer = net_impl::SocketWriteVectored::new(self, bufs, self.write_timeout);
+        yield_with(&writer);
+        writer.done()
+    }
+
     fn flush(&mut self) -> io::Result<()> {
         // TcpStream just return Ok(()), no need to yield
         (&self.sys).flush()
@@ -212,6 +759,11 @@ impl<'a> Read for &'a TcpStream {
         let s = unsafe { &mut *(*self as *const _ as *mut _) };
         TcpStream::read(s, buf)
     }
+
+    fn read_vectored(&mut self, bufs: &mut [io::IoSliceMut]) -> io::Result<usize> {
+        let s = unsafe { &mut *(*self as *const _ as *mut _) };
+        TcpStream::read_vectored(s, bufs)
+    }
 }
 
 impl<'a> Write for &'a TcpStream {
@@ -220,6 +772,11 @@ impl<'a> Write for &'a TcpStream {
         TcpStream::write(s, buf)
     }
 
+    fn write_vectored(&mut self, bufs: &[io::IoSlice]) -> io::Result<usize> {
+        let s = unsafe { &mut *(*self as *const _ as *mut _) };
+        TcpStream::write_vectored(s, bufs)
+    }
+
     fn flush(&mut self) -> io::Result<()> {
         let s = unsafe { &mut *(*self as *const _ as *mut _) };
         TcpStream::flush(s)
@@ -231,6 +788,14 @@ impl io_impl::AsIoData for TcpStream {
     fn as_io_data(&self) -> &io_impl::IoData {
         &self.io
     }
+
+    fn io_read_timeout(&self) -> Option<Duration> {
+        self.read_timeout
+    }
+
+    fn io_write_timeout(&self) -> Option<Duration> {
+        self.write_timeout
+    }
 }
 
 // ===== TcpListener =====
@@ -262,11 +827,34 @@ impl TcpListener {
         &self.sys
     }
 
+    /// register a std `TcpListener` so that it can be used in coroutines
+    pub fn from_std(s: net::TcpListener) -> io::Result<TcpListener> {
+        TcpListener::new(s)
+    }
+
+    /// remove the listener from the selector and put it back to blocking mode
+    pub fn into_std(self) -> io::Result<net::TcpListener> {
+        let TcpListener { io, sys, .. } = self;
+        drop(io);
+        sys.set_nonblocking(false)?;
+        Ok(sys)
+    }
+
     pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<TcpListener> {
         let s = net::TcpListener::bind(addr)?;
         TcpListener::new(s)
     }
 
+    /// bind with `SO_REUSEPORT` set, so that several listeners can bind the same
+    /// port and the kernel would balance the incoming connections among them
+    pub fn bind_reuseport<A: ToSocketAddrs>(addr: A) -> io::Result<TcpListener> {
+        TcpListenerBuilder::new()
+            .reuse_address(true)
+            .reuse_port(true)
+            .bind(addr)?
+            .listen()
+    }
+
     pub fn accept(&self) -> io::Result<(TcpStream, SocketAddr)> {
         if self.ctx.check_nonblocking(|b| self.sys.set_nonblocking(b))?
             || !self.ctx.check_context(|b| self.sys.set_nonblocking(b))?
@@ -319,6 +907,35 @@ impl TcpListener {
         Ok(())
     }
 
+    pub fn set_ttl(&self, ttl: u32) -> io::Result<()> {
+        self.sys.set_ttl(ttl)
+    }
+
+    pub fn ttl(&self) -> io::Result<u32> {
+        self.sys.ttl()
+    }
+
+    /// set `SO_RCVBUF` of the listening socket, the accepted streams inherit
+    /// it, use `TcpListenerBuilder` to set it before the listen
+    pub fn set_recv_buffer_size(&self, size: usize) -> io::Result<()> {
+        sockopt::with_socket(&self.sys, |s| s.set_recv_buffer_size(size))
+    }
+
+    /// return `SO_RCVBUF` of the listening socket
+    pub fn recv_buffer_size(&self) -> io::Result<usize> {
+        sockopt::with_socket(&self.sys, |s| s.recv_buffer_size())
+    }
+
+    /// set `SO_SNDBUF` of the listening socket, the accepted streams inherit it
+    pub fn set_send_buffer_size(&self, size: usize) -> io::Result<()> {
+        sockopt::with_socket(&self.sys, |s| s.set_send_buffer_size(size))
+    }
+
+    /// return `SO_SNDBUF` of the listening socket
+    pub fn send_buffer_size(&self) -> io::Result<usize> {
+        sockopt::with_socket(&self.sys, |s| s.send_buffer_size())
+    }
+
     pub fn take_error(&self) -> io::Result<Option<io::Error>> {
         self.sys.take_error()
     }
@@ -348,6 +965,180 @@ impl<'a> Iterator for Incoming<'a> {
     }
 }
 
+// ===== TcpListenerBuilder =====
+//
+//
+
+/// Configure the listening socket before the `bind` and `listen` calls
+///
+/// ```no_run
+/// use may::net::TcpListenerBuilder;
+///
+/// let listener = TcpListenerBuilder::new()
+///     .reuse_port(true)
+///     .backlog(1024)
+///     .bind("127.0.0.1:8080")
+///     .unwrap()
+///     .listen()
+///     .unwrap();
+/// ```
+#[derive(Debug)]
+pub struct TcpListenerBuilder {
+    reuse_address: Option<bool>,
+    reuse_port: bool,
+    only_v6: Option<bool>,
+    recv_buffer_size: Option<usize>,
+    send_buffer_size: Option<usize>,
+    backlog: i32,
+    // the bound socket
+    socket: Option<Socket>,
+}
+
+impl TcpListenerBuilder {
+    /// create a builder with the system defaults and a backlog of 128
+    pub fn new() -> TcpListenerBuilder {
+        TcpListenerBuilder {
+            reuse_address: None,
+            reuse_port: false,
+            only_v6: None,
+            recv_buffer_size: None,
+            send_buffer_size: None,
+            backlog: 128,
+            socket: None,
+        }
+    }
+
+    /// set `SO_REUSEADDR` on the socket
+    pub fn reuse_address(mut self, reuse: bool) -> TcpListenerBuilder {
+        self.reuse_address = Some(reuse);
+        self
+    }
+
+    /// set `SO_REUSEPORT` on the socket, only supported on unix
+    pub fn reuse_port(mut self, reuse: bool) -> TcpListenerBuilder {
+        self.reuse_port = reuse;
+        self
+    }
+
+    /// set `IPV6_V6ONLY` on the socket, ignored for ipv4 addresses
+    pub fn only_v6(mut self, only_v6: bool) -> TcpListenerBuilder {
+        self.only_v6 = Some(only_v6);
+        self
+    }
+
+    /// set `SO_RCVBUF` on the socket
+    pub fn recv_buffer_size(mut self, size: usize) -> TcpListenerBuilder {
+        self.recv_buffer_size = Some(size);
+        self
+    }
+
+    /// set `SO_SNDBUF` on the socket
+    pub fn send_buffer_size(mut self, size: usize) -> TcpListenerBuilder {
+        self.send_buffer_size = Some(size);
+        self
+    }
+
+    /// set the backlog passed to `listen`
+    pub fn backlog(mut self, backlog: i32) -> TcpListenerBuilder {
+        self.backlog = backlog;
+        self
+    }
+
+    /// create the socket with the options applied and bind it to `addr`
+    pub fn bind<A: ToSocketAddrs>(mut self, addr: A) -> io::Result<TcpListenerBuilder> {
+        if self.socket.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the socket is already bound",
+            ));
+        }
+
+        let mut last_err = None;
+        for addr in addr.to_socket_addrs()? {
+            match self.bind_addr(&addr) {
+                Ok(s) => {
+                    self.socket = Some(s);
+                    return Ok(self);
+                }
+                Err(e) => last_err = Some(e),
+            }
+        }
+
+        Err(last_err.unwrap_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "could not resolve to any addresses",
+            )
+        }))
+    }
+
+    fn bind_addr(&self, addr: &SocketAddr) -> io::Result<Socket> {
+        let domain = match *addr {
+            SocketAddr::V4(..) => Domain::ipv4(),
+            SocketAddr::V6(..) => Domain::ipv6(),
+        };
+        let s = Socket::new(domain, Type::stream(), None)?;
+        if let Some(reuse) = self.reuse_address {
+            s.set_reuse_address(reuse)?;
+        }
+        if self.reuse_port {
+            set_reuse_port(&s)?;
+        }
+        if let (Some(only_v6), &SocketAddr::V6(..)) = (self.only_v6, addr) {
+            s.set_only_v6(only_v6)?;
+        }
+        if let Some(size) = self.recv_buffer_size {
+            s.set_recv_buffer_size(size)?;
+        }
+        if let Some(size) = self.send_buffer_size {
+            s.set_send_buffer_size(size)?;
+        }
+        s.bind(&(*addr).into())?;
+        Ok(s)
+    }
+
+    /// start listening on the bound socket and register it to the selector
+    pub fn listen(self) -> io::Result<TcpListener> {
+        let s = self.socket.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "the socket is not bound")
+        })?;
+        s.listen(self.backlog)?;
+        TcpListener::new(s.into_tcp_listener())
+    }
+}
+
+impl Default for TcpListenerBuilder {
+    fn default() -> TcpListenerBuilder {
+        TcpListenerBuilder::new()
+    }
+}
+
+#[cfg(unix)]
+fn set_reuse_port(s: &Socket) -> io::Result<()> {
+    s.set_reuse_port(true)
+}
+
+#[cfg(not(unix))]
+fn set_reuse_port(_s: &Socket) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "SO_REUSEPORT is not supported on this platform",
+    ))
+}
+
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android", target_os = "macos",
+                         target_os = "ios", target_os = "freebsd"))))]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    use std::os::unix::fs::FileExt;
+    file.read_at(buf, offset)
+}
+
+#[cfg(windows)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    use std::os::windows::fs::FileExt;
+    file.seek_read(buf, offset)
+}
+
 // ===== UNIX ext =====
 //
 //
@@ -358,8 +1149,10 @@ use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
 #[cfg(unix)]
 impl IntoRawFd for TcpStream {
     fn into_raw_fd(self) -> RawFd {
-        self.sys.as_raw_fd()
-        // drop self will dereg from the selector
+        // dereg from the selector, the fd is owned by the caller
+        let TcpStream { io, sys, .. } = self;
+        drop(io);
+        sys.into_raw_fd()
     }
 }
 
@@ -381,8 +1174,10 @@ impl FromRawFd for TcpStream {
 #[cfg(unix)]
 impl IntoRawFd for TcpListener {
     fn into_raw_fd(self) -> RawFd {
-        self.sys.as_raw_fd()
-        // drop self will dereg from the selector
+        // dereg from the selector, the fd is owned by the caller
+        let TcpListener { io, sys, .. } = self;
+        drop(io);
+        sys.into_raw_fd()
     }
 }
 
@@ -455,3 +1250,976 @@ impl FromRawSocket for TcpListener {
             .unwrap_or_else(|e| panic!("from_raw_socket for TcpListener, err = {:?}", e))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nodelay() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let j = go!(move || {
+            let s = TcpStream::connect(addr).unwrap();
+            s.set_nodelay(true).unwrap();
+            assert_eq!(s.nodelay().unwrap(), true);
+            s.set_nodelay(false).unwrap();
+            assert_eq!(s.nodelay().unwrap(), false);
+        });
+
+        let (s, _) = listener.accept().unwrap();
+        s.set_nodelay(true).unwrap();
+        assert_eq!(s.nodelay().unwrap(), true);
+        j.join().unwrap();
+    }
+
+    #[test]
+    fn vectored() {
+        use std::io::{IoSlice, IoSliceMut};
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // big enough to fill the socket buffer so that the writer would yield
+        let head = vec![1u8; 1024 * 1024];
+        let body = vec![2u8; 3 * 1024 * 1024];
+        let total = head.len() + body.len();
+
+        let j = go!(move || {
+            let (mut s, _) = listener.accept().unwrap();
+            let mut buf = [0u8; 4];
+            let (a, b) = buf.split_at_mut(2);
+            // nothing is sent yet, this would yield the coroutine
+            let n = s.read_vectored(&mut [IoSliceMut::new(a), IoSliceMut::new(b)])
+                .unwrap();
+            assert_eq!(n, 4);
+            assert_eq!(buf, [0, 1, 2, 3]);
+
+            let mut data = vec![];
+            s.read_to_end(&mut data).unwrap();
+            data
+        });
+
+        let writer = go!(move || {
+            let mut s = TcpStream::connect(addr).unwrap();
+            ::coroutine::sleep(Duration::from_millis(10));
+            let n = s.write_vectored(&[IoSlice::new(&[0, 1]), IoSlice::new(&[2, 3])])
+                .unwrap();
+            assert_eq!(n, 4);
+
+            // handle the partial writes across the slice boundaries
+            let mut written = 0;
+            while written < total {
+                let n = if written < head.len() {
+                    let bufs = [IoSlice::new(&head[written..]), IoSlice::new(&body)];
+                    s.write_vectored(&bufs).unwrap()
+                } else {
+                    let bufs = [IoSlice::new(&body[written - head.len()..])];
+                    s.write_vectored(&bufs).unwrap()
+                };
+                written += n;
+            }
+        });
+
+        writer.join().unwrap();
+        let data = j.join().unwrap();
+        assert_eq!(data.len(), total);
+        assert!(data[..1024 * 1024].iter().all(|&b| b == 1));
+        assert!(data[1024 * 1024..].iter().all(|&b| b == 2));
+    }
+
+    #[test]
+    fn read_write_timeout() {
+        use std::time::Instant;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let j = go!(move || {
+            let mut s = TcpStream::connect(addr).unwrap();
+            let zero = Duration::from_secs(0);
+            assert_eq!(
+                s.set_read_timeout(Some(zero)).unwrap_err().kind(),
+                io::ErrorKind::InvalidInput
+            );
+
+            let dur = Duration::from_millis(50);
+            s.set_read_timeout(Some(dur)).unwrap();
+            assert_eq!(s.read_timeout().unwrap(), Some(dur));
+            let start = Instant::now();
+            let mut buf = [0u8; 4];
+            let err = s.read(&mut buf).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+            assert!(start.elapsed() >= dur);
+
+            // back to the default parking behavior
+            s.set_read_timeout(None).unwrap();
+            s.write_all(b"ping").unwrap();
+            s.read_exact(&mut buf).unwrap();
+            assert_eq!(&buf, b"pong");
+        });
+
+        let (mut s, _) = listener.accept().unwrap();
+        let mut buf = [0u8; 4];
+        s.read_exact(&mut buf).unwrap();
+        // wait longer than the timeout before the reply
+        ::std::thread::sleep(Duration::from_millis(100));
+        s.write_all(b"pong").unwrap();
+        j.join().unwrap();
+    }
+
+    #[test]
+    fn cancel_write_with_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let j = go!(move || {
+            let mut s = TcpStream::connect(addr).unwrap();
+            s.set_write_timeout(Some(Duration::from_secs(10))).unwrap();
+            // nobody reads, the write would block until cancelled
+            let buf = vec![0u8; 1024 * 1024];
+            loop {
+                s.write(&buf).unwrap();
+            }
+        });
+
+        let _s = listener.accept().unwrap();
+        ::std::thread::sleep(Duration::from_millis(50));
+        let start = ::std::time::Instant::now();
+        unsafe { j.coroutine().cancel() };
+        assert!(j.join().is_err());
+        // cancelled without waiting for the write timeout
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn peek() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let j = go!(move || {
+            let (mut s, _) = listener.accept().unwrap();
+            let mut buf = [0u8; 4];
+            // nothing is sent yet, the peek would yield
+            let n = s.peek(&mut buf).unwrap();
+            assert_eq!(&buf[..n], &b"sniff"[..n]);
+
+            // the read gets the same bytes
+            let mut data = vec![];
+            s.read_to_end(&mut data).unwrap();
+            assert_eq!(&data, b"sniff");
+        });
+
+        let mut s = TcpStream::connect(addr).unwrap();
+        ::std::thread::sleep(Duration::from_millis(10));
+        s.write_all(b"sniff").unwrap();
+        drop(s);
+        j.join().unwrap();
+    }
+
+    #[test]
+    fn shutdown() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let j = go!(move || {
+            let (mut s, _) = listener.accept().unwrap();
+            // the request is terminated by the FIN
+            let mut req = vec![];
+            s.read_to_end(&mut req).unwrap();
+            assert_eq!(&req, b"GET");
+            s.write_all(b"200").unwrap();
+
+            // a parked reader is waked up by the read shutdown
+            let s1 = s.try_clone().unwrap();
+            let reader = go!(move || {
+                let mut buf = [0u8; 4];
+                assert_eq!(s.read(&mut buf).unwrap(), 0);
+            });
+            ::coroutine::sleep(Duration::from_millis(10));
+            s1.shutdown(Shutdown::Read).unwrap();
+            reader.join().unwrap();
+        });
+
+        let mut s = TcpStream::connect(addr).unwrap();
+        s.write_all(b"GET").unwrap();
+        s.shutdown(Shutdown::Write).unwrap();
+        let err = s.write(b"more").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+
+        // still able to read the response
+        let mut rsp = [0u8; 3];
+        s.read_exact(&mut rsp).unwrap();
+        assert_eq!(&rsp, b"200");
+        j.join().unwrap();
+    }
+
+    #[test]
+    fn bind_reuseport() {
+        let l1 = TcpListener::bind_reuseport("127.0.0.1:0").unwrap();
+        let addr = l1.local_addr().unwrap();
+        let l2 = TcpListener::bind_reuseport(addr).unwrap();
+        assert_eq!(l2.local_addr().unwrap(), addr);
+
+        // a plain bind still conflicts with the reuseport listeners
+        assert!(TcpListener::bind(addr).is_err());
+
+        let j = go!(move || {
+            TcpStream::connect(addr).unwrap();
+        });
+        j.join().unwrap();
+    }
+
+    #[test]
+    fn listener_builder() {
+        let err = TcpListenerBuilder::new().listen().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        let l1 = TcpListenerBuilder::new()
+            .reuse_address(true)
+            .reuse_port(true)
+            .recv_buffer_size(64 * 1024)
+            .send_buffer_size(64 * 1024)
+            .backlog(256)
+            .bind("127.0.0.1:0")
+            .unwrap()
+            .listen()
+            .unwrap();
+        let addr = l1.local_addr().unwrap();
+        let l2 = TcpListenerBuilder::new()
+            .reuse_port(true)
+            .backlog(256)
+            .bind(addr)
+            .unwrap()
+            .listen()
+            .unwrap();
+
+        // the kernel spreads the connections over both listeners
+        const N: usize = 64;
+        let clients = (0..N)
+            .map(|_| TcpStream::connect(addr).unwrap())
+            .collect::<Vec<_>>();
+
+        let drain = |l: &TcpListener| {
+            l.set_nonblocking(true).unwrap();
+            let mut n = 0;
+            loop {
+                match l.accept() {
+                    Ok(_) => n += 1,
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return n,
+                    Err(e) => panic!("accept failed: {}", e),
+                }
+            }
+        };
+        let (n1, n2) = (drain(&l1), drain(&l2));
+        assert_eq!(n1 + n2, N);
+        assert!(n1 > 0 && n2 > 0);
+        drop(clients);
+
+        // only_v6 is applied to ipv6 addresses
+        if let Ok(b) = TcpListenerBuilder::new().only_v6(true).bind("[::1]:0") {
+            let l = b.listen().unwrap();
+            assert!(l.local_addr().unwrap().is_ipv6());
+        }
+    }
+
+    #[test]
+    fn linger_reset() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // the peer sees how the stream is closed
+        fn closed_by_reset(s: TcpStream, peer: &mut TcpStream) -> bool {
+            drop(s);
+            match peer.read(&mut [0u8; 4]) {
+                Ok(0) => false,
+                Err(ref e) if e.kind() == io::ErrorKind::ConnectionReset => true,
+                ret => panic!("unexpected read result {:?}", ret),
+            }
+        }
+
+        let j = go!(move || {
+            for &reset in &[true, false] {
+                let dur = if reset { Some(Duration::from_secs(0)) } else { None };
+
+                // the connected stream
+                let s = TcpStream::connect(addr).unwrap();
+                let (mut peer, _) = listener.accept().unwrap();
+                s.set_linger(dur).unwrap();
+                assert_eq!(s.linger().unwrap(), dur);
+                assert_eq!(closed_by_reset(s, &mut peer), reset);
+
+                // the accepted stream
+                let mut peer = TcpStream::connect(addr).unwrap();
+                let (s, _) = listener.accept().unwrap();
+                s.set_linger(Some(Duration::from_secs(0))).unwrap();
+                // `None` restores the graceful close
+                s.set_linger(dur).unwrap();
+                assert_eq!(s.linger().unwrap(), dur);
+                assert_eq!(closed_by_reset(s, &mut peer), reset);
+            }
+        });
+        j.join().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn into_std_deregisters() {
+        use std::os::unix::io::AsRawFd;
+        use std::time::Instant;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (s, mut peer) = go!(move || {
+            let s = TcpStream::connect(addr).unwrap();
+            let (peer, _) = listener.accept().unwrap();
+            (s, peer)
+        }).join()
+            .unwrap();
+
+        // a coroutine parked on the original fd
+        let clone = s.try_clone().unwrap();
+        let reader = go!(move || {
+            let mut buf = [0u8; 4];
+            (&s).read_exact(&mut buf).unwrap();
+            buf
+        });
+        ::std::thread::sleep(Duration::from_millis(20));
+
+        let fd = clone.as_raw_fd();
+        let std = clone.into_std().unwrap();
+        assert_eq!(std.as_raw_fd(), fd);
+
+        // the socket is in blocking mode again
+        std.set_read_timeout(Some(Duration::from_millis(30))).unwrap();
+        let now = Instant::now();
+        let e = (&std).read(&mut [0u8; 1]).unwrap_err();
+        assert!(
+            e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut,
+            "{:?}",
+            e
+        );
+        assert!(now.elapsed() >= Duration::from_millis(20));
+        std.set_read_timeout(None).unwrap();
+
+        // the fd is gone from the selector, adding it again doesn't fail
+        let again = TcpStream::from_std(std).unwrap();
+
+        // the parked coroutine is still waked by its own registration
+        peer.write_all(b"ping").unwrap();
+        assert_eq!(&reader.join().unwrap(), b"ping");
+        drop(again);
+    }
+
+    #[test]
+    fn std_round_trip() {
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let listener = TcpListener::from_std(listener).unwrap();
+
+        let server = go!(move || {
+            let (mut s, _) = listener.accept().unwrap();
+            ::coroutine::sleep(Duration::from_millis(10));
+            s.write_all(b"hello world").unwrap();
+            let mut buf = [0u8; 4];
+            s.read_exact(&mut buf).unwrap();
+            assert_eq!(&buf, b"ping");
+            s.write_all(b"bye").unwrap();
+
+            // the std listener is usable in blocking mode again
+            let listener = listener.into_std().unwrap();
+            assert_eq!(listener.local_addr().unwrap(), addr);
+            listener.accept().unwrap();
+        });
+
+        let client = go!(move || {
+            let s = net::TcpStream::connect(addr).unwrap();
+            let mut s = TcpStream::from_std(s).unwrap();
+            // nothing is written yet, this would yield
+            let mut buf = [0u8; 5];
+            s.read_exact(&mut buf).unwrap();
+            assert_eq!(&buf, b"hello");
+
+            // the rest of the data is still in flight
+            let mut s = s.into_std().unwrap();
+            let mut buf = [0u8; 6];
+            s.read_exact(&mut buf).unwrap();
+            assert_eq!(&buf, b" world");
+            s.write_all(b"ping").unwrap();
+
+            // register the same socket again
+            let mut s = TcpStream::from_std(s).unwrap();
+            let mut buf = [0u8; 3];
+            s.read_exact(&mut buf).unwrap();
+            assert_eq!(&buf, b"bye");
+            net::TcpStream::connect(addr).unwrap();
+        });
+
+        client.join().unwrap();
+        server.join().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn raw_fd_round_trip() {
+        // like a listener passed by the socket activation
+        let fd = net::TcpListener::bind("127.0.0.1:0").unwrap().into_raw_fd();
+        let listener = unsafe { TcpListener::from_raw_fd(fd) };
+        let addr = listener.local_addr().unwrap();
+
+        let server = go!(move || {
+            let (s, _) = listener.accept().unwrap();
+            // the fd is still open after leaving may
+            let mut s = unsafe { net::TcpStream::from_raw_fd(s.into_raw_fd()) };
+            s.set_nonblocking(false).unwrap();
+            s.write_all(b"hi").unwrap();
+            unsafe { net::TcpListener::from_raw_fd(listener.into_raw_fd()) }
+        });
+
+        let mut s = net::TcpStream::connect(addr).unwrap();
+        let mut buf = [0u8; 2];
+        s.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"hi");
+        let listener = server.join().unwrap();
+        assert_eq!(listener.local_addr().unwrap(), addr);
+    }
+
+    #[test]
+    fn connect_timeout() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+        use std::time::Instant;
+
+        let addr = "127.0.0.1:0".parse().unwrap();
+        match TcpStream::connect_timeout(&addr, Duration::from_secs(0)) {
+            Err(ref e) if e.kind() == io::ErrorKind::InvalidInput => {}
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(_) => panic!("unexpected success"),
+        }
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let (stop1, ticks1) = (stop.clone(), ticks.clone());
+        let ticker = go!(move || while !stop1.load(Ordering::Relaxed) {
+            ticks1.fetch_add(1, Ordering::Relaxed);
+            ::coroutine::sleep(Duration::from_millis(10));
+        });
+
+        // a blackholed address, nothing would answer the SYN
+        let addr = "10.255.255.1:80".parse().unwrap();
+        let dur = Duration::from_millis(100);
+        let j = go!(move || {
+            let start = Instant::now();
+            let ret = TcpStream::connect_timeout(&addr, dur);
+            (ret.map(|_| ()), start.elapsed())
+        });
+        let (ret, elapsed) = j.join().unwrap();
+        stop.store(true, Ordering::Relaxed);
+        ticker.join().unwrap();
+
+        match ret {
+            Err(ref e) if e.kind() == io::ErrorKind::TimedOut => {
+                assert!(elapsed >= dur);
+                assert!(elapsed < dur * 10);
+                // other coroutines keep running while connecting
+                assert!(ticks.load(Ordering::Relaxed) > 1);
+            }
+            // the network is not blackholing the address, nothing to check
+            _ => {}
+        }
+    }
+
+    fn temp_file(name: &str, data: &[u8]) -> File {
+        use std::fs::{self, OpenOptions};
+
+        let path = ::std::env::temp_dir().join(format!("may_{}_{}", name, ::std::process::id()));
+        fs::write(&path, data).unwrap();
+        let file = OpenOptions::new().read(true).open(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        file
+    }
+
+    #[test]
+    fn send_file() {
+        // larger than the socket buffer, the sender must yield
+        let data = (0..4 * 1024 * 1024).map(|i| i as u8).collect::<Vec<_>>();
+        let file = temp_file("send_file", &data);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let j = go!(move || {
+            let s = TcpStream::connect(addr).unwrap();
+            let len = data.len() as u64;
+            assert_eq!(s.send_file(&file, 100, len - 100).unwrap(), len - 100);
+            // short send near EOF
+            assert_eq!(s.send_file(&file, len - 10, 100).unwrap(), 10);
+            assert_eq!(s.send_file(&file, len + 10, 100).unwrap(), 0);
+            data
+        });
+
+        let (mut s, _) = listener.accept().unwrap();
+        let mut buf = Vec::new();
+        let reader = go!(move || {
+            s.read_to_end(&mut buf).unwrap();
+            buf
+        });
+        let data = j.join().unwrap();
+        let buf = reader.join().unwrap();
+        let len = data.len();
+        assert_eq!(buf.len(), len - 100 + 10);
+        assert!(&buf[..len - 100] == &data[100..]);
+        assert!(&buf[len - 100..] == &data[len - 10..]);
+    }
+
+    #[test]
+    fn send_file_broken_pipe() {
+        let data = vec![7u8; 4 * 1024 * 1024];
+        let file = temp_file("send_file_broken_pipe", &data);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let j = go!(move || {
+            let s = TcpStream::connect(addr).unwrap();
+            loop {
+                match s.send_file(&file, 0, data.len() as u64) {
+                    Ok(_) => {}
+                    Err(e) => return e.kind(),
+                }
+            }
+        });
+
+        let (s, _) = listener.accept().unwrap();
+        ::std::thread::sleep(Duration::from_millis(50));
+        drop(s);
+        let kind = j.join().unwrap();
+        assert!(kind == io::ErrorKind::BrokenPipe || kind == io::ErrorKind::ConnectionReset);
+    }
+
+    #[test]
+    fn send_file_timeout() {
+        let data = vec![7u8; 16 * 1024 * 1024];
+        let file = temp_file("send_file_timeout", &data);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let j = go!(move || {
+            let s = TcpStream::connect(addr).unwrap();
+            s.set_write_timeout(Some(Duration::from_millis(50))).unwrap();
+            // nobody reads, the part in the socket buffer is reported
+            let len = data.len() as u64;
+            let mut sent = s.send_file(&file, 0, len).unwrap();
+            // the resumed sends fill the growing buffer, then time out
+            loop {
+                assert!(sent > 0 && sent < len);
+                match s.send_file(&file, sent, len - sent) {
+                    Ok(n) => sent += n,
+                    Err(e) => return e.kind(),
+                }
+            }
+        });
+
+        let _s = listener.accept().unwrap();
+        assert_eq!(j.join().unwrap(), io::ErrorKind::TimedOut);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn deadline_on_clones() {
+        use std::time::Instant;
+        use io::DeadlineExceeded;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let s = TcpStream::connect(addr).unwrap();
+        let s2 = s.try_clone().unwrap();
+
+        let start = Instant::now();
+        // the short deadline expires while the other clone is still waiting
+        let short = go!(move || {
+            let mut buf = [0u8; 4];
+            let e = s.read_exact_deadline(&mut buf, start + Duration::from_millis(50))
+                .unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+            assert!(start.elapsed() < Duration::from_millis(150));
+            assert_eq!(s.read_timeout().unwrap(), None);
+            DeadlineExceeded::from_io_error(&e).unwrap().transferred()
+        });
+        let long = go!(move || {
+            let mut buf = [0u8; 4];
+            s2.read_exact_deadline(&mut buf, start + Duration::from_secs(5))
+                .unwrap();
+            buf
+        });
+
+        let (mut peer, _) = listener.accept().unwrap();
+        assert_eq!(short.join().unwrap(), 0);
+        peer.write_all(&[1, 2, 3, 4]).unwrap();
+        assert_eq!(long.join().unwrap(), [1, 2, 3, 4]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn deadline_partial() {
+        use std::time::Instant;
+        use io::DeadlineExceeded;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let j = go!(move || {
+            let s = TcpStream::connect(addr).unwrap();
+            let mut buf = [0u8; 8];
+            let deadline = Instant::now() + Duration::from_millis(100);
+            let e = s.read_exact_deadline(&mut buf, deadline).unwrap_err();
+            let n = DeadlineExceeded::from_io_error(&e).unwrap().transferred();
+            assert_eq!(&buf[..n], &[1, 2, 3]);
+
+            // nobody reads, the socket buffer is filled up
+            let data = vec![7u8; 16 * 1024 * 1024];
+            let deadline = Instant::now() + Duration::from_millis(50);
+            let e = s.write_all_deadline(&data, deadline).unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+            let n = DeadlineExceeded::from_io_error(&e).unwrap().transferred();
+            assert!(n > 0 && n < data.len());
+
+            // an expired deadline doesn't wait at all
+            let e = s.read_deadline(&mut buf, deadline).unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+            n
+        });
+
+        let (mut peer, _) = listener.accept().unwrap();
+        peer.write_all(&[1, 2, 3]).unwrap();
+        assert!(j.join().unwrap() > 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn deadline_in_thread() {
+        use std::time::Instant;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let s = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (mut peer, _) = listener.accept().unwrap();
+
+        let mut buf = [0u8; 4];
+        let start = Instant::now();
+        let e = s.read_deadline(&mut buf, start + Duration::from_millis(50))
+            .unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+        assert!(start.elapsed() >= Duration::from_millis(50));
+
+        peer.write_all(b"ping").unwrap();
+        s.read_exact_deadline(&mut buf, Instant::now() + Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(&buf, b"ping");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn wait_read_cancel() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let j = go!(move || {
+            let s = TcpStream::connect(addr).unwrap();
+            // writable at once, the peer sends nothing
+            s.wait_write().unwrap();
+            s.wait_read().unwrap();
+        });
+
+        let _s = listener.accept().unwrap();
+        ::std::thread::sleep(Duration::from_millis(20));
+        unsafe { j.coroutine().cancel() };
+        assert!(j.join().is_err());
+    }
+
+    #[test]
+    fn connect_host() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        // localhost may resolve to ::1 first, which is refused
+        let j = go!(move || TcpStream::connect_host("localhost", port).unwrap().peer_addr());
+        let (_s, _) = listener.accept().unwrap();
+        assert_eq!(j.join().unwrap().unwrap().port(), port);
+
+        // in thread context
+        let s = TcpStream::connect_host("127.0.0.1", port).unwrap();
+        assert_eq!(s.peer_addr().unwrap().port(), port);
+    }
+
+    #[test]
+    fn connect_slow_lookup() {
+        use std::vec;
+        use std::net::ToSocketAddrs;
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+        use coroutine::Builder;
+
+        // a lookup that blocks the thread where it's resolved
+        struct SlowAddr(SocketAddr);
+        impl ToSocketAddrs for SlowAddr {
+            type Iter = vec::IntoIter<SocketAddr>;
+            fn to_socket_addrs(&self) -> io::Result<Self::Iter> {
+                ::std::thread::sleep(Duration::from_millis(200));
+                Ok(vec![self.0].into_iter())
+            }
+        }
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let done = Arc::new(AtomicBool::new(false));
+        let ticks = Arc::new(AtomicUsize::new(0));
+        // share one worker with the lookup
+        let ticker = {
+            let done = done.clone();
+            let ticks = ticks.clone();
+            go!(Builder::new().pin_to(0), move || while !done.load(Ordering::Relaxed) {
+                ::coroutine::sleep(Duration::from_millis(5));
+                ticks.fetch_add(1, Ordering::Relaxed);
+            }).unwrap()
+        };
+
+        let started = Arc::new(AtomicBool::new(false));
+        let j = {
+            let started = started.clone();
+            go!(Builder::new().pin_to(0), move || {
+                started.store(true, Ordering::Relaxed);
+                TcpStream::connect(SlowAddr(addr)).map(|s| s.peer_addr().unwrap())
+            }).unwrap()
+        };
+        while !started.load(Ordering::Relaxed) {
+            ::std::thread::sleep(Duration::from_millis(1));
+        }
+
+        // the timed sleeps go on while the lookup is in flight
+        let n = ticks.load(Ordering::Relaxed);
+        ::std::thread::sleep(Duration::from_millis(100));
+        assert!(!j.is_done());
+        assert!(ticks.load(Ordering::Relaxed) > n);
+
+        let (_s, _) = listener.accept().unwrap();
+        assert_eq!(j.join().unwrap().unwrap(), addr);
+        done.store(true, Ordering::Relaxed);
+        ticker.join().unwrap();
+    }
+
+    #[test]
+    fn connect_host_nxdomain() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+        use coroutine::Builder;
+
+        let done = Arc::new(AtomicBool::new(false));
+        let ticks = Arc::new(AtomicUsize::new(0));
+        // share one worker with the lookup
+        let ticker = {
+            let done = done.clone();
+            let ticks = ticks.clone();
+            go!(Builder::new().pin_to(0), move || while !done.load(Ordering::Relaxed) {
+                ::coroutine::sleep(Duration::from_millis(5));
+                ticks.fetch_add(1, Ordering::Relaxed);
+            }).unwrap()
+        };
+
+        let started = Arc::new(AtomicBool::new(false));
+        let j = {
+            let started = started.clone();
+            go!(Builder::new().pin_to(0), move || {
+                started.store(true, Ordering::Relaxed);
+                // keep the lookup in flight long enough to see the ticks
+                TcpStream::connect_host_with("no-such-host.invalid", 80, |host, port| {
+                    ::std::thread::sleep(Duration::from_millis(200));
+                    ::net::resolve::lookup(host, port)
+                })
+            }).unwrap()
+        };
+        while !started.load(Ordering::Relaxed) {
+            ::std::thread::sleep(Duration::from_millis(1));
+        }
+
+        // the timed sleeps go on while the lookup is in flight
+        let n = ticks.load(Ordering::Relaxed);
+        ::std::thread::sleep(Duration::from_millis(100));
+        assert!(!j.is_done());
+        assert!(ticks.load(Ordering::Relaxed) > n);
+
+        assert!(j.join().unwrap().is_err());
+        done.store(true, Ordering::Relaxed);
+        ticker.join().unwrap();
+    }
+
+    #[test]
+    fn connect_from() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let remote = listener.local_addr().unwrap();
+        // a fixed port to check the bound address
+        let fixed = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = fixed.local_addr().unwrap().port();
+        drop(fixed);
+
+        let j = go!(move || {
+            // the ephemeral port on a fixed ip
+            let local: SocketAddr = "127.0.0.1:0".parse().unwrap();
+            let s = TcpStream::connect_from(local, remote).unwrap();
+            let addr = s.local_addr().unwrap();
+            assert_eq!(addr.ip(), local.ip());
+            assert!(addr.port() != 0);
+
+            let local = SocketAddr::new(local.ip(), port);
+            let s = TcpStream::connect_from(local, remote).unwrap();
+            assert_eq!(s.local_addr().unwrap(), local);
+            s.local_addr().unwrap()
+        });
+
+        let (_s1, _) = listener.accept().unwrap();
+        let (_s2, peer) = listener.accept().unwrap();
+        assert_eq!(j.join().unwrap(), peer);
+
+        // thread context
+        let local: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let s = TcpStream::connect_from(local, remote).unwrap();
+        assert_eq!(s.local_addr().unwrap().ip(), local.ip());
+
+        let v6: SocketAddr = "[::1]:0".parse().unwrap();
+        let err = TcpStream::connect_from(v6, remote).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn cancel_and_timeout_errors() {
+        use coroutine::is_cancel_err;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let read = move |timeout: Option<Duration>| {
+            go!(move || {
+                let mut s = TcpStream::connect(addr).unwrap();
+                s.set_read_timeout(timeout).unwrap();
+                let mut buf = [0u8; 4];
+                // the peer sends nothing
+                s.read(&mut buf).unwrap_err()
+            })
+        };
+
+        let canceled = read(None);
+        let _s1 = listener.accept().unwrap();
+        let timed_out = read(Some(Duration::from_millis(20)));
+        let _s2 = listener.accept().unwrap();
+
+        ::std::thread::sleep(Duration::from_millis(50));
+        unsafe { canceled.coroutine().cancel() };
+
+        let e = canceled.join().unwrap();
+        assert!(is_cancel_err(&e));
+        assert_eq!(e.kind(), io::ErrorKind::Other);
+        let e = timed_out.join().unwrap();
+        assert!(!is_cancel_err(&e));
+        assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn cancel_error_reported_once() {
+        use coroutine::is_cancel_err;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, rx) = ::std::sync::mpsc::channel();
+        let j = go!(move || {
+            let mut s = TcpStream::connect(addr).unwrap();
+            let mut buf = [0u8; 4];
+            let e = s.read(&mut buf).unwrap_err();
+            tx.send(is_cancel_err(&e)).unwrap();
+            // ignore the error and block again
+            s.read(&mut buf).ok();
+            tx.send(false).unwrap();
+        });
+
+        let _s = listener.accept().unwrap();
+        ::std::thread::sleep(Duration::from_millis(20));
+        unsafe { j.coroutine().cancel() };
+        assert!(j.join().is_err());
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![true]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn wait_read_in_thread() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let s = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (mut peer, _) = listener.accept().unwrap();
+
+        s.set_read_timeout(Some(Duration::from_millis(20))).unwrap();
+        assert_eq!(s.wait_read().unwrap_err().kind(), io::ErrorKind::TimedOut);
+
+        peer.write_all(b"ping").unwrap();
+        s.wait_read().unwrap();
+        let mut buf = [0u8; 4];
+        (&s).read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"ping");
+    }
+
+    #[test]
+    fn shutdown_wakes_reader() {
+        use std::sync::Arc;
+        use std::time::Instant;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        for &clone in &[false, true] {
+            let s = Arc::new(TcpStream::connect(addr).unwrap());
+            let _peer = listener.accept().unwrap();
+            s.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+            let r = if clone {
+                Arc::new(s.try_clone().unwrap())
+            } else {
+                s.clone()
+            };
+
+            // the connection is idle, the reader is parked
+            let reader = go!(move || {
+                let mut buf = [0u8; 4];
+                (&*r).read(&mut buf).unwrap()
+            });
+            ::std::thread::sleep(Duration::from_millis(20));
+
+            let start = Instant::now();
+            let t = s.clone();
+            go!(move || t.shutdown(Shutdown::Both).unwrap())
+                .join()
+                .unwrap();
+            assert_eq!(reader.join().unwrap(), 0);
+            assert!(start.elapsed() < Duration::from_millis(500));
+        }
+    }
+
+    #[test]
+    fn shutdown_wakes_writer() {
+        use std::sync::Arc;
+        use std::time::Instant;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let s = Arc::new(TcpStream::connect(addr).unwrap());
+        let _peer = listener.accept().unwrap();
+        s.set_write_timeout(Some(Duration::from_secs(10))).unwrap();
+        let w = Arc::new(s.try_clone().unwrap());
+
+        // nobody reads, the writer is parked on the full socket buffer
+        let writer = go!(move || {
+            let buf = vec![0u8; 1024 * 1024];
+            loop {
+                if let Err(e) = (&*w).write(&buf) {
+                    return e.kind();
+                }
+            }
+        });
+        ::std::thread::sleep(Duration::from_millis(50));
+
+        let start = Instant::now();
+        s.shutdown(Shutdown::Write).unwrap();
+        assert_eq!(writer.join().unwrap(), io::ErrorKind::BrokenPipe);
+        assert!(start.elapsed() < Duration::from_millis(500));
+    }
+}
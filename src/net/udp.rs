@@ -1,15 +1,19 @@
 use std::io;
+use std::sync::Mutex;
 use std::time::Duration;
 use std::net::{self, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
 use io as io_impl;
 use io::net as net_impl;
 use yield_now::yield_with;
+use super::sockopt;
 
 #[derive(Debug)]
 pub struct UdpSocket {
     io: io_impl::IoData,
     sys: net::UdpSocket,
     ctx: io_impl::IoContext,
+    // shared by the coroutines using the socket, set by `connect`
+    peer: Mutex<Option<SocketAddr>>,
     read_timeout: Option<Duration>,
     write_timeout: Option<Duration>,
 }
@@ -21,10 +25,13 @@ impl UdpSocket {
         // to avoid unnecessary context switch
         s.set_nonblocking(true)?;
 
+        // the socket may be already connected when created from raw fd
+        let peer = s.peer_addr().ok();
         io_impl::add_socket(&s).map(|io| UdpSocket {
             io: io,
             sys: s,
             ctx: io_impl::IoContext::new(),
+            peer: Mutex::new(peer),
             read_timeout: None,
             write_timeout: None,
         })
@@ -34,23 +41,60 @@ impl UdpSocket {
         &self.sys
     }
 
+    /// register a std `UdpSocket` so that it can be used in coroutines
+    pub fn from_std(s: net::UdpSocket) -> io::Result<UdpSocket> {
+        UdpSocket::new(s)
+    }
+
+    /// remove the socket from the selector and put it back to blocking mode
+    pub fn into_std(self) -> io::Result<net::UdpSocket> {
+        let UdpSocket { io, sys, .. } = self;
+        drop(io);
+        sys.set_nonblocking(false)?;
+        Ok(sys)
+    }
+
     pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<UdpSocket> {
         net::UdpSocket::bind(addr).and_then(|s| UdpSocket::new(s))
     }
 
+    /// associate a default peer, after that `send` and `recv` can be used
+    /// and only datagrams from the peer are received
     pub fn connect<A: ToSocketAddrs>(&self, addr: A) -> io::Result<()> {
         // for udp connect it's a nonblocking operation
         // so we just use the system call
-        self.sys.connect(addr)
+        let mut last_err = None;
+        for addr in addr.to_socket_addrs()? {
+            match self.sys.connect(addr) {
+                Ok(()) => {
+                    *self.peer.lock().unwrap() = Some(addr);
+                    return Ok(());
+                }
+                Err(e) => last_err = Some(e),
+            }
+        }
+
+        Err(last_err.unwrap_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "could not resolve to any addresses",
+            )
+        }))
     }
 
     pub fn local_addr(&self) -> io::Result<SocketAddr> {
         self.sys.local_addr()
     }
 
+    /// return the peer address set by `connect`
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.peer.lock().unwrap().ok_or_else(not_connected)
+    }
+
     #[cfg(not(windows))]
     pub fn try_clone(&self) -> io::Result<UdpSocket> {
         let s = self.sys.try_clone().and_then(|s| UdpSocket::new(s))?;
+        *s.peer.lock().unwrap() = *self.peer.lock().unwrap();
         s.set_read_timeout(self.read_timeout).unwrap();
         s.set_write_timeout(self.write_timeout).unwrap();
         Ok(s)
@@ -66,6 +110,7 @@ impl UdpSocket {
             io: io_impl::IoData::new(0),
             sys: s,
             ctx: io_impl::IoContext::new(),
+            peer: Mutex::new(*self.peer.lock().unwrap()),
             read_timeout: self.read_timeout,
             write_timeout: self.write_timeout,
         })
@@ -111,7 +156,55 @@ impl UdpSocket {
         reader.done()
     }
 
+    /// receive a datagram without removing it from the queue
+    #[cfg(unix)]
+    pub fn peek_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        if self.ctx.check_nonblocking(|b| self.sys.set_nonblocking(b))?
+            || !self.ctx.check_context(|b| self.sys.set_nonblocking(b))?
+        {
+            // this can't be nonblocking!!
+            return self.sys.peek_from(buf);
+        }
+
+        self.io.reset();
+        // this is an earlier return try for nonblocking read
+        match self.sys.peek_from(buf) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            ret => return ret,
+        }
+
+        let reader = net_impl::UdpRecvFrom::new_peek(self, buf);
+        yield_with(&reader);
+        reader.done()
+    }
+
+    /// receive a datagram from the connected peer without removing it from the queue
+    #[cfg(unix)]
+    pub fn peek(&self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.ctx.check_nonblocking(|b| self.sys.set_nonblocking(b))?
+            || !self.ctx.check_context(|b| self.sys.set_nonblocking(b))?
+        {
+            // this can't be nonblocking!!
+            return self.sys.peek(buf);
+        }
+
+        self.io.reset();
+        // this is an earlier return try for nonblocking read
+        match self.sys.peek(buf) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            ret => return ret,
+        }
+
+        let reader = net_impl::SocketPeek::new(self, buf, self.read_timeout);
+        yield_with(&reader);
+        reader.done()
+    }
+
     pub fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        if self.peer.lock().unwrap().is_none() {
+            return Err(not_connected());
+        }
+
         if self.ctx.check_nonblocking(|b| self.sys.set_nonblocking(b))?
             || !self.ctx.check_context(|b| self.sys.set_nonblocking(b))?
         {
@@ -218,6 +311,26 @@ impl UdpSocket {
         self.sys.set_ttl(ttl)
     }
 
+    /// set `SO_RCVBUF`, the datagrams that arrive when it's full are dropped
+    pub fn set_recv_buffer_size(&self, size: usize) -> io::Result<()> {
+        sockopt::with_socket(&self.sys, |s| s.set_recv_buffer_size(size))
+    }
+
+    /// return `SO_RCVBUF`, linux reports the doubled size it keeps
+    pub fn recv_buffer_size(&self) -> io::Result<usize> {
+        sockopt::with_socket(&self.sys, |s| s.recv_buffer_size())
+    }
+
+    /// set `SO_SNDBUF`, a send waits when the queued datagrams fill it
+    pub fn set_send_buffer_size(&self, size: usize) -> io::Result<()> {
+        sockopt::with_socket(&self.sys, |s| s.set_send_buffer_size(size))
+    }
+
+    /// return `SO_SNDBUF`
+    pub fn send_buffer_size(&self) -> io::Result<usize> {
+        sockopt::with_socket(&self.sys, |s| s.send_buffer_size())
+    }
+
     pub fn join_multicast_v4(&self, multiaddr: &Ipv4Addr, interface: &Ipv4Addr) -> io::Result<()> {
         self.sys.join_multicast_v4(multiaddr, interface)
     }
@@ -239,6 +352,10 @@ impl UdpSocket {
     }
 }
 
+fn not_connected() -> io::Error {
+    io::Error::new(io::ErrorKind::NotConnected, "udp socket is not connected")
+}
+
 #[cfg(unix)]
 impl io_impl::AsIoData for UdpSocket {
     fn as_io_data(&self) -> &io_impl::IoData {
@@ -256,8 +373,10 @@ use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
 #[cfg(unix)]
 impl IntoRawFd for UdpSocket {
     fn into_raw_fd(self) -> RawFd {
-        self.sys.as_raw_fd()
-        // drop self will dereg from the selector
+        // dereg from the selector, the fd is owned by the caller
+        let UdpSocket { io, sys, .. } = self;
+        drop(io);
+        sys.into_raw_fd()
     }
 }
 
@@ -306,3 +425,266 @@ impl FromRawSocket for UdpSocket {
             .unwrap_or_else(|e| panic!("from_raw_socket for UdpSocket, err = {:?}", e))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn connected_send_recv() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server_addr = server.local_addr().unwrap();
+        let other = UdpSocket::bind("127.0.0.1:0").unwrap();
+
+        let client = go!(move || {
+            let s = UdpSocket::bind("127.0.0.1:0").unwrap();
+            let err = s.send(b"hello").unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::NotConnected);
+            assert_eq!(s.peer_addr().unwrap_err().kind(), io::ErrorKind::NotConnected);
+
+            s.connect(server_addr).unwrap();
+            assert_eq!(s.peer_addr().unwrap(), server_addr);
+            assert_eq!(s.send(b"hello").unwrap(), 5);
+
+            // nothing is ready yet, this recv would yield the coroutine
+            let mut buf = [0u8; 16];
+            let n = s.recv(&mut buf).unwrap();
+            assert_eq!(&buf[..n], b"world");
+        });
+
+        let mut buf = [0u8; 16];
+        let (n, peer) = server.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"hello");
+
+        // datagrams from other peers are filtered by the kernel
+        other.send_to(b"noise", peer).unwrap();
+        server.send_to(b"world", peer).unwrap();
+        client.join().unwrap();
+    }
+
+    #[test]
+    fn std_round_trip() {
+        let server = net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server = UdpSocket::from_std(server).unwrap();
+        let server_addr = server.local_addr().unwrap();
+        let client = net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let client_addr = client.local_addr().unwrap();
+        client.connect(server_addr).unwrap();
+
+        let j = go!(move || {
+            let client = UdpSocket::from_std(client).unwrap();
+            assert_eq!(client.peer_addr().unwrap(), server_addr);
+            // nothing is sent yet, this would yield
+            let mut buf = [0u8; 16];
+            let n = client.recv(&mut buf).unwrap();
+            assert_eq!(&buf[..n], b"one");
+
+            // the second datagram is still queued
+            let client = client.into_std().unwrap();
+            let n = client.recv(&mut buf).unwrap();
+            assert_eq!(&buf[..n], b"two");
+            client.send(b"ack").unwrap();
+        });
+
+        ::std::thread::sleep(Duration::from_millis(10));
+        server.send_to(b"one", client_addr).unwrap();
+        server.send_to(b"two", client_addr).unwrap();
+        let server = server.into_std().unwrap();
+        let mut buf = [0u8; 16];
+        let (n, peer) = server.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"ack");
+        assert_eq!(peer, client_addr);
+        j.join().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn peek_from() {
+        use std::time::Instant;
+
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server_addr = server.local_addr().unwrap();
+
+        let j = go!(move || {
+            let mut buf = [0u8; 16];
+            for i in 0..3 {
+                // nothing is sent yet, the peek would yield
+                let (n, peer) = server.peek_from(&mut buf).unwrap();
+                let msg = format!("msg{}", i);
+                assert_eq!(&buf[..n], msg.as_bytes());
+
+                // the read gets the same datagram without hanging
+                let mut data = [0u8; 16];
+                let (m, p) = server.recv_from(&mut data).unwrap();
+                assert_eq!(&data[..m], msg.as_bytes());
+                assert_eq!(p, peer);
+            }
+
+            // respect the read timeout
+            let dur = Duration::from_millis(20);
+            server.set_read_timeout(Some(dur)).unwrap();
+            let start = Instant::now();
+            let err = server.peek_from(&mut buf).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+            assert!(start.elapsed() >= dur);
+        });
+
+        let writer = go!(move || {
+            let s = UdpSocket::bind("127.0.0.1:0").unwrap();
+            s.connect(server_addr).unwrap();
+            for i in 0..3 {
+                ::coroutine::sleep(Duration::from_millis(10));
+                s.send(format!("msg{}", i).as_bytes()).unwrap();
+            }
+        });
+
+        writer.join().unwrap();
+        j.join().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn raw_fd_round_trip() {
+        let fd = net::UdpSocket::bind("127.0.0.1:0").unwrap().into_raw_fd();
+        let s = unsafe { UdpSocket::from_raw_fd(fd) };
+        let addr = s.local_addr().unwrap();
+
+        let h = go!(move || {
+            let mut buf = [0u8; 4];
+            let (n, _) = s.recv_from(&mut buf).unwrap();
+            assert_eq!(&buf[..n], b"ping");
+            // the fd is still open after leaving may
+            unsafe { net::UdpSocket::from_raw_fd(s.into_raw_fd()) }
+        });
+
+        let client = net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        client.send_to(b"ping", addr).unwrap();
+        let s = h.join().unwrap();
+        assert_eq!(s.local_addr().unwrap(), addr);
+    }
+
+    #[test]
+    fn independent_timeouts() {
+        use std::time::Instant;
+
+        let read_dur = Duration::from_millis(50);
+        let write_dur = Duration::from_secs(2);
+        let j = go!(move || {
+            let s = UdpSocket::bind("127.0.0.1:0").unwrap();
+            s.set_read_timeout(Some(read_dur)).unwrap();
+            s.set_write_timeout(Some(write_dur)).unwrap();
+            assert_eq!(s.read_timeout().unwrap(), Some(read_dur));
+            assert_eq!(s.write_timeout().unwrap(), Some(write_dur));
+
+            // zero duration is rejected like std
+            let err = s.set_read_timeout(Some(Duration::from_secs(0))).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+            let err = s.set_write_timeout(Some(Duration::from_secs(0))).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+            assert_eq!(s.read_timeout().unwrap(), Some(read_dur));
+
+            // nobody sends, the recv waits for the read timeout only
+            let mut buf = [0u8; 16];
+            let start = Instant::now();
+            let err = s.recv_from(&mut buf).unwrap_err();
+            let elapsed = start.elapsed();
+            assert!(
+                err.kind() == io::ErrorKind::WouldBlock || err.kind() == io::ErrorKind::TimedOut
+            );
+            assert!(elapsed >= read_dur);
+            assert!(elapsed < write_dur);
+        });
+        j.join().unwrap();
+    }
+
+    #[test]
+    fn multicast_v4() {
+        let mdns = Ipv4Addr::new(224, 0, 0, 251);
+        let any = Ipv4Addr::new(0, 0, 0, 0);
+
+        let s = UdpSocket::bind("0.0.0.0:0").unwrap();
+        let port = s.local_addr().unwrap().port();
+        s.join_multicast_v4(&mdns, &any).unwrap();
+        s.set_multicast_loop_v4(true).unwrap();
+        assert!(s.multicast_loop_v4().unwrap());
+        s.set_multicast_ttl_v4(4).unwrap();
+        assert_eq!(s.multicast_ttl_v4().unwrap(), 4);
+
+        s.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+        let j = go!(move || {
+            let mut buf = [0u8; 16];
+            // nothing is sent yet, this recv_from would yield the coroutine
+            match s.recv_from(&mut buf) {
+                Ok((n, _)) => assert_eq!(&buf[..n], b"mdns"),
+                // the host may have no multicast loopback
+                Err(ref e)
+                    if e.kind() == io::ErrorKind::TimedOut
+                        || e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(e) => panic!("recv_from failed: {}", e),
+            }
+            s.leave_multicast_v4(&mdns, &any).unwrap();
+        });
+
+        let sender = UdpSocket::bind("0.0.0.0:0").unwrap();
+        sender.set_multicast_loop_v4(true).unwrap();
+        ::coroutine::sleep(Duration::from_millis(10));
+        sender.send_to(b"mdns", (mdns, port)).unwrap();
+        j.join().unwrap();
+    }
+
+    #[test]
+    fn multicast_v6() {
+        let mdns = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0xfb);
+        // the host may have no ipv6 support
+        let s = match UdpSocket::bind("[::]:0") {
+            Ok(s) => s,
+            Err(_) => return,
+        };
+        s.join_multicast_v6(&mdns, 0).unwrap();
+        s.set_multicast_loop_v6(false).unwrap();
+        assert!(!s.multicast_loop_v6().unwrap());
+        s.set_multicast_loop_v6(true).unwrap();
+        assert!(s.multicast_loop_v6().unwrap());
+        s.leave_multicast_v6(&mdns, 0).unwrap();
+        // it's not a member any more
+        assert!(s.leave_multicast_v6(&mdns, 0).is_err());
+    }
+
+    // linux reports the ICMP port unreachable of a connected socket as the
+    // pending socket error, the other platforms don't always do it
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn take_error_port_unreachable() {
+        // get a port that nobody listens on
+        let addr = UdpSocket::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+
+        let j = go!(move || {
+            let s = UdpSocket::bind("127.0.0.1:0").unwrap();
+            s.connect(addr).unwrap();
+            assert!(s.take_error().unwrap().is_none());
+            s.send(b"ping").unwrap();
+            // wait for the ICMP error without touching the socket
+            ::coroutine::sleep(Duration::from_millis(50));
+            let err = s.take_error().unwrap().expect("no pending error");
+            assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+            // the error is cleared after it's taken
+            assert!(s.take_error().unwrap().is_none());
+        });
+        j.join().unwrap();
+    }
+
+    #[test]
+    fn recv_from_cancel() {
+        use coroutine::is_cancel_err;
+
+        let h = go!(|| {
+            let s = UdpSocket::bind("127.0.0.1:0").unwrap();
+            let mut buf = [0u8; 16];
+            s.recv_from(&mut buf).unwrap_err()
+        });
+        ::std::thread::sleep(Duration::from_millis(20));
+        unsafe { h.coroutine().cancel() };
+        let e = h.join().unwrap();
+        assert!(is_cancel_err(&e));
+    }
+}
@@ -1,3 +1,8 @@
 #![cfg(unix)]
 
 pub mod net;
+mod pipe;
+mod signal;
+
+pub use self::pipe::{pipe, PipeReader, PipeWriter};
+pub use self::signal::{signal, Signal, SignalKind};
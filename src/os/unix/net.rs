@@ -6,13 +6,93 @@ use std::net::Shutdown;
 use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
 use std::os::unix::net::{self, SocketAddr};
 use std::path::Path;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use io::CoIo;
+use libc;
+use io::{deadline, AsIoData, CoIo, IoData, ReadyEvent};
+use config::config;
 use yield_now::yield_with;
+use socket2::{Domain, SockAddr, Socket, Type};
+use net::sockopt::with_socket;
 use io::sys::net as net_impl;
 use coroutine_impl::is_coroutine;
 
+// build the address in the abstract namespace, the leading NUL byte is added here
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn abstract_addr(name: &[u8]) -> io::Result<SockAddr> {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut path = Vec::with_capacity(name.len() + 1);
+    path.push(0);
+    path.extend_from_slice(name);
+    SockAddr::unix(OsStr::from_bytes(&path))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn abstract_unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Other,
+        "abstract unix socket address is only supported on Linux",
+    )
+}
+
+/// Credentials of the process on the other end of a Unix stream socket.
+///
+/// Returned by [`UnixStream::peer_cred`].
+///
+/// [`UnixStream::peer_cred`]: struct.UnixStream.html#method.peer_cred
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UCred {
+    /// The effective user id of the peer.
+    pub uid: libc::uid_t,
+    /// The effective group id of the peer.
+    pub gid: libc::gid_t,
+    /// The process id of the peer, `None` if the platform doesn't report it.
+    pub pid: Option<libc::pid_t>,
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn peer_cred(fd: RawFd) -> io::Result<UCred> {
+    use std::mem;
+
+    let mut cred: libc::ucred = unsafe { mem::zeroed() };
+    let mut len = mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut cred as *mut _ as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(UCred {
+        uid: cred.uid,
+        gid: cred.gid,
+        pid: Some(cred.pid),
+    })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn peer_cred(fd: RawFd) -> io::Result<UCred> {
+    let mut uid: libc::uid_t = 0;
+    let mut gid: libc::gid_t = 0;
+    if unsafe { libc::getpeereid(fd, &mut uid, &mut gid) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(UCred {
+        uid: uid,
+        gid: gid,
+        pid: None,
+    })
+}
+
 /// A Unix stream socket.
 ///
 /// # Examples
@@ -27,7 +107,7 @@ use coroutine_impl::is_coroutine;
 /// stream.read_to_string(&mut response).unwrap();
 /// println!("{}", response);
 /// ```
-pub struct UnixStream(CoIo<net::UnixStream>);
+pub struct UnixStream(pub(crate) CoIo<net::UnixStream>);
 
 impl fmt::Debug for UnixStream {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
@@ -70,7 +150,88 @@ impl UnixStream {
             return Ok(UnixStream(CoIo::new(stream)?));
         }
 
-        let mut c = net_impl::UnixStreamConnect::new(path)?;
+        let timeout = config().get_connect_timeout();
+        let mut c = net_impl::UnixStreamConnect::new(path, Some(timeout))?;
+
+        if c.is_connected()? {
+            return c.done();
+        }
+
+        yield_with(&c);
+        c.done()
+    }
+
+    /// Connects to the socket named by `path` with a timeout.
+    ///
+    /// The connect attempt would fail with `TimedOut` error if it's not
+    /// completed within the `timeout` duration. It is an error to pass the
+    /// zero `Duration` to this method.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use may::os::unix::net::UnixStream;
+    ///
+    /// let dur = Duration::from_millis(200);
+    /// let socket = UnixStream::connect_timeout("/tmp/sock", dur).unwrap();
+    /// ```
+    pub fn connect_timeout<P: AsRef<Path>>(path: P, timeout: Duration) -> io::Result<UnixStream> {
+        if timeout == Duration::from_secs(0) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "cannot set a 0 duration timeout",
+            ));
+        }
+
+        if !is_coroutine() {
+            let addr = SockAddr::unix(path)?;
+            let socket = Socket::new(Domain::unix(), Type::stream(), None)?;
+            socket.connect_timeout(&addr, timeout)?;
+            return Ok(UnixStream(CoIo::new(socket.into_unix_stream())?));
+        }
+
+        let mut c = net_impl::UnixStreamConnect::new(path, Some(timeout))?;
+
+        if c.is_connected()? {
+            return c.done();
+        }
+
+        yield_with(&c);
+        c.done()
+    }
+
+    /// Connects to the socket bound to the abstract `name`.
+    ///
+    /// The name is in the Linux abstract namespace, it doesn't include the
+    /// leading NUL byte and has no connection with the filesystem. On other
+    /// platforms an error is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use may::os::unix::net::UnixStream;
+    ///
+    /// let socket = match UnixStream::connect_abstract(b"my_service") {
+    ///     Ok(sock) => sock,
+    ///     Err(e) => {
+    ///         println!("Couldn't connect: {:?}", e);
+    ///         return
+    ///     }
+    /// };
+    /// ```
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn connect_abstract(name: &[u8]) -> io::Result<UnixStream> {
+        let addr = abstract_addr(name)?;
+        let timeout = config().get_connect_timeout();
+
+        if !is_coroutine() {
+            let socket = Socket::new(Domain::unix(), Type::stream(), None)?;
+            socket.connect(&addr)?;
+            return Ok(UnixStream(CoIo::new(socket.into_unix_stream())?));
+        }
+
+        let mut c = net_impl::UnixStreamConnect::from_addr(addr, Some(timeout))?;
 
         if c.is_connected()? {
             return c.done();
@@ -80,6 +241,14 @@ impl UnixStream {
         c.done()
     }
 
+    /// Connects to the socket bound to the abstract `name`.
+    ///
+    /// The abstract namespace is a Linux extension, this always returns an error.
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub fn connect_abstract(_name: &[u8]) -> io::Result<UnixStream> {
+        Err(abstract_unsupported())
+    }
+
     /// Creates an unnamed pair of connected sockets.
     ///
     /// Returns two `UnixStream`s which are connected to each other.
@@ -104,6 +273,38 @@ impl UnixStream {
         Ok((i1, i2))
     }
 
+    /// Registers a std `UnixStream` so that it can be used in coroutines.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::os::unix::net;
+    /// use may::os::unix::net::UnixStream;
+    ///
+    /// let (s, _) = net::UnixStream::pair().unwrap();
+    /// let stream = UnixStream::from_std(s).unwrap();
+    /// ```
+    pub fn from_std(s: net::UnixStream) -> io::Result<UnixStream> {
+        Ok(UnixStream(CoIo::new(s)?))
+    }
+
+    /// Removes the socket from the selector and puts it back to blocking
+    /// mode. The fd is kept open.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use may::os::unix::net::UnixStream;
+    ///
+    /// let (s, _) = UnixStream::pair().unwrap();
+    /// let stream = s.into_std().unwrap();
+    /// ```
+    pub fn into_std(self) -> io::Result<net::UnixStream> {
+        let s = self.0.into_inner();
+        s.set_nonblocking(false)?;
+        Ok(s)
+    }
+
     /// Creates a new independently owned handle to the underlying socket.
     ///
     /// The returned `UnixStream` is a reference to the same stream that this
@@ -224,6 +425,56 @@ impl UnixStream {
         self.0.write_timeout()
     }
 
+    /// Waits until the socket is readable without reading anything.
+    ///
+    /// It's for the code that does the io on the raw fd by itself. Only the
+    /// current coroutine is blocked and the wait is limited by the read
+    /// timeout. The readiness may be already gone when the caller reads,
+    /// e.g. taken by another reader of the same fd, so a following read
+    /// could still return `WouldBlock` and the caller should wait again.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use may::os::unix::net::UnixStream;
+    ///
+    /// let socket = UnixStream::connect("/tmp/sock").unwrap();
+    /// socket.wait_read().expect("wait_read failed");
+    /// ```
+    pub fn wait_read(&self) -> io::Result<()> {
+        self.0.wait_read()
+    }
+
+    /// Waits until the socket is writable without writing anything.
+    ///
+    /// The same as `wait_read`, but for the write and limited by the write
+    /// timeout.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use may::os::unix::net::UnixStream;
+    ///
+    /// let socket = UnixStream::connect("/tmp/sock").unwrap();
+    /// socket.wait_write().expect("wait_write failed");
+    /// ```
+    pub fn wait_write(&self) -> io::Result<()> {
+        self.0.wait_write()
+    }
+
+    /// Returns the event that the socket is readable, for `cqueue_add!`.
+    ///
+    /// The socket should be read in the bottom half of the arm, see
+    /// `TcpStream::read_ready_event`.
+    pub fn read_ready_event(&self) -> ReadyEvent<CoIo<net::UnixStream>> {
+        self.0.read_ready_event()
+    }
+
+    /// Returns the event that the socket is writable, for `cqueue_add!`.
+    pub fn write_ready_event(&self) -> ReadyEvent<CoIo<net::UnixStream>> {
+        self.0.write_ready_event()
+    }
+
     /// Moves the socket into or out of nonblocking mode.
     ///
     /// # Examples
@@ -254,306 +505,389 @@ impl UnixStream {
         self.0.inner().take_error()
     }
 
-    /// Shuts down the read, write, or both halves of this connection.
+    /// Sets `SO_RCVBUF`.
     ///
-    /// This function will cause all pending and future I/O calls on the
-    /// specified portions to immediately return with an appropriate value
-    /// (see the documentation of libstd `Shutdown`).
+    /// Linux flow controls a unix stream by the send buffer of the writer, so
+    /// it has little effect there.
     ///
     /// # Examples
     ///
     /// ```no_run
     /// use may::os::unix::net::UnixStream;
-    /// use std::net::Shutdown;
     ///
     /// let socket = UnixStream::connect("/tmp/sock").unwrap();
-    /// socket.shutdown(Shutdown::Both).expect("shutdown function failed");
+    /// socket.set_recv_buffer_size(64 * 1024).unwrap();
     /// ```
-    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
-        self.0.inner().shutdown(how)
-    }
-}
-
-impl io::Read for UnixStream {
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        io::Read::read(&mut &*self, buf)
-    }
-}
-
-impl<'a> io::Read for &'a UnixStream {
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        (&self.0).read(buf)
-    }
-}
-
-impl io::Write for UnixStream {
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        io::Write::write(&mut &*self, buf)
-    }
-
-    fn flush(&mut self) -> io::Result<()> {
-        io::Write::flush(&mut &*self)
-    }
-}
-
-impl<'a> io::Write for &'a UnixStream {
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        (&self.0).write(buf)
-    }
-
-    fn flush(&mut self) -> io::Result<()> {
-        (&self.0).flush()
-    }
-}
-
-impl AsRawFd for UnixStream {
-    fn as_raw_fd(&self) -> RawFd {
-        self.0.as_raw_fd()
+    pub fn set_recv_buffer_size(&self, size: usize) -> io::Result<()> {
+        with_socket(self, |s| s.set_recv_buffer_size(size))
     }
-}
 
-impl FromRawFd for UnixStream {
-    unsafe fn from_raw_fd(fd: RawFd) -> UnixStream {
-        let stream = FromRawFd::from_raw_fd(fd);
-        UnixStream(CoIo::new(stream).expect("can't convert to UnixStream"))
+    /// Returns `SO_RCVBUF`, Linux reports the doubled size it keeps.
+    pub fn recv_buffer_size(&self) -> io::Result<usize> {
+        with_socket(self, |s| s.recv_buffer_size())
     }
-}
 
-impl IntoRawFd for UnixStream {
-    fn into_raw_fd(self) -> RawFd {
-        self.0.into_raw_fd()
+    /// Sets `SO_SNDBUF`, it bounds the data written but not read by the peer
+    /// yet, a write waits when it's full.
+    pub fn set_send_buffer_size(&self, size: usize) -> io::Result<()> {
+        with_socket(self, |s| s.set_send_buffer_size(size))
     }
-}
-
-/// A structure representing a Unix domain socket server.
-///
-/// # Examples
-///
-/// ```no_run
-/// use std::thread;
-/// use may::os::unix::net::{UnixStream, UnixListener};
-///
-/// fn handle_client(stream: UnixStream) {
-///     // ...
-/// }
-///
-/// let listener = UnixListener::bind("/path/to/the/socket").unwrap();
-///
-/// // accept connections and process them, spawning a new thread for each one
-/// for stream in listener.incoming() {
-///     match stream {
-///         Ok(stream) => {
-///             /* connection succeeded */
-///             thread::spawn(|| handle_client(stream));
-///         }
-///         Err(err) => {
-///             /* connection failed */
-///             break;
-///         }
-///     }
-/// }
-/// ```
-pub struct UnixListener(pub(crate) CoIo<net::UnixListener>);
 
-impl fmt::Debug for UnixListener {
-    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        let mut builder = fmt.debug_struct("UnixListener");
-        builder.field("fd", &self.as_raw_fd());
-        if let Ok(addr) = self.local_addr() {
-            builder.field("local", &addr);
-        }
-        builder.finish()
+    /// Returns `SO_SNDBUF`.
+    pub fn send_buffer_size(&self) -> io::Result<usize> {
+        with_socket(self, |s| s.send_buffer_size())
     }
-}
 
-impl UnixListener {
-    /// Creates a new `UnixListener` bound to the specified socket.
+    /// Returns the credentials of the process on the other end of the socket.
+    ///
+    /// The `pid` is only available on Linux, it is `None` on other platforms.
     ///
     /// # Examples
     ///
     /// ```no_run
-    /// use may::os::unix::net::UnixListener;
+    /// use may::os::unix::net::UnixStream;
     ///
-    /// let listener = match UnixListener::bind("/path/to/the/socket") {
-    ///     Ok(sock) => sock,
-    ///     Err(e) => {
-    ///         println!("Couldn't connect: {:?}", e);
-    ///         return
-    ///     }
-    /// };
+    /// let socket = UnixStream::connect("/tmp/sock").unwrap();
+    /// let cred = socket.peer_cred().expect("Couldn't get peer credentials");
+    /// println!("peer uid = {}", cred.uid);
     /// ```
-    pub fn bind<P: AsRef<Path>>(path: P) -> io::Result<UnixListener> {
-        let listener = net::UnixListener::bind(path)?;
-        Ok(UnixListener(CoIo::new(listener)?))
+    pub fn peer_cred(&self) -> io::Result<UCred> {
+        peer_cred(self.as_raw_fd())
     }
 
-    /// Accepts a new incoming connection to this listener.
+    /// Receives data on the socket without removing it from the queue.
     ///
-    /// This function will block the calling thread until a new Unix connection
-    /// is established. When established, the corresponding `UnixStream` and
-    /// the remote peer's address will be returned.
+    /// A following `read` would return the same data. On success, returns
+    /// the number of bytes peeked.
     ///
     /// # Examples
     ///
     /// ```no_run
-    /// use may::os::unix::net::UnixListener;
-    ///
-    /// let listener = UnixListener::bind("/path/to/the/socket").unwrap();
+    /// use may::os::unix::net::UnixStream;
     ///
-    /// match listener.accept() {
-    ///     Ok((socket, addr)) => println!("Got a client: {:?}", addr),
-    ///     Err(e) => println!("accept function failed: {:?}", e),
-    /// }
+    /// let socket = UnixStream::connect("/tmp/sock").unwrap();
+    /// let mut buf = [0; 10];
+    /// let len = socket.peek(&mut buf).expect("peek failed");
     /// ```
-    pub fn accept(&self) -> io::Result<(UnixStream, SocketAddr)> {
+    pub fn peek(&self, buf: &mut [u8]) -> io::Result<usize> {
         if !self.0.ctx_check()? {
-            let (s, a) = self.0.inner().accept()?;
-            return Ok((UnixStream(CoIo::new(s)?), a));
+            // this can't be nonblocking!!
+            return net_impl::peek(self.as_raw_fd(), buf);
         }
 
         self.0.io_reset();
-        match self.0.inner().accept() {
+        // this is an earlier return try for nonblocking read
+        match net_impl::peek(self.as_raw_fd(), buf) {
             Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
-            Err(e) => return Err(e),
-            Ok((s, a)) => return Ok((UnixStream(CoIo::new(s)?), a)),
+            ret => return ret,
         }
 
-        let a = net_impl::UnixListenerAccept::new(self)?;
-        yield_with(&a);
-        a.done()
+        let reader = net_impl::SocketPeek::new(&self.0, buf, self.read_timeout()?);
+        yield_with(&reader);
+        reader.done()
     }
 
-    /// Creates a new independently owned handle to the underlying socket.
+    /// Sends data together with the file descriptors to the peer.
     ///
-    /// The returned `UnixListener` is a reference to the same socket that this
-    /// object references. Both handles can be used to accept incoming
-    /// connections and options set on one listener will affect the other.
+    /// The `fds` are passed as a `SCM_RIGHTS` control message, they are still
+    /// owned by the caller after the call. On success, returns the number of
+    /// bytes written.
     ///
     /// # Examples
     ///
     /// ```no_run
-    /// use may::os::unix::net::UnixListener;
-    ///
-    /// let listener = UnixListener::bind("/path/to/the/socket").unwrap();
+    /// use std::os::unix::io::AsRawFd;
+    /// use may::os::unix::net::UnixStream;
     ///
-    /// let listener_copy = listener.try_clone().expect("try_clone failed");
+    /// let file = std::fs::File::open("/dev/null").unwrap();
+    /// let socket = UnixStream::connect("/tmp/sock").unwrap();
+    /// socket.send_with_fds(b"fd", &[file.as_raw_fd()]).expect("send_with_fds failed");
     /// ```
-    pub fn try_clone(&self) -> io::Result<UnixListener> {
-        let listener = self.0.inner().try_clone()?;
-        Ok(UnixListener(CoIo::new(listener)?))
+    pub fn send_with_fds(&self, buf: &[u8], fds: &[RawFd]) -> io::Result<usize> {
+        if !self.0.ctx_check()? {
+            // this can't be nonblocking!!
+            return net_impl::send_with_fds(self.as_raw_fd(), buf, fds);
+        }
+
+        self.0.io_reset();
+        // this is an earlier return try for nonblocking write
+        match net_impl::send_with_fds(self.as_raw_fd(), buf, fds) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            ret => return ret,
+        }
+
+        let writer = net_impl::UnixSendFds::new(self, buf, fds);
+        yield_with(&writer);
+        writer.done()
     }
 
-    /// Returns the local socket address of this listener.
+    /// Receives data and the file descriptors sent by the peer.
+    ///
+    /// On success, returns the number of bytes read and the number of fds
+    /// stored in `fds`. The received fds are owned by the caller and must be
+    /// closed by it, on Linux they are opened with `O_CLOEXEC`. If `fds` is too
+    /// small to hold all the passed fds, the received ones are closed and an
+    /// error of kind `Other` is returned.
     ///
     /// # Examples
     ///
     /// ```no_run
-    /// use may::os::unix::net::UnixListener;
-    ///
-    /// let listener = UnixListener::bind("/path/to/the/socket").unwrap();
+    /// use may::os::unix::net::UnixStream;
     ///
-    /// let addr = listener.local_addr().expect("Couldn't get local address");
+    /// let socket = UnixStream::connect("/tmp/sock").unwrap();
+    /// let mut buf = [0; 16];
+    /// let mut fds = [0; 4];
+    /// let (n, cnt) = socket.recv_with_fds(&mut buf, &mut fds).expect("recv_with_fds failed");
+    /// println!("received {} bytes and fds {:?}", n, &fds[..cnt]);
     /// ```
-    pub fn local_addr(&self) -> io::Result<SocketAddr> {
-        self.0.inner().local_addr()
+    pub fn recv_with_fds(&self, buf: &mut [u8], fds: &mut [RawFd]) -> io::Result<(usize, usize)> {
+        if !self.0.ctx_check()? {
+            // this can't be nonblocking!!
+            return net_impl::recv_with_fds(self.as_raw_fd(), buf, fds);
+        }
+
+        self.0.io_reset();
+        // this is an earlier return try for nonblocking read
+        match net_impl::recv_with_fds(self.as_raw_fd(), buf, fds) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            ret => return ret,
+        }
+
+        let reader = net_impl::UnixRecvFds::new(self, buf, fds);
+        yield_with(&reader);
+        reader.done()
     }
 
-    /// Moves the socket into or out of nonblocking mode.
+    /// Reads some data from the socket, waiting no later than the deadline.
+    ///
+    /// The deadline only applies to this call, the read timeout of the socket
+    /// is ignored and not changed. An expired deadline gives a `TimedOut`
+    /// error carrying `DeadlineExceeded`.
     ///
     /// # Examples
     ///
     /// ```no_run
-    /// use may::os::unix::net::UnixListener;
-    ///
-    /// let listener = UnixListener::bind("/path/to/the/socket").unwrap();
+    /// use may::os::unix::net::UnixStream;
+    /// use std::time::{Duration, Instant};
     ///
-    /// listener.set_nonblocking(true).expect("Couldn't set non blocking");
+    /// let socket = UnixStream::connect("/tmp/sock").unwrap();
+    /// let mut buf = [0; 10];
+    /// let deadline = Instant::now() + Duration::from_secs(1);
+    /// let len = socket.read_deadline(&mut buf, deadline).expect("read failed");
     /// ```
-    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
-        self.0.set_nonblocking(nonblocking)
+    pub fn read_deadline(&self, buf: &mut [u8], deadline: Instant) -> io::Result<usize> {
+        self.read_until(buf, deadline)
+            .map_err(|e| deadline::exceeded(e, 0))
     }
 
-    /// Returns the value of the `SO_ERROR` option.
+    /// Reads the exact number of bytes to fill the buffer before the deadline.
+    ///
+    /// When the deadline expires in the middle, the `DeadlineExceeded` of the
+    /// returned error tells how many bytes are already read into the buffer.
     ///
     /// # Examples
     ///
     /// ```no_run
-    /// use may::os::unix::net::UnixListener;
-    ///
-    /// let listener = UnixListener::bind("/tmp/sock").unwrap();
+    /// use may::io::DeadlineExceeded;
+    /// use may::os::unix::net::UnixStream;
+    /// use std::time::{Duration, Instant};
     ///
-    /// if let Ok(Some(err)) = listener.take_error() {
-    ///     println!("Got error: {:?}", err);
+    /// let socket = UnixStream::connect("/tmp/sock").unwrap();
+    /// let mut buf = [0; 10];
+    /// let deadline = Instant::now() + Duration::from_secs(1);
+    /// if let Err(e) = socket.read_exact_deadline(&mut buf, deadline) {
+    ///     if let Some(d) = DeadlineExceeded::from_io_error(&e) {
+    ///         println!("only {} bytes read", d.transferred());
+    ///     }
     /// }
     /// ```
-    pub fn take_error(&self) -> io::Result<Option<io::Error>> {
-        self.0.inner().take_error()
+    pub fn read_exact_deadline(&self, buf: &mut [u8], deadline: Instant) -> io::Result<()> {
+        deadline::read_exact(buf, |buf| self.read_until(buf, deadline))
     }
 
-    /// Returns an iterator over incoming connections.
+    /// Writes some data to the socket, waiting no later than the deadline.
     ///
-    /// The iterator will never return `None` and will also not yield the
-    /// peer's `SocketAddr` structure.
+    /// The deadline only applies to this call, the write timeout of the socket
+    /// is ignored and not changed. An expired deadline gives a `TimedOut`
+    /// error carrying `DeadlineExceeded`.
     ///
     /// # Examples
     ///
     /// ```no_run
-    /// use std::thread;
-    /// use may::os::unix::net::{UnixStream, UnixListener};
+    /// use may::os::unix::net::UnixStream;
+    /// use std::time::{Duration, Instant};
     ///
-    /// fn handle_client(stream: UnixStream) {
-    ///     // ...
-    /// }
+    /// let socket = UnixStream::connect("/tmp/sock").unwrap();
+    /// let deadline = Instant::now() + Duration::from_secs(1);
+    /// let len = socket.write_deadline(b"hello", deadline).expect("write failed");
+    /// ```
+    pub fn write_deadline(&self, buf: &[u8], deadline: Instant) -> io::Result<usize> {
+        self.write_until(buf, deadline)
+            .map_err(|e| deadline::exceeded(e, 0))
+    }
+
+    /// Writes the whole buffer to the socket before the deadline.
     ///
-    /// let listener = UnixListener::bind("/path/to/the/socket").unwrap();
+    /// When the deadline expires in the middle, the `DeadlineExceeded` of the
+    /// returned error tells how many bytes are already written.
     ///
-    /// for stream in listener.incoming() {
-    ///     match stream {
-    ///         Ok(stream) => {
-    ///             thread::spawn(|| handle_client(stream));
-    ///         }
-    ///         Err(err) => {
-    ///             break;
-    ///         }
-    ///     }
-    /// }
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use may::os::unix::net::UnixStream;
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let socket = UnixStream::connect("/tmp/sock").unwrap();
+    /// let deadline = Instant::now() + Duration::from_secs(1);
+    /// socket.write_all_deadline(b"hello", deadline).expect("write failed");
     /// ```
-    pub fn incoming<'a>(&'a self) -> Incoming<'a> {
-        Incoming { listener: self }
+    pub fn write_all_deadline(&self, buf: &[u8], deadline: Instant) -> io::Result<()> {
+        deadline::write_all(buf, |buf| self.write_until(buf, deadline))
+    }
+
+    fn read_until(&self, buf: &mut [u8], deadline: Instant) -> io::Result<usize> {
+        if !self.0.ctx_check()? {
+            deadline::wait_fd(self.as_raw_fd(), libc::POLLIN, deadline)?;
+            return io::Read::read(&mut self.0.inner(), buf);
+        }
+
+        // an expired deadline fails before the io
+        deadline::remaining(deadline)?;
+        self.0.io_reset();
+        // this is an earlier return try for nonblocking read
+        match io::Read::read(&mut self.0.inner(), buf) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            ret => return ret,
+        }
+
+        let reader = net_impl::SocketRead::new_until(&self.0, buf, deadline)?;
+        yield_with(&reader);
+        reader.done()
+    }
+
+    fn write_until(&self, buf: &[u8], deadline: Instant) -> io::Result<usize> {
+        if !self.0.ctx_check()? {
+            deadline::wait_fd(self.as_raw_fd(), libc::POLLOUT, deadline)?;
+            return io::Write::write(&mut self.0.inner(), buf);
+        }
+
+        // an expired deadline fails before the io
+        deadline::remaining(deadline)?;
+        self.0.io_reset();
+        // this is an earlier return try for nonblocking write
+        match io::Write::write(&mut self.0.inner(), buf) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            ret => return ret,
+        }
+
+        let writer = net_impl::SocketWrite::new_until(&self.0, buf, deadline)?;
+        yield_with(&writer);
+        writer.done()
+    }
+
+    /// Shuts down the read, write, or both halves of this connection.
+    ///
+    /// This function will cause all pending and future I/O calls on the
+    /// specified portions to immediately return with an appropriate value
+    /// (see the documentation of libstd `Shutdown`). The coroutines blocked
+    /// on the socket or its clones are waked up.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use may::os::unix::net::UnixStream;
+    /// use std::net::Shutdown;
+    ///
+    /// let socket = UnixStream::connect("/tmp/sock").unwrap();
+    /// socket.shutdown(Shutdown::Both).expect("shutdown function failed");
+    /// ```
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.0.inner().shutdown(how)
     }
 }
 
-impl AsRawFd for UnixListener {
-    fn as_raw_fd(&self) -> RawFd {
-        self.0.as_raw_fd()
+impl io::Read for UnixStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        io::Read::read(&mut &*self, buf)
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [io::IoSliceMut]) -> io::Result<usize> {
+        io::Read::read_vectored(&mut &*self, bufs)
     }
 }
 
-impl FromRawFd for UnixListener {
-    unsafe fn from_raw_fd(fd: RawFd) -> UnixListener {
-        let listener = FromRawFd::from_raw_fd(fd);
-        UnixListener(CoIo::new(listener).expect("can't convert to UnixListener"))
+impl<'a> io::Read for &'a UnixStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (&self.0).read(buf)
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [io::IoSliceMut]) -> io::Result<usize> {
+        (&self.0).read_vectored(bufs)
     }
 }
 
-impl IntoRawFd for UnixListener {
-    fn into_raw_fd(self) -> RawFd {
-        self.0.into_raw_fd()
+impl io::Write for UnixStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        io::Write::write(&mut &*self, buf)
+    }
+
+    fn write_vectored(&mut self, bufs: &[io::IoSlice]) -> io::Result<usize> {
+        io::Write::write_vectored(&mut &*self, bufs)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::Write::flush(&mut &*self)
     }
 }
 
-impl<'a> IntoIterator for &'a UnixListener {
-    type Item = io::Result<UnixStream>;
-    type IntoIter = Incoming<'a>;
+impl<'a> io::Write for &'a UnixStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (&self.0).write(buf)
+    }
 
-    fn into_iter(self) -> Incoming<'a> {
-        self.incoming()
+    fn write_vectored(&mut self, bufs: &[io::IoSlice]) -> io::Result<usize> {
+        (&self.0).write_vectored(bufs)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (&self.0).flush()
     }
 }
 
-/// An iterator over incoming connections to a `UnixListener`.
-///
-/// It will never return `None`.
+impl AsIoData for UnixStream {
+    fn as_io_data(&self) -> &IoData {
+        self.0.as_io_data()
+    }
+
+    fn io_read_timeout(&self) -> Option<Duration> {
+        self.0.io_read_timeout()
+    }
+
+    fn io_write_timeout(&self) -> Option<Duration> {
+        self.0.io_write_timeout()
+    }
+}
+
+impl AsRawFd for UnixStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+impl FromRawFd for UnixStream {
+    unsafe fn from_raw_fd(fd: RawFd) -> UnixStream {
+        let stream = FromRawFd::from_raw_fd(fd);
+        UnixStream(CoIo::new(stream).expect("can't convert to UnixStream"))
+    }
+}
+
+impl IntoRawFd for UnixStream {
+    fn into_raw_fd(self) -> RawFd {
+        self.0.into_raw_fd()
+    }
+}
+
+/// A structure representing a Unix domain socket server.
 ///
 /// # Examples
 ///
@@ -567,484 +901,1280 @@ impl<'a> IntoIterator for &'a UnixListener {
 ///
 /// let listener = UnixListener::bind("/path/to/the/socket").unwrap();
 ///
+/// // accept connections and process them, spawning a new thread for each one
 /// for stream in listener.incoming() {
 ///     match stream {
 ///         Ok(stream) => {
+///             /* connection succeeded */
 ///             thread::spawn(|| handle_client(stream));
 ///         }
 ///         Err(err) => {
+///             /* connection failed */
 ///             break;
 ///         }
 ///     }
 /// }
 /// ```
-#[derive(Debug)]
-pub struct Incoming<'a> {
-    listener: &'a UnixListener,
-}
-
-impl<'a> Iterator for Incoming<'a> {
-    type Item = io::Result<UnixStream>;
-
-    fn next(&mut self) -> Option<io::Result<UnixStream>> {
-        Some(self.listener.accept().map(|s| s.0))
-    }
-
-    fn size_hint(&self) -> (usize, Option<usize>) {
-        (usize::max_value(), None)
-    }
-}
-
-/// A Unix datagram socket.
-///
-/// # Examples
-///
-/// ```no_run
-/// use may::os::unix::net::UnixDatagram;
-///
-/// let socket = UnixDatagram::bind("/path/to/my/socket").unwrap();
-/// socket.send_to(b"hello world", "/path/to/other/socket").unwrap();
-/// let mut buf = [0; 100];
-/// let (count, address) = socket.recv_from(&mut buf).unwrap();
-/// println!("socket {:?} sent {:?}", address, &buf[..count]);
-/// ```
-pub struct UnixDatagram(pub(crate) CoIo<net::UnixDatagram>);
+pub struct UnixListener(pub(crate) CoIo<net::UnixListener>);
 
-impl fmt::Debug for UnixDatagram {
+impl fmt::Debug for UnixListener {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        let mut builder = fmt.debug_struct("UnixDatagram");
+        let mut builder = fmt.debug_struct("UnixListener");
         builder.field("fd", &self.as_raw_fd());
         if let Ok(addr) = self.local_addr() {
             builder.field("local", &addr);
         }
-        if let Ok(addr) = self.peer_addr() {
-            builder.field("peer", &addr);
-        }
         builder.finish()
     }
 }
 
-impl UnixDatagram {
-    /// Creates a Unix datagram socket bound to the given path.
+impl UnixListener {
+    /// Creates a new `UnixListener` bound to the specified socket.
     ///
     /// # Examples
     ///
     /// ```no_run
-    /// use may::os::unix::net::UnixDatagram;
+    /// use may::os::unix::net::UnixListener;
     ///
-    /// let sock = match UnixDatagram::bind("/path/to/the/socket") {
+    /// let listener = match UnixListener::bind("/path/to/the/socket") {
     ///     Ok(sock) => sock,
     ///     Err(e) => {
-    ///         println!("Couldn't bind: {:?}", e);
+    ///         println!("Couldn't connect: {:?}", e);
     ///         return
     ///     }
     /// };
     /// ```
-    pub fn bind<P: AsRef<Path>>(path: P) -> io::Result<UnixDatagram> {
-        let datagram = net::UnixDatagram::bind(path)?;
-        Ok(UnixDatagram(CoIo::new(datagram)?))
+    pub fn bind<P: AsRef<Path>>(path: P) -> io::Result<UnixListener> {
+        let listener = net::UnixListener::bind(path)?;
+        Ok(UnixListener(CoIo::new(listener)?))
     }
 
-    /// Creates a Unix Datagram socket which is not bound to any address.
+    /// Creates a new `UnixListener` bound to the abstract `name`.
+    ///
+    /// The name is in the Linux abstract namespace, so no file is created. On
+    /// other platforms an error is returned.
     ///
     /// # Examples
     ///
     /// ```no_run
-    /// use may::os::unix::net::UnixDatagram;
+    /// use may::os::unix::net::UnixListener;
     ///
-    /// let sock = match UnixDatagram::unbound() {
+    /// let listener = match UnixListener::bind_abstract(b"my_service") {
     ///     Ok(sock) => sock,
     ///     Err(e) => {
-    ///         println!("Couldn't unbound: {:?}", e);
+    ///         println!("Couldn't bind: {:?}", e);
     ///         return
     ///     }
     /// };
     /// ```
-    pub fn unbound() -> io::Result<UnixDatagram> {
-        let datagram = net::UnixDatagram::unbound()?;
-        Ok(UnixDatagram(CoIo::new(datagram)?))
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn bind_abstract(name: &[u8]) -> io::Result<UnixListener> {
+        let socket = Socket::new(Domain::unix(), Type::stream(), None)?;
+        socket.bind(&abstract_addr(name)?)?;
+        socket.listen(128)?;
+        Ok(UnixListener(CoIo::new(socket.into_unix_listener())?))
     }
 
-    /// Create an unnamed pair of connected sockets.
-    ///
-    /// Returns two `UnixDatagrams`s which are connected to each other.
-    ///
-    /// # Examples
-    ///
-    /// ```no_run
-    /// use may::os::unix::net::UnixDatagram;
+    /// Creates a new `UnixListener` bound to the abstract `name`.
     ///
-    /// let (sock1, sock2) = match UnixDatagram::pair() {
-    ///     Ok((sock1, sock2)) => (sock1, sock2),
-    ///     Err(e) => {
-    ///         println!("Couldn't unbound: {:?}", e);
-    ///         return
-    ///     }
-    /// };
-    /// ```
-    pub fn pair() -> io::Result<(UnixDatagram, UnixDatagram)> {
-        let (i1, i2) = net::UnixDatagram::pair()?;
-        let i1 = UnixDatagram(CoIo::new(i1)?);
-        let i2 = UnixDatagram(CoIo::new(i2)?);
-        Ok((i1, i2))
+    /// The abstract namespace is a Linux extension, this always returns an error.
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub fn bind_abstract(_name: &[u8]) -> io::Result<UnixListener> {
+        Err(abstract_unsupported())
     }
 
-    /// Connects the socket to the specified address.
-    ///
-    /// The [`send`] method may be used to send data to the specified address.
-    /// [`recv`] and [`recv_from`] will only receive data from that address.
+    /// Accepts a new incoming connection to this listener.
     ///
-    /// [`send`]: #method.send
-    /// [`recv`]: #method.recv
-    /// [`recv_from`]: #method.recv_from
+    /// This function will block the calling thread until a new Unix connection
+    /// is established. When established, the corresponding `UnixStream` and
+    /// the remote peer's address will be returned.
     ///
     /// # Examples
     ///
     /// ```no_run
-    /// use may::os::unix::net::UnixDatagram;
+    /// use may::os::unix::net::UnixListener;
     ///
-    /// let sock = UnixDatagram::unbound().unwrap();
-    /// match sock.connect("/path/to/the/socket") {
-    ///     Ok(sock) => sock,
-    ///     Err(e) => {
-    ///         println!("Couldn't connect: {:?}", e);
-    ///         return
-    ///     }
-    /// };
+    /// let listener = UnixListener::bind("/path/to/the/socket").unwrap();
+    ///
+    /// match listener.accept() {
+    ///     Ok((socket, addr)) => println!("Got a client: {:?}", addr),
+    ///     Err(e) => println!("accept function failed: {:?}", e),
+    /// }
     /// ```
-    pub fn connect<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
-        // for UnixDatagram connect it's a nonblocking operation
-        // so we just use the system call
-        self.0.inner().connect(path)
+    pub fn accept(&self) -> io::Result<(UnixStream, SocketAddr)> {
+        if !self.0.ctx_check()? {
+            let (s, a) = self.0.inner().accept()?;
+            return Ok((UnixStream(CoIo::new(s)?), a));
+        }
+
+        self.0.io_reset();
+        match self.0.inner().accept() {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            Err(e) => return Err(e),
+            Ok((s, a)) => return Ok((UnixStream(CoIo::new(s)?), a)),
+        }
+
+        let a = net_impl::UnixListenerAccept::new(self)?;
+        yield_with(&a);
+        a.done()
     }
 
     /// Creates a new independently owned handle to the underlying socket.
     ///
-    /// The returned `UnixDatagram` is a reference to the same socket that this
+    /// The returned `UnixListener` is a reference to the same socket that this
     /// object references. Both handles can be used to accept incoming
-    /// connections and options set on one side will affect the other.
+    /// connections and options set on one listener will affect the other.
     ///
     /// # Examples
     ///
     /// ```no_run
-    /// use may::os::unix::net::UnixDatagram;
+    /// use may::os::unix::net::UnixListener;
     ///
-    /// let sock = UnixDatagram::bind("/path/to/the/socket").unwrap();
+    /// let listener = UnixListener::bind("/path/to/the/socket").unwrap();
     ///
-    /// let sock_copy = sock.try_clone().expect("try_clone failed");
+    /// let listener_copy = listener.try_clone().expect("try_clone failed");
     /// ```
-    pub fn try_clone(&self) -> io::Result<UnixDatagram> {
-        let datagram = self.0.inner().try_clone()?;
-        Ok(UnixDatagram(CoIo::new(datagram)?))
+    pub fn try_clone(&self) -> io::Result<UnixListener> {
+        let listener = self.0.inner().try_clone()?;
+        Ok(UnixListener(CoIo::new(listener)?))
     }
 
-    /// Returns the address of this socket.
+    /// Returns the local socket address of this listener.
     ///
     /// # Examples
     ///
     /// ```no_run
-    /// use may::os::unix::net::UnixDatagram;
+    /// use may::os::unix::net::UnixListener;
     ///
-    /// let sock = UnixDatagram::bind("/path/to/the/socket").unwrap();
+    /// let listener = UnixListener::bind("/path/to/the/socket").unwrap();
     ///
-    /// let addr = sock.local_addr().expect("Couldn't get local address");
+    /// let addr = listener.local_addr().expect("Couldn't get local address");
     /// ```
     pub fn local_addr(&self) -> io::Result<SocketAddr> {
         self.0.inner().local_addr()
     }
 
-    /// Returns the address of this socket's peer.
-    ///
-    /// The [`connect`] method will connect the socket to a peer.
-    ///
-    /// [`connect`]: #method.connect
+    /// Moves the socket into or out of nonblocking mode.
     ///
     /// # Examples
     ///
     /// ```no_run
-    /// use may::os::unix::net::UnixDatagram;
+    /// use may::os::unix::net::UnixListener;
     ///
-    /// let sock = UnixDatagram::unbound().unwrap();
-    /// sock.connect("/path/to/the/socket").unwrap();
+    /// let listener = UnixListener::bind("/path/to/the/socket").unwrap();
     ///
-    /// let addr = sock.peer_addr().expect("Couldn't get peer address");
+    /// listener.set_nonblocking(true).expect("Couldn't set non blocking");
     /// ```
-    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
-        self.0.inner().peer_addr()
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        self.0.set_nonblocking(nonblocking)
     }
 
-    /// Receives data from the socket.
-    ///
-    /// On success, returns the number of bytes read and the address from
-    /// whence the data came.
+    /// Returns the value of the `SO_ERROR` option.
     ///
     /// # Examples
     ///
     /// ```no_run
-    /// use may::os::unix::net::UnixDatagram;
+    /// use may::os::unix::net::UnixListener;
     ///
-    /// let sock = UnixDatagram::unbound().unwrap();
-    /// let mut buf = vec![0; 10];
-    /// match sock.recv_from(buf.as_mut_slice()) {
-    ///     Ok((size, sender)) => println!("received {} bytes from {:?}", size, sender),
-    ///     Err(e) => println!("recv_from function failed: {:?}", e),
+    /// let listener = UnixListener::bind("/tmp/sock").unwrap();
+    ///
+    /// if let Ok(Some(err)) = listener.take_error() {
+    ///     println!("Got error: {:?}", err);
     /// }
     /// ```
-    pub fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
-        if !self.0.ctx_check()? {
-            // this can't be nonblocking!!
-            return self.0.inner().recv_from(buf);
-        }
-
-        self.0.io_reset();
-        // this is an earlier return try for nonblocking read
-        match self.0.inner().recv_from(buf) {
-            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
-            ret => return ret,
-        }
+    pub fn take_error(&self) -> io::Result<Option<io::Error>> {
+        self.0.inner().take_error()
+    }
 
-        let reader = net_impl::UnixRecvFrom::new(self, buf);
-        yield_with(&reader);
-        reader.done()
+    /// Sets `SO_RCVBUF` of the listening socket.
+    pub fn set_recv_buffer_size(&self, size: usize) -> io::Result<()> {
+        with_socket(self, |s| s.set_recv_buffer_size(size))
     }
 
-    /// Receives data from the socket.
-    ///
-    /// On success, returns the number of bytes read.
-    ///
-    /// # Examples
-    ///
-    /// ```no_run
-    /// use may::os::unix::net::UnixDatagram;
-    ///
-    /// let sock = UnixDatagram::bind("/path/to/the/socket").unwrap();
-    /// let mut buf = vec![0; 10];
-    /// sock.recv(buf.as_mut_slice()).expect("recv function failed");
-    /// ```
-    pub fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
-        if !self.0.ctx_check()? {
-            // this can't be nonblocking!!
-            return self.0.inner().recv(buf);
-        }
+    /// Returns `SO_RCVBUF` of the listening socket.
+    pub fn recv_buffer_size(&self) -> io::Result<usize> {
+        with_socket(self, |s| s.recv_buffer_size())
+    }
 
-        self.0.io_reset();
-        // this is an earlier return try for nonblocking read
-        match self.0.inner().recv(buf) {
-            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
-            ret => return ret,
-        }
+    /// Sets `SO_SNDBUF` of the listening socket.
+    pub fn set_send_buffer_size(&self, size: usize) -> io::Result<()> {
+        with_socket(self, |s| s.set_send_buffer_size(size))
+    }
 
-        let reader = net_impl::SocketRead::new(&self.0, buf, self.read_timeout().unwrap());
-        yield_with(&reader);
-        reader.done()
+    /// Returns `SO_SNDBUF` of the listening socket.
+    pub fn send_buffer_size(&self) -> io::Result<usize> {
+        with_socket(self, |s| s.send_buffer_size())
     }
 
-    /// Sends data on the socket to the specified address.
+    /// Returns an iterator over incoming connections.
     ///
-    /// On success, returns the number of bytes written.
+    /// The iterator will never return `None` and will also not yield the
+    /// peer's `SocketAddr` structure.
     ///
     /// # Examples
     ///
     /// ```no_run
-    /// use may::os::unix::net::UnixDatagram;
+    /// use std::thread;
+    /// use may::os::unix::net::{UnixStream, UnixListener};
     ///
-    /// let sock = UnixDatagram::unbound().unwrap();
-    /// sock.send_to(b"omelette au fromage", "/some/sock").expect("send_to function failed");
+    /// fn handle_client(stream: UnixStream) {
+    ///     // ...
+    /// }
+    ///
+    /// let listener = UnixListener::bind("/path/to/the/socket").unwrap();
+    ///
+    /// for stream in listener.incoming() {
+    ///     match stream {
+    ///         Ok(stream) => {
+    ///             thread::spawn(|| handle_client(stream));
+    ///         }
+    ///         Err(err) => {
+    ///             break;
+    ///         }
+    ///     }
+    /// }
     /// ```
-    pub fn send_to<P: AsRef<Path>>(&self, buf: &[u8], path: P) -> io::Result<usize> {
-        if !self.0.ctx_check()? {
-            // this can't be nonblocking!!
-            return self.0.inner().send_to(buf, path);
-        }
+    pub fn incoming<'a>(&'a self) -> Incoming<'a> {
+        Incoming { listener: self }
+    }
+}
 
-        self.0.io_reset();
-        // this is an earlier return try for nonblocking read
-        match self.0.inner().send_to(buf, path.as_ref()) {
-            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
-            ret => return ret,
-        }
+impl AsRawFd for UnixListener {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
 
-        let writer = net_impl::UnixSendTo::new(self, buf, path.as_ref())?;
-        yield_with(&writer);
-        writer.done()
+impl FromRawFd for UnixListener {
+    unsafe fn from_raw_fd(fd: RawFd) -> UnixListener {
+        let listener = FromRawFd::from_raw_fd(fd);
+        UnixListener(CoIo::new(listener).expect("can't convert to UnixListener"))
     }
+}
 
-    /// Sends data on the socket to the socket's peer.
-    ///
-    /// The peer address may be set by the `connect` method, and this method
-    /// will return an error if the socket has not already been connected.
-    ///
-    /// On success, returns the number of bytes written.
+impl IntoRawFd for UnixListener {
+    fn into_raw_fd(self) -> RawFd {
+        self.0.into_raw_fd()
+    }
+}
+
+impl<'a> IntoIterator for &'a UnixListener {
+    type Item = io::Result<UnixStream>;
+    type IntoIter = Incoming<'a>;
+
+    fn into_iter(self) -> Incoming<'a> {
+        self.incoming()
+    }
+}
+
+/// An iterator over incoming connections to a `UnixListener`.
+///
+/// It will never return `None`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::thread;
+/// use may::os::unix::net::{UnixStream, UnixListener};
+///
+/// fn handle_client(stream: UnixStream) {
+///     // ...
+/// }
+///
+/// let listener = UnixListener::bind("/path/to/the/socket").unwrap();
+///
+/// for stream in listener.incoming() {
+///     match stream {
+///         Ok(stream) => {
+///             thread::spawn(|| handle_client(stream));
+///         }
+///         Err(err) => {
+///             break;
+///         }
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Incoming<'a> {
+    listener: &'a UnixListener,
+}
+
+impl<'a> Iterator for Incoming<'a> {
+    type Item = io::Result<UnixStream>;
+
+    fn next(&mut self) -> Option<io::Result<UnixStream>> {
+        Some(self.listener.accept().map(|s| s.0))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::max_value(), None)
+    }
+}
+
+/// A Unix datagram socket.
+///
+/// # Examples
+///
+/// ```no_run
+/// use may::os::unix::net::UnixDatagram;
+///
+/// let socket = UnixDatagram::bind("/path/to/my/socket").unwrap();
+/// socket.send_to(b"hello world", "/path/to/other/socket").unwrap();
+/// let mut buf = [0; 100];
+/// let (count, address) = socket.recv_from(&mut buf).unwrap();
+/// println!("socket {:?} sent {:?}", address, &buf[..count]);
+/// ```
+pub struct UnixDatagram(pub(crate) CoIo<net::UnixDatagram>);
+
+impl fmt::Debug for UnixDatagram {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let mut builder = fmt.debug_struct("UnixDatagram");
+        builder.field("fd", &self.as_raw_fd());
+        if let Ok(addr) = self.local_addr() {
+            builder.field("local", &addr);
+        }
+        if let Ok(addr) = self.peer_addr() {
+            builder.field("peer", &addr);
+        }
+        builder.finish()
+    }
+}
+
+impl UnixDatagram {
+    /// Creates a Unix datagram socket bound to the given path.
     ///
     /// # Examples
     ///
     /// ```no_run
     /// use may::os::unix::net::UnixDatagram;
     ///
-    /// let sock = UnixDatagram::unbound().unwrap();
-    /// sock.connect("/some/sock").expect("Couldn't connect");
-    /// sock.send(b"omelette au fromage").expect("send_to function failed");
+    /// let sock = match UnixDatagram::bind("/path/to/the/socket") {
+    ///     Ok(sock) => sock,
+    ///     Err(e) => {
+    ///         println!("Couldn't bind: {:?}", e);
+    ///         return
+    ///     }
+    /// };
     /// ```
-    pub fn send(&self, buf: &[u8]) -> io::Result<usize> {
-        if !self.0.ctx_check()? {
-            // this can't be nonblocking!!
-            return self.0.inner().send(buf);
-        }
+    pub fn bind<P: AsRef<Path>>(path: P) -> io::Result<UnixDatagram> {
+        let datagram = net::UnixDatagram::bind(path)?;
+        Ok(UnixDatagram(CoIo::new(datagram)?))
+    }
 
-        self.0.io_reset();
-        // this is an earlier return try for nonblocking write
-        match self.0.inner().send(buf) {
-            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
-            ret => return ret,
-        }
+    /// Creates a Unix datagram socket bound to the abstract `name`.
+    ///
+    /// The name is in the Linux abstract namespace, so no file is created. On
+    /// other platforms an error is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use may::os::unix::net::UnixDatagram;
+    ///
+    /// let sock = match UnixDatagram::bind_abstract(b"my_service") {
+    ///     Ok(sock) => sock,
+    ///     Err(e) => {
+    ///         println!("Couldn't bind: {:?}", e);
+    ///         return
+    ///     }
+    /// };
+    /// ```
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn bind_abstract(name: &[u8]) -> io::Result<UnixDatagram> {
+        let socket = Socket::new(Domain::unix(), Type::dgram(), None)?;
+        socket.bind(&abstract_addr(name)?)?;
+        Ok(UnixDatagram(CoIo::new(socket.into_unix_datagram())?))
+    }
 
-        let writer = net_impl::SocketWrite::new(&self.0, buf, self.0.write_timeout().unwrap());
-        yield_with(&writer);
-        writer.done()
+    /// Creates a Unix datagram socket bound to the abstract `name`.
+    ///
+    /// The abstract namespace is a Linux extension, this always returns an error.
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub fn bind_abstract(_name: &[u8]) -> io::Result<UnixDatagram> {
+        Err(abstract_unsupported())
     }
 
-    /// Sets the read timeout for the socket.
+    /// Connects the socket to the abstract `name`.
     ///
-    /// If the provided value is `None`, then [`recv`] and [`recv_from`] calls will
-    /// block indefinitely. It is an error to pass the zero `Duration` to this
-    /// method.
+    /// After that [`send`] and [`recv`] can be used with that peer. On platforms
+    /// other than Linux an error is returned.
     ///
+    /// [`send`]: #method.send
     /// [`recv`]: #method.recv
-    /// [`recv_from`]: #method.recv_from
     ///
     /// # Examples
     ///
     /// ```no_run
     /// use may::os::unix::net::UnixDatagram;
-    /// use std::time::Duration;
     ///
     /// let sock = UnixDatagram::unbound().unwrap();
-    /// sock.set_read_timeout(Some(Duration::new(1, 0))).expect("set_read_timeout function failed");
+    /// sock.connect_abstract(b"my_service").expect("Couldn't connect");
     /// ```
-    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
-        self.0.inner().set_read_timeout(timeout)?;
-        self.0.set_read_timeout(timeout)
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn connect_abstract(&self, name: &[u8]) -> io::Result<()> {
+        let addr = abstract_addr(name)?;
+        // for UnixDatagram connect it's a nonblocking operation
+        // borrow the fd to call the system call through socket2
+        let socket = unsafe { Socket::from_raw_fd(self.as_raw_fd()) };
+        let ret = socket.connect(&addr);
+        // the fd is still owned by self
+        let _ = socket.into_raw_fd();
+        ret
     }
 
-    /// Sets the write timeout for the socket.
+    /// Connects the socket to the abstract `name`.
     ///
-    /// If the provided value is `None`, then [`send`] and [`send_to`] calls will
-    /// block indefinitely. It is an error to pass the zero `Duration` to this
-    /// method.
+    /// The abstract namespace is a Linux extension, this always returns an error.
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub fn connect_abstract(&self, _name: &[u8]) -> io::Result<()> {
+        Err(abstract_unsupported())
+    }
+
+    /// Creates a Unix Datagram socket which is not bound to any address.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use may::os::unix::net::UnixDatagram;
+    ///
+    /// let sock = match UnixDatagram::unbound() {
+    ///     Ok(sock) => sock,
+    ///     Err(e) => {
+    ///         println!("Couldn't unbound: {:?}", e);
+    ///         return
+    ///     }
+    /// };
+    /// ```
+    pub fn unbound() -> io::Result<UnixDatagram> {
+        let datagram = net::UnixDatagram::unbound()?;
+        Ok(UnixDatagram(CoIo::new(datagram)?))
+    }
+
+    /// Create an unnamed pair of connected sockets.
+    ///
+    /// Returns two `UnixDatagrams`s which are connected to each other.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use may::os::unix::net::UnixDatagram;
+    ///
+    /// let (sock1, sock2) = match UnixDatagram::pair() {
+    ///     Ok((sock1, sock2)) => (sock1, sock2),
+    ///     Err(e) => {
+    ///         println!("Couldn't unbound: {:?}", e);
+    ///         return
+    ///     }
+    /// };
+    /// ```
+    pub fn pair() -> io::Result<(UnixDatagram, UnixDatagram)> {
+        let (i1, i2) = net::UnixDatagram::pair()?;
+        let i1 = UnixDatagram(CoIo::new(i1)?);
+        let i2 = UnixDatagram(CoIo::new(i2)?);
+        Ok((i1, i2))
+    }
+
+    /// Connects the socket to the specified address.
+    ///
+    /// The [`send`] method may be used to send data to the specified address.
+    /// [`recv`] and [`recv_from`] will only receive data from that address.
     ///
     /// [`send`]: #method.send
-    /// [`send_to`]: #method.send_to
+    /// [`recv`]: #method.recv
+    /// [`recv_from`]: #method.recv_from
     ///
     /// # Examples
     ///
     /// ```no_run
     /// use may::os::unix::net::UnixDatagram;
-    /// use std::time::Duration;
     ///
     /// let sock = UnixDatagram::unbound().unwrap();
-    /// sock.set_write_timeout(Some(Duration::new(1, 0)))
-    ///     .expect("set_write_timeout function failed");
+    /// match sock.connect("/path/to/the/socket") {
+    ///     Ok(sock) => sock,
+    ///     Err(e) => {
+    ///         println!("Couldn't connect: {:?}", e);
+    ///         return
+    ///     }
+    /// };
+    /// ```
+    pub fn connect<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        // for UnixDatagram connect it's a nonblocking operation
+        // so we just use the system call
+        self.0.inner().connect(path)
+    }
+
+    /// Creates a new independently owned handle to the underlying socket.
+    ///
+    /// The returned `UnixDatagram` is a reference to the same socket that this
+    /// object references. Both handles can be used to accept incoming
+    /// connections and options set on one side will affect the other.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use may::os::unix::net::UnixDatagram;
+    ///
+    /// let sock = UnixDatagram::bind("/path/to/the/socket").unwrap();
+    ///
+    /// let sock_copy = sock.try_clone().expect("try_clone failed");
+    /// ```
+    pub fn try_clone(&self) -> io::Result<UnixDatagram> {
+        let datagram = self.0.inner().try_clone()?;
+        Ok(UnixDatagram(CoIo::new(datagram)?))
+    }
+
+    /// Returns the address of this socket.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use may::os::unix::net::UnixDatagram;
+    ///
+    /// let sock = UnixDatagram::bind("/path/to/the/socket").unwrap();
+    ///
+    /// let addr = sock.local_addr().expect("Couldn't get local address");
     /// ```
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.0.inner().local_addr()
+    }
+
+    /// Returns the address of this socket's peer.
+    ///
+    /// The [`connect`] method will connect the socket to a peer.
+    ///
+    /// [`connect`]: #method.connect
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use may::os::unix::net::UnixDatagram;
+    ///
+    /// let sock = UnixDatagram::unbound().unwrap();
+    /// sock.connect("/path/to/the/socket").unwrap();
+    ///
+    /// let addr = sock.peer_addr().expect("Couldn't get peer address");
+    /// ```
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.0.inner().peer_addr()
+    }
+
+    /// Receives data from the socket.
+    ///
+    /// On success, returns the number of bytes read and the address from
+    /// whence the data came.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use may::os::unix::net::UnixDatagram;
+    ///
+    /// let sock = UnixDatagram::unbound().unwrap();
+    /// let mut buf = vec![0; 10];
+    /// match sock.recv_from(buf.as_mut_slice()) {
+    ///     Ok((size, sender)) => println!("received {} bytes from {:?}", size, sender),
+    ///     Err(e) => println!("recv_from function failed: {:?}", e),
+    /// }
+    /// ```
+    pub fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        if !self.0.ctx_check()? {
+            // this can't be nonblocking!!
+            return self.0.inner().recv_from(buf);
+        }
+
+        self.0.io_reset();
+        // this is an earlier return try for nonblocking read
+        match self.0.inner().recv_from(buf) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            ret => return ret,
+        }
+
+        let reader = net_impl::UnixRecvFrom::new(self, buf);
+        yield_with(&reader);
+        reader.done()
+    }
+
+    /// Receives data from the socket.
+    ///
+    /// On success, returns the number of bytes read.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use may::os::unix::net::UnixDatagram;
+    ///
+    /// let sock = UnixDatagram::bind("/path/to/the/socket").unwrap();
+    /// let mut buf = vec![0; 10];
+    /// sock.recv(buf.as_mut_slice()).expect("recv function failed");
+    /// ```
+    pub fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.0.ctx_check()? {
+            // this can't be nonblocking!!
+            return self.0.inner().recv(buf);
+        }
+
+        self.0.io_reset();
+        // this is an earlier return try for nonblocking read
+        match self.0.inner().recv(buf) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            ret => return ret,
+        }
+
+        let reader = net_impl::SocketRead::new(&self.0, buf, self.read_timeout().unwrap());
+        yield_with(&reader);
+        reader.done()
+    }
+
+    /// Sends data on the socket to the specified address.
+    ///
+    /// On success, returns the number of bytes written.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use may::os::unix::net::UnixDatagram;
+    ///
+    /// let sock = UnixDatagram::unbound().unwrap();
+    /// sock.send_to(b"omelette au fromage", "/some/sock").expect("send_to function failed");
+    /// ```
+    pub fn send_to<P: AsRef<Path>>(&self, buf: &[u8], path: P) -> io::Result<usize> {
+        if !self.0.ctx_check()? {
+            // this can't be nonblocking!!
+            return self.0.inner().send_to(buf, path);
+        }
+
+        self.0.io_reset();
+        // this is an earlier return try for nonblocking read
+        match self.0.inner().send_to(buf, path.as_ref()) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            ret => return ret,
+        }
+
+        let writer = net_impl::UnixSendTo::new(self, buf, path.as_ref())?;
+        yield_with(&writer);
+        writer.done()
+    }
+
+    /// Sends data on the socket to the socket's peer.
+    ///
+    /// The peer address may be set by the `connect` method, and this method
+    /// will return an error if the socket has not already been connected.
+    ///
+    /// On success, returns the number of bytes written.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use may::os::unix::net::UnixDatagram;
+    ///
+    /// let sock = UnixDatagram::unbound().unwrap();
+    /// sock.connect("/some/sock").expect("Couldn't connect");
+    /// sock.send(b"omelette au fromage").expect("send_to function failed");
+    /// ```
+    pub fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        if !self.0.ctx_check()? {
+            // this can't be nonblocking!!
+            return self.0.inner().send(buf);
+        }
+
+        self.0.io_reset();
+        // this is an earlier return try for nonblocking write
+        match self.0.inner().send(buf) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            ret => return ret,
+        }
+
+        let writer = net_impl::SocketWrite::new(&self.0, buf, self.0.write_timeout().unwrap());
+        yield_with(&writer);
+        writer.done()
+    }
+
+    /// Sets the read timeout for the socket.
+    ///
+    /// If the provided value is `None`, then [`recv`] and [`recv_from`] calls will
+    /// block indefinitely. It is an error to pass the zero `Duration` to this
+    /// method.
+    ///
+    /// [`recv`]: #method.recv
+    /// [`recv_from`]: #method.recv_from
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use may::os::unix::net::UnixDatagram;
+    /// use std::time::Duration;
+    ///
+    /// let sock = UnixDatagram::unbound().unwrap();
+    /// sock.set_read_timeout(Some(Duration::new(1, 0))).expect("set_read_timeout function failed");
+    /// ```
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.0.inner().set_read_timeout(timeout)?;
+        self.0.set_read_timeout(timeout)
+    }
+
+    /// Sets the write timeout for the socket.
+    ///
+    /// If the provided value is `None`, then [`send`] and [`send_to`] calls will
+    /// block indefinitely. It is an error to pass the zero `Duration` to this
+    /// method.
+    ///
+    /// [`send`]: #method.send
+    /// [`send_to`]: #method.send_to
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use may::os::unix::net::UnixDatagram;
+    /// use std::time::Duration;
+    ///
+    /// let sock = UnixDatagram::unbound().unwrap();
+    /// sock.set_write_timeout(Some(Duration::new(1, 0)))
+    ///     .expect("set_write_timeout function failed");
+    /// ```
+    pub fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.0.inner().set_write_timeout(timeout)?;
+        self.0.set_write_timeout(timeout)
+    }
+
+    /// Returns the read timeout of this socket.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use may::os::unix::net::UnixDatagram;
+    /// use std::time::Duration;
+    ///
+    /// let sock = UnixDatagram::unbound().unwrap();
+    /// sock.set_read_timeout(Some(Duration::new(1, 0))).expect("set_read_timeout function failed");
+    /// assert_eq!(sock.read_timeout().unwrap(), Some(Duration::new(1, 0)));
+    /// ```
+    pub fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        self.0.read_timeout()
+    }
+
+    /// Returns the write timeout of this socket.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use may::os::unix::net::UnixDatagram;
+    /// use std::time::Duration;
+    ///
+    /// let sock = UnixDatagram::unbound().unwrap();
+    /// sock.set_write_timeout(Some(Duration::new(1, 0)))
+    ///     .expect("set_write_timeout function failed");
+    /// assert_eq!(sock.write_timeout().unwrap(), Some(Duration::new(1, 0)));
+    /// ```
+    pub fn write_timeout(&self) -> io::Result<Option<Duration>> {
+        self.0.write_timeout()
+    }
+
+    /// Moves the socket into or out of nonblocking mode.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use may::os::unix::net::UnixDatagram;
+    ///
+    /// let sock = UnixDatagram::unbound().unwrap();
+    /// sock.set_nonblocking(true).expect("set_nonblocking function failed");
+    /// ```
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        self.0.set_nonblocking(nonblocking)
+    }
+
+    /// Returns the value of the `SO_ERROR` option.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use may::os::unix::net::UnixDatagram;
+    ///
+    /// let sock = UnixDatagram::unbound().unwrap();
+    /// if let Ok(Some(err)) = sock.take_error() {
+    ///     println!("Got error: {:?}", err);
+    /// }
+    /// ```
+    pub fn take_error(&self) -> io::Result<Option<io::Error>> {
+        self.0.inner().take_error()
+    }
+
+    /// Sets `SO_RCVBUF`.
+    ///
+    /// Linux limits the queue of a unix datagram socket by the number of the
+    /// datagrams instead, see `net.unix.max_dgram_qlen`.
+    pub fn set_recv_buffer_size(&self, size: usize) -> io::Result<()> {
+        with_socket(self, |s| s.set_recv_buffer_size(size))
+    }
+
+    /// Returns `SO_RCVBUF`.
+    pub fn recv_buffer_size(&self) -> io::Result<usize> {
+        with_socket(self, |s| s.recv_buffer_size())
+    }
+
+    /// Sets `SO_SNDBUF`, it's the upper limit of the size of a datagram.
+    pub fn set_send_buffer_size(&self, size: usize) -> io::Result<()> {
+        with_socket(self, |s| s.set_send_buffer_size(size))
+    }
+
+    /// Returns `SO_SNDBUF`, the largest datagram is a bit smaller than it.
+    pub fn send_buffer_size(&self) -> io::Result<usize> {
+        with_socket(self, |s| s.send_buffer_size())
+    }
+
+    /// Shut down the read, write, or both halves of this connection.
+    ///
+    /// This function will cause all pending and future I/O calls on the
+    /// specified portions to immediately return with an appropriate value
+    /// (see the documentation of libstd `Shutdown`).
+    ///
+    /// ```no_run
+    /// use may::os::unix::net::UnixDatagram;
+    /// use std::net::Shutdown;
+    ///
+    /// let sock = UnixDatagram::unbound().unwrap();
+    /// sock.shutdown(Shutdown::Both).expect("shutdown function failed");
+    /// ```
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.0.inner().shutdown(how)
+    }
+}
+
+impl AsRawFd for UnixDatagram {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+impl FromRawFd for UnixDatagram {
+    unsafe fn from_raw_fd(fd: RawFd) -> UnixDatagram {
+        let datagram = FromRawFd::from_raw_fd(fd);
+        UnixDatagram(CoIo::new(datagram).expect("can't convert to UnixDatagram"))
+    }
+}
+
+impl IntoRawFd for UnixDatagram {
+    fn into_raw_fd(self) -> RawFd {
+        self.0.into_raw_fd()
+    }
+}
+
+// getsockname/getpeername don't care about the socket type, borrow the fd
+// as a std stream to get the std `SocketAddr`
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn seqpacket_addr(fd: RawFd, peer: bool) -> io::Result<SocketAddr> {
+    let s = unsafe { net::UnixStream::from_raw_fd(fd) };
+    let ret = if peer { s.peer_addr() } else { s.local_addr() };
+    // the fd is still owned by the seqpacket socket
+    let _ = s.into_raw_fd();
+    ret
+}
+
+/// A Unix sequenced-packet socket server, listening for connections.
+///
+/// The accepted `UnixSeqpacket`s preserve the boundaries of the sent records.
+/// This is only available on Linux.
+///
+/// # Examples
+///
+/// ```no_run
+/// use may::os::unix::net::UnixSeqpacketListener;
+///
+/// let listener = UnixSeqpacketListener::bind("/path/to/the/socket").unwrap();
+/// for conn in listener.incoming() {
+///     match conn {
+///         Ok(conn) => {
+///             let mut buf = [0; 64];
+///             let n = conn.recv(&mut buf).unwrap();
+///             conn.send(&buf[..n]).unwrap();
+///         }
+///         Err(err) => {
+///             println!("accept failed: {}", err);
+///             break;
+///         }
+///     }
+/// }
+/// ```
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub struct UnixSeqpacketListener(pub(crate) CoIo<Socket>);
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl fmt::Debug for UnixSeqpacketListener {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let mut builder = fmt.debug_struct("UnixSeqpacketListener");
+        builder.field("fd", &self.as_raw_fd());
+        if let Ok(addr) = self.local_addr() {
+            builder.field("local", &addr);
+        }
+        builder.finish()
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl UnixSeqpacketListener {
+    /// Creates a new `UnixSeqpacketListener` bound to the specified socket.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use may::os::unix::net::UnixSeqpacketListener;
+    ///
+    /// let listener = match UnixSeqpacketListener::bind("/path/to/the/socket") {
+    ///     Ok(sock) => sock,
+    ///     Err(e) => {
+    ///         println!("Couldn't bind: {:?}", e);
+    ///         return
+    ///     }
+    /// };
+    /// ```
+    pub fn bind<P: AsRef<Path>>(path: P) -> io::Result<UnixSeqpacketListener> {
+        let socket = Socket::new(Domain::unix(), Type::seqpacket(), None)?;
+        socket.bind(&SockAddr::unix(path)?)?;
+        socket.listen(128)?;
+        Ok(UnixSeqpacketListener(CoIo::new(socket)?))
+    }
+
+    /// Accepts a new incoming connection to this listener.
+    ///
+    /// This function will block the calling coroutine until a new connection
+    /// is established.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use may::os::unix::net::UnixSeqpacketListener;
+    ///
+    /// let listener = UnixSeqpacketListener::bind("/path/to/the/socket").unwrap();
+    ///
+    /// match listener.accept() {
+    ///     Ok(socket) => println!("Got a client: {:?}", socket),
+    ///     Err(e) => println!("accept function failed: {:?}", e),
+    /// }
+    /// ```
+    pub fn accept(&self) -> io::Result<UnixSeqpacket> {
+        if !self.0.ctx_check()? {
+            // this can't be nonblocking!!
+            let (s, _) = self.0.inner().accept()?;
+            return Ok(UnixSeqpacket(CoIo::new(s)?));
+        }
+
+        self.0.io_reset();
+        match self.0.inner().accept() {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            Err(e) => return Err(e),
+            Ok((s, _)) => return Ok(UnixSeqpacket(CoIo::new(s)?)),
+        }
+
+        let a = net_impl::UnixSeqpacketAccept::new(self)?;
+        yield_with(&a);
+        let s = a.done()?;
+        Ok(UnixSeqpacket(CoIo::new(s)?))
+    }
+
+    /// Returns the local socket address of this listener.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        seqpacket_addr(self.as_raw_fd(), false)
+    }
+
+    /// Moves the socket into or out of nonblocking mode.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        self.0.set_nonblocking(nonblocking)
+    }
+
+    /// Returns the value of the `SO_ERROR` option.
+    pub fn take_error(&self) -> io::Result<Option<io::Error>> {
+        self.0.inner().take_error()
+    }
+
+    /// Sets `SO_RCVBUF` of the listening socket.
+    pub fn set_recv_buffer_size(&self, size: usize) -> io::Result<()> {
+        with_socket(self, |s| s.set_recv_buffer_size(size))
+    }
+
+    /// Returns `SO_RCVBUF` of the listening socket.
+    pub fn recv_buffer_size(&self) -> io::Result<usize> {
+        with_socket(self, |s| s.recv_buffer_size())
+    }
+
+    /// Sets `SO_SNDBUF` of the listening socket.
+    pub fn set_send_buffer_size(&self, size: usize) -> io::Result<()> {
+        with_socket(self, |s| s.set_send_buffer_size(size))
+    }
+
+    /// Returns `SO_SNDBUF` of the listening socket.
+    pub fn send_buffer_size(&self) -> io::Result<usize> {
+        with_socket(self, |s| s.send_buffer_size())
+    }
+
+    /// Returns an iterator over incoming connections.
+    ///
+    /// The iterator will never return `None`.
+    pub fn incoming<'a>(&'a self) -> SeqpacketIncoming<'a> {
+        SeqpacketIncoming { listener: self }
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl AsRawFd for UnixSeqpacketListener {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl FromRawFd for UnixSeqpacketListener {
+    unsafe fn from_raw_fd(fd: RawFd) -> UnixSeqpacketListener {
+        let socket = Socket::from_raw_fd(fd);
+        UnixSeqpacketListener(CoIo::new(socket).expect("can't convert to UnixSeqpacketListener"))
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl IntoRawFd for UnixSeqpacketListener {
+    fn into_raw_fd(self) -> RawFd {
+        self.0.into_raw_fd()
+    }
+}
+
+/// An iterator over incoming connections to a `UnixSeqpacketListener`.
+///
+/// It will never return `None`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[derive(Debug)]
+pub struct SeqpacketIncoming<'a> {
+    listener: &'a UnixSeqpacketListener,
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl<'a> Iterator for SeqpacketIncoming<'a> {
+    type Item = io::Result<UnixSeqpacket>;
+
+    fn next(&mut self) -> Option<io::Result<UnixSeqpacket>> {
+        Some(self.listener.accept())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::max_value(), None)
+    }
+}
+
+/// A Unix sequenced-packet socket.
+///
+/// Each `send` is delivered as one record and `recv` returns exactly one
+/// record. This is only available on Linux.
+///
+/// # Examples
+///
+/// ```no_run
+/// use may::os::unix::net::UnixSeqpacket;
+///
+/// let socket = UnixSeqpacket::connect("/path/to/the/socket").unwrap();
+/// socket.send(b"hello").unwrap();
+/// let mut buf = [0; 64];
+/// let n = socket.recv(&mut buf).unwrap();
+/// println!("got {:?}", &buf[..n]);
+/// ```
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub struct UnixSeqpacket(pub(crate) CoIo<Socket>);
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl fmt::Debug for UnixSeqpacket {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let mut builder = fmt.debug_struct("UnixSeqpacket");
+        builder.field("fd", &self.as_raw_fd());
+        if let Ok(addr) = self.local_addr() {
+            builder.field("local", &addr);
+        }
+        if let Ok(addr) = self.peer_addr() {
+            builder.field("peer", &addr);
+        }
+        builder.finish()
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl UnixSeqpacket {
+    /// Connects to the socket named by `path`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use may::os::unix::net::UnixSeqpacket;
+    ///
+    /// let socket = match UnixSeqpacket::connect("/tmp/sock") {
+    ///     Ok(sock) => sock,
+    ///     Err(e) => {
+    ///         println!("Couldn't connect: {:?}", e);
+    ///         return
+    ///     }
+    /// };
+    /// ```
+    pub fn connect<P: AsRef<Path>>(path: P) -> io::Result<UnixSeqpacket> {
+        let addr = SockAddr::unix(path)?;
+        let timeout = config().get_connect_timeout();
+
+        if !is_coroutine() {
+            let socket = Socket::new(Domain::unix(), Type::seqpacket(), None)?;
+            socket.connect(&addr)?;
+            return Ok(UnixSeqpacket(CoIo::new(socket)?));
+        }
+
+        let ty = Type::seqpacket();
+        let mut c = net_impl::UnixStreamConnect::with_type(addr, ty, Some(timeout))?;
+
+        if !c.is_connected()? {
+            yield_with(&c);
+        }
+        let (socket, io) = c.done_socket()?;
+        Ok(UnixSeqpacket(CoIo::from_raw(socket, io)))
+    }
+
+    /// Creates an unnamed pair of connected sockets.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use may::os::unix::net::UnixSeqpacket;
+    ///
+    /// let (sock1, sock2) = UnixSeqpacket::pair().unwrap();
+    /// ```
+    pub fn pair() -> io::Result<(UnixSeqpacket, UnixSeqpacket)> {
+        let (i1, i2) = Socket::pair(Domain::unix(), Type::seqpacket(), None)?;
+        let i1 = UnixSeqpacket(CoIo::new(i1)?);
+        let i2 = UnixSeqpacket(CoIo::new(i2)?);
+        Ok((i1, i2))
+    }
+
+    /// Sends one record to the peer.
+    ///
+    /// On success, returns the number of bytes written.
+    pub fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        if !self.0.ctx_check()? {
+            // this can't be nonblocking!!
+            return self.0.inner().send(buf);
+        }
+
+        self.0.io_reset();
+        // this is an earlier return try for nonblocking write
+        match self.0.inner().send(buf) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            ret => return ret,
+        }
+
+        let writer = net_impl::SocketWrite::new(&self.0, buf, self.write_timeout()?);
+        yield_with(&writer);
+        writer.done()
+    }
+
+    /// Receives one record from the peer.
+    ///
+    /// On success, returns the number of bytes read, `0` means the peer has
+    /// closed the connection. If the record doesn't fit in `buf` the rest of
+    /// it is discarded and an error of kind `InvalidData` is returned.
+    pub fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.0.ctx_check()? {
+            // this can't be nonblocking!!
+            return net_impl::recv_packet(self.as_raw_fd(), buf);
+        }
+
+        self.0.io_reset();
+        // this is an earlier return try for nonblocking read
+        match net_impl::recv_packet(self.as_raw_fd(), buf) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            ret => return ret,
+        }
+
+        let reader = net_impl::UnixSeqpacketRecv::new(&self.0, buf, self.read_timeout()?);
+        yield_with(&reader);
+        reader.done()
+    }
+
+    /// Returns the socket address of the local half of this connection.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        seqpacket_addr(self.as_raw_fd(), false)
+    }
+
+    /// Returns the socket address of the remote half of this connection.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        seqpacket_addr(self.as_raw_fd(), true)
+    }
+
+    /// Sets the read timeout for the socket.
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.0.inner().set_read_timeout(timeout)?;
+        self.0.set_read_timeout(timeout)
+    }
+
+    /// Sets the write timeout for the socket.
     pub fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
         self.0.inner().set_write_timeout(timeout)?;
         self.0.set_write_timeout(timeout)
     }
 
     /// Returns the read timeout of this socket.
-    ///
-    /// # Examples
-    ///
-    /// ```no_run
-    /// use may::os::unix::net::UnixDatagram;
-    /// use std::time::Duration;
-    ///
-    /// let sock = UnixDatagram::unbound().unwrap();
-    /// sock.set_read_timeout(Some(Duration::new(1, 0))).expect("set_read_timeout function failed");
-    /// assert_eq!(sock.read_timeout().unwrap(), Some(Duration::new(1, 0)));
-    /// ```
     pub fn read_timeout(&self) -> io::Result<Option<Duration>> {
         self.0.read_timeout()
     }
 
     /// Returns the write timeout of this socket.
-    ///
-    /// # Examples
-    ///
-    /// ```no_run
-    /// use may::os::unix::net::UnixDatagram;
-    /// use std::time::Duration;
-    ///
-    /// let sock = UnixDatagram::unbound().unwrap();
-    /// sock.set_write_timeout(Some(Duration::new(1, 0)))
-    ///     .expect("set_write_timeout function failed");
-    /// assert_eq!(sock.write_timeout().unwrap(), Some(Duration::new(1, 0)));
-    /// ```
     pub fn write_timeout(&self) -> io::Result<Option<Duration>> {
         self.0.write_timeout()
     }
 
     /// Moves the socket into or out of nonblocking mode.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use may::os::unix::net::UnixDatagram;
-    ///
-    /// let sock = UnixDatagram::unbound().unwrap();
-    /// sock.set_nonblocking(true).expect("set_nonblocking function failed");
-    /// ```
     pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
         self.0.set_nonblocking(nonblocking)
     }
 
     /// Returns the value of the `SO_ERROR` option.
-    ///
-    /// # Examples
-    ///
-    /// ```no_run
-    /// use may::os::unix::net::UnixDatagram;
-    ///
-    /// let sock = UnixDatagram::unbound().unwrap();
-    /// if let Ok(Some(err)) = sock.take_error() {
-    ///     println!("Got error: {:?}", err);
-    /// }
-    /// ```
     pub fn take_error(&self) -> io::Result<Option<io::Error>> {
         self.0.inner().take_error()
     }
 
-    /// Shut down the read, write, or both halves of this connection.
-    ///
-    /// This function will cause all pending and future I/O calls on the
-    /// specified portions to immediately return with an appropriate value
-    /// (see the documentation of libstd `Shutdown`).
-    ///
-    /// ```no_run
-    /// use may::os::unix::net::UnixDatagram;
-    /// use std::net::Shutdown;
-    ///
-    /// let sock = UnixDatagram::unbound().unwrap();
-    /// sock.shutdown(Shutdown::Both).expect("shutdown function failed");
-    /// ```
+    /// Sets `SO_RCVBUF`, the packets are flow controlled by the send buffer
+    /// of the peer instead.
+    pub fn set_recv_buffer_size(&self, size: usize) -> io::Result<()> {
+        with_socket(self, |s| s.set_recv_buffer_size(size))
+    }
+
+    /// Returns `SO_RCVBUF`.
+    pub fn recv_buffer_size(&self) -> io::Result<usize> {
+        with_socket(self, |s| s.recv_buffer_size())
+    }
+
+    /// Sets `SO_SNDBUF`, it limits the size of a packet and the packets
+    /// that are not received by the peer yet.
+    pub fn set_send_buffer_size(&self, size: usize) -> io::Result<()> {
+        with_socket(self, |s| s.set_send_buffer_size(size))
+    }
+
+    /// Returns `SO_SNDBUF`.
+    pub fn send_buffer_size(&self) -> io::Result<usize> {
+        with_socket(self, |s| s.send_buffer_size())
+    }
+
+    /// Shuts down the read, write, or both halves of this connection.
     pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
         self.0.inner().shutdown(how)
     }
 }
 
-impl AsRawFd for UnixDatagram {
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl AsRawFd for UnixSeqpacket {
     fn as_raw_fd(&self) -> RawFd {
         self.0.as_raw_fd()
     }
 }
 
-impl FromRawFd for UnixDatagram {
-    unsafe fn from_raw_fd(fd: RawFd) -> UnixDatagram {
-        let datagram = FromRawFd::from_raw_fd(fd);
-        UnixDatagram(CoIo::new(datagram).expect("can't convert to UnixDatagram"))
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl FromRawFd for UnixSeqpacket {
+    unsafe fn from_raw_fd(fd: RawFd) -> UnixSeqpacket {
+        let socket = Socket::from_raw_fd(fd);
+        UnixSeqpacket(CoIo::new(socket).expect("can't convert to UnixSeqpacket"))
     }
 }
 
-impl IntoRawFd for UnixDatagram {
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl IntoRawFd for UnixSeqpacket {
     fn into_raw_fd(self) -> RawFd {
         self.0.into_raw_fd()
     }
@@ -1102,6 +2232,64 @@ mod test {
         thread.join().unwrap();
     }
 
+    #[test]
+    fn connect_timeout() {
+        let dir = tmpdir();
+        let socket_path = dir.path().join("sock");
+        let dur = Duration::from_millis(200);
+
+        let listener = or_panic!(UnixListener::bind(&socket_path));
+        let path = socket_path.clone();
+        let j = go!(move || {
+            let stream = or_panic!(UnixStream::connect_timeout(&path, dur));
+            assert_eq!(Some(&*path), stream.peer_addr().unwrap().as_pathname());
+        });
+        or_panic!(listener.accept());
+        j.join().unwrap();
+
+        // thread context
+        let stream = or_panic!(UnixStream::connect_timeout(&socket_path, dur));
+        assert_eq!(
+            Some(&*socket_path),
+            stream.peer_addr().unwrap().as_pathname()
+        );
+
+        match UnixStream::connect_timeout(&socket_path, Duration::from_secs(0)) {
+            Err(ref e) if e.kind() == io::ErrorKind::InvalidInput => {}
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(_) => panic!("unexpected success"),
+        }
+    }
+
+    #[test]
+    fn connect_refused() {
+        let dir = tmpdir();
+        let socket_path = dir.path().join("sock");
+
+        // bound but not listening
+        let socket = or_panic!(Socket::new(Domain::unix(), Type::stream(), None));
+        or_panic!(socket.bind(&or_panic!(SockAddr::unix(&socket_path))));
+
+        let path = socket_path.clone();
+        let j = go!(move || {
+            let start = ::std::time::Instant::now();
+            match UnixStream::connect(&path) {
+                Err(ref e) if e.kind() == io::ErrorKind::ConnectionRefused => {}
+                Err(e) => panic!("unexpected error {}", e),
+                Ok(_) => panic!("unexpected success"),
+            }
+            assert!(start.elapsed() < Duration::from_secs(1));
+        });
+        j.join().unwrap();
+
+        // thread context
+        match UnixStream::connect(&socket_path) {
+            Err(ref e) if e.kind() == io::ErrorKind::ConnectionRefused => {}
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(_) => panic!("unexpected success"),
+        }
+    }
+
     #[test]
     fn pair() {
         let msg1 = b"hello";
@@ -1125,6 +2313,197 @@ mod test {
         thread.join().unwrap();
     }
 
+    #[test]
+    fn pair_ping_pong() {
+        let (mut s1, mut s2) = or_panic!(UnixStream::pair());
+
+        // each side blocks on the read until the other writes
+        let ping = go!(move || for i in 0..100u8 {
+            or_panic!(s1.write_all(&[i]));
+            let mut buf = [0; 1];
+            or_panic!(s1.read_exact(&mut buf));
+            assert_eq!(buf[0], i + 1);
+        });
+
+        let pong = go!(move || for _ in 0..100 {
+            let mut buf = [0; 1];
+            or_panic!(s2.read_exact(&mut buf));
+            or_panic!(s2.write_all(&[buf[0] + 1]));
+        });
+
+        ping.join().unwrap();
+        pong.join().unwrap();
+    }
+
+    #[test]
+    fn vectored() {
+        use std::io::{IoSlice, IoSliceMut};
+
+        let (mut s1, mut s2) = or_panic!(UnixStream::pair());
+        let thread = go!(move || {
+            let mut head = [0; 3];
+            let mut body = [0; 5];
+            // nothing is written yet, the read would yield
+            let n = {
+                let mut bufs = [IoSliceMut::new(&mut head), IoSliceMut::new(&mut body)];
+                or_panic!(s1.read_vectored(&mut bufs))
+            };
+            assert_eq!(n, 8);
+            assert_eq!(&head, b"hdr");
+            assert_eq!(&body, b"hello");
+        });
+
+        ::std::thread::sleep(Duration::from_millis(10));
+        let bufs = [IoSlice::new(b"hdr"), IoSlice::new(b"hello")];
+        assert_eq!(or_panic!(s2.write_vectored(&bufs)), 8);
+        thread.join().unwrap();
+    }
+
+    #[test]
+    fn pass_fds() {
+        use std::fs::File;
+
+        let (parent, child) = or_panic!(UnixStream::pair());
+        let (rd, wr) = or_panic!(::nix::unistd::pipe());
+
+        let j = go!(move || {
+            // nothing is sent yet, the coroutine would be suspended
+            let mut buf = [0; 4];
+            let mut fds = [0; 2];
+            let (n, cnt) = or_panic!(child.recv_with_fds(&mut buf, &mut fds));
+            assert_eq!(&buf[..n], b"pipe");
+            assert_eq!(cnt, 1);
+
+            let mut reader = unsafe { File::from_raw_fd(fds[0]) };
+            let mut data = String::new();
+            or_panic!(reader.read_to_string(&mut data));
+            assert_eq!(data, "through the pipe");
+
+            // the fds buffer is too small
+            let mut buf = [0; 4];
+            let mut fds = [0; 1];
+            let err = child.recv_with_fds(&mut buf, &mut fds).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::Other);
+        });
+
+        ::std::thread::sleep(Duration::from_millis(10));
+        or_panic!(parent.send_with_fds(b"pipe", &[rd]));
+        // the sender still owns its copy of the fd
+        unsafe { ::libc::close(rd) };
+        let mut writer = unsafe { File::from_raw_fd(wr) };
+        or_panic!(writer.write_all(b"through the pipe"));
+        drop(writer);
+
+        let f1 = or_panic!(File::open("/dev/null"));
+        let f2 = or_panic!(File::open("/dev/null"));
+        or_panic!(parent.send_with_fds(b"two!", &[f1.as_raw_fd(), f2.as_raw_fd()]));
+        j.join().unwrap();
+    }
+
+    #[test]
+    fn peer_cred() {
+        let dir = tmpdir();
+        let socket_path = dir.path().join("sock");
+
+        let listener = or_panic!(UnixListener::bind(&socket_path));
+        let j = go!(move || {
+            let stream = or_panic!(UnixStream::connect(&socket_path));
+            let cred = or_panic!(stream.peer_cred());
+            assert_eq!(cred.uid, unsafe { libc::geteuid() });
+        });
+
+        let stream = or_panic!(listener.accept()).0;
+        let cred = or_panic!(stream.peer_cred());
+        assert_eq!(cred.uid, unsafe { libc::geteuid() });
+        assert_eq!(cred.gid, unsafe { libc::getegid() });
+        if cfg!(any(target_os = "linux", target_os = "android")) {
+            assert_eq!(cred.pid, Some(unsafe { libc::getpid() }));
+        }
+        j.join().unwrap();
+    }
+
+    #[test]
+    fn peek() {
+        let (mut s1, mut s2) = or_panic!(UnixStream::pair());
+        let thread = go!(move || {
+            let mut buf = [0; 5];
+            // nothing is written yet, the peek would yield
+            let n = or_panic!(s1.peek(&mut buf));
+            assert_eq!(&buf[..n], &b"hello"[..n]);
+
+            let mut buf = [0; 5];
+            or_panic!(s1.read_exact(&mut buf));
+            assert_eq!(&buf, b"hello");
+        });
+
+        ::std::thread::sleep(Duration::from_millis(10));
+        or_panic!(s2.write_all(b"hello"));
+        thread.join().unwrap();
+    }
+
+    #[test]
+    fn shutdown() {
+        let (mut s1, mut s2) = or_panic!(UnixStream::pair());
+        let s3 = or_panic!(s1.try_clone());
+
+        let reader = go!(move || {
+            // a parked reader is waked up by the read shutdown
+            let mut buf = [0; 4];
+            assert_eq!(or_panic!(s1.read(&mut buf)), 0);
+        });
+        ::std::thread::sleep(Duration::from_millis(10));
+        or_panic!(s3.shutdown(Shutdown::Read));
+        reader.join().unwrap();
+
+        or_panic!(s2.shutdown(Shutdown::Write));
+        let err = s2.write(b"more").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+        let mut buf = vec![];
+        // the dup fd must be registered even the previous one is just closed
+        or_panic!(or_panic!(s3.try_clone()).read_to_end(&mut buf));
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn std_round_trip() {
+        let (s1, mut s2) = or_panic!(net::UnixStream::pair());
+        let is_nonblocking = |fd: RawFd| unsafe {
+            libc::fcntl(fd, libc::F_GETFL) & libc::O_NONBLOCK != 0
+        };
+
+        let reader = go!(move || {
+            let mut s = or_panic!(UnixStream::from_std(s1));
+            assert!(is_nonblocking(s.as_raw_fd()));
+            // nothing is written yet, this would yield
+            let mut buf = [0; 5];
+            or_panic!(s.read_exact(&mut buf));
+            assert_eq!(&buf, b"hello");
+
+            // the rest of the data is still in flight
+            let mut s = or_panic!(s.into_std());
+            assert!(!is_nonblocking(s.as_raw_fd()));
+            let mut buf = [0; 6];
+            or_panic!(s.read_exact(&mut buf));
+            assert_eq!(&buf, b" world");
+            or_panic!(s.write_all(b"ping"));
+
+            // register the same fd again
+            let mut s = or_panic!(UnixStream::from_std(s));
+            let mut buf = vec![];
+            or_panic!(s.read_to_end(&mut buf));
+            assert_eq!(buf, b"bye");
+        });
+
+        ::std::thread::sleep(Duration::from_millis(10));
+        or_panic!(s2.write_all(b"hello world"));
+        let mut buf = [0; 4];
+        or_panic!(s2.read_exact(&mut buf));
+        assert_eq!(&buf, b"ping");
+        or_panic!(s2.write_all(b"bye"));
+        drop(s2);
+        reader.join().unwrap();
+    }
+
     #[test]
     fn try_clone() {
         let dir = tmpdir();
@@ -1336,6 +2715,81 @@ mod test {
         assert_eq!(msg, &buf[..]);
     }
 
+    #[test]
+    fn datagram_echo() {
+        let dir = tmpdir();
+        let server_path = dir.path().join("server");
+        let server = or_panic!(UnixDatagram::bind(&server_path));
+
+        let clients = 10;
+        let server = go!(move || for _ in 0..clients * 2 {
+            let mut buf = [0; 32];
+            let (n, addr) = or_panic!(server.recv_from(&mut buf));
+            let peer = addr.as_pathname().expect("client must be bound");
+            or_panic!(server.send_to(&buf[..n], peer));
+        });
+
+        let handles = (0..clients)
+            .map(|i| {
+                let path = dir.path().join(format!("client{}", i));
+                let server_path = server_path.clone();
+                go!(move || {
+                    let sock = or_panic!(UnixDatagram::bind(&path));
+                    or_panic!(sock.connect(&server_path));
+                    for j in 0..2 {
+                        let msg = format!("client{}-{}", i, j);
+                        or_panic!(sock.send(msg.as_bytes()));
+                        let mut buf = [0; 32];
+                        let n = or_panic!(sock.recv(&mut buf));
+                        assert_eq!(msg.as_bytes(), &buf[..n]);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for h in handles {
+            h.join().unwrap();
+        }
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn datagram_unnamed_peer() {
+        let dir = tmpdir();
+        let server_path = dir.path().join("server");
+        let server = or_panic!(UnixDatagram::bind(&server_path));
+
+        let client = go!(move || {
+            let sock = or_panic!(UnixDatagram::unbound());
+            or_panic!(sock.send_to(b"unbound", &server_path));
+        });
+        let j = go!(move || {
+            let mut buf = [0; 16];
+            let (n, addr) = or_panic!(server.recv_from(&mut buf));
+            assert_eq!(&buf[..n], b"unbound");
+            // the unbound sender has no address to reply to
+            assert!(addr.is_unnamed());
+            assert!(addr.as_pathname().is_none());
+        });
+        client.join().unwrap();
+        j.join().unwrap();
+
+        // the pair sockets are unnamed, but they could still talk back
+        let (s1, s2) = or_panic!(UnixDatagram::pair());
+        let j = go!(move || {
+            let mut buf = [0; 16];
+            let (n, addr) = or_panic!(s2.recv_from(&mut buf));
+            assert!(addr.is_unnamed());
+            or_panic!(s2.send(&buf[..n]));
+        });
+        or_panic!(s1.send(b"pair"));
+        let mut buf = [0; 16];
+        let (n, addr) = or_panic!(s1.recv_from(&mut buf));
+        assert_eq!(&buf[..n], b"pair");
+        assert!(addr.is_unnamed());
+        j.join().unwrap();
+    }
+
     #[test]
     fn datagram_pair() {
         let msg1 = b"hello";
@@ -1359,8 +2813,226 @@ mod test {
         thread.join().unwrap();
     }
 
+    #[test]
+    fn datagram_recv_yield() {
+        let dir = tmpdir();
+        let path1 = dir.path().join("sock1");
+        let path2 = dir.path().join("sock2");
+
+        let sock1 = or_panic!(UnixDatagram::bind(&path1));
+        let sock2 = or_panic!(UnixDatagram::bind(&path2));
+
+        let j = go!(move || {
+            // nothing is sent yet, the coroutine would be suspended
+            let mut buf = [0; 11];
+            let (size, addr) = or_panic!(sock1.recv_from(&mut buf));
+            assert_eq!(size, 11);
+            assert_eq!(addr.as_pathname(), Some(&*path2));
+            assert_eq!(b"hello world", &buf[..]);
+
+            // timeout is honored in coroutine context
+            or_panic!(sock1.set_read_timeout(Some(Duration::from_millis(10))));
+            match sock1.recv(&mut buf) {
+                Err(ref e) if e.kind() == io::ErrorKind::TimedOut => {}
+                Err(e) => panic!("unexpected error {}", e),
+                Ok(_) => panic!("unexpected success"),
+            }
+        });
+
+        let ticker = go!(|| for _ in 0..5 {
+            ::coroutine::yield_now();
+        });
+        // other coroutines are not blocked by the pending recv
+        ticker.join().unwrap();
+
+        ::std::thread::sleep(Duration::from_millis(10));
+        or_panic!(sock2.send_to(b"hello world", &path1));
+        j.join().unwrap();
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn connect_abstract() {
+        let name = format!("may_test_{}", ::std::process::id());
+        let addr = or_panic!(abstract_addr(name.as_bytes()));
+        let socket = or_panic!(Socket::new(Domain::unix(), Type::stream(), None));
+        or_panic!(socket.bind(&addr));
+        or_panic!(socket.listen(128));
+        let listener = UnixListener(or_panic!(CoIo::new(socket.into_unix_listener())));
+
+        let n = name.clone();
+        let j = go!(move || {
+            let mut stream = or_panic!(UnixStream::connect_abstract(n.as_bytes()));
+            or_panic!(stream.write_all(b"hello"));
+        });
+
+        let mut stream = or_panic!(listener.accept()).0;
+        let mut buf = vec![];
+        or_panic!(stream.read_to_end(&mut buf));
+        assert_eq!(&buf[..], b"hello");
+        j.join().unwrap();
+
+        // thread context
+        or_panic!(UnixStream::connect_abstract(name.as_bytes()));
+        or_panic!(listener.accept());
+
+        match UnixStream::connect_abstract(b"may_test_not_exist") {
+            Err(ref e) if e.kind() == io::ErrorKind::ConnectionRefused => {}
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(_) => panic!("unexpected success"),
+        }
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn bind_abstract() {
+        // the abstract names are global, keep them apart from other runs
+        let name = format!("may_test_bind_{}", ::std::process::id());
+
+        let listener = or_panic!(UnixListener::bind_abstract(name.as_bytes()));
+        let addr = or_panic!(listener.local_addr());
+        assert!(addr.as_pathname().is_none());
+        assert!(!addr.is_unnamed());
+
+        let n = name.clone();
+        let j = go!(move || {
+            let stream = or_panic!(UnixStream::connect_abstract(n.as_bytes()));
+            let addr = or_panic!(stream.peer_addr());
+            assert!(addr.as_pathname().is_none());
+            assert!(!addr.is_unnamed());
+        });
+        or_panic!(listener.accept());
+        j.join().unwrap();
+
+        // binding the same name again is refused
+        assert!(UnixListener::bind_abstract(name.as_bytes()).is_err());
+
+        // abstract datagram peers round-trip through recv_from
+        let server_name = format!("may_test_dgram_server_{}", ::std::process::id());
+        let client_name = format!("may_test_dgram_client_{}", ::std::process::id());
+        let server = or_panic!(UnixDatagram::bind_abstract(server_name.as_bytes()));
+        let client = or_panic!(UnixDatagram::bind_abstract(client_name.as_bytes()));
+        or_panic!(client.connect_abstract(server_name.as_bytes()));
+        or_panic!(client.send(b"hello"));
+        let mut buf = [0; 5];
+        let (n, addr) = or_panic!(server.recv_from(&mut buf));
+        assert_eq!(&buf[..n], b"hello");
+        assert!(addr.as_pathname().is_none());
+        assert!(!addr.is_unnamed());
+        assert_eq!(
+            format!("{:?}", addr),
+            format!("{:?}", or_panic!(client.local_addr()))
+        );
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn seqpacket_echo() {
+        let dir = tmpdir();
+        let socket_path = dir.path().join("sock");
+
+        let listener = or_panic!(UnixSeqpacketListener::bind(&socket_path));
+        let server = go!(move || {
+            let conn = or_panic!(listener.accept());
+            let mut buf = [0; 16];
+            loop {
+                let n = or_panic!(conn.recv(&mut buf));
+                if n == 0 {
+                    break;
+                }
+                or_panic!(conn.send(&buf[..n]));
+            }
+        });
+
+        let client = go!(move || {
+            let conn = or_panic!(UnixSeqpacket::connect(&socket_path));
+            assert_eq!(conn.peer_addr().unwrap().as_pathname(), Some(&*socket_path));
+            // send all the records before reading, they must not be merged
+            for i in 0..5u8 {
+                assert_eq!(or_panic!(conn.send(&[i; 8])), 8);
+            }
+            for i in 0..5u8 {
+                let mut buf = [0; 16];
+                assert_eq!(or_panic!(conn.recv(&mut buf)), 8);
+                assert_eq!(&buf[..8], &[i; 8]);
+            }
+            or_panic!(conn.shutdown(Shutdown::Write));
+        });
+
+        client.join().unwrap();
+        server.join().unwrap();
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn seqpacket_truncated() {
+        let (s1, s2) = or_panic!(UnixSeqpacket::pair());
+        let j = go!(move || {
+            // nothing is sent yet, the recv would yield
+            let mut buf = [0; 4];
+            let err = s1.recv(&mut buf).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+            assert_eq!(&buf, b"hell");
+            // the rest of the record is discarded
+            assert_eq!(or_panic!(s1.recv(&mut buf)), 2);
+            assert_eq!(&buf[..2], b"ok");
+        });
+
+        ::std::thread::sleep(Duration::from_millis(10));
+        or_panic!(s2.send(b"hello world"));
+        or_panic!(s2.send(b"ok"));
+        j.join().unwrap();
+    }
+
     #[test]
     fn abstract_namespace_not_allowed() {
         assert!(UnixStream::connect("\0asdf").is_err());
     }
+
+    #[test]
+    fn deadline_on_clones() {
+        use io::DeadlineExceeded;
+
+        let (s1, mut s2) = or_panic!(UnixStream::pair());
+        let s1_clone = or_panic!(s1.try_clone());
+
+        let start = Instant::now();
+        let short = go!(move || {
+            let mut buf = [0; 4];
+            let e = s1.read_exact_deadline(&mut buf, start + Duration::from_millis(50))
+                .unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+            assert!(start.elapsed() < Duration::from_millis(150));
+            DeadlineExceeded::from_io_error(&e).unwrap().transferred()
+        });
+        let long = go!(move || {
+            let mut buf = [0; 4];
+            or_panic!(s1_clone.read_exact_deadline(&mut buf, start + Duration::from_secs(5)));
+            buf
+        });
+
+        // nothing is sent until the short deadline expires
+        assert_eq!(short.join().unwrap(), 0);
+        or_panic!(s2.write_all(b"ping"));
+        assert_eq!(&long.join().unwrap(), b"ping");
+    }
+
+    #[test]
+    fn shutdown_wakes_reader() {
+        use std::sync::Arc;
+
+        let (s, _peer) = or_panic!(UnixStream::pair());
+        let s = Arc::new(s);
+        let r = Arc::new(or_panic!(s.try_clone()));
+        let reader = go!(move || {
+            let mut buf = [0; 4];
+            (&*r).read(&mut buf).unwrap()
+        });
+        ::std::thread::sleep(Duration::from_millis(20));
+
+        let start = Instant::now();
+        or_panic!(s.shutdown(Shutdown::Read));
+        assert_eq!(reader.join().unwrap(), 0);
+        assert!(start.elapsed() < Duration::from_millis(500));
+    }
 }
@@ -0,0 +1,298 @@
+//! Unix anonymous pipe that can be used in coroutine context
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
+use std::time::Duration;
+
+use libc;
+use io::{AsIoData, CoIo, IoData};
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn raw_pipe() -> io::Result<[RawFd; 2]> {
+    let mut fds = [0; 2];
+    let flags = libc::O_NONBLOCK | libc::O_CLOEXEC;
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), flags) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(fds)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn raw_pipe() -> io::Result<[RawFd; 2]> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    for fd in &fds {
+        // the non blocking mode is set by CoIo
+        if unsafe { libc::ioctl(*fd, libc::FIOCLEX) } == -1 {
+            let err = io::Error::last_os_error();
+            unsafe {
+                libc::close(fds[0]);
+                libc::close(fds[1]);
+            }
+            return Err(err);
+        }
+    }
+    Ok(fds)
+}
+
+/// Create an anonymous pipe, both ends are registered to the selector
+///
+/// reads and writes on the returned ends only block the current coroutine.
+/// writing to a pipe whose reader is dropped returns a `BrokenPipe` error,
+/// as long as `SIGPIPE` is ignored which is the default for rust programs.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::io::{Read, Write};
+/// use may::os::unix::pipe;
+///
+/// let (mut reader, mut writer) = pipe().unwrap();
+/// writer.write_all(b"hello").unwrap();
+/// drop(writer);
+///
+/// let mut s = String::new();
+/// reader.read_to_string(&mut s).unwrap();
+/// assert_eq!(s, "hello");
+/// ```
+pub fn pipe() -> io::Result<(PipeReader, PipeWriter)> {
+    let fds = raw_pipe()?;
+    let (r, w) = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+    Ok((PipeReader(CoIo::new(r)?), PipeWriter(CoIo::new(w)?)))
+}
+
+/// The reading end of a pipe
+pub struct PipeReader(CoIo<File>);
+
+/// The writing end of a pipe
+pub struct PipeWriter(CoIo<File>);
+
+impl PipeReader {
+    /// Sets the read timeout, `None` means block forever
+    pub fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.0.set_read_timeout(dur)
+    }
+
+    /// Returns the read timeout of this pipe
+    pub fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        self.0.read_timeout()
+    }
+
+    /// Moves this pipe into or out of nonblocking mode
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        self.0.set_nonblocking(nonblocking)
+    }
+}
+
+impl PipeWriter {
+    /// Sets the write timeout, `None` means block forever
+    pub fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.0.set_write_timeout(dur)
+    }
+
+    /// Returns the write timeout of this pipe
+    pub fn write_timeout(&self) -> io::Result<Option<Duration>> {
+        self.0.write_timeout()
+    }
+
+    /// Moves this pipe into or out of nonblocking mode
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        self.0.set_nonblocking(nonblocking)
+    }
+}
+
+impl Read for PipeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        Read::read(&mut &*self, buf)
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [io::IoSliceMut]) -> io::Result<usize> {
+        Read::read_vectored(&mut &*self, bufs)
+    }
+}
+
+impl<'a> Read for &'a PipeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (&self.0).read(buf)
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [io::IoSliceMut]) -> io::Result<usize> {
+        (&self.0).read_vectored(bufs)
+    }
+}
+
+impl Write for PipeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Write::write(&mut &*self, buf)
+    }
+
+    fn write_vectored(&mut self, bufs: &[io::IoSlice]) -> io::Result<usize> {
+        Write::write_vectored(&mut &*self, bufs)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Write::flush(&mut &*self)
+    }
+}
+
+impl<'a> Write for &'a PipeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (&self.0).write(buf)
+    }
+
+    fn write_vectored(&mut self, bufs: &[io::IoSlice]) -> io::Result<usize> {
+        (&self.0).write_vectored(bufs)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (&self.0).flush()
+    }
+}
+
+impl AsIoData for PipeReader {
+    fn as_io_data(&self) -> &IoData {
+        self.0.as_io_data()
+    }
+
+    fn io_read_timeout(&self) -> Option<Duration> {
+        self.0.io_read_timeout()
+    }
+}
+
+impl AsIoData for PipeWriter {
+    fn as_io_data(&self) -> &IoData {
+        self.0.as_io_data()
+    }
+
+    fn io_write_timeout(&self) -> Option<Duration> {
+        self.0.io_write_timeout()
+    }
+}
+
+impl AsRawFd for PipeReader {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+impl FromRawFd for PipeReader {
+    unsafe fn from_raw_fd(fd: RawFd) -> PipeReader {
+        let file = File::from_raw_fd(fd);
+        PipeReader(CoIo::new(file).expect("can't convert to PipeReader"))
+    }
+}
+
+impl IntoRawFd for PipeReader {
+    fn into_raw_fd(self) -> RawFd {
+        self.0.into_raw_fd()
+    }
+}
+
+impl AsRawFd for PipeWriter {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+impl FromRawFd for PipeWriter {
+    unsafe fn from_raw_fd(fd: RawFd) -> PipeWriter {
+        let file = File::from_raw_fd(fd);
+        PipeWriter(CoIo::new(file).expect("can't convert to PipeWriter"))
+    }
+}
+
+impl IntoRawFd for PipeWriter {
+    fn into_raw_fd(self) -> RawFd {
+        self.0.into_raw_fd()
+    }
+}
+
+impl fmt::Debug for PipeReader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PipeReader")
+            .field("fd", &self.as_raw_fd())
+            .finish()
+    }
+}
+
+impl fmt::Debug for PipeWriter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PipeWriter")
+            .field("fd", &self.as_raw_fd())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let (mut r, mut w) = pipe().unwrap();
+
+        let reader = go!(move || {
+            let mut buf = vec![0u8; 1024 * 1024];
+            r.read_exact(&mut buf).unwrap();
+            assert!(buf.iter().all(|b| *b == 7));
+            let mut rest = Vec::new();
+            r.read_to_end(&mut rest).unwrap();
+            assert!(rest.is_empty());
+        });
+
+        // larger than the pipe buffer, the writer must yield
+        let writer = go!(move || {
+            w.write_all(&vec![7u8; 1024 * 1024]).unwrap();
+        });
+
+        writer.join().unwrap();
+        reader.join().unwrap();
+    }
+
+    #[test]
+    fn read_timeout() {
+        let (r, _w) = pipe().unwrap();
+        let dur = Duration::from_millis(10);
+        r.set_read_timeout(Some(dur)).unwrap();
+        assert_eq!(r.read_timeout().unwrap(), Some(dur));
+
+        let h = go!(move || {
+            let mut buf = [0u8; 1];
+            let err = (&r).read(&mut buf).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        });
+        h.join().unwrap();
+    }
+
+    #[test]
+    fn broken_pipe() {
+        let (r, mut w) = pipe().unwrap();
+        drop(r);
+
+        let h = go!(move || {
+            let err = w.write(b"hello").unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+        });
+        h.join().unwrap();
+    }
+
+    #[test]
+    fn from_raw_fd() {
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        let mut r = unsafe { PipeReader::from_raw_fd(fds[0]) };
+        let mut w = unsafe { PipeWriter::from_raw_fd(fds[1]) };
+
+        let h = go!(move || {
+            let mut buf = [0u8; 5];
+            r.read_exact(&mut buf).unwrap();
+            assert_eq!(&buf, b"hello");
+        });
+        go!(move || w.write_all(b"hello").unwrap()).join().unwrap();
+        h.join().unwrap();
+    }
+}
@@ -0,0 +1,324 @@
+//! Unix signal handling that can be used in coroutine context
+//!
+//! the signal handler only records the signal and writes a byte to a self
+//! pipe, a driver thread reads the pipe and wakes up all the listeners.
+//! this works without blocking the signal in every thread, which `signalfd`
+//! would require, and it's the same for all the unix selectors. the driver
+//! is not a coroutine, so it never holds up the scheduler shutdown
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::unix::io::{FromRawFd, RawFd};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Once, ONCE_INIT};
+use std::thread;
+
+use libc;
+use sync::{Condvar, Mutex};
+
+// the max signal number that can be listened, covers the realtime signals
+const MAX_SIGNUM: usize = 128;
+
+struct Globals {
+    // the write end of the self pipe, written by the signal handler
+    wake_fd: RawFd,
+    // how many times each signal is received
+    counts: Vec<AtomicUsize>,
+    // if the handler is installed for the signal
+    installed: Vec<AtomicBool>,
+    // serialize the handler installation
+    install_lock: Mutex<()>,
+    // the listeners wait here for the driver to notify
+    lock: Mutex<()>,
+    cond: Condvar,
+}
+
+static mut GLOBALS: *const Globals = 0 as *const _;
+
+// the read end blocks the driver thread, the write end never blocks
+fn signal_pipe() -> io::Result<(File, RawFd)> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    let ret = unsafe {
+        libc::fcntl(fds[0], libc::F_SETFD, libc::FD_CLOEXEC) != -1
+            && libc::fcntl(fds[1], libc::F_SETFD, libc::FD_CLOEXEC) != -1
+            && libc::fcntl(fds[1], libc::F_SETFL, libc::O_NONBLOCK) != -1
+    };
+    if !ret {
+        let err = io::Error::last_os_error();
+        unsafe {
+            libc::close(fds[0]);
+            libc::close(fds[1]);
+        }
+        return Err(err);
+    }
+    Ok((unsafe { File::from_raw_fd(fds[0]) }, fds[1]))
+}
+
+#[cold]
+#[inline(never)]
+fn init_globals() {
+    let (mut reader, wake_fd) = signal_pipe().expect("failed to create the signal pipe");
+    let globals = Box::new(Globals {
+        wake_fd: wake_fd,
+        counts: (0..MAX_SIGNUM).map(|_| AtomicUsize::new(0)).collect(),
+        installed: (0..MAX_SIGNUM).map(|_| AtomicBool::new(false)).collect(),
+        install_lock: Mutex::new(()),
+        lock: Mutex::new(()),
+        cond: Condvar::new(),
+    });
+    unsafe { GLOBALS = Box::into_raw(globals) };
+
+    // the driver thread that wakes up the listeners
+    thread::Builder::new()
+        .name("may_signal".to_owned())
+        .spawn(move || {
+            let globals = unsafe { &*GLOBALS };
+            let mut buf = [0u8; 64];
+            loop {
+                match reader.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(_) => {}
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(e) => {
+                        error!("signal driver read error: {}", e);
+                        break;
+                    }
+                }
+                let _g = globals.lock.lock().unwrap();
+                globals.cond.notify_all();
+            }
+        })
+        .expect("failed to spawn the signal driver");
+}
+
+fn globals() -> &'static Globals {
+    static ONCE: Once = ONCE_INIT;
+    ONCE.call_once(init_globals);
+    unsafe { &*GLOBALS }
+}
+
+extern "C" fn handler(signum: libc::c_int) {
+    // only async signal safe operations are allowed here
+    let globals = unsafe { &*GLOBALS };
+    globals.counts[signum as usize].fetch_add(1, Ordering::SeqCst);
+    let errno = io::Error::last_os_error().raw_os_error().unwrap_or(0);
+    // the pipe is non blocking, it's fine to drop the byte if it's full
+    unsafe { libc::write(globals.wake_fd, b"s".as_ptr() as *const _, 1) };
+    restore_errno(errno);
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn restore_errno(errno: libc::c_int) {
+    unsafe { *libc::__errno_location() = errno };
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd",
+          target_os = "dragonfly"))]
+fn restore_errno(errno: libc::c_int) {
+    unsafe { *libc::__error() = errno };
+}
+
+#[cfg(any(target_os = "netbsd", target_os = "openbsd", target_os = "bitrig"))]
+fn restore_errno(errno: libc::c_int) {
+    unsafe { *libc::__errno() = errno };
+}
+
+fn install(globals: &Globals, signum: libc::c_int) -> io::Result<()> {
+    if globals.installed[signum as usize].load(Ordering::Acquire) {
+        return Ok(());
+    }
+
+    let _g = globals.install_lock.lock().unwrap();
+    if globals.installed[signum as usize].load(Ordering::Acquire) {
+        return Ok(());
+    }
+
+    unsafe {
+        let mut action: libc::sigaction = ::std::mem::zeroed();
+        action.sa_sigaction = handler as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        if libc::sigaction(signum, &action, ::std::ptr::null_mut()) == -1 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    globals.installed[signum as usize].store(true, Ordering::Release);
+    Ok(())
+}
+
+/// The kind of the signal to listen for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SignalKind(libc::c_int);
+
+impl SignalKind {
+    /// create from the raw signal number
+    pub fn from_raw(signum: libc::c_int) -> SignalKind {
+        SignalKind(signum)
+    }
+
+    /// return the raw signal number
+    pub fn as_raw(&self) -> libc::c_int {
+        self.0
+    }
+
+    /// SIGINT, the interrupt from the terminal
+    pub fn interrupt() -> SignalKind {
+        SignalKind(libc::SIGINT)
+    }
+
+    /// SIGTERM, the termination request
+    pub fn terminate() -> SignalKind {
+        SignalKind(libc::SIGTERM)
+    }
+
+    /// SIGHUP, the terminal is closed or the config should be reloaded
+    pub fn hangup() -> SignalKind {
+        SignalKind(libc::SIGHUP)
+    }
+
+    /// SIGQUIT, the quit request from the terminal
+    pub fn quit() -> SignalKind {
+        SignalKind(libc::SIGQUIT)
+    }
+
+    /// SIGCHLD, a child process is changed
+    pub fn child() -> SignalKind {
+        SignalKind(libc::SIGCHLD)
+    }
+
+    /// SIGUSR1
+    pub fn user_defined1() -> SignalKind {
+        SignalKind(libc::SIGUSR1)
+    }
+
+    /// SIGUSR2
+    pub fn user_defined2() -> SignalKind {
+        SignalKind(libc::SIGUSR2)
+    }
+}
+
+/// A listener of a signal, created by `signal`
+#[derive(Debug)]
+pub struct Signal {
+    signum: libc::c_int,
+    // the signal count that is already delivered
+    seen: usize,
+}
+
+/// listen for the signal
+///
+/// the signal handler is installed on the first call for the signal, which
+/// replaces the default action of it. the signals that arrive after the
+/// listener is created are never lost, but multiple of them between two
+/// `recv` calls are coalesced into one. every listener of the same signal
+/// would receive it
+///
+/// return an `InvalidInput` error for the signals that can't be handled,
+/// e.g. `SIGKILL`, `SIGSTOP` and `SIGSEGV`
+///
+/// # Examples
+///
+/// ```no_run
+/// use may::os::unix::{signal, SignalKind};
+///
+/// let mut term = signal(SignalKind::terminate()).unwrap();
+/// term.recv();
+/// println!("got SIGTERM, shutting down");
+/// ```
+pub fn signal(kind: SignalKind) -> io::Result<Signal> {
+    let signum = kind.as_raw();
+    let forbidden = [
+        libc::SIGKILL,
+        libc::SIGSTOP,
+        libc::SIGSEGV,
+        libc::SIGBUS,
+        libc::SIGILL,
+        libc::SIGFPE,
+    ];
+    if signum <= 0 || signum as usize >= MAX_SIGNUM || forbidden.contains(&signum) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the signal can't be listened",
+        ));
+    }
+
+    let globals = globals();
+    install(globals, signum)?;
+    Ok(Signal {
+        signum: signum,
+        seen: globals.counts[signum as usize].load(Ordering::SeqCst),
+    })
+}
+
+impl Signal {
+    /// block until the signal is received since the last delivery
+    pub fn recv(&mut self) {
+        let globals = globals();
+        let count = &globals.counts[self.signum as usize];
+        let mut guard = globals.lock.lock().unwrap();
+        loop {
+            let cnt = count.load(Ordering::SeqCst);
+            if cnt != self.seen {
+                self.seen = cnt;
+                return;
+            }
+            guard = globals.cond.wait(guard).unwrap();
+        }
+    }
+
+    /// return true if the signal is received since the last delivery
+    pub fn try_recv(&mut self) -> bool {
+        let cnt = globals().counts[self.signum as usize].load(Ordering::SeqCst);
+        if cnt != self.seen {
+            self.seen = cnt;
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn recv_in_coroutine() {
+        let mut sig1 = signal(SignalKind::user_defined1()).unwrap();
+        let mut sig2 = signal(SignalKind::user_defined1()).unwrap();
+        let h1 = go!(move || sig1.recv());
+        let h2 = go!(move || sig2.recv());
+
+        thread::sleep(Duration::from_millis(50));
+        // all the listeners are waked up
+        unsafe { libc::raise(libc::SIGUSR1) };
+        h1.join().unwrap();
+        h2.join().unwrap();
+    }
+
+    #[test]
+    fn coalesced() {
+        let mut sig = signal(SignalKind::user_defined2()).unwrap();
+        assert!(!sig.try_recv());
+        // nobody is waiting, the signals are kept for the next call
+        unsafe {
+            libc::raise(libc::SIGUSR2);
+            libc::raise(libc::SIGUSR2);
+        }
+        go!(move || {
+            sig.recv();
+            assert!(!sig.try_recv());
+        }).join()
+            .unwrap();
+    }
+
+    #[test]
+    fn forbidden() {
+        let err = signal(SignalKind::from_raw(libc::SIGKILL)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
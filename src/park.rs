@@ -9,10 +9,14 @@ use scheduler::get_scheduler;
 use timeout_list::TimeoutHandle;
 use yield_now::{get_co_para, yield_now, yield_with};
 use coroutine_impl::{co_cancel_data, run_coroutine, CoroutineImpl, EventSource};
+use listener::ParkReason;
 
+/// The reasons that a park returns without an unpark
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum ParkError {
+    /// the parked coroutine is canceled
     Canceled,
+    /// the timeout expires
     Timeout,
 }
 
@@ -172,12 +176,16 @@ impl Park {
         // let _gen = self.state.load(Ordering::Acquire);
         // println!("unparked gen={}, self={:p}", gen, self);
 
+        // always consume the para, a left over cancel error would otherwise
+        // be seen by the next coroutine that reuses this one from the pool
+        let para = get_co_para();
+
         // after return back, we should check if it's timeout or canceled
         if self.is_canceled.load(Ordering::Relaxed) {
             return Err(ParkError::Canceled);
         }
 
-        if let Some(err) = get_co_para() {
+        if let Some(err) = para {
             match err.kind() {
                 ErrorKind::TimedOut => return Err(ParkError::Timeout),
                 ErrorKind::Other => return Err(ParkError::Canceled),
@@ -244,6 +252,10 @@ impl EventSource for Park {
         }
     }
 
+    fn park_reason(&self) -> Option<ParkReason> {
+        Some(ParkReason::Sync)
+    }
+
     // when the cancel is true we check the panic or do nothing
     fn yield_back(&self, cancel: &'static Cancel) {
         // we would inc the generation by 2 to another generation
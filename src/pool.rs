@@ -1,13 +1,38 @@
+use std::mem;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use generator::Gn;
 use config::config;
 use coroutine_impl::CoroutineImpl;
 use may_queue::mpmc_bounded::Queue;
 
+// the spawns that reuse a pooled stack and that map a new one
+static HITS: AtomicUsize = AtomicUsize::new(0);
+static MISSES: AtomicUsize = AtomicUsize::new(0);
+
+// a pooled coroutine with the stack recorded by it
+struct Pooled {
+    co: CoroutineImpl,
+    // `(top, bytes)`, the top is 0 when the pages are released
+    stack: (usize, usize),
+    // the release check count when it's put into the pool
+    epoch: usize,
+}
+
 /// the raw coroutine pool, with stack and register prepared
 /// you need to tack care of the local storage
 pub struct CoroutinePool {
     // the pool must support mpmc operation!
-    pool: Queue<CoroutineImpl>,
+    pool: Queue<Pooled>,
+    // the number of the coroutines in the pool, the queue could hold a bit
+    // more than the capacity since its size is a power of 2
+    len: AtomicUsize,
+    capacity: usize,
+    // the number of the release checks
+    epoch: AtomicUsize,
+    // the coroutines of the other stack sizes, indexed by the size
+    sized: Mutex<HashMap<usize, Vec<CoroutineImpl>>>,
 }
 
 impl CoroutinePool {
@@ -21,27 +46,146 @@ impl CoroutinePool {
     pub fn new() -> Self {
         let capacity = config().get_pool_capacity();
         let pool = Queue::with_capacity(capacity);
-        for _ in 0..capacity {
-            let co = Self::create_dummy_coroutine();
-            pool.push(co).unwrap();
+        let prealloc = config().get_prealloc_stacks();
+        for _ in 0..prealloc {
+            let pooled = Pooled {
+                co: Self::create_dummy_coroutine(),
+                stack: (0, 0),
+                epoch: 0,
+            };
+            pool.push(pooled).ok().expect("the pool is full");
         }
 
-        CoroutinePool { pool: pool }
+        CoroutinePool {
+            pool: pool,
+            len: AtomicUsize::new(prealloc),
+            capacity: capacity,
+            epoch: AtomicUsize::new(0),
+            sized: Mutex::new(HashMap::new()),
+        }
     }
 
     /// get a raw coroutine from the pool
     #[inline]
     pub fn get(&self) -> CoroutineImpl {
         match self.pool.pop() {
-            Some(co) => co,
-            None => Self::create_dummy_coroutine(),
+            Some(pooled) => {
+                self.len.fetch_sub(1, Ordering::Relaxed);
+                HITS.fetch_add(1, Ordering::Relaxed);
+                pooled.co
+            }
+            None => {
+                MISSES.fetch_add(1, Ordering::Relaxed);
+                Self::create_dummy_coroutine()
+            }
         }
     }
 
-    /// put a raw coroutine into the pool
+    /// put a raw coroutine into the pool, it's dropped with its stack when
+    /// the pool is full. the stack is `(top, bytes)` recorded by the coroutine
     #[inline]
-    pub fn put(&self, co: CoroutineImpl) {
+    pub fn put(&self, co: CoroutineImpl, stack: (usize, usize)) {
+        if self.len.fetch_add(1, Ordering::Relaxed) >= self.capacity {
+            self.len.fetch_sub(1, Ordering::Relaxed);
+            return;
+        }
+        let pooled = Pooled {
+            co: co,
+            stack: stack,
+            epoch: self.epoch.load(Ordering::Relaxed),
+        };
         // discard the co if push failed
-        self.pool.push(co).ok();
+        if self.pool.push(pooled).is_err() {
+            self.len.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    /// release the pages of the pooled stacks that are not used since the
+    /// last call, it's called periodically by the release thread
+    pub fn release_idle(&self) {
+        let epoch = self.epoch.fetch_add(1, Ordering::Relaxed);
+        // go through the pool once, the spawns meanwhile could miss them
+        let n = self.len.load(Ordering::Relaxed);
+        for _ in 0..n {
+            let mut pooled = match self.pool.pop() {
+                Some(pooled) => pooled,
+                None => break,
+            };
+            if pooled.epoch < epoch {
+                release_stack(pooled.stack);
+                pooled.stack = (0, 0);
+            }
+            // the len is not changed, so there is always a slot for it
+            if self.pool.push(pooled).is_err() {
+                self.len.fetch_sub(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// get a raw coroutine of the stack size that is not the default one,
+    /// `None` if there is no such one in the pool
+    pub fn get_sized(&self, size: usize) -> Option<CoroutineImpl> {
+        let co = self.sized
+            .lock()
+            .unwrap()
+            .get_mut(&size)
+            .and_then(|list| list.pop());
+        match co {
+            Some(_) => HITS.fetch_add(1, Ordering::Relaxed),
+            None => MISSES.fetch_add(1, Ordering::Relaxed),
+        };
+        co
+    }
+
+    /// put a raw coroutine of the stack size that is not the default one
+    /// into the pool, the stack is `(top, bytes)` recorded by the coroutine
+    pub fn put_sized(&self, size: usize, stack: (usize, usize), co: CoroutineImpl) {
+        let mut sized = self.sized.lock().unwrap();
+        let list = sized.entry(size).or_insert_with(Vec::new);
+        if list.len() >= config().get_stack_pool_capacity() {
+            return;
+        }
+        release_stack(stack);
+        list.push(co);
+    }
+
+    /// drop all the pooled coroutines and their stacks
+    pub fn shrink(&self) {
+        while let Some(pooled) = self.pool.pop() {
+            self.len.fetch_sub(1, Ordering::Relaxed);
+            drop(pooled);
+        }
+        let sized = mem::replace(&mut *self.sized.lock().unwrap(), HashMap::new());
+        drop(sized);
     }
 }
+
+/// the spawns that reuse a pooled stack and that map a new one
+pub fn counters() -> (usize, usize) {
+    (HITS.load(Ordering::Relaxed), MISSES.load(Ordering::Relaxed))
+}
+
+// give the pages of an idle stack back to the system, they are mapped
+// again with zeros on the next use
+#[cfg(unix)]
+fn release_stack((top, bytes): (usize, usize)) {
+    use libc;
+
+    if top == 0 {
+        return;
+    }
+    let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize };
+    // the recorded top is a bit below the real top, and the stack is at
+    // least `bytes` long. keep the lowest page that has the overflow marks
+    // and the highest page that has the context of the coroutine
+    let start = (top.saturating_sub(bytes) + 2 * page) & !(page - 1);
+    let end = (top & !(page - 1)).saturating_sub(page);
+    if end > start {
+        unsafe {
+            libc::madvise(start as *mut libc::c_void, end - start, libc::MADV_DONTNEED);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn release_stack(_stack: (usize, usize)) {}
@@ -0,0 +1,411 @@
+//! Coroutine aware child process
+//!
+//! compatible with `std::process` except that waiting for the child and
+//! doing io on its stdio pipes only block the current coroutine.
+//! on linux the exit of the child is watched by a `pidfd` registered to the
+//! selector, elsewhere a reaper thread is used to wake up the coroutine
+//! please ref the doc from std::process
+
+use std::ffi::OsStr;
+use std::fmt;
+use std::io::{self, Read};
+use std::os::unix::io::{FromRawFd, IntoRawFd};
+use std::path::Path;
+use std::process;
+use std::thread;
+
+use libc;
+use coroutine;
+use sync::mpsc;
+use coroutine_impl::is_coroutine;
+use os::unix::{PipeReader, PipeWriter};
+
+pub use std::process::{ExitStatus, Output, Stdio};
+
+/// A handle to the child's stdin, see `Child`
+pub type ChildStdin = PipeWriter;
+/// A handle to the child's stdout, see `Child`
+pub type ChildStdout = PipeReader;
+/// A handle to the child's stderr, see `Child`
+pub type ChildStderr = PipeReader;
+
+/// A process builder, the same as `std::process::Command`
+///
+/// # Examples
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate may;
+///
+/// use may::process::Command;
+///
+/// fn main() {
+///     let h = go!(|| {
+///         let output = Command::new("echo").arg("hello").output().unwrap();
+///         assert_eq!(output.stdout, b"hello\n");
+///     });
+///     h.join().unwrap();
+/// }
+/// ```
+pub struct Command {
+    inner: process::Command,
+    // if the stdio is explicitly configured
+    stdin: bool,
+    stdout: bool,
+    stderr: bool,
+}
+
+impl Command {
+    /// Constructs a new `Command` for launching the program at `program`
+    pub fn new<S: AsRef<OsStr>>(program: S) -> Command {
+        Command {
+            inner: process::Command::new(program),
+            stdin: false,
+            stdout: false,
+            stderr: false,
+        }
+    }
+
+    /// Adds an argument to pass to the program
+    pub fn arg<S: AsRef<OsStr>>(&mut self, arg: S) -> &mut Command {
+        self.inner.arg(arg);
+        self
+    }
+
+    /// Adds multiple arguments to pass to the program
+    pub fn args<I, S>(&mut self, args: I) -> &mut Command
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.inner.args(args);
+        self
+    }
+
+    /// Inserts or updates an environment variable mapping
+    pub fn env<K, V>(&mut self, key: K, val: V) -> &mut Command
+    where
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        self.inner.env(key, val);
+        self
+    }
+
+    /// Adds or updates multiple environment variable mappings
+    pub fn envs<I, K, V>(&mut self, vars: I) -> &mut Command
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        self.inner.envs(vars);
+        self
+    }
+
+    /// Removes an environment variable mapping
+    pub fn env_remove<K: AsRef<OsStr>>(&mut self, key: K) -> &mut Command {
+        self.inner.env_remove(key);
+        self
+    }
+
+    /// Clears the entire environment map for the child process
+    pub fn env_clear(&mut self) -> &mut Command {
+        self.inner.env_clear();
+        self
+    }
+
+    /// Sets the working directory for the child process
+    pub fn current_dir<P: AsRef<Path>>(&mut self, dir: P) -> &mut Command {
+        self.inner.current_dir(dir);
+        self
+    }
+
+    /// Configuration for the child process's standard input
+    pub fn stdin<T: Into<Stdio>>(&mut self, cfg: T) -> &mut Command {
+        self.inner.stdin(cfg);
+        self.stdin = true;
+        self
+    }
+
+    /// Configuration for the child process's standard output
+    pub fn stdout<T: Into<Stdio>>(&mut self, cfg: T) -> &mut Command {
+        self.inner.stdout(cfg);
+        self.stdout = true;
+        self
+    }
+
+    /// Configuration for the child process's standard error
+    pub fn stderr<T: Into<Stdio>>(&mut self, cfg: T) -> &mut Command {
+        self.inner.stderr(cfg);
+        self.stderr = true;
+        self
+    }
+
+    /// Executes the command as a child process, returning a handle to it
+    ///
+    /// by default stdin, stdout and stderr are inherited from the parent
+    pub fn spawn(&mut self) -> io::Result<Child> {
+        let child = self.inner.spawn()?;
+        Ok(Child::new(child))
+    }
+
+    /// Executes the command as a child process, collecting all of its output
+    ///
+    /// by default stdout and stderr are captured and stdin is not inherited
+    pub fn output(&mut self) -> io::Result<Output> {
+        if !self.stdin {
+            self.inner.stdin(Stdio::null());
+        }
+        if !self.stdout {
+            self.inner.stdout(Stdio::piped());
+        }
+        if !self.stderr {
+            self.inner.stderr(Stdio::piped());
+        }
+        let ret = self.inner.spawn();
+        self.reset_default_stdio();
+        Child::new(ret?).wait_with_output()
+    }
+
+    /// Executes the command as a child process, waiting for it to finish
+    ///
+    /// by default stdin, stdout and stderr are inherited from the parent
+    pub fn status(&mut self) -> io::Result<ExitStatus> {
+        self.spawn()?.wait()
+    }
+
+    // restore the stdio that is not explicitly configured
+    fn reset_default_stdio(&mut self) {
+        if !self.stdin {
+            self.inner.stdin(Stdio::inherit());
+        }
+        if !self.stdout {
+            self.inner.stdout(Stdio::inherit());
+        }
+        if !self.stderr {
+            self.inner.stderr(Stdio::inherit());
+        }
+    }
+}
+
+impl fmt::Debug for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+/// Representation of a running or exited child process
+///
+/// the stdio handles are coroutine aware pipes
+pub struct Child {
+    inner: process::Child,
+    /// The handle for writing to the child's stdin, if it has been captured
+    pub stdin: Option<ChildStdin>,
+    /// The handle for reading from the child's stdout, if it has been captured
+    pub stdout: Option<ChildStdout>,
+    /// The handle for reading from the child's stderr, if it has been captured
+    pub stderr: Option<ChildStderr>,
+}
+
+impl Child {
+    fn new(mut child: process::Child) -> Child {
+        let stdin = child
+            .stdin
+            .take()
+            .map(|s| unsafe { PipeWriter::from_raw_fd(s.into_raw_fd()) });
+        let stdout = child
+            .stdout
+            .take()
+            .map(|s| unsafe { PipeReader::from_raw_fd(s.into_raw_fd()) });
+        let stderr = child
+            .stderr
+            .take()
+            .map(|s| unsafe { PipeReader::from_raw_fd(s.into_raw_fd()) });
+        Child {
+            inner: child,
+            stdin: stdin,
+            stdout: stdout,
+            stderr: stderr,
+        }
+    }
+
+    /// Returns the OS-assigned process identifier of the child
+    pub fn id(&self) -> u32 {
+        self.inner.id()
+    }
+
+    /// Forces the child process to exit
+    pub fn kill(&mut self) -> io::Result<()> {
+        self.inner.kill()
+    }
+
+    /// Attempts to collect the exit status of the child if it has already exited
+    pub fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        self.inner.try_wait()
+    }
+
+    /// Waits for the child to exit completely, returning the status that it
+    /// exited with. the stdin handle is closed before waiting
+    ///
+    /// in coroutine context only the current coroutine is blocked
+    pub fn wait(&mut self) -> io::Result<ExitStatus> {
+        drop(self.stdin.take());
+        if !is_coroutine() {
+            return self.inner.wait();
+        }
+
+        if let Some(status) = self.inner.try_wait()? {
+            return Ok(status);
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            // fall back to the reaper thread if pidfd is not supported
+            if let Ok(fd) = pidfd::open(self.id()) {
+                return pidfd::wait(&mut self.inner, fd);
+            }
+        }
+
+        wait_reaper(&mut self.inner)
+    }
+
+    /// Simultaneously waits for the child to exit and collect all remaining
+    /// output on the stdout/stderr handles
+    pub fn wait_with_output(mut self) -> io::Result<Output> {
+        drop(self.stdin.take());
+        let stdout = self.stdout.take();
+        let stderr = self.stderr.take();
+
+        let (stdout, stderr) = coroutine::scope(|s| {
+            // read the stderr in another coroutine to avoid dead lock
+            let err = go!(s, move || read_all(stderr));
+            let out = read_all(stdout);
+            (out, err.join())
+        });
+
+        let status = self.wait()?;
+        Ok(Output {
+            status: status,
+            stdout: stdout?,
+            stderr: stderr?,
+        })
+    }
+}
+
+impl fmt::Debug for Child {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Child")
+            .field("id", &self.id())
+            .field("stdin", &self.stdin)
+            .field("stdout", &self.stdout)
+            .field("stderr", &self.stderr)
+            .finish()
+    }
+}
+
+fn read_all(r: Option<PipeReader>) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    if let Some(mut r) = r {
+        r.read_to_end(&mut buf)?;
+    }
+    Ok(buf)
+}
+
+// wait the child exit in a thread without reaping it, then wake up the
+// coroutine to collect the exit status
+fn wait_reaper(child: &mut process::Child) -> io::Result<ExitStatus> {
+    let pid = child.id() as libc::id_t;
+    let (tx, rx) = mpsc::channel();
+    thread::Builder::new()
+        .name("may_reaper".to_owned())
+        .spawn(move || {
+            let mut info: libc::siginfo_t = unsafe { ::std::mem::zeroed() };
+            let ret = loop {
+                let flags = libc::WEXITED | libc::WNOWAIT;
+                if unsafe { libc::waitid(libc::P_PID, pid, &mut info, flags) } == 0 {
+                    break Ok(());
+                }
+                let err = io::Error::last_os_error();
+                if err.kind() != io::ErrorKind::Interrupted {
+                    break Err(err);
+                }
+            };
+            tx.send(ret).ok();
+        })?;
+
+    rx.recv()
+        .map_err(|_| io::Error::new(io::ErrorKind::Other, "reaper thread panicked"))??;
+    match child.try_wait()? {
+        Some(status) => Ok(status),
+        None => child.wait(),
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod pidfd {
+    use std::io;
+    use std::process::{Child, ExitStatus};
+    use std::os::unix::io::{AsRawFd, RawFd};
+
+    use libc;
+    use io::CoIo;
+    use io::sys::WaitIo;
+    use yield_now::yield_with;
+
+    pub struct PidFd(RawFd);
+
+    impl AsRawFd for PidFd {
+        fn as_raw_fd(&self) -> RawFd {
+            self.0
+        }
+    }
+
+    impl Drop for PidFd {
+        fn drop(&mut self) {
+            unsafe { libc::close(self.0) };
+        }
+    }
+
+    pub fn open(pid: u32) -> io::Result<PidFd> {
+        let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid as libc::pid_t, 0) };
+        if fd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(PidFd(fd as RawFd))
+    }
+
+    // the pidfd is readable when the child exits
+    pub fn wait(child: &mut Child, fd: PidFd) -> io::Result<ExitStatus> {
+        let io = CoIo::new(fd)?;
+        loop {
+            io.io_reset();
+            if let Some(status) = child.try_wait()? {
+                return Ok(status);
+            }
+
+            let waiter = WaitIo::new(&io, None);
+            yield_with(&waiter);
+            waiter.done()?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reaper_wait() {
+        let h = go!(|| {
+            let mut child = process::Command::new("sh")
+                .arg("-c")
+                .arg("sleep 0.05; exit 2")
+                .spawn()
+                .unwrap();
+            let status = wait_reaper(&mut child).unwrap();
+            assert_eq!(status.code(), Some(2));
+        });
+        h.join().unwrap();
+    }
+}
@@ -0,0 +1,121 @@
+//! the list of the live coroutines
+//!
+//! all the live coroutines are kept for `shutdown`. only the coroutines
+//! spawned when `Config::set_coroutine_registry` is on are shown by `dump`,
+//! and only they pay for updating the state. the list is sharded by the id,
+//! so the spawns and the exits on different workers seldom meet on a lock
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use scheduler::get_scheduler;
+use coroutine_impl::Coroutine;
+
+/// The state of a coroutine reported by `dump`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CoroutineState {
+    /// in the ready list waiting for a worker
+    Ready,
+    /// running on a worker
+    Running,
+    /// suspended by a park, a sleep or the sync primitives
+    Parked,
+    /// suspended on an io request
+    Io,
+}
+
+/// A snapshot of a live coroutine
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoroutineInfo {
+    /// the unique id of the coroutine
+    pub id: usize,
+    /// the name of the coroutine
+    pub name: Option<String>,
+    /// the state at the time of the dump
+    pub state: CoroutineState,
+    /// the index of the worker that runs the coroutine last time, the io
+    /// workers are indexed after the normal workers. `None` if it's not
+    /// run yet or it's run in a thread out of the scheduler
+    pub worker: Option<usize>,
+}
+
+// the number of the shards of the list
+const SHARDS: usize = 16;
+
+pub(crate) struct Registry {
+    shards: Vec<Mutex<HashMap<usize, Coroutine>>>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Registry {
+            shards: (0..SHARDS).map(|_| Mutex::new(HashMap::new())).collect(),
+        }
+    }
+
+    #[inline]
+    fn shard(&self, id: usize) -> &Mutex<HashMap<usize, Coroutine>> {
+        &self.shards[id % SHARDS]
+    }
+
+    pub fn add(&self, co: &Coroutine) {
+        self.shard(co.id()).lock().unwrap().insert(co.id(), co.clone());
+    }
+
+    pub fn remove(&self, co: &Coroutine) {
+        self.shard(co.id()).lock().unwrap().remove(&co.id());
+    }
+
+    // a snapshot of all the live coroutines
+    pub fn all(&self) -> Vec<Coroutine> {
+        self.shards
+            .iter()
+            .flat_map(|s| s.lock().unwrap().values().cloned().collect::<Vec<_>>())
+            .collect()
+    }
+}
+
+/// list the live coroutines in the order of spawning
+///
+/// only the coroutines spawned while `Config::set_coroutine_registry` is on
+/// are listed. the states are read one by one without stopping the
+/// scheduler, so they could change while the dump is taken
+///
+/// this takes a lock, call it from a normal thread or coroutine, such as a
+/// thread that waits for a signal, not from within a signal handler
+///
+/// # Examples
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate may;
+///
+/// use may::coroutine::{self, Builder, CoroutineState};
+///
+/// fn main() {
+///     may::config().set_coroutine_registry(true);
+///     let h = go!(Builder::new().name("idle".to_owned()), || coroutine::park()).unwrap();
+///     coroutine::sleep(std::time::Duration::from_millis(20));
+///
+///     let info = coroutine::dump()
+///         .into_iter()
+///         .find(|i| i.id == h.coroutine().id())
+///         .unwrap();
+///     assert_eq!(info.name.as_ref().map(|s| &**s), Some("idle"));
+///     assert_eq!(info.state, CoroutineState::Parked);
+///
+///     h.coroutine().unpark();
+///     h.join().unwrap();
+/// }
+/// ```
+pub fn dump() -> Vec<CoroutineInfo> {
+    let mut list = get_scheduler()
+        .registry
+        .all()
+        .iter()
+        .filter(|co| co.is_listed())
+        .map(|co| co.info())
+        .collect::<Vec<_>>();
+    list.sort_by_key(|info| info.id);
+    list
+}
@@ -1,18 +1,28 @@
+//! the coroutine scheduler
+//!
+//! the scheduler itself is internal, only the runtime metrics are public
+
 use std::io;
+use std::cmp;
 use std::thread;
-use std::time::Duration;
-use std::sync::atomic::Ordering;
-use std::sync::{Arc, Once, ONCE_INIT};
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Once, ONCE_INIT};
 
 use timeout_list;
 use config::config;
 use sync::AtomicOption;
-use pool::CoroutinePool;
+use pool::{self, CoroutinePool};
 use yield_now::set_co_para;
 use io::{EventLoop, Selector};
+use crossbeam::CachePadded;
 use crossbeam::sync::SegQueue as mpmc;
 use may_queue::mpmc_bounded::Queue as WaitList;
-use coroutine_impl::{run_coroutine, CoroutineImpl};
+use local::CoroutineLocal;
+use registry::{CoroutineState, Registry};
+use stack_stats::StackHistogram;
+use coroutine_impl::{current_cancel_data, current_description, is_coroutine, run_coroutine, CoroutineImpl};
 
 #[cfg(nightly)]
 use std::intrinsics::likely;
@@ -22,28 +32,115 @@ fn likely(e: bool) -> bool {
     e
 }
 
+// the stack overflow report is only supported on unix
+#[cfg(unix)]
+use stack_guard::{init as guard_init, init_thread as guard_init_thread};
+#[cfg(not(unix))]
+fn guard_init() {}
+#[cfg(not(unix))]
+fn guard_init_thread() {}
+
 // here we use Arc<AtomicOption<>> for that in the select implementation
 // other event may try to consume the coroutine while timer thread consume it
 type TimerData = Arc<AtomicOption<CoroutineImpl>>;
 type TimerThread = timeout_list::TimerThread<TimerData>;
 
 // filter out the cancel panic, don't print anything for it
+// and tell which coroutine panicked
+// the hook is process wide, chaining it more than once repeats the report
 fn filter_cancel_panic() {
     use std::panic;
     use generator::Error;
-    let old = panic::take_hook();
-    ::std::panic::set_hook(Box::new(move |info| {
-        match info.payload().downcast_ref::<Error>() {
-            // this is not an error at all, ignore it
-            Some(_e @ &Error::Cancel) => return,
-            _ => {}
-        }
-        old(info);
-    }));
+    static HOOK: Once = ONCE_INIT;
+    HOOK.call_once(|| {
+        let old = panic::take_hook();
+        ::std::panic::set_hook(Box::new(move |info| {
+            match info.payload().downcast_ref::<Error>() {
+                // this is not an error at all, ignore it
+                Some(_e @ &Error::Cancel) => return,
+                _ => {}
+            }
+            // the unwrapped cancel error of a canceled io, reported as the
+            // cancel when the coroutine exits
+            if is_coroutine() && current_cancel_data().is_io_reported() {
+                return;
+            }
+            // the default hook only names the worker thread
+            if let Some(co) = current_description() {
+                eprintln!("{} panicked", co);
+            }
+            old(info);
+        }));
+    });
 }
 
 static mut SCHED: *const Scheduler = 0 as *const _;
 
+// the index of the worker thread, the io workers follow the normal workers
+thread_local!{static WORKER_ID: Cell<Option<usize>> = Cell::new(None);}
+
+// the index of the current worker thread
+#[inline]
+pub(crate) fn worker_id() -> Option<usize> {
+    WORKER_ID.with(|id| id.get())
+}
+
+/// the index of the worker thread that runs the current code
+///
+/// the normal workers are indexed from 0 to `Config::get_workers` - 1,
+/// they are the ones that a coroutine could be pinned to by
+/// `Builder::pin_to`. the io workers that run the coroutines waked by the
+/// io events are indexed after them. `None` is returned out of the workers
+pub fn current_worker_id() -> Option<usize> {
+    worker_id()
+}
+
+// every so many picks a worker looks at the normal/low ready list first
+const NORMAL_FIRST_TICK: usize = 8;
+const LOW_FIRST_TICK: usize = 32;
+
+/// The scheduling priority of a coroutine
+///
+/// a worker that looks for work picks the ready coroutines of higher priority
+/// first. the lower ones are still picked first once in a while, so under
+/// load they are delayed but never starved
+///
+/// the priority only orders the ready list of the workers, the coroutines
+/// that are waked up by io events and run on the io workers directly are
+/// not affected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Priority {
+    /// for the latency critical coroutines
+    High,
+    /// the default priority
+    Normal,
+    /// for the background coroutines
+    Low,
+}
+
+impl Default for Priority {
+    fn default() -> Priority {
+        Priority::Normal
+    }
+}
+
+// the priority of the coroutine which is set at spawn
+#[inline]
+fn priority_of(co: &CoroutineImpl) -> Priority {
+    let local = co.get_local_data() as *const CoroutineLocal;
+    if local.is_null() {
+        return Priority::Normal;
+    }
+    unsafe { (*local).get_priority() }
+}
+
+// the runtime counters reported by `metrics`
+static LIVE_COROUTINES: AtomicUsize = AtomicUsize::new(0);
+static SPAWNED_COROUTINES: AtomicUsize = AtomicUsize::new(0);
+static FINISHED_COROUTINES: AtomicUsize = AtomicUsize::new(0);
+static IO_EVENTS: AtomicUsize = AtomicUsize::new(0);
+static PARKED_WORKERS: AtomicUsize = AtomicUsize::new(0);
+
 #[cold]
 #[inline(never)]
 fn init_scheduler() {
@@ -55,23 +152,32 @@ fn init_scheduler() {
         io_workers = 1;
         run_on_io = false;
     }
-    let b: Box<Scheduler> = Scheduler::new(io_workers, run_on_io);
+    let b: Box<Scheduler> = Scheduler::new(workers, io_workers, run_on_io);
     unsafe {
         SCHED = Box::into_raw(b);
     }
+    let stack_protection = config().get_stack_protection();
+    if stack_protection {
+        guard_init();
+    }
+
+    filter_cancel_panic();
 
+    let mut threads = Vec::new();
     // run the workers in background
-    for _id in 0..workers {
-        thread::spawn(move || {
-            filter_cancel_panic();
+    for id in 0..workers {
+        threads.push(thread::spawn(move || {
+            WORKER_ID.with(|w| w.set(Some(id)));
+            if stack_protection {
+                guard_init_thread();
+            }
             let s = unsafe { &*SCHED };
-            s.run();
-        });
+            s.run(id);
+        }));
     }
 
     // timer thread
-    thread::spawn(move || {
-        filter_cancel_panic();
+    threads.push(thread::spawn(move || {
         let s = unsafe { &*SCHED };
         // timer function
         let timer_event_handler = |co: Arc<AtomicOption<CoroutineImpl>>| {
@@ -84,22 +190,45 @@ fn init_scheduler() {
         };
 
         s.timer_thread.run(&timer_event_handler);
-    });
+    }));
 
     // io event loop thread
     for id in 0..io_workers {
-        thread::spawn(move || {
-            filter_cancel_panic();
+        threads.push(thread::spawn(move || {
+            WORKER_ID.with(|w| w.set(Some(workers + id)));
+            if stack_protection {
+                guard_init_thread();
+            }
             let s = unsafe { &*SCHED };
-            s.event_loop.run(id).unwrap_or_else(|e| {
+            s.event_loop.run(id, &s.stopped).unwrap_or_else(|e| {
                 panic!("event_loop failed running, err={}", e);
             });
+        }));
+    }
+
+    // stack release thread
+    if let Some(idle) = config().get_stack_idle_release() {
+        let t = thread::spawn(move || {
+            let s = unsafe { &*SCHED };
+            loop {
+                thread::park_timeout(idle);
+                if s.stopped.load(Ordering::Acquire) {
+                    break;
+                }
+                s.pool.release_idle();
+            }
         });
+        let s = unsafe { &*SCHED };
+        *s.releaser.lock().unwrap() = Some(t.thread().clone());
+        threads.push(t);
     }
+
+    let s = unsafe { &*SCHED };
+    *s.threads.lock().unwrap() = threads;
 }
 
 #[inline]
-pub fn get_scheduler() -> &'static Scheduler {
+pub(crate) fn get_scheduler() -> &'static Scheduler {
     unsafe {
         if likely(!SCHED.is_null()) {
             return &*SCHED;
@@ -111,55 +240,261 @@ pub fn get_scheduler() -> &'static Scheduler {
     unsafe { &*SCHED }
 }
 
-pub struct Scheduler {
+// the counters that change at each switch, one set for each thread that
+// runs the coroutines so that they don't share a cache line. the ready
+// count is added by the scheduling thread and subtracted by the worker,
+// so only the sum of all the sets is meaningful
+struct RunCounters {
+    ready: AtomicUsize,
+    running: AtomicUsize,
+    resumes: AtomicUsize,
+}
+
+impl RunCounters {
+    fn new() -> Self {
+        RunCounters {
+            ready: AtomicUsize::new(0),
+            running: AtomicUsize::new(0),
+            resumes: AtomicUsize::new(0),
+        }
+    }
+}
+
+// the per worker data
+struct Worker {
+    // the coroutines that are pinned to the worker, never run by others
+    pinned: mpmc<CoroutineImpl>,
+    // the number of the coroutines in the pinned queue
+    pinned_len: AtomicUsize,
+    // the worker thread, set when it starts
+    thread: Mutex<Option<thread::Thread>>,
+    // the worker is registered in the wait list
+    waiting: AtomicBool,
+}
+
+impl Worker {
+    fn new() -> Self {
+        Worker {
+            pinned: mpmc::new(),
+            pinned_len: AtomicUsize::new(0),
+            thread: Mutex::new(None),
+            waiting: AtomicBool::new(false),
+        }
+    }
+}
+
+pub(crate) struct Scheduler {
     pub pool: CoroutinePool,
     event_loop: EventLoop,
-    ready_list: mpmc<CoroutineImpl>,
-    wait_list: WaitList<thread::Thread>,
+    // the ready lists indexed by the priority
+    ready_list: [mpmc<CoroutineImpl>; 3],
+    workers: Vec<Worker>,
+    // indexed by the worker id, the last one is shared by the other threads
+    counters: Vec<CachePadded<RunCounters>>,
+    // the parked workers, by the index
+    wait_list: WaitList<(usize, thread::Thread)>,
     timer_thread: TimerThread,
+    pub registry: Registry,
+    pub stack_stats: StackHistogram,
+    // no more spawns after the shutdown starts
+    closing: AtomicBool,
+    // ask the scheduler threads to exit
+    stopped: AtomicBool,
+    threads: Mutex<Vec<thread::JoinHandle<()>>>,
+    // the thread that releases the idle stacks of the pool
+    releaser: Mutex<Option<thread::Thread>>,
 }
 
 impl Scheduler {
-    pub fn new(io_workers: usize, run_on_io: bool) -> Box<Self> {
+    pub fn new(workers: usize, io_workers: usize, run_on_io: bool) -> Box<Self> {
         Box::new(Scheduler {
             pool: CoroutinePool::new(),
             event_loop: EventLoop::new(io_workers, run_on_io).expect("can't create event_loop"),
-            ready_list: mpmc::new(),
+            ready_list: [mpmc::new(), mpmc::new(), mpmc::new()],
+            workers: (0..workers).map(|_| Worker::new()).collect(),
+            counters: (0..workers + io_workers + 1)
+                .map(|_| CachePadded::new(RunCounters::new()))
+                .collect(),
             timer_thread: TimerThread::new(),
             wait_list: WaitList::with_capacity(256), // workers: workers,
+            registry: Registry::new(),
+            stack_stats: StackHistogram::new(),
+            closing: AtomicBool::new(false),
+            stopped: AtomicBool::new(false),
+            releaser: Mutex::new(None),
+            threads: Mutex::new(Vec::new()),
         })
     }
 
-    fn run(&self) {
-        loop {
+    // pick the next ready coroutine of the highest priority, except that
+    // the lower priorities are looked at first once in a while
+    #[inline]
+    fn pop_ready(&self, tick: usize) -> Option<CoroutineImpl> {
+        let order = if tick % LOW_FIRST_TICK == 0 {
+            [Priority::Low, Priority::Normal, Priority::High]
+        } else if tick % NORMAL_FIRST_TICK == 0 {
+            [Priority::Normal, Priority::High, Priority::Low]
+        } else {
+            [Priority::High, Priority::Normal, Priority::Low]
+        };
+        order
+            .iter()
+            .filter_map(|&p| self.ready_list[p as usize].try_pop())
+            .next()
+    }
+
+    fn is_ready_empty(&self) -> bool {
+        self.ready_list.iter().all(|l| l.is_empty())
+    }
+
+    // unpark a worker in the wait list
+    #[inline]
+    fn wake_one(&self) {
+        self.wait_list.pop().map(|(id, t)| {
+            self.workers[id].waiting.store(false, Ordering::Release);
+            t.unpark();
+        });
+    }
+
+    fn run(&self, id: usize) {
+        let me = &self.workers[id];
+        *me.thread.lock().unwrap() = Some(thread::current());
+        let mut tick: usize = 0;
+        while !self.stopped.load(Ordering::Acquire) {
+            tick = tick.wrapping_add(1);
+            // the pinned coroutines first, nobody else could run them
+            if let Some(co) = me.pinned.try_pop() {
+                me.pinned_len.fetch_sub(1, Ordering::Relaxed);
+                self.counters().ready.fetch_sub(1, Ordering::Relaxed);
+                run_coroutine(co);
+                continue;
+            }
+
             // steal from the ready list
-            if let Some(co) = self.ready_list.try_pop() {
+            if let Some(co) = self.pop_ready(tick) {
+                self.counters().ready.fetch_sub(1, Ordering::Relaxed);
                 run_coroutine(co);
                 continue;
             }
 
-            // first register thread handle
-            let mut handle = thread::current();
-            while let Err(h) = self.wait_list.push(handle) {
-                handle = h;
+            // first register thread handle, a worker that is waked for the
+            // pinned coroutines is still in the list
+            if !me.waiting.swap(true, Ordering::AcqRel) {
+                let mut handle = (id, thread::current());
+                while let Err(h) = self.wait_list.push(handle) {
+                    handle = h;
+                }
             }
 
             // do a re-check
-            if !self.ready_list.is_empty() {
-                self.wait_list.pop().map(|t| t.unpark());
+            if !self.is_ready_empty() {
+                self.wake_one();
+            }
+            if !me.pinned.is_empty() || self.stopped.load(Ordering::Acquire) {
+                continue;
             }
 
             // thread::park_timeout(Duration::from_millis(100));
+            PARKED_WORKERS.fetch_add(1, Ordering::Relaxed);
             thread::park();
+            PARKED_WORKERS.fetch_sub(1, Ordering::Relaxed);
         }
     }
 
+    // the number of the workers that the scheduler is started with
+    #[inline]
+    pub fn worker_count(&self) -> usize {
+        self.workers.len()
+    }
+
+    // the counters of the current thread
+    #[inline]
+    fn counters(&self) -> &RunCounters {
+        let last = self.counters.len() - 1;
+        let id = worker_id().map_or(last, |id| cmp::min(id, last));
+        &self.counters[id]
+    }
+
+    // the sum of one counter of all the threads
+    fn sum_counters<F: Fn(&RunCounters) -> &AtomicUsize>(&self, f: F) -> usize {
+        let sum = self.counters.iter().fold(0usize, |sum, c| {
+            sum.wrapping_add(f(c).load(Ordering::Relaxed))
+        });
+        // the counters are read one by one, a subtraction could be seen
+        // before the addition it pairs with
+        cmp::max(sum as isize, 0) as usize
+    }
+
+    // return true if the shutdown is started, the spawns are refused
+    #[inline]
+    pub fn is_closing(&self) -> bool {
+        self.closing.load(Ordering::Acquire)
+    }
+
+    // ask all the scheduler threads to exit
+    fn signal_stop(&self) {
+        self.stopped.store(true, Ordering::Release);
+        for w in self.workers.iter() {
+            w.thread.lock().unwrap().as_ref().map(|t| t.unpark());
+        }
+        self.timer_thread.stop();
+        self.releaser.lock().unwrap().as_ref().map(|t| t.unpark());
+        self.event_loop.wakeup_all();
+    }
+
+    // stop all the scheduler threads and wait for them
+    fn stop(&self) -> io::Result<()> {
+        self.signal_stop();
+        let threads = ::std::mem::replace(&mut *self.threads.lock().unwrap(), Vec::new());
+        for t in threads {
+            t.join()
+                .map_err(|_| io::Error::new(io::ErrorKind::Other, "scheduler thread panicked"))?;
+        }
+        self.pool.shrink();
+        Ok(())
+    }
+
     /// put the coroutine to ready list so that next time it can be scheduled
     #[inline]
     pub fn schedule(&self, co: CoroutineImpl) {
-        self.ready_list.push(co);
+        self.counters().ready.fetch_add(1, Ordering::Relaxed);
+        let local = co.get_local_data() as *const CoroutineLocal;
+        if !local.is_null() {
+            unsafe { (*local).get_co().set_state(CoroutineState::Ready) };
+        }
+        let pin = if local.is_null() {
+            None
+        } else {
+            unsafe { (*local).get_pin() }
+        };
+        if let Some(id) = pin {
+            // only the pinned worker could run it, wake it directly
+            let worker = &self.workers[id];
+            worker.pinned_len.fetch_add(1, Ordering::Relaxed);
+            worker.pinned.push(co);
+            worker.thread.lock().unwrap().as_ref().map(|t| t.unpark());
+            return;
+        }
+        let priority = priority_of(&co);
+        self.ready_list[priority as usize].push(co);
         // signal one waiting thread if any
-        self.wait_list.pop().map(|t| t.unpark());
+        self.wake_one();
+    }
+
+    /// put the new coroutines that are not pinned to the ready list together,
+    /// and wake the parked workers at once to share them
+    pub fn schedule_batch(&self, cos: Vec<CoroutineImpl>) {
+        let n = cos.len();
+        self.counters().ready.fetch_add(n, Ordering::Relaxed);
+        for co in cos {
+            let local = co.get_local_data() as *const CoroutineLocal;
+            unsafe { (*local).get_co().set_state(CoroutineState::Ready) };
+            let priority = priority_of(&co);
+            self.ready_list[priority as usize].push(co);
+        }
+        for _ in 0..cmp::min(n, self.workers.len()) {
+            self.wake_one();
+        }
     }
 
     #[inline]
@@ -181,3 +516,278 @@ impl Scheduler {
         self.event_loop.get_selector()
     }
 }
+
+// return true if the scheduler is initialized
+#[inline]
+pub(crate) fn is_started() -> bool {
+    unsafe { !SCHED.is_null() }
+}
+
+// count the coroutine that is spawned
+#[inline]
+pub(crate) fn coroutine_spawned() {
+    LIVE_COROUTINES.fetch_add(1, Ordering::Relaxed);
+    SPAWNED_COROUTINES.fetch_add(1, Ordering::Relaxed);
+}
+
+// count the coroutine that is finished
+#[inline]
+pub(crate) fn coroutine_done() {
+    LIVE_COROUTINES.fetch_sub(1, Ordering::Relaxed);
+    FINISHED_COROUTINES.fetch_add(1, Ordering::Relaxed);
+}
+
+// count the coroutine that is resumed on a worker, until it's suspended
+#[inline]
+pub(crate) fn coroutine_resumed() {
+    let c = get_scheduler().counters();
+    c.running.fetch_add(1, Ordering::Relaxed);
+    c.resumes.fetch_add(1, Ordering::Relaxed);
+}
+
+#[inline]
+pub(crate) fn coroutine_suspended() {
+    get_scheduler()
+        .counters()
+        .running
+        .fetch_sub(1, Ordering::Relaxed);
+}
+
+// count the io events that are got by one select
+#[inline]
+pub(crate) fn count_io_events(n: usize) {
+    if n != 0 {
+        IO_EVENTS.fetch_add(n, Ordering::Relaxed);
+    }
+}
+
+/// A snapshot of the scheduler runtime counters
+///
+/// the counters are read one by one without a lock, so they may be slightly
+/// inconsistent with each other when the scheduler is busy. the total
+/// numbers only grow, sample them twice to get the rates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Metrics {
+    /// the number of the worker threads
+    pub workers: usize,
+    /// the number of the worker threads that are parked for no work
+    pub parked_workers: usize,
+    /// the number of the coroutines that are spawned and not finished yet
+    pub live_coroutines: usize,
+    /// the total number of the spawned coroutines
+    pub spawned_coroutines: usize,
+    /// the total number of the finished coroutines, include the panicked
+    /// and the canceled ones
+    pub finished_coroutines: usize,
+    /// the number of the coroutines in the ready list waiting for a worker
+    ///
+    /// it includes the ones that are pinned to a worker, which
+    /// `pinned_queue_depths` tells for each worker. the other ones are in a
+    /// ready list shared by all the workers, so there is no steal count
+    pub ready_coroutines: usize,
+    /// the number of the coroutines that are running on a worker
+    pub running_coroutines: usize,
+    /// the number of the coroutines that are suspended by a park, a sleep,
+    /// the sync primitives or an io request. `coroutine::dump` tells the
+    /// io waits from the others
+    pub suspended_coroutines: usize,
+    /// the total number of the times that a coroutine is resumed
+    pub resumes: usize,
+    /// the total number of the io events got by the selectors, the wakeups
+    /// of the selectors are not counted
+    pub io_events: usize,
+    /// the number of the pending timers, include the io timeouts
+    pub timers: usize,
+    /// the number of the spawns that reuse a cached coroutine and its stack
+    pub stack_pool_hits: usize,
+    /// the number of the spawns that allocate a new stack, the tracked
+    /// coroutines are not counted
+    pub stack_pool_misses: usize,
+}
+
+/// return a snapshot of the scheduler runtime metrics
+///
+/// it's cheap to call from any coroutine or thread, it only reads a few
+/// atomic counters and doesn't start the scheduler
+///
+/// # Examples
+///
+/// ```rust
+/// let m = may::scheduler::metrics();
+/// println!("live coroutines: {}", m.live_coroutines);
+/// ```
+pub fn metrics() -> Metrics {
+    let (stack_pool_hits, stack_pool_misses) = pool::counters();
+    let live = LIVE_COROUTINES.load(Ordering::Relaxed);
+    let (workers, ready, running, resumes) = if unsafe { SCHED.is_null() } {
+        (0, 0, 0, 0)
+    } else {
+        let s = get_scheduler();
+        (
+            s.worker_count(),
+            s.sum_counters(|c| &c.ready),
+            s.sum_counters(|c| &c.running),
+            s.sum_counters(|c| &c.resumes),
+        )
+    };
+    Metrics {
+        workers: workers,
+        parked_workers: PARKED_WORKERS.load(Ordering::Relaxed),
+        live_coroutines: live,
+        spawned_coroutines: SPAWNED_COROUTINES.load(Ordering::Relaxed),
+        finished_coroutines: FINISHED_COROUTINES.load(Ordering::Relaxed),
+        ready_coroutines: ready,
+        running_coroutines: running,
+        suspended_coroutines: live.saturating_sub(ready + running),
+        resumes: resumes,
+        io_events: IO_EVENTS.load(Ordering::Relaxed),
+        timers: timeout_list::pending_timers(),
+        stack_pool_hits: stack_pool_hits,
+        stack_pool_misses: stack_pool_misses,
+    }
+}
+
+/// return the number of the ready coroutines that are pinned to each
+/// worker, by the worker index
+///
+/// the queue of a worker grows when its pinned coroutines are waked faster
+/// than it runs them. it's empty if the scheduler is not started
+pub fn pinned_queue_depths() -> Vec<usize> {
+    if unsafe { SCHED.is_null() } {
+        return Vec::new();
+    }
+    get_scheduler()
+        .workers
+        .iter()
+        .map(|w| w.pinned_len.load(Ordering::Relaxed))
+        .collect()
+}
+
+/// shut down the scheduler
+///
+/// the new spawns are refused at once, `Builder::spawn` returns an error
+/// and `spawn` panics. all the live coroutines are canceled and they are
+/// waited for `timeout` to unwind. then the worker, timer and io threads
+/// exit and are joined. the second call is a no-op
+///
+/// if some coroutines are still running after the timeout, for example in
+/// a loop that never blocks, the threads are only asked to exit and a
+/// `TimedOut` error is returned. calling it from a coroutine returns an
+/// error, the coroutine would be waiting for itself
+///
+/// the selectors are kept for the streams and listeners that are not
+/// dropped yet, the io on them returns errors or blocks forever
+///
+/// # Examples
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate may;
+///
+/// use std::time::Duration;
+///
+/// fn main() {
+///     go!(|| may::coroutine::park());
+///     may::shutdown(Duration::from_secs(1)).unwrap();
+///     // the scheduler is gone
+///     assert!(go!(may::coroutine::Builder::new(), || {}).is_err());
+/// }
+/// ```
+pub fn shutdown(timeout: Duration) -> io::Result<()> {
+    shutdown_with_grace(Duration::from_secs(0), timeout)
+}
+
+/// shut down the scheduler after the live coroutines are drained
+///
+/// it's the same as `shutdown`, except that the live coroutines are first
+/// given `grace` to finish by themselves, the new spawns are refused during
+/// that. the ones that are left are then canceled and waited for `timeout`
+/// to unwind, so it returns within about `grace` plus `timeout`
+///
+/// a server should check `is_shutting_down` in its accept loop, so that it
+/// stops taking new connections while the ones in flight are finished. it
+/// sleeps and takes locks, call it from a normal thread such as the one
+/// that waits for the signals, not from within a signal handler
+///
+/// # Examples
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate may;
+///
+/// use std::time::Duration;
+/// use may::coroutine;
+/// use may::scheduler::shutdown_with_grace;
+///
+/// fn main() {
+///     // finished within the grace
+///     let h = go!(|| coroutine::sleep(Duration::from_millis(10)));
+///     // canceled after the grace
+///     go!(|| coroutine::park());
+///
+///     shutdown_with_grace(Duration::from_millis(100), Duration::from_secs(1)).unwrap();
+///     assert!(h.join().is_ok());
+/// }
+/// ```
+pub fn shutdown_with_grace(grace: Duration, timeout: Duration) -> io::Result<()> {
+    if is_coroutine() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            "can't shut down the scheduler from a coroutine",
+        ));
+    }
+    if unsafe { SCHED.is_null() } {
+        return Ok(());
+    }
+    let s = get_scheduler();
+    if s.closing.swap(true, Ordering::AcqRel) {
+        return Ok(());
+    }
+
+    if !wait_coroutines(grace) {
+        for co in s.registry.all() {
+            unsafe { co.cancel() };
+        }
+        if !wait_coroutines(timeout) {
+            s.signal_stop();
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "coroutines are still running after the shutdown timeout",
+            ));
+        }
+    }
+    s.stop()
+}
+
+// wait for all the coroutines to be done, return false on timeout
+fn wait_coroutines(timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    while LIVE_COROUTINES.load(Ordering::Acquire) != 0 {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    true
+}
+
+/// return true if `shutdown` is called, the new spawns are refused
+///
+/// the long running loops, such as the accept loop of a server, could
+/// check it to quit during the grace of the shutdown
+#[inline]
+pub fn is_shutting_down() -> bool {
+    is_started() && get_scheduler().is_closing()
+}
+
+/// drop all the cached coroutines and give their stacks back to the system
+///
+/// the cache is filled again by the coroutines that are done later, call it
+/// after a burst of spawns when the memory is more precious than the spawn
+/// cost
+pub fn shrink_stack_pool() {
+    if unsafe { SCHED.is_null() } {
+        return;
+    }
+    get_scheduler().pool.shrink();
+}
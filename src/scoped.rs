@@ -6,7 +6,8 @@ use std::panic;
 use std::thread;
 use std::rc::Rc;
 use std::sync::Arc;
-use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::cell::{Cell, RefCell};
 use std::sync::atomic::Ordering;
 
 use coroutine_impl::{spawn, Coroutine};
@@ -207,3 +208,97 @@ impl<'a> Drop for Scope<'a> {
         self.drop_all()
     }
 }
+
+/// A scope that collects the results of its coroutines
+pub struct CollectScope<'a, T> {
+    children: RefCell<Vec<(JoinHandle<()>, Arc<AtomicOption<T>>)>>,
+    // the spawned closures may borrow anything that lives for 'a
+    _marker: PhantomData<Cell<&'a ()>>,
+}
+
+/// Create a new scope that joins all the spawned coroutines and returns
+/// their results in the spawn order.
+///
+/// a panicked coroutine gives the `Err` with its panic payload, the others
+/// are still joined, so no coroutine outlives the scope even if the scope
+/// closure itself panics
+///
+/// # Examples
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate may;
+///
+/// use may::coroutine;
+///
+/// fn main() {
+///     let data = vec![1, 2, 3, 4];
+///     let ret = coroutine::scope_collect(|s| {
+///         for chunk in data.chunks(2) {
+///             go!(s, move || chunk.iter().sum::<i32>());
+///         }
+///     });
+///     let sums: Vec<i32> = ret.into_iter().map(|r| r.unwrap()).collect();
+///     assert_eq!(sums, vec![3, 7]);
+/// }
+/// ```
+pub fn scope_collect<'a, F, T>(f: F) -> Vec<thread::Result<T>>
+where
+    F: FnOnce(&CollectScope<'a, T>),
+{
+    let scope = CollectScope {
+        children: RefCell::new(Vec::new()),
+        _marker: PhantomData,
+    };
+    f(&scope);
+    scope.join_all()
+}
+
+impl<'a, T> fmt::Debug for CollectScope<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "CollectScope {{ ... }}")
+    }
+}
+
+impl<'a, T> CollectScope<'a, T> {
+    /// Create a scoped coroutine whose result is collected by the scope.
+    ///
+    /// the same as `Scope::spawn`, the returned handle could be used to
+    /// unpark or cancel the coroutine
+    pub unsafe fn spawn<F>(&self, f: F) -> Coroutine
+    where
+        F: FnOnce() -> T + Send + 'a,
+        T: Send + 'a,
+    {
+        let their_packet = Arc::new(AtomicOption::none());
+        let my_packet = their_packet.clone();
+
+        let join_handle = spawn_unsafe(move || {
+            their_packet.swap(f(), Ordering::Relaxed);
+        });
+
+        let co = join_handle.coroutine().clone();
+        self.children.borrow_mut().push((join_handle, my_packet));
+        co
+    }
+
+    // join all the coroutines spawned so far
+    fn join_all(&self) -> Vec<thread::Result<T>> {
+        let children = mem::replace(&mut *self.children.borrow_mut(), Vec::new());
+        children
+            .into_iter()
+            .map(|(handle, packet)| {
+                handle
+                    .join()
+                    .map(|_| packet.take(Ordering::Relaxed).unwrap())
+            })
+            .collect()
+    }
+}
+
+impl<'a, T> Drop for CollectScope<'a, T> {
+    fn drop(&mut self) {
+        // only left over when the scope closure panics
+        self.join_all();
+    }
+}
@@ -1,10 +1,12 @@
+use std::fmt;
 use std::thread;
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use sync::AtomicOption;
 use scheduler::get_scheduler;
 use yield_now::{get_co_para, yield_with};
 use coroutine_impl::{co_cancel_data, is_coroutine, CoroutineImpl, EventSource};
+use listener::ParkReason;
 
 struct Sleep {
     dur: Duration,
@@ -25,6 +27,10 @@ impl EventSource for Sleep {
             unsafe { cancel.cancel() };
         }
     }
+
+    fn park_reason(&self) -> Option<ParkReason> {
+        Some(ParkReason::Timer)
+    }
 }
 
 /// block the current coroutine until timeout
@@ -38,3 +44,170 @@ pub fn sleep(dur: Duration) {
     // consume the timeout error
     get_co_para();
 }
+
+/// block the current coroutine until the deadline
+///
+/// it never returns before the deadline, advance the deadline by a fixed
+/// step for a periodic task that doesn't drift
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::{Duration, Instant};
+/// use may::coroutine;
+///
+/// let step = Duration::from_millis(10);
+/// let mut deadline = Instant::now();
+/// for _ in 0..3 {
+///     deadline += step;
+///     coroutine::sleep_until(deadline);
+///     assert!(Instant::now() >= deadline);
+/// }
+/// ```
+pub fn sleep_until(deadline: Instant) {
+    loop {
+        let now = Instant::now();
+        if now >= deadline {
+            return;
+        }
+        // the timer could fire a bit early due to its resolution
+        sleep(deadline - now);
+    }
+}
+
+/// What an `Interval` does with the ticks that are missed by a slow
+/// coroutine
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MissedTickBehavior {
+    /// fire the missed ticks one after another at once until it catches up,
+    /// so that no tick is lost. this is the default
+    Burst,
+    /// fire the latest missed tick only, the next one is the following
+    /// multiple of the period
+    Skip,
+}
+
+/// A timer that fires at the multiples of a period after the start
+///
+/// the deadlines are fixed by the start and the period, so the time taken
+/// by the work between the ticks doesn't shift them. unlike `Ticker` it's
+/// not a channel, it's waited by `tick`, which could also be an arm of
+/// `select!`
+///
+/// # Examples
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate may;
+///
+/// use std::time::Duration;
+/// use may::coroutine;
+/// use may::sync::mpsc::channel;
+///
+/// fn main() {
+///     let interval = coroutine::interval(Duration::from_millis(10));
+///     let (tx, rx) = channel();
+///     go!(move || {
+///         coroutine::sleep(Duration::from_millis(35));
+///         tx.send(()).unwrap();
+///     });
+///
+///     let mut ticks = 0;
+///     loop {
+///         let id = select!(
+///             _ = interval.tick() => ticks += 1,
+///             _ = rx.recv() => {}
+///         );
+///         if id == 1 {
+///             break;
+///         }
+///     }
+///     println!("{} ticks before the message", ticks);
+/// }
+/// ```
+pub struct Interval {
+    period: Duration,
+    behavior: MissedTickBehavior,
+    // the deadline of the next tick
+    next: Mutex<Instant>,
+}
+
+/// create an `Interval` that fires every `period`, the first tick is a
+/// period after now
+pub fn interval(period: Duration) -> Interval {
+    interval_at(Instant::now() + period, period)
+}
+
+/// create an `Interval` that fires at `start` and then every `period`
+pub fn interval_at(start: Instant, period: Duration) -> Interval {
+    assert!(period > Duration::from_secs(0), "zero interval period");
+    Interval {
+        period: period,
+        behavior: MissedTickBehavior::Burst,
+        next: Mutex::new(start),
+    }
+}
+
+impl Interval {
+    /// block until the next tick and return its deadline
+    ///
+    /// a canceled wait doesn't consume the tick, the next call waits for
+    /// the same one. when it's shared, each tick is got by only one caller
+    pub fn tick(&self) -> Instant {
+        loop {
+            let deadline = *self.next.lock().unwrap();
+            sleep_until(deadline);
+
+            let mut next = self.next.lock().unwrap();
+            if *next != deadline {
+                // got by another caller
+                continue;
+            }
+            let fired = match self.behavior {
+                MissedTickBehavior::Burst => deadline,
+                MissedTickBehavior::Skip => {
+                    let missed = nanos(Instant::now() - deadline) / nanos(self.period);
+                    deadline + from_nanos(missed.saturating_mul(nanos(self.period)))
+                }
+            };
+            *next = fired + self.period;
+            return fired;
+        }
+    }
+
+    /// set what to do with the missed ticks
+    pub fn set_missed_tick_behavior(&mut self, behavior: MissedTickBehavior) {
+        self.behavior = behavior;
+    }
+
+    /// get what to do with the missed ticks
+    pub fn missed_tick_behavior(&self) -> MissedTickBehavior {
+        self.behavior
+    }
+
+    /// get the period of the ticks
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+}
+
+impl fmt::Debug for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Interval")
+            .field("period", &self.period)
+            .field("behavior", &self.behavior)
+            .finish()
+    }
+}
+
+#[inline]
+fn nanos(dur: Duration) -> u64 {
+    dur.as_secs()
+        .saturating_mul(1_000_000_000)
+        .saturating_add(dur.subsec_nanos() as u64)
+}
+
+#[inline]
+fn from_nanos(ns: u64) -> Duration {
+    Duration::new(ns / 1_000_000_000, (ns % 1_000_000_000) as u32)
+}
@@ -0,0 +1,142 @@
+//! report the coroutine that overflows its stack
+//!
+//! the coroutine stacks are allocated by `generator` with a `PROT_NONE`
+//! guard page below the bottom, so an overflow faults instead of writing
+//! into the memory next to the stack. the pooled coroutines keep their stack
+//! together with the guard page. the `SIGSEGV`/`SIGBUS` handler installed
+//! here tells which coroutine hits its guard page and aborts. the other
+//! faults are passed to the previous handler
+
+use std::mem;
+use std::ptr;
+use std::sync::{Once, ONCE_INIT};
+
+use libc;
+use generator::get_local_data;
+use local::CoroutineLocal;
+
+// the size of the signal stack installed for the workers without one
+const ALT_STACK_SIZE: usize = 0x10000;
+
+static mut OLD_SEGV: Option<libc::sigaction> = None;
+static mut OLD_BUS: Option<libc::sigaction> = None;
+
+fn page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+// write to stderr without any allocation, the fault may happen in malloc
+fn write_str(s: &str) {
+    unsafe { libc::write(2, s.as_ptr() as *const libc::c_void, s.len()) };
+}
+
+fn write_num(mut n: usize) {
+    let mut buf = [0u8; 20];
+    let mut i = buf.len();
+    loop {
+        i -= 1;
+        buf[i] = b'0' + (n % 10) as u8;
+        n /= 10;
+        if n == 0 {
+            break;
+        }
+    }
+    unsafe {
+        libc::write(
+            2,
+            buf[i..].as_ptr() as *const libc::c_void,
+            buf.len() - i,
+        )
+    };
+}
+
+extern "C" fn handler(signum: libc::c_int, info: *mut libc::siginfo_t, _ctx: *mut libc::c_void) {
+    let local = get_local_data() as *const CoroutineLocal;
+    if !local.is_null() {
+        let local = unsafe { &*local };
+        let addr = unsafe { (*info).si_addr() } as usize;
+        let (top, size) = local.get_stack();
+        // the stack is rounded to pages, and the guard page is below it
+        let bottom = top.saturating_sub(size + 2 * page_size());
+        if top != 0 && addr >= bottom && addr < top {
+            let co = local.get_co();
+            write_str("coroutine '");
+            write_str(co.name().unwrap_or("<unnamed>"));
+            write_str("' (id ");
+            write_num(co.id());
+            write_str(") has overflowed its stack, stack size = ");
+            write_num(size);
+            write_str(" bytes\n");
+            unsafe { libc::abort() };
+        }
+    }
+
+    // not an overflow of a coroutine, restore the previous handler and
+    // return, the fault happens again and is handled by it
+    unsafe {
+        let old = if signum == libc::SIGSEGV {
+            OLD_SEGV
+        } else {
+            OLD_BUS
+        };
+        match old {
+            Some(ref old) => libc::sigaction(signum, old, ptr::null_mut()),
+            None => libc::signal(signum, libc::SIG_DFL) as libc::c_int,
+        };
+    }
+}
+
+unsafe fn install(signum: libc::c_int) -> Option<libc::sigaction> {
+    let mut action: libc::sigaction = mem::zeroed();
+    action.sa_sigaction = handler as libc::sighandler_t;
+    action.sa_flags = libc::SA_SIGINFO | libc::SA_ONSTACK;
+    libc::sigemptyset(&mut action.sa_mask);
+    let mut old: libc::sigaction = mem::zeroed();
+    if libc::sigaction(signum, &action, &mut old) == -1 {
+        error!("failed to install the stack overflow handler");
+        return None;
+    }
+    Some(old)
+}
+
+/// install the handler for the process, only the first call takes effect
+pub fn init() {
+    static ONCE: Once = ONCE_INIT;
+    ONCE.call_once(|| unsafe {
+        OLD_SEGV = install(libc::SIGSEGV);
+        OLD_BUS = install(libc::SIGBUS);
+    });
+}
+
+/// make sure the worker thread has a signal stack, the handler can't run
+/// on the stack that is overflowed
+pub fn init_thread() {
+    unsafe {
+        let mut old: libc::stack_t = mem::zeroed();
+        libc::sigaltstack(ptr::null(), &mut old);
+        if old.ss_flags & libc::SS_DISABLE == 0 {
+            // the std runtime already installs one for its threads
+            return;
+        }
+
+        let stack = libc::mmap(
+            ptr::null_mut(),
+            ALT_STACK_SIZE,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANON,
+            -1,
+            0,
+        );
+        if stack == libc::MAP_FAILED {
+            error!("failed to allocate the signal stack");
+            return;
+        }
+        // the workers only exit on shutdown, the stack is never freed
+        let new = libc::stack_t {
+            ss_sp: stack,
+            ss_flags: 0,
+            ss_size: ALT_STACK_SIZE,
+        };
+        libc::sigaltstack(&new, ptr::null_mut());
+    }
+}
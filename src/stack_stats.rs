@@ -0,0 +1,82 @@
+//! the histogram of the peak stack usage of the tracked coroutines
+//!
+//! only the coroutines spawned with `track_stack` are measured, the others
+//! never touch the histogram
+
+use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use scheduler::get_scheduler;
+
+/// The peak stack usage of the tracked coroutines that are done
+///
+/// # Examples
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate may;
+///
+/// use may::coroutine::{self, Builder};
+///
+/// fn main() {
+///     let h = go!(Builder::new().track_stack(true), || {}).unwrap();
+///     h.join().unwrap();
+///
+///     let stats = coroutine::stack_stats();
+///     assert!(stats.count >= 1);
+///     // the stack should be sized to the largest bucket with some margin
+///     let &(limit, _) = stats.buckets.last().unwrap();
+///     assert!(limit.is_power_of_two());
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackStats {
+    /// the number of the measured coroutines
+    pub count: usize,
+    /// the `(limit, count)` pairs in the increasing order of the limit,
+    /// `count` coroutines used more than the previous power of two and at
+    /// most `limit` bytes. the empty buckets are not listed
+    pub buckets: Vec<(usize, usize)>,
+}
+
+pub(crate) struct StackHistogram {
+    buckets: Vec<AtomicUsize>,
+}
+
+impl StackHistogram {
+    pub fn new() -> Self {
+        StackHistogram {
+            // one bucket for each power of two
+            buckets: (0..mem::size_of::<usize>() * 8)
+                .map(|_| AtomicUsize::new(0))
+                .collect(),
+        }
+    }
+
+    // count the peak usage in bytes of a coroutine
+    pub fn record(&self, bytes: usize) {
+        let i = bytes.next_power_of_two().trailing_zeros() as usize;
+        self.buckets[i].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// get the histogram of the peak stack usage
+///
+/// the coroutines are counted when they are done, together with the value
+/// of `JoinHandle::stack_high_water`. run the program once with
+/// `Config::set_track_stack` on, and the stack size could be set to cover
+/// the largest bucket
+pub fn stack_stats() -> StackStats {
+    let buckets = get_scheduler()
+        .stack_stats
+        .buckets
+        .iter()
+        .enumerate()
+        .map(|(i, n)| (1 << i, n.load(Ordering::Relaxed)))
+        .filter(|&(_, n)| n != 0)
+        .collect::<Vec<_>>();
+    StackStats {
+        count: buckets.iter().map(|&(_, n)| n).sum(),
+        buckets: buckets,
+    }
+}
@@ -0,0 +1,198 @@
+use std::fmt;
+use super::{Condvar, Mutex};
+
+/// A barrier enables multiple threads/coroutines to synchronize the
+/// beginning of some computation.
+///
+/// the barrier is reusable, each round is identified by its generation so
+/// a coroutine that enters the next round early is never counted twice
+///
+/// # Examples
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate may;
+///
+/// use std::sync::Arc;
+/// use may::sync::Barrier;
+///
+/// fn main() {
+///     let n = 10;
+///     let barrier = Arc::new(Barrier::new(n));
+///     let handles = (0..n)
+///         .map(|_| {
+///             let c = barrier.clone();
+///             // The same messages will be printed together.
+///             // You will NOT see any interleaving.
+///             go!(move || {
+///                 println!("before wait");
+///                 c.wait();
+///                 println!("after wait");
+///             })
+///         })
+///         .collect::<Vec<_>>();
+///     // Wait for other coroutines to finish.
+///     for h in handles {
+///         h.join().unwrap();
+///     }
+/// }
+/// ```
+pub struct Barrier {
+    lock: Mutex<BarrierState>,
+    cvar: Condvar,
+    num: usize,
+}
+
+// the inner state of the barrier
+struct BarrierState {
+    count: usize,
+    generation_id: usize,
+}
+
+/// A `BarrierWaitResult` is returned by `wait` when all threads/coroutines
+/// in the `Barrier` have rendezvoused.
+pub struct BarrierWaitResult(bool);
+
+impl Barrier {
+    /// Creates a new barrier that can block a given number of threads/coroutines.
+    ///
+    /// A barrier will block `n`-1 threads/coroutines which call `wait` and
+    /// then wake up all of them at once when the `n`th one calls `wait`.
+    pub fn new(n: usize) -> Barrier {
+        Barrier {
+            lock: Mutex::new(BarrierState {
+                count: 0,
+                generation_id: 0,
+            }),
+            cvar: Condvar::new(),
+            num: n,
+        }
+    }
+
+    /// Blocks the current thread/coroutine until all of them have
+    /// rendezvoused here.
+    ///
+    /// A single (arbitrary) one will receive a `BarrierWaitResult` that
+    /// returns `true` from `is_leader` when returning from this function,
+    /// and all the others will receive a result that returns `false`.
+    pub fn wait(&self) -> BarrierWaitResult {
+        let mut lock = self.lock.lock().unwrap();
+        let local_gen = lock.generation_id;
+        lock.count += 1;
+        if lock.count < self.num {
+            // We need a while loop to guard against spurious wakeups.
+            while local_gen == lock.generation_id {
+                lock = self.cvar.wait(lock).unwrap();
+            }
+            BarrierWaitResult(false)
+        } else {
+            lock.count = 0;
+            lock.generation_id = lock.generation_id.wrapping_add(1);
+            self.cvar.notify_all();
+            BarrierWaitResult(true)
+        }
+    }
+}
+
+impl fmt::Debug for Barrier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("Barrier { .. }")
+    }
+}
+
+impl BarrierWaitResult {
+    /// Returns whether this thread/coroutine from `wait` is the "leader thread".
+    ///
+    /// Only one of them will have `true` returned from their result, all
+    /// others will have `false` returned.
+    pub fn is_leader(&self) -> bool {
+        self.0
+    }
+}
+
+impl fmt::Debug for BarrierWaitResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BarrierWaitResult")
+            .field("is_leader", &self.is_leader())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use sync::mpsc::channel;
+    use std::sync::mpsc::TryRecvError;
+    use super::*;
+
+    #[test]
+    fn test_barrier() {
+        const N: usize = 10;
+
+        let barrier = Arc::new(Barrier::new(N));
+        let (tx, rx) = channel();
+
+        for i in 0..N - 1 {
+            let c = barrier.clone();
+            let tx = tx.clone();
+            if i % 2 == 0 {
+                go!(move || {
+                    tx.send(c.wait().is_leader()).unwrap();
+                });
+            } else {
+                thread::spawn(move || {
+                    tx.send(c.wait().is_leader()).unwrap();
+                });
+            }
+        }
+
+        // At this point, all spawned waiters should be blocked,
+        // so we shouldn't get anything from the port
+        thread::sleep(::std::time::Duration::from_millis(10));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+
+        let mut leader_found = barrier.wait().is_leader();
+
+        // Now, the barrier is cleared and we should get data.
+        for _ in 0..N - 1 {
+            if rx.recv().unwrap() {
+                assert!(!leader_found);
+                leader_found = true;
+            }
+        }
+        assert!(leader_found);
+    }
+
+    #[test]
+    fn test_barrier_reuse() {
+        const N: usize = 8;
+        const ROUNDS: usize = 100;
+
+        let barrier = Arc::new(Barrier::new(N));
+        let leaders = Arc::new(AtomicUsize::new(0));
+        let arrived = Arc::new(AtomicUsize::new(0));
+
+        let handles = (0..N)
+            .map(|_| {
+                let barrier = barrier.clone();
+                let leaders = leaders.clone();
+                let arrived = arrived.clone();
+                go!(move || for round in 0..ROUNDS {
+                    arrived.fetch_add(1, Ordering::SeqCst);
+                    if barrier.wait().is_leader() {
+                        leaders.fetch_add(1, Ordering::SeqCst);
+                    }
+                    // nobody could leave the round before all arrived
+                    assert!(arrived.load(Ordering::SeqCst) >= (round + 1) * N);
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(leaders.load(Ordering::SeqCst), ROUNDS);
+    }
+}
@@ -55,13 +55,49 @@ pub enum Parker {
     Thread(ThreadPark),
 }
 
+/// The parking primitive that the sync types are built on
+///
+/// `park` blocks the coroutine or the thread that creates the blocker, and
+/// `unpark` wakes it up from anywhere. a coroutine is yielded back to the
+/// scheduler while parked, and its timeout is a timer of the scheduler. an
+/// `unpark` before the `park` is remembered, the `park` returns at once.
+/// create it on the coroutine or the thread that is going to park, and only
+/// park there
+///
+/// # Examples
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate may;
+///
+/// use std::time::Duration;
+/// use may::coroutine::ParkError;
+/// use may::sync::Blocker;
+///
+/// fn main() {
+///     let h = go!(|| {
+///         let blocker = Blocker::current();
+///         let r = blocker.park(Some(Duration::from_millis(10)));
+///         assert_eq!(r, Err(ParkError::Timeout));
+///
+///         let b = blocker.clone();
+///         go!(move || b.unpark());
+///         blocker.park(None).unwrap();
+///     });
+///     h.join().unwrap();
+/// }
+/// ```
 #[derive(Debug)]
 pub struct Blocker {
     parker: Parker,
 }
 
 impl Blocker {
-    /// create a new blocker
+    /// create a new blocker for the current coroutine or thread
+    ///
+    /// a cancel of the parked coroutine unwinds it like the other blocking
+    /// calls. when `ignore_cancel` is true the `park` returns
+    /// `ParkError::Canceled` instead, and the caller deals with the cancel
     pub fn new(ignore_cancel: bool) -> Self {
         let parker = if is_coroutine() {
             let park = Park::new();
@@ -75,11 +111,16 @@ impl Blocker {
         Blocker { parker: parker }
     }
 
-    /// get the internal shared blocker
+    /// create a shared blocker for the current coroutine or thread, a
+    /// cancel unwinds the parked coroutine
     pub fn current() -> Arc<Self> {
         Arc::new(Self::new(false))
     }
 
+    /// block until `unpark` is called or the timeout expires
+    ///
+    /// return `ParkError::Timeout` on the timeout, and `ParkError::Canceled`
+    /// if the parked coroutine is canceled when the cancel is ignored
     #[inline]
     pub fn park(&self, timeout: Option<Duration>) -> Result<(), ParkError> {
         match self.parker {
@@ -88,6 +129,7 @@ impl Blocker {
         }
     }
 
+    /// wake up the parked one, or let the next `park` return at once
     #[inline]
     pub fn unpark(&self) {
         match self.parker {
@@ -120,20 +162,22 @@ impl SyncBlocker {
         })
     }
 
+    // the unparked and release flags are written by different sides and each
+    // side reads the other's flag after its own write, so they need SeqCst
     #[inline]
     pub fn is_unparked(&self) -> bool {
-        self.unparked.load(Ordering::Acquire)
+        self.unparked.load(Ordering::SeqCst)
     }
     // set the Flag for the release action
     #[inline]
     pub fn set_release(&self) {
-        self.release.store(true, Ordering::Release);
+        self.release.store(true, Ordering::SeqCst);
     }
 
     // take the release Flag
     #[inline]
     pub fn take_release(&self) -> bool {
-        self.release.swap(false, Ordering::Acquire)
+        self.release.swap(false, Ordering::SeqCst)
     }
 
     #[inline]
@@ -144,6 +188,6 @@ impl SyncBlocker {
     #[inline]
     pub fn unpark(&self) {
         self.blocker.unpark();
-        self.unparked.store(true, Ordering::Release);
+        self.unparked.store(true, Ordering::SeqCst);
     }
 }
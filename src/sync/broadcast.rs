@@ -0,0 +1,468 @@
+//! broadcast channel implementation
+//! every message is cloned to all the receivers that are subscribed when
+//! the message is sent. the messages are kept in a bounded ring, a receiver
+//! that falls behind the ring would get a `Lagged` error instead of
+//! blocking the sender
+
+use std::{error, fmt};
+use std::cell::Cell;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use super::{Blocker, Mutex};
+
+/// Error returned by `Sender::send` when there is no receiver
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub struct SendError<T>(pub T);
+
+/// Error returned by `Receiver::recv`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RecvError {
+    /// all the senders are dropped and there is no more message
+    Closed,
+    /// the receiver fell behind, the oldest `n` messages are skipped
+    Lagged(u64),
+}
+
+/// Error returned by `Receiver::try_recv`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TryRecvError {
+    /// there is no new message yet
+    Empty,
+    /// all the senders are dropped and there is no more message
+    Closed,
+    /// the receiver fell behind, the oldest `n` messages are skipped
+    Lagged(u64),
+}
+
+/// Error returned by `Receiver::recv_timeout`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RecvTimeoutError {
+    /// no new message arrived within the timeout
+    Timeout,
+    /// all the senders are dropped and there is no more message
+    Closed,
+    /// the receiver fell behind, the oldest `n` messages are skipped
+    Lagged(u64),
+}
+
+struct State<T> {
+    // the ring buffer, message with sequence `n` is at `n % capacity`
+    buf: Vec<Option<T>>,
+    // the sequence of the next message
+    tail: u64,
+    senders: usize,
+    receivers: usize,
+    // blocked receivers waiting for the next message
+    to_wake: Vec<Arc<Blocker>>,
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+}
+
+/// The sending half of a broadcast channel
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+/// The receiving half of a broadcast channel
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+    // the sequence of the next message to receive
+    next: Cell<u64>,
+}
+
+unsafe impl<T: Send> Send for Sender<T> {}
+unsafe impl<T: Send> Sync for Sender<T> {}
+unsafe impl<T: Send> Send for Receiver<T> {}
+
+/// create a broadcast channel that keeps the last `capacity` messages
+///
+/// # Examples
+///
+/// ```rust
+/// use may::sync::broadcast;
+///
+/// let (tx, rx1) = broadcast::channel(16);
+/// let rx2 = tx.subscribe();
+/// tx.send(10).unwrap();
+///
+/// assert_eq!(rx1.recv(), Ok(10));
+/// assert_eq!(rx2.recv(), Ok(10));
+/// ```
+pub fn channel<T: Clone>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "broadcast channel capacity must be positive");
+    let mut buf = Vec::with_capacity(capacity);
+    for _ in 0..capacity {
+        buf.push(None);
+    }
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            buf: buf,
+            tail: 0,
+            senders: 1,
+            receivers: 1,
+            to_wake: Vec::new(),
+        }),
+    });
+    let rx = Receiver {
+        shared: shared.clone(),
+        next: Cell::new(0),
+    };
+    (Sender { shared: shared }, rx)
+}
+
+/// /////////////////////////////////////////////////////////////////////////////
+/// Sender
+/// /////////////////////////////////////////////////////////////////////////////
+
+impl<T: Clone> Sender<T> {
+    /// send the message to all the subscribed receivers, never blocks
+    ///
+    /// return the number of receivers that would see the message
+    pub fn send(&self, t: T) -> Result<usize, SendError<T>> {
+        let to_wake = {
+            let mut state = self.shared.state.lock().unwrap();
+            if state.receivers == 0 {
+                return Err(SendError(t));
+            }
+            let idx = (state.tail % state.buf.len() as u64) as usize;
+            state.buf[idx] = Some(t);
+            state.tail += 1;
+            ::std::mem::replace(&mut state.to_wake, Vec::new())
+        };
+
+        for w in &to_wake {
+            w.unpark();
+        }
+        Ok(self.receiver_count())
+    }
+
+    /// create a new receiver that would see all the messages sent after
+    pub fn subscribe(&self) -> Receiver<T> {
+        let mut state = self.shared.state.lock().unwrap();
+        state.receivers += 1;
+        Receiver {
+            shared: self.shared.clone(),
+            next: Cell::new(state.tail),
+        }
+    }
+
+    /// return the number of the active receivers
+    pub fn receiver_count(&self) -> usize {
+        self.shared.state.lock().unwrap().receivers
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Sender<T> {
+        self.shared.state.lock().unwrap().senders += 1;
+        Sender {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let to_wake = {
+            let mut state = self.shared.state.lock().unwrap();
+            state.senders -= 1;
+            if state.senders > 0 {
+                return;
+            }
+            ::std::mem::replace(&mut state.to_wake, Vec::new())
+        };
+
+        // wake up all the blocked receivers to see the close
+        for w in &to_wake {
+            w.unpark();
+        }
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Sender {{ .. }}")
+    }
+}
+
+/// /////////////////////////////////////////////////////////////////////////////
+/// Receiver
+/// /////////////////////////////////////////////////////////////////////////////
+
+impl<T: Clone> Receiver<T> {
+    // try to get the next message, register the blocker if nothing is ready
+    fn recv_impl(&self, blocker: Option<&Arc<Blocker>>) -> Result<T, TryRecvError> {
+        let mut state = self.shared.state.lock().unwrap();
+        let cap = state.buf.len() as u64;
+        let next = self.next.get();
+        if next < state.tail {
+            // the oldest messages are already overwritten
+            if state.tail - next > cap {
+                let oldest = state.tail - cap;
+                self.next.set(oldest);
+                return Err(TryRecvError::Lagged(oldest - next));
+            }
+            let idx = (next % cap) as usize;
+            self.next.set(next + 1);
+            return Ok(state.buf[idx].clone().expect("broadcast slot is empty"));
+        }
+
+        if state.senders == 0 {
+            return Err(TryRecvError::Closed);
+        }
+        // the blocker of a wait is registered once, it's dropped from the
+        // list by the sender that wakes it
+        if let Some(b) = blocker {
+            if !state.to_wake.iter().any(|w| Arc::ptr_eq(w, b)) {
+                state.to_wake.push(b.clone());
+            }
+        }
+        Err(TryRecvError::Empty)
+    }
+
+    // remove the blocker of a wait that gives up
+    fn remove_waiter(&self, blocker: &Arc<Blocker>) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.to_wake.retain(|w| !Arc::ptr_eq(w, blocker));
+    }
+
+    /// try to receive the next message without blocking
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        self.recv_impl(None)
+    }
+
+    /// block until the next message arrives
+    pub fn recv(&self) -> Result<T, RecvError> {
+        let cur = Blocker::current();
+        loop {
+            match self.recv_impl(Some(&cur)) {
+                Ok(t) => return Ok(t),
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Closed) => return Err(RecvError::Closed),
+                Err(TryRecvError::Lagged(n)) => return Err(RecvError::Lagged(n)),
+            }
+            cur.park(None).ok();
+        }
+    }
+
+    /// block until the next message arrives or the timeout elapsed
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        let cur = Blocker::current();
+        loop {
+            match self.recv_impl(Some(&cur)) {
+                Ok(t) => return Ok(t),
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Closed) => return Err(RecvTimeoutError::Closed),
+                Err(TryRecvError::Lagged(n)) => return Err(RecvTimeoutError::Lagged(n)),
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                // or the senders would keep waking the dead blocker
+                self.remove_waiter(&cur);
+                return Err(RecvTimeoutError::Timeout);
+            }
+            cur.park(Some(deadline - now)).ok();
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().receivers -= 1;
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Receiver {{ .. }}")
+    }
+}
+
+/// /////////////////////////////////////////////////////////////////////////////
+/// Errors
+/// /////////////////////////////////////////////////////////////////////////////
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        "SendError(..)".fmt(f)
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        "sending on a channel with no receiver".fmt(f)
+    }
+}
+
+impl<T: Send> error::Error for SendError<T> {
+    fn description(&self) -> &str {
+        "sending on a channel with no receiver"
+    }
+}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RecvError::Closed => "receiving on a closed channel".fmt(f),
+            RecvError::Lagged(n) => write!(f, "receiver lagged behind by {} messages", n),
+        }
+    }
+}
+
+impl error::Error for RecvError {
+    fn description(&self) -> &str {
+        match *self {
+            RecvError::Closed => "receiving on a closed channel",
+            RecvError::Lagged(_) => "receiver lagged behind",
+        }
+    }
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TryRecvError::Empty => "receiving on an empty channel".fmt(f),
+            TryRecvError::Closed => "receiving on a closed channel".fmt(f),
+            TryRecvError::Lagged(n) => write!(f, "receiver lagged behind by {} messages", n),
+        }
+    }
+}
+
+impl error::Error for TryRecvError {
+    fn description(&self) -> &str {
+        match *self {
+            TryRecvError::Empty => "receiving on an empty channel",
+            TryRecvError::Closed => "receiving on a closed channel",
+            TryRecvError::Lagged(_) => "receiver lagged behind",
+        }
+    }
+}
+
+impl fmt::Display for RecvTimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RecvTimeoutError::Timeout => "timed out waiting on channel".fmt(f),
+            RecvTimeoutError::Closed => "receiving on a closed channel".fmt(f),
+            RecvTimeoutError::Lagged(n) => write!(f, "receiver lagged behind by {} messages", n),
+        }
+    }
+}
+
+impl error::Error for RecvTimeoutError {
+    fn description(&self) -> &str {
+        match *self {
+            RecvTimeoutError::Timeout => "timed out waiting on channel",
+            RecvTimeoutError::Closed => "receiving on a closed channel",
+            RecvTimeoutError::Lagged(_) => "receiver lagged behind",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::Duration;
+    use super::*;
+
+    #[test]
+    fn smoke() {
+        let (tx, rx) = channel(4);
+        assert_eq!(tx.send(1), Ok(1));
+        assert_eq!(rx.recv(), Ok(1));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+        drop(rx);
+        assert_eq!(tx.send(2), Err(SendError(2)));
+    }
+
+    #[test]
+    fn fan_out() {
+        const N: usize = 10;
+        const M: usize = 100;
+
+        let (tx, rx) = channel(M);
+        let mut vec = vec![];
+        for _ in 0..N {
+            let rx = tx.subscribe();
+            vec.push(go!(move || {
+                // every receiver sees all the messages in order
+                for i in 0..M {
+                    assert_eq!(rx.recv(), Ok(i));
+                }
+                assert_eq!(rx.recv(), Err(RecvError::Closed));
+            }));
+        }
+        drop(rx);
+        assert_eq!(tx.receiver_count(), N);
+
+        thread::sleep(Duration::from_millis(10));
+        for i in 0..M {
+            assert_eq!(tx.send(i), Ok(N));
+        }
+        drop(tx);
+        for h in vec {
+            h.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn subscribe_late() {
+        let (tx, rx1) = channel(4);
+        tx.send(1).unwrap();
+        let rx2 = tx.subscribe();
+        tx.send(2).unwrap();
+        assert_eq!(rx1.recv(), Ok(1));
+        assert_eq!(rx1.recv(), Ok(2));
+        // only the messages sent after subscribe are seen
+        assert_eq!(rx2.recv(), Ok(2));
+        assert_eq!(rx2.try_recv(), Err(TryRecvError::Empty));
+    }
+
+    #[test]
+    fn lagged() {
+        let (tx, rx) = channel(2);
+        for i in 0..5 {
+            // the sender is never blocked by the slow receiver
+            tx.send(i).unwrap();
+        }
+        assert_eq!(rx.recv(), Err(RecvError::Lagged(3)));
+        assert_eq!(rx.recv(), Ok(3));
+        assert_eq!(rx.recv(), Ok(4));
+        drop(tx);
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Closed));
+    }
+
+    #[test]
+    fn recv_timeout() {
+        let (tx, rx) = channel::<i32>(2);
+        let h = go!(move || {
+            let ret = rx.recv_timeout(Duration::from_millis(10));
+            assert_eq!(ret, Err(RecvTimeoutError::Timeout));
+            assert_eq!(rx.recv_timeout(Duration::from_secs(10)), Ok(1));
+        });
+        thread::sleep(Duration::from_millis(50));
+        tx.send(1).unwrap();
+        h.join().unwrap();
+    }
+
+    #[test]
+    fn recv_timeout_leaves_list() {
+        let (tx, rx) = channel::<i32>(2);
+        for _ in 0..10 {
+            let ret = rx.recv_timeout(Duration::from_millis(1));
+            assert_eq!(ret, Err(RecvTimeoutError::Timeout));
+        }
+        assert!(rx.shared.state.lock().unwrap().to_wake.is_empty());
+
+        // a wait that is waked by the send leaves the list as well
+        let h = go!(move || rx.recv_timeout(Duration::from_secs(10)));
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(tx.shared.state.lock().unwrap().to_wake.len(), 1);
+        tx.send(1).unwrap();
+        assert_eq!(h.join().unwrap(), Ok(1));
+        assert!(tx.shared.state.lock().unwrap().to_wake.is_empty());
+    }
+}
@@ -270,6 +270,47 @@ mod tests {
         drop(g);
     }
 
+    #[test]
+    fn wait_timeout_co() {
+        const N: usize = 100;
+
+        let data = Arc::new((Mutex::new(Vec::new()), Condvar::new()));
+
+        let data1 = data.clone();
+        let consumer = go!(move || {
+            let &(ref lock, ref cond) = &*data1;
+            let mut got = 0;
+            let mut timeouts = 0;
+            let mut q = lock.lock().unwrap();
+            while got < N {
+                if let Some(_) = q.pop() {
+                    got += 1;
+                    continue;
+                }
+                // the guard is held again no matter how the wait returns
+                let (g, res) = cond.wait_timeout(q, Duration::from_millis(5)).unwrap();
+                q = g;
+                if res.timed_out() {
+                    timeouts += 1;
+                    assert!(data1.0.try_lock().is_err());
+                }
+            }
+            timeouts
+        });
+
+        let &(ref lock, ref cond) = &*data;
+        for i in 0..N {
+            if i % 10 == 0 {
+                // let the consumer time out now and then
+                thread::sleep(Duration::from_millis(10));
+            }
+            lock.lock().unwrap().push(i);
+            cond.notify_one();
+        }
+        assert!(consumer.join().unwrap() > 0);
+        assert!(lock.lock().unwrap().is_empty());
+    }
+
     #[test]
     #[should_panic]
     fn two_mutexes() {
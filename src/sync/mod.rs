@@ -1,10 +1,14 @@
 mod mutex;
+mod barrier;
 mod rwlock;
 mod poison;
 mod condvar;
 mod semphore;
+mod notify;
+mod sync_flag;
 mod blocking;
 mod mpsc_list;
+mod wait_group;
 #[cfg(nightly)]
 #[path = "atomic_option.rs"]
 mod atomic_option;
@@ -14,10 +18,15 @@ mod atomic_option;
 
 pub mod mpsc;
 pub mod mpmc;
+pub mod broadcast;
 pub(crate) mod delay_drop;
 pub use self::blocking::Blocker;
+pub use self::barrier::{Barrier, BarrierWaitResult};
 pub use self::semphore::Semphore;
+pub use self::notify::Notify;
+pub use self::sync_flag::SyncFlag;
+pub use self::wait_group::WaitGroup;
 pub use self::mutex::{Mutex, MutexGuard};
 pub use self::atomic_option::AtomicOption;
 pub use self::condvar::{Condvar, WaitTimeoutResult};
-pub use self::rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+pub use self::rwlock::{RwLock, RwLockReadGuard, RwLockUpgradableReadGuard, RwLockWriteGuard};
@@ -725,6 +725,28 @@ mod tests {
         assert_eq!(rx.recv_timeout(Duration::from_millis(1)), Ok(()));
     }
 
+    #[test]
+    fn recv_timeout_then_recv() {
+        let (tx, rx) = channel::<i32>();
+        let rx2 = rx.clone();
+        let rx = go!(move || {
+            assert_eq!(
+                rx.recv_timeout(Duration::from_millis(10)),
+                Err(RecvTimeoutError::Timeout)
+            );
+            rx
+        }).join()
+            .unwrap();
+
+        // the send goes to the live waiter, not the timed out one
+        let h = go!(move || rx2.recv().unwrap());
+        ::coroutine::sleep(Duration::from_millis(10));
+        tx.send(1).unwrap();
+        assert_eq!(h.join().unwrap(), 1);
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+        assert_eq!(tx.pressure(), 0);
+    }
+
     #[test]
     fn stress_recv_timeout_two_threads() {
         let (tx, rx) = channel();
@@ -1,15 +1,16 @@
 //! compatible with std::sync::mpsc except for both thread and coroutine
 //! please ref the doc from std::sync::mpsc
 use std::fmt;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 use std::panic::{RefUnwindSafe, UnwindSafe};
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::sync::mpsc::{RecvError, RecvTimeoutError, SendError, TryRecvError};
+use std::sync::mpsc::{RecvError, RecvTimeoutError, SendError, TryRecvError, TrySendError};
 use super::mpsc_list;
-use super::{AtomicOption, Blocker};
+use super::{AtomicOption, Blocker, Semphore};
+use park::ParkError;
+use cancel::trigger_cancel_panic;
 
-// TODO: SyncSender
 /// /////////////////////////////////////////////////////////////////////////////
 /// InnerQueue
 /// /////////////////////////////////////////////////////////////////////////////
@@ -21,6 +22,15 @@ struct InnerQueue<T> {
     channels: AtomicUsize,
     // if rx is dropped
     port_dropped: AtomicBool,
+    // free slots for the sync channel, each received item posts one
+    // for the rendezvous channel it's the ack for the in flight item
+    slots: Option<Semphore>,
+    // only one rendezvous sender could have its item in flight
+    sending: Option<Semphore>,
+    // the in flight item that is given back when the port is dropped
+    returned: AtomicOption<T>,
+    // the rendezvous push can't cross the port drop
+    push_lock: Mutex<()>,
 }
 
 impl<T> InnerQueue<T> {
@@ -30,6 +40,97 @@ impl<T> InnerQueue<T> {
             to_wake: AtomicOption::none(),
             channels: AtomicUsize::new(1),
             port_dropped: AtomicBool::new(false),
+            slots: None,
+            sending: None,
+            returned: AtomicOption::none(),
+            push_lock: Mutex::new(()),
+        }
+    }
+
+    pub fn with_bound(bound: usize) -> InnerQueue<T> {
+        InnerQueue {
+            queue: mpsc_list::Queue::new(),
+            to_wake: AtomicOption::none(),
+            channels: AtomicUsize::new(1),
+            port_dropped: AtomicBool::new(false),
+            slots: Some(Semphore::new(bound)),
+            sending: if bound == 0 {
+                Some(Semphore::new(1))
+            } else {
+                None
+            },
+            returned: AtomicOption::none(),
+            push_lock: Mutex::new(()),
+        }
+    }
+
+    // block until there is a free slot
+    pub fn send_sync(&self, t: T) -> Result<(), T> {
+        if self.port_dropped.load(Ordering::SeqCst) {
+            return Err(t);
+        }
+        let slots = self.slots.as_ref().expect("not a sync channel");
+        match self.sending {
+            None => {
+                slots.wait();
+                let ret = self.send(t);
+                if ret.is_err() {
+                    // the port is dropped, pass the wakeup on to the next
+                    // blocked sender so that all of them get the error
+                    slots.post();
+                }
+                ret
+            }
+            Some(ref sending) => {
+                sending.wait();
+                let ret = self.hand_over(t, slots);
+                sending.post();
+                ret
+            }
+        }
+    }
+
+    pub fn try_send_sync(&self, t: T) -> Result<(), TrySendError<T>> {
+        if self.port_dropped.load(Ordering::SeqCst) {
+            return Err(TrySendError::Disconnected(t));
+        }
+        let slots = self.slots.as_ref().expect("not a sync channel");
+        let sending = match self.sending {
+            None => {
+                if !slots.try_wait() {
+                    return Err(TrySendError::Full(t));
+                }
+                return self.send(t).map_err(TrySendError::Disconnected);
+            }
+            Some(ref sending) => sending,
+        };
+
+        // rendezvous, only hand over to a receiver that is already waiting
+        if !sending.try_wait() {
+            return Err(TrySendError::Full(t));
+        }
+        let ret = if self.to_wake.is_none() {
+            Err(TrySendError::Full(t))
+        } else {
+            self.hand_over(t, slots)
+                .map_err(TrySendError::Disconnected)
+        };
+        sending.post();
+        ret
+    }
+
+    // push the rendezvous item and wait until the receiver takes it
+    fn hand_over(&self, t: T, ack: &Semphore) -> Result<(), T> {
+        {
+            // either the port sees the item when dropped, or we see the drop
+            let _lock = self.push_lock.lock().unwrap();
+            self.send(t)?;
+        }
+        ack.wait();
+        // the port is dropped before taking the item
+        match self.returned.take(Ordering::Acquire) {
+            Some(t) => Err(t),
+            None => Ok(()),
         }
     }
 
@@ -56,7 +157,10 @@ impl<T> InnerQueue<T> {
         // re-check the queue
         match self.try_recv() {
             Err(TryRecvError::Empty) => {
-                cur.park(dur).ok();
+                if cur.park(dur).is_err() {
+                    // timeout, don't leave the dead waiter for the next send
+                    self.to_wake.take(Ordering::Acquire);
+                }
             }
             data => {
                 // no need to park, contention with send
@@ -73,7 +177,7 @@ impl<T> InnerQueue<T> {
     }
 
     pub fn try_recv(&self) -> Result<T, TryRecvError> {
-        match self.queue.pop() {
+        let ret = match self.queue.pop() {
             Some(data) => Ok(data),
             None => {
                 match self.channels.load(Ordering::SeqCst) {
@@ -82,7 +186,12 @@ impl<T> InnerQueue<T> {
                     _ => Err(TryRecvError::Empty),
                 }
             }
+        };
+        if ret.is_ok() {
+            // release the slot to the blocked sender
+            self.slots.as_ref().map(|s| s.post());
         }
+        ret
     }
 
     pub fn clone_chan(&self) {
@@ -101,9 +210,25 @@ impl<T> InnerQueue<T> {
     }
 
     pub fn drop_port(&self) {
-        self.port_dropped.store(true, Ordering::SeqCst);
+        {
+            // a rendezvous push in progress is finished before the drop
+            let _lock = self.push_lock.lock().unwrap();
+            self.port_dropped.store(true, Ordering::SeqCst);
+        }
+        if self.sending.is_some() {
+            // give the in flight item back to the rendezvous sender
+            self.queue
+                .pop()
+                .map(|t| self.returned.swap(t, Ordering::Release));
+        }
         // clear all the data
         while let Some(_) = self.queue.pop() {}
+        // wake up the blocked senders, each sender waits at most one slot
+        if let Some(ref slots) = self.slots {
+            for _ in 0..self.channels.load(Ordering::SeqCst) {
+                slots.post();
+            }
+        }
     }
 }
 
@@ -142,11 +267,29 @@ unsafe impl<T: Send> Send for Sender<T> {}
 impl<T: Send> UnwindSafe for Sender<T> {}
 impl<T: Send> RefUnwindSafe for Sender<T> {}
 
+pub struct SyncSender<T> {
+    inner: Arc<InnerQueue<T>>,
+}
+
+unsafe impl<T: Send> Send for SyncSender<T> {}
+impl<T: Send> UnwindSafe for SyncSender<T> {}
+impl<T: Send> RefUnwindSafe for SyncSender<T> {}
+
 pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
     let a = Arc::new(InnerQueue::new());
     (Sender::new(a.clone()), Receiver::new(a))
 }
 
+/// create a bounded channel, `send` blocks when `bound` items are buffered
+///
+/// a `bound` of 0 makes a rendezvous channel, `send` returns only after the
+/// receiver has taken the item and `try_send` only succeeds when the
+/// receiver is already waiting for it
+pub fn sync_channel<T>(bound: usize) -> (SyncSender<T>, Receiver<T>) {
+    let a = Arc::new(InnerQueue::with_bound(bound));
+    (SyncSender::new(a.clone()), Receiver::new(a))
+}
+
 /// /////////////////////////////////////////////////////////////////////////////
 /// Sender
 /// /////////////////////////////////////////////////////////////////////////////
@@ -180,6 +323,43 @@ impl<T> fmt::Debug for Sender<T> {
     }
 }
 
+/// /////////////////////////////////////////////////////////////////////////////
+/// SyncSender
+/// /////////////////////////////////////////////////////////////////////////////
+
+impl<T> SyncSender<T> {
+    fn new(inner: Arc<InnerQueue<T>>) -> SyncSender<T> {
+        SyncSender { inner: inner }
+    }
+
+    pub fn send(&self, t: T) -> Result<(), SendError<T>> {
+        self.inner.send_sync(t).map_err(SendError)
+    }
+
+    pub fn try_send(&self, t: T) -> Result<(), TrySendError<T>> {
+        self.inner.try_send_sync(t)
+    }
+}
+
+impl<T> Clone for SyncSender<T> {
+    fn clone(&self) -> SyncSender<T> {
+        self.inner.clone_chan();
+        SyncSender::new(self.inner.clone())
+    }
+}
+
+impl<T> Drop for SyncSender<T> {
+    fn drop(&mut self) {
+        self.inner.drop_chan();
+    }
+}
+
+impl<T> fmt::Debug for SyncSender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SyncSender {{ .. }}")
+    }
+}
+
 /// /////////////////////////////////////////////////////////////////////////////
 /// Receiver
 /// /////////////////////////////////////////////////////////////////////////////
@@ -214,6 +394,7 @@ impl<T> Receiver<T> {
 
     fn recv_max_until(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
         let deadline = Instant::now() + timeout;
+        let mut timeout = timeout;
         loop {
             match self.inner.recv(Some(timeout)) {
                 Ok(t) => return Ok(t),
@@ -222,10 +403,12 @@ impl<T> Receiver<T> {
             }
 
             // If we're already passed the deadline, and we're here without
-            // data, return a timeout, else try again.
-            if Instant::now() >= deadline {
+            // data, return a timeout, else wait for the rest of the time.
+            let now = Instant::now();
+            if now >= deadline {
                 return Err(RecvTimeoutError::Timeout);
             }
+            timeout = deadline - now;
         }
     }
 
@@ -291,6 +474,221 @@ impl<T> fmt::Debug for Receiver<T> {
     }
 }
 
+/// /////////////////////////////////////////////////////////////////////////////
+/// After
+/// /////////////////////////////////////////////////////////////////////////////
+
+/// A receiver that delivers only one message after the timeout elapsed
+///
+/// the message is the `Instant` when it's delivered, following receives
+/// return a disconnected error. no timer is running until it's waited on
+pub struct After {
+    deadline: Instant,
+    fired: AtomicBool,
+}
+
+/// create a receiver that fires once after `dur`
+///
+/// it's mainly used as the timeout arm of `select!`
+///
+/// # Examples
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate may;
+///
+/// use std::time::Duration;
+/// use may::sync::mpsc::{after, channel};
+///
+/// fn main() {
+///     let (_tx, rx) = channel::<u32>();
+///     let id = select!(
+///         _ = rx.recv() => {},
+///         _ = after(Duration::from_millis(10)).recv() => {}
+///     );
+///     assert_eq!(id, 1);
+/// }
+/// ```
+pub fn after(dur: Duration) -> After {
+    After {
+        deadline: Instant::now() + dur,
+        fired: AtomicBool::new(false),
+    }
+}
+
+impl After {
+    pub fn try_recv(&self) -> Result<Instant, TryRecvError> {
+        if self.fired.load(Ordering::Acquire) {
+            return Err(TryRecvError::Disconnected);
+        }
+        let now = Instant::now();
+        if now < self.deadline {
+            return Err(TryRecvError::Empty);
+        }
+        if self.fired.swap(true, Ordering::AcqRel) {
+            return Err(TryRecvError::Disconnected);
+        }
+        Ok(now)
+    }
+
+    pub fn recv(&self) -> Result<Instant, RecvError> {
+        if self.fired.load(Ordering::Acquire) {
+            return Err(RecvError);
+        }
+        let now = Instant::now();
+        if now < self.deadline {
+            ::sleep::sleep(self.deadline - now);
+        }
+        if self.fired.swap(true, Ordering::AcqRel) {
+            return Err(RecvError);
+        }
+        Ok(Instant::now())
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<Instant, RecvTimeoutError> {
+        if self.fired.load(Ordering::Acquire) {
+            return Err(RecvTimeoutError::Disconnected);
+        }
+        let now = Instant::now();
+        if now + timeout < self.deadline {
+            ::sleep::sleep(timeout);
+            return Err(RecvTimeoutError::Timeout);
+        }
+        self.recv().map_err(|_| RecvTimeoutError::Disconnected)
+    }
+}
+
+impl fmt::Debug for After {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "After {{ .. }}")
+    }
+}
+
+/// /////////////////////////////////////////////////////////////////////////////
+/// Ticker
+/// /////////////////////////////////////////////////////////////////////////////
+
+/// A receiver that delivers a message once per period
+///
+/// the message is the `Instant` when it's delivered. the ticks that are
+/// missed by a slow receiver are coalesced into one, and the next tick is a
+/// full period after it. no timer is running until it's waited on
+pub struct Ticker {
+    period: Duration,
+    // the deadline of the next tick
+    next: Mutex<Instant>,
+    stopped: AtomicBool,
+    // the waiting receivers of a shared ticker, waked by stop
+    to_wake: Mutex<Vec<Arc<Blocker>>>,
+}
+
+/// create a receiver that fires every `period`
+///
+/// # Examples
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate may;
+///
+/// use std::time::Duration;
+/// use may::coroutine;
+///
+/// fn main() {
+///     let ticker = coroutine::ticker(Duration::from_millis(10));
+///     let h = go!(move || {
+///         for _ in 0..3 {
+///             ticker.recv().unwrap();
+///             // flush the metrics
+///         }
+///         ticker.stop();
+///         assert!(ticker.recv().is_err());
+///     });
+///     h.join().unwrap();
+/// }
+/// ```
+pub fn ticker(period: Duration) -> Ticker {
+    assert!(period > Duration::from_secs(0), "zero ticker period");
+    Ticker {
+        period: period,
+        next: Mutex::new(Instant::now() + period),
+        stopped: AtomicBool::new(false),
+        to_wake: Mutex::new(Vec::new()),
+    }
+}
+
+impl Ticker {
+    // fire the tick if it's due, or return the time to wait for it
+    fn tick(&self) -> Result<Instant, Duration> {
+        let mut next = self.next.lock().unwrap();
+        let now = Instant::now();
+        if now < *next {
+            return Err(*next - now);
+        }
+        *next += self.period;
+        if *next <= now {
+            // coalesce the missed ticks
+            *next = now + self.period;
+        }
+        Ok(now)
+    }
+
+    pub fn try_recv(&self) -> Result<Instant, TryRecvError> {
+        if self.stopped.load(Ordering::Acquire) {
+            return Err(TryRecvError::Disconnected);
+        }
+        self.tick().map_err(|_| TryRecvError::Empty)
+    }
+
+    pub fn recv(&self) -> Result<Instant, RecvError> {
+        loop {
+            if self.stopped.load(Ordering::Acquire) {
+                return Err(RecvError);
+            }
+            let dur = match self.tick() {
+                Ok(now) => return Ok(now),
+                Err(dur) => dur,
+            };
+
+            let cur = Blocker::current();
+            {
+                let mut to_wake = self.to_wake.lock().unwrap();
+                // re-check the stop, it drains the list under the lock
+                if self.stopped.load(Ordering::Acquire) {
+                    return Err(RecvError);
+                }
+                // register the waiter
+                to_wake.push(cur.clone());
+            }
+            let ret = cur.park(Some(dur));
+            self.to_wake
+                .lock()
+                .unwrap()
+                .retain(|w| !Arc::ptr_eq(w, &cur));
+            if ret == Err(ParkError::Canceled) {
+                trigger_cancel_panic();
+            }
+        }
+    }
+
+    /// stop the ticker, the waiting and the following receives return a
+    /// disconnected error
+    ///
+    /// a ticker shared by several receivers wakes all of them
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Release);
+        let to_wake = ::std::mem::replace(&mut *self.to_wake.lock().unwrap(), Vec::new());
+        for w in to_wake {
+            w.unpark();
+        }
+    }
+}
+
+impl fmt::Debug for Ticker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Ticker {{ .. }}")
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::env;
@@ -945,6 +1343,130 @@ mod tests {
         assert_eq!(iter.next().is_none(), true);
     }
 
+    #[test]
+    fn test_recv_into_iter_coroutine() {
+        let (tx, rx) = channel::<usize>();
+        let (done_tx, done_rx) = channel();
+
+        // the consumer parks on each recv, its worker keeps running
+        let consumer = go!(move || {
+            let sum = rx.into_iter().sum::<usize>();
+            done_tx.send(Instant::now()).unwrap();
+            sum
+        });
+
+        let producers = (0..4)
+            .map(|i| {
+                let tx = tx.clone();
+                go!(move || for j in 0..10 {
+                    tx.send(i * 10 + j).unwrap();
+                    ::coroutine::yield_now();
+                })
+            })
+            .collect::<Vec<_>>();
+        for p in producers {
+            p.join().unwrap();
+        }
+
+        // the loop ends as soon as the last sender is gone
+        ::coroutine::sleep(Duration::from_millis(20));
+        assert!(done_rx.try_recv().is_err());
+        let dropped = Instant::now();
+        drop(tx);
+        assert_eq!(consumer.join().unwrap(), (0..40).sum());
+        let ended = done_rx.recv().unwrap();
+        assert!(ended.duration_since(dropped) < Duration::from_millis(100));
+    }
+
+    #[test]
+    fn recv_timeout_clears_waiter() {
+        let (tx, rx) = channel::<i32>();
+        let rx = go!(move || {
+            assert_eq!(
+                rx.recv_timeout(Duration::from_millis(10)),
+                Err(RecvTimeoutError::Timeout)
+            );
+            // the send after the timeout doesn't wake a dead waiter
+            assert!(rx.inner.to_wake.is_none());
+            rx
+        }).join()
+            .unwrap();
+
+        let h = go!(move || rx.recv().unwrap());
+        ::coroutine::sleep(Duration::from_millis(10));
+        tx.send(1).unwrap();
+        assert_eq!(h.join().unwrap(), 1);
+    }
+
+    #[test]
+    fn recv_timeout_send_at_expiry() {
+        // the data sent around the deadline is either got or left in the
+        // channel, never lost
+        fn check(rx: &Receiver<usize>, i: usize) {
+            match rx.recv_timeout(Duration::from_millis(2)) {
+                Ok(v) => assert_eq!(v, i),
+                Err(RecvTimeoutError::Timeout) => {
+                    assert!(rx.inner.to_wake.is_none());
+                    assert_eq!(rx.recv(), Ok(i));
+                }
+                Err(e) => panic!("unexpected {:?}", e),
+            }
+        }
+
+        let (tx, rx) = channel();
+        go!(move || {
+            for i in 0..100 {
+                let tx = tx.clone();
+                go!(move || {
+                    ::coroutine::sleep(Duration::from_millis(1 + i as u64 % 3));
+                    tx.send(i).unwrap();
+                });
+                check(&rx, i);
+            }
+        }).join()
+            .unwrap();
+
+        // in thread context
+        let (tx, rx) = channel();
+        for i in 0..100 {
+            let tx = tx.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(1 + i as u64 % 3));
+                tx.send(i).unwrap();
+            });
+            check(&rx, i);
+        }
+    }
+
+    #[test]
+    fn recv_timeout_disconnect() {
+        let (tx, rx) = channel::<()>();
+        let h = go!(move || {
+            let now = Instant::now();
+            let r = rx.recv_timeout(Duration::from_secs(10));
+            (r, now.elapsed())
+        });
+        ::coroutine::sleep(Duration::from_millis(20));
+        drop(tx);
+        let (r, dur) = h.join().unwrap();
+        assert_eq!(r, Err(RecvTimeoutError::Disconnected));
+        assert!(dur < Duration::from_secs(1));
+
+        // in thread context
+        let (tx, rx) = channel::<()>();
+        let h = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            drop(tx);
+        });
+        let now = Instant::now();
+        assert_eq!(
+            rx.recv_timeout(Duration::from_secs(10)),
+            Err(RecvTimeoutError::Disconnected)
+        );
+        assert!(now.elapsed() < Duration::from_secs(1));
+        h.join().unwrap();
+    }
+
     #[test]
     fn try_recv_states() {
         let (tx1, rx1) = channel::<i32>();
@@ -992,17 +1514,97 @@ mod tests {
 
         // wait for the child thread to exit before we exit
         rx2.recv().unwrap();
-    } /*
+    }
+
+    #[test]
+    fn after_fires_once() {
+        let timer = after(Duration::from_millis(20));
+        assert_eq!(timer.try_recv(), Err(TryRecvError::Empty));
+        assert_eq!(
+            timer.recv_timeout(Duration::from_millis(1)),
+            Err(RecvTimeoutError::Timeout)
+        );
+
+        let h = go!(move || {
+            let start = Instant::now();
+            assert!(timer.recv().is_ok());
+            assert!(start.elapsed() >= Duration::from_millis(10));
+            // it's only delivered once
+            assert_eq!(timer.recv(), Err(RecvError));
+            assert_eq!(timer.try_recv(), Err(TryRecvError::Disconnected));
+        });
+        h.join().unwrap();
+    }
+
+    #[test]
+    fn ticker_coalesce() {
+        let ticker = ticker(Duration::from_millis(10));
+        assert_eq!(ticker.try_recv(), Err(TryRecvError::Empty));
+
+        let h = go!(move || {
+            let start = Instant::now();
+            for _ in 0..5 {
+                ticker.recv().unwrap();
+            }
+            let elapsed = start.elapsed();
+            assert!(elapsed >= Duration::from_millis(45));
+            assert!(elapsed < Duration::from_millis(500));
+
+            // the ticks missed by a slow receiver are delivered only once
+            ::coroutine::sleep(Duration::from_millis(50));
+            assert!(ticker.try_recv().is_ok());
+            assert_eq!(ticker.try_recv(), Err(TryRecvError::Empty));
+            ticker
+        });
+        let ticker = h.join().unwrap();
+        ticker.stop();
+        assert_eq!(ticker.try_recv(), Err(TryRecvError::Disconnected));
+    }
+
+    #[test]
+    fn ticker_stop_wakes_receiver() {
+        use std::sync::Arc;
+
+        let ticker = Arc::new(ticker(Duration::from_secs(10)));
+        let t = ticker.clone();
+        let h = go!(move || t.recv());
+        ::coroutine::sleep(Duration::from_millis(20));
+        let start = Instant::now();
+        ticker.stop();
+        assert_eq!(h.join().unwrap(), Err(RecvError));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn ticker_stop_wakes_all_receivers() {
+        use std::sync::Arc;
+
+        let ticker = Arc::new(ticker(Duration::from_secs(10)));
+        let hs = (0..5)
+            .map(|_| {
+                let t = ticker.clone();
+                go!(move || t.recv())
+            })
+            .collect::<Vec<_>>();
+        ::coroutine::sleep(Duration::from_millis(20));
+        assert_eq!(ticker.to_wake.lock().unwrap().len(), 5);
+        let start = Instant::now();
+        ticker.stop();
+        for h in hs {
+            assert_eq!(h.join().unwrap(), Err(RecvError));
+        }
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert!(ticker.to_wake.lock().unwrap().is_empty());
+    }
 }
 
 #[cfg(test)]
 mod sync_tests {
-    use prelude::v1::*;
-
-    use env;
-    use thread;
+    use std::env;
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use std::sync::mpsc::{RecvTimeoutError, TryRecvError, TrySendError};
     use super::*;
-    use time::Duration;
 
     pub fn stress_factor() -> usize {
         match env::var("RUST_TEST_STRESS") {
@@ -1021,7 +1623,7 @@ mod sync_tests {
     #[test]
     fn drop_full() {
         let (tx, _rx) = sync_channel::<Box<isize>>(1);
-        tx.send(box 1).unwrap();
+        tx.send(Box::new(1)).unwrap();
     }
 
     #[test]
@@ -1052,6 +1654,39 @@ mod sync_tests {
         assert_eq!(rx.recv().unwrap(), 1);
     }
 
+    #[test]
+    fn rendezvous_coroutines() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let (req_tx, req_rx) = sync_channel::<usize>(0);
+        let (resp_tx, resp_rx) = sync_channel::<usize>(0);
+        let taken = Arc::new(AtomicUsize::new(0));
+
+        let t = taken.clone();
+        let server = go!(move || {
+            // the sender waits for this slow receiver
+            ::coroutine::sleep(Duration::from_millis(50));
+            for req in req_rx.iter() {
+                t.store(req, Ordering::SeqCst);
+                resp_tx.send(req * 2).unwrap();
+            }
+        });
+
+        let start = Instant::now();
+        req_tx.send(1).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(40));
+        // the send and the recv cross in both directions
+        for i in 1..100 {
+            assert_eq!(resp_rx.recv().unwrap(), i * 2);
+            assert_eq!(taken.load(Ordering::SeqCst), i);
+            req_tx.send(i + 1).unwrap();
+        }
+        assert_eq!(resp_rx.recv().unwrap(), 200);
+        drop(req_tx);
+        server.join().unwrap();
+    }
+
     #[test]
     fn smoke_port_gone() {
         let (tx, rx) = sync_channel::<i32>(0);
@@ -1059,6 +1694,20 @@ mod sync_tests {
         assert!(tx.send(1).is_err());
     }
 
+    #[test]
+    fn port_gone_while_rendezvous() {
+        // the item is never received, so every send must fail
+        for i in 0..200 {
+            let (tx, rx) = sync_channel::<i32>(0);
+            let t = thread::spawn(move || tx.send(i));
+            if i % 2 == 0 {
+                thread::yield_now();
+            }
+            drop(rx);
+            assert_eq!(t.join().unwrap(), Err(SendError(i)));
+        }
+    }
+
     #[test]
     fn smoke_shared_port_gone2() {
         let (tx, rx) = sync_channel::<i32>(0);
@@ -1068,6 +1717,39 @@ mod sync_tests {
         assert!(tx2.send(1).is_err());
     }
 
+    #[test]
+    fn port_gone_full() {
+        let (tx, rx) = sync_channel::<i32>(1);
+        tx.send(1).unwrap();
+        drop(rx);
+        // repeated sends never block after the port is gone
+        for i in 0..3 {
+            assert_eq!(tx.send(i), Err(SendError(i)));
+        }
+    }
+
+    #[test]
+    fn port_gone_wakes_all_blocked() {
+        let (tx, rx) = sync_channel::<i32>(1);
+        tx.send(0).unwrap();
+        let senders = (0..4)
+            .map(|i| {
+                let tx = tx.clone();
+                go!(move || {
+                    // block on the full channel, then send again after the error
+                    let a = tx.send(i).is_err();
+                    let b = tx.send(i).is_err();
+                    a && b
+                })
+            })
+            .collect::<Vec<_>>();
+        thread::sleep(Duration::from_millis(20));
+        drop(rx);
+        for s in senders {
+            assert!(s.join().unwrap());
+        }
+    }
+
     #[test]
     fn port_gone_concurrent() {
         let (tx, rx) = sync_channel::<i32>(0);
@@ -1240,7 +1922,7 @@ mod sync_tests {
         // Testing that the sender cleans up the payload if receiver is closed
         let (tx, rx) = sync_channel::<Box<i32>>(0);
         drop(rx);
-        assert!(tx.send(box 0).is_err());
+        assert!(tx.send(Box::new(0)).is_err());
     }
 
     #[test]
@@ -1259,8 +1941,8 @@ mod sync_tests {
     #[test]
     fn oneshot_single_thread_send_then_recv() {
         let (tx, rx) = sync_channel::<Box<i32>>(1);
-        tx.send(box 10).unwrap();
-        assert!(rx.recv().unwrap() == box 10);
+        tx.send(Box::new(10)).unwrap();
+        assert!(rx.recv().unwrap() == Box::new(10));
     }
 
     #[test]
@@ -1270,18 +1952,18 @@ mod sync_tests {
         assert!(rx.recv().unwrap() == 10);
     }
 
-    // #[test]
-    // fn oneshot_single_thread_try_send_closed() {
-    //     let (tx, rx) = sync_channel::<i32>(0);
-    //     drop(rx);
-    //     assert_eq!(tx.try_send(10), Err(TrySendError::Disconnected(10)));
-    // }
+    #[test]
+    fn oneshot_single_thread_try_send_closed() {
+        let (tx, rx) = sync_channel::<i32>(0);
+        drop(rx);
+        assert_eq!(tx.try_send(10), Err(TrySendError::Disconnected(10)));
+    }
 
-    // #[test]
-    // fn oneshot_single_thread_try_send_closed2() {
-    //     let (tx, _rx) = sync_channel::<i32>(0);
-    //     assert_eq!(tx.try_send(10), Err(TrySendError::Full(10)));
-    // }
+    #[test]
+    fn oneshot_single_thread_try_send_closed2() {
+        let (tx, _rx) = sync_channel::<i32>(0);
+        assert_eq!(tx.try_send(10), Err(TrySendError::Full(10)));
+    }
 
     #[test]
     fn oneshot_single_thread_try_recv_open() {
@@ -1332,10 +2014,10 @@ mod sync_tests {
     fn oneshot_multi_task_recv_then_send() {
         let (tx, rx) = sync_channel::<Box<i32>>(0);
         let _t = thread::spawn(move || {
-            assert!(rx.recv().unwrap() == box 10);
+            assert!(rx.recv().unwrap() == Box::new(10));
         });
 
-        tx.send(box 10).unwrap();
+        tx.send(Box::new(10)).unwrap();
     }
 
     #[test]
@@ -1345,7 +2027,7 @@ mod sync_tests {
             drop(tx);
         });
         let res = thread::spawn(move || {
-                assert!(rx.recv().unwrap() == box 10);
+                assert!(rx.recv().unwrap() == Box::new(10));
             })
             .join();
         assert!(res.is_err());
@@ -1400,9 +2082,9 @@ mod sync_tests {
         for _ in 0..stress_factor() {
             let (tx, rx) = sync_channel::<Box<i32>>(0);
             let _t = thread::spawn(move || {
-                tx.send(box 10).unwrap();
+                tx.send(Box::new(10)).unwrap();
             });
-            assert!(rx.recv().unwrap() == box 10);
+            assert!(rx.recv().unwrap() == Box::new(10));
         }
     }
 
@@ -1607,26 +2289,26 @@ mod sync_tests {
         donerx.recv().unwrap();
     }
 
-    // #[test]
-    // fn try_send1() {
-    //     let (tx, _rx) = sync_channel::<i32>(0);
-    //     assert_eq!(tx.try_send(1), Err(TrySendError::Full(1)));
-    // }
+    #[test]
+    fn try_send1() {
+        let (tx, _rx) = sync_channel::<i32>(0);
+        assert_eq!(tx.try_send(1), Err(TrySendError::Full(1)));
+    }
 
-    // #[test]
-    // fn try_send2() {
-    //     let (tx, _rx) = sync_channel::<i32>(1);
-    //     assert_eq!(tx.try_send(1), Ok(()));
-    //     assert_eq!(tx.try_send(1), Err(TrySendError::Full(1)));
-    // }
+    #[test]
+    fn try_send2() {
+        let (tx, _rx) = sync_channel::<i32>(1);
+        assert_eq!(tx.try_send(1), Ok(()));
+        assert_eq!(tx.try_send(1), Err(TrySendError::Full(1)));
+    }
 
-    // #[test]
-    // fn try_send3() {
-    //     let (tx, rx) = sync_channel::<i32>(1);
-    //     assert_eq!(tx.try_send(1), Ok(()));
-    //     drop(rx);
-    //     assert_eq!(tx.try_send(1), Err(TrySendError::Disconnected(1)));
-    // }
+    #[test]
+    fn try_send3() {
+        let (tx, rx) = sync_channel::<i32>(1);
+        assert_eq!(tx.try_send(1), Ok(()));
+        drop(rx);
+        assert_eq!(tx.try_send(1), Err(TrySendError::Disconnected(1)));
+    }
 
     #[test]
     fn issue_15761() {
@@ -1660,9 +2342,35 @@ mod sync_tests {
         assert_eq!(format!("{:?}", rx), "Receiver { .. }");
     }
 
-    // #[test]
-    // fn fmt_debug_sync_sender() {
-    //     let (tx, _) = sync_channel::<i32>(1);
-    //     assert_eq!(format!("{:?}", tx), "SyncSender { .. }");
-    // }*/
+    #[test]
+    fn backpressure_coroutine() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        for &bound in &[0, 2] {
+            let (tx, rx) = sync_channel::<usize>(bound);
+            let sent = Arc::new(AtomicUsize::new(0));
+            let sent1 = sent.clone();
+            let h = go!(move || for i in 0..10 {
+                tx.send(i).unwrap();
+                sent1.fetch_add(1, Ordering::SeqCst);
+            });
+
+            // the producer is parked once the buffer is full
+            thread::sleep(Duration::from_millis(50));
+            assert_eq!(sent.load(Ordering::SeqCst), bound);
+            for i in 0..10 {
+                assert_eq!(rx.recv().unwrap(), i);
+            }
+            h.join().unwrap();
+            assert_eq!(sent.load(Ordering::SeqCst), 10);
+            assert_eq!(rx.recv(), Err(RecvError));
+        }
+    }
+
+    #[test]
+    fn fmt_debug_sync_sender() {
+        let (tx, _) = sync_channel::<i32>(1);
+        assert_eq!(format!("{:?}", tx), "SyncSender { .. }");
+    }
 }
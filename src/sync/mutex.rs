@@ -3,6 +3,7 @@
 use std::fmt;
 use std::sync::Arc;
 use std::cell::UnsafeCell;
+use std::time::{Duration, Instant};
 use std::ops::{Deref, DerefMut};
 use std::panic::{RefUnwindSafe, UnwindSafe};
 use std::sync::atomic::{fence, AtomicUsize, Ordering};
@@ -13,6 +14,12 @@ use super::poison;
 use super::mpsc_list;
 use super::blocking::SyncBlocker;
 
+/// A mutual exclusion primitive for both thread and coroutine
+///
+/// the lock is fair. the waiters are queued in the arrival order, and the
+/// unlock hands the ownership to the head of the queue directly, `try_lock`
+/// never succeeds while there are waiters. so a waiter only waits for the
+/// ones that come before it, never for the latecomers
 pub struct Mutex<T: ?Sized> {
     // the waiting blocker list
     to_wake: mpsc_list::Queue<Arc<SyncBlocker>>,
@@ -57,6 +64,32 @@ impl<T: ?Sized> Mutex<T> {
             Err(TryLockError::Poisoned(e)) => return Err(e),
         }
 
+        self.lock_inner(None);
+        MutexGuard::new(self)
+    }
+
+    /// acquire the lock, giving up once `dur` has elapsed
+    ///
+    /// a timed out attempt returns `TryLockError::WouldBlock`. the waiter is
+    /// left in the queue with the release flag set, so whoever pops it later
+    /// just passes the lock on to the next waiter
+    pub fn lock_timeout(&self, dur: Duration) -> TryLockResult<MutexGuard<T>> {
+        // try lock first
+        match self.try_lock() {
+            Ok(g) => return Ok(g),
+            Err(TryLockError::WouldBlock) => {}
+            Err(e) => return Err(e),
+        }
+
+        if !self.lock_inner(Some(dur)) {
+            return Err(TryLockError::WouldBlock);
+        }
+        Ok(MutexGuard::new(self)?)
+    }
+
+    // wait in the queue for the lock, return false if it's timed out
+    fn lock_inner(&self, dur: Option<Duration>) -> bool {
+        let deadline = dur.map(|d| Instant::now() + d);
         let cur = SyncBlocker::current();
         // register blocker first
         self.to_wake.push(cur.clone());
@@ -67,11 +100,28 @@ impl<T: ?Sized> Mutex<T> {
                 .map_or_else(|| panic!("got null blocker!"), |w| self.unpark_one(w));
         }
         loop {
-            match cur.park(None) {
+            let left = deadline.map(|d| {
+                let now = Instant::now();
+                if d > now {
+                    d - now
+                } else {
+                    Duration::from_secs(0)
+                }
+            });
+            match cur.park(left) {
                 Ok(_) => {
-                    break;
+                    return true;
+                }
+                Err(ParkError::Timeout) => {
+                    // we may got the lock just before the timeout
+                    if cur.is_unparked() {
+                        return true;
+                    }
+                    // register
+                    cur.set_release();
+                    // re-check unpark status
+                    return cur.is_unparked() && cur.take_release();
                 }
-                Err(ParkError::Timeout) => unreachable!("mutext timeout"),
                 Err(ParkError::Canceled) => {
                     let b_ignore = if ::coroutine_impl::is_coroutine() {
                         let cancel = ::coroutine_impl::current_cancel_data();
@@ -82,7 +132,7 @@ impl<T: ?Sized> Mutex<T> {
                     // check the unpark status
                     if cur.is_unparked() {
                         if b_ignore {
-                            break;
+                            return true;
                         }
                         self.unlock();
                     } else {
@@ -92,7 +142,7 @@ impl<T: ?Sized> Mutex<T> {
                         if cur.is_unparked() {
                             if cur.take_release() {
                                 if b_ignore {
-                                    break;
+                                    return true;
                                 }
                                 self.unlock();
                             }
@@ -108,8 +158,6 @@ impl<T: ?Sized> Mutex<T> {
                 }
             }
         }
-
-        MutexGuard::new(self)
     }
 
     pub fn try_lock(&self) -> TryLockResult<MutexGuard<T>> {
@@ -305,6 +353,101 @@ mod tests {
         *m.try_lock().unwrap() = ();
     }
 
+    #[test]
+    fn fair_hand_off() {
+        use std::time::Duration;
+
+        // the waiters get the lock in the arrival order
+        let m = Arc::new(Mutex::new(Vec::new()));
+        let g = m.lock().unwrap();
+        let mut vec = vec![];
+        for i in 0..5 {
+            let m = m.clone();
+            vec.push(go!(move || m.lock().unwrap().push(i)));
+            thread::sleep(Duration::from_millis(10));
+        }
+        // no barging while there are waiters
+        assert!(m.try_lock().is_err());
+        drop(g);
+        for h in vec {
+            h.join().unwrap();
+        }
+        assert_eq!(*m.lock().unwrap(), vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn fair_bounded_wait() {
+        use coroutine::{yield_now, Builder};
+
+        const N: usize = 8;
+        const M: usize = 200;
+        // the longest wait counted by the locks granted to the others. the
+        // contenders share one worker, so no preemption gets in the count
+        let m = Arc::new(Mutex::new(0));
+        let granted = Arc::new(AtomicUsize::new(0));
+        let mut vec = vec![];
+        for _ in 0..N {
+            let m = m.clone();
+            let granted = granted.clone();
+            let h = go!(Builder::new().pin_to(0), move || for _ in 0..M {
+                let before = granted.load(Ordering::SeqCst);
+                let mut worst = m.lock().unwrap();
+                let others = granted.fetch_add(1, Ordering::SeqCst) - before;
+                if others > *worst {
+                    *worst = others;
+                }
+                // let the others queue up
+                yield_now();
+            });
+            vec.push(h.unwrap());
+        }
+        for h in vec {
+            h.join().unwrap();
+        }
+        assert_eq!(granted.load(Ordering::SeqCst), N * M);
+        // each of the others gets the lock at most once
+        let worst = *m.lock().unwrap();
+        assert!(worst < N, "waited for {} others", worst);
+    }
+
+    #[test]
+    fn lock_timeout() {
+        use std::time::Duration;
+
+        let m = Arc::new(Mutex::new(0));
+        let g = m.lock().unwrap();
+
+        let m1 = m.clone();
+        let h1 = go!(move || match m1.lock_timeout(Duration::from_millis(50)) {
+            Err(TryLockError::WouldBlock) => {}
+            _ => panic!("lock_timeout should time out"),
+        });
+        let m2 = m.clone();
+        let h2 = thread::spawn(move || match m2.lock_timeout(Duration::from_millis(50)) {
+            Err(TryLockError::WouldBlock) => {}
+            _ => panic!("lock_timeout should time out"),
+        });
+        h1.join().unwrap();
+        h2.join().unwrap();
+
+        // the timed out waiters are still queued, a later waiter must get
+        // the lock through them
+        let m3 = m.clone();
+        let h3 = go!(move || {
+            *m3.lock_timeout(Duration::from_secs(10)).unwrap() += 1;
+        });
+        let m4 = m.clone();
+        let h4 = go!(move || {
+            *m4.lock().unwrap() += 1;
+        });
+        thread::sleep(Duration::from_millis(50));
+        drop(g);
+        h3.join().unwrap();
+        h4.join().unwrap();
+        assert_eq!(*m.lock_timeout(Duration::from_millis(10)).unwrap(), 2);
+        assert!(m.try_lock().is_ok());
+    }
+
     #[test]
     fn test_into_inner() {
         let m = Mutex::new(NonCopy(10));
@@ -0,0 +1,326 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use super::{Blocker, Mutex};
+
+struct Waiter {
+    blocker: Arc<Blocker>,
+    notified: AtomicBool,
+}
+
+struct State {
+    // a stored notification for the next `notified` call
+    permit: bool,
+    // the FIFO waiting list
+    to_wake: VecDeque<Arc<Waiter>>,
+}
+
+/// Notify primitive
+///
+/// notify a thread/coroutine that is waiting on `notified`. `notify_one`
+/// and `notify_waiters` can be called from any thread, including the
+/// foreign threads that are not managed by may. the wakeup goes through
+/// the scheduler directly, so the waiter is resumed promptly
+///
+/// if `notify_one` is called when nobody is waiting, a permit is stored
+/// and the next `notified` returns immediately. the permits don't add up,
+/// there is at most one stored at a time
+///
+/// # Examples
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate may;
+///
+/// use std::thread;
+/// use std::sync::Arc;
+/// use may::sync::Notify;
+///
+/// fn main() {
+///     let notify = Arc::new(Notify::new());
+///     let notify2 = notify.clone();
+///
+///     let h = go!(move || notify2.notified());
+///     thread::spawn(move || notify.notify_one());
+///     h.join().unwrap();
+/// }
+/// ```
+pub struct Notify {
+    state: Mutex<State>,
+}
+
+// remove the waiter from the list if it's canceled before notified
+// or hand over the notification if it's already taken
+struct WaitGuard<'a> {
+    notify: &'a Notify,
+    waiter: Arc<Waiter>,
+}
+
+impl<'a> WaitGuard<'a> {
+    // remove the waiter from the list, return false if it's already picked
+    // by a notification
+    fn remove(&self) -> bool {
+        let mut state = self.notify.state.lock().unwrap_or_else(|e| e.into_inner());
+        let pos = state
+            .to_wake
+            .iter()
+            .position(|w| Arc::ptr_eq(w, &self.waiter));
+        match pos {
+            Some(i) => {
+                state.to_wake.remove(i);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl<'a> Drop for WaitGuard<'a> {
+    fn drop(&mut self) {
+        if self.waiter.notified.load(Ordering::Acquire) {
+            return;
+        }
+
+        if !self.remove() {
+            // a `notify_one` has picked us, pass it to the next one
+            self.notify.notify_one();
+        }
+    }
+}
+
+impl Notify {
+    /// create a new Notify without a stored permit
+    pub fn new() -> Self {
+        Notify {
+            state: Mutex::new(State {
+                permit: false,
+                to_wake: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// wake up the first waiter, if there is no waiter a permit is stored
+    pub fn notify_one(&self) {
+        let waiter = {
+            let mut state = self.state.lock().unwrap();
+            match state.to_wake.pop_front() {
+                Some(w) => w,
+                None => {
+                    state.permit = true;
+                    return;
+                }
+            }
+        };
+
+        waiter.notified.store(true, Ordering::Release);
+        waiter.blocker.unpark();
+    }
+
+    /// wake up all the current waiters, no permit is stored
+    pub fn notify_waiters(&self) {
+        let to_wake = {
+            let mut state = self.state.lock().unwrap();
+            ::std::mem::replace(&mut state.to_wake, VecDeque::new())
+        };
+
+        for w in to_wake {
+            w.notified.store(true, Ordering::Release);
+            w.blocker.unpark();
+        }
+    }
+
+    /// block until notified, return immediately if a permit is stored
+    pub fn notified(&self) {
+        self.wait(None);
+    }
+
+    /// block until notified or the timeout expires, return false on the
+    /// timeout
+    ///
+    /// the waiter leaves the list on the timeout, so the notifications after
+    /// that go to the other waiters or are stored as the permit. a
+    /// notification that picks the waiter just before it leaves is not lost,
+    /// true is returned for it
+    pub fn notified_timeout(&self, dur: Duration) -> bool {
+        self.wait(Some(Instant::now() + dur))
+    }
+
+    fn wait(&self, deadline: Option<Instant>) -> bool {
+        let waiter = {
+            let mut state = self.state.lock().unwrap();
+            if state.permit {
+                state.permit = false;
+                return true;
+            }
+
+            let waiter = Arc::new(Waiter {
+                blocker: Blocker::current(),
+                notified: AtomicBool::new(false),
+            });
+            state.to_wake.push_back(waiter.clone());
+            waiter
+        };
+
+        let g = WaitGuard {
+            notify: self,
+            waiter: waiter.clone(),
+        };
+        while !waiter.notified.load(Ordering::Acquire) {
+            let timeout = match deadline {
+                Some(d) => {
+                    let now = Instant::now();
+                    if now >= d {
+                        let picked = !g.remove();
+                        // nothing is left for the guard to do
+                        waiter.notified.store(true, Ordering::Release);
+                        return picked;
+                    }
+                    Some(d - now)
+                }
+                None => None,
+            };
+            waiter.blocker.park(timeout).ok();
+        }
+        true
+    }
+}
+
+impl Default for Notify {
+    fn default() -> Self {
+        Notify::new()
+    }
+}
+
+impl fmt::Debug for Notify {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Notify {{ .. }}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+    use super::*;
+
+    #[test]
+    fn stored_permit() {
+        let notify = Notify::new();
+        // the permits don't add up
+        notify.notify_one();
+        notify.notify_one();
+        notify.notified();
+        assert!(notify.state.lock().unwrap().permit == false);
+
+        // notify_waiters doesn't store a permit
+        notify.notify_waiters();
+        assert!(notify.state.lock().unwrap().permit == false);
+    }
+
+    #[test]
+    fn foreign_thread_latency() {
+        let notify = Arc::new(Notify::new());
+        let notify2 = notify.clone();
+
+        let h = go!(move || {
+            notify2.notified();
+            Instant::now()
+        });
+
+        thread::sleep(Duration::from_millis(50));
+        let start = thread::spawn(move || {
+            let start = Instant::now();
+            notify.notify_one();
+            start
+        }).join()
+            .unwrap();
+
+        let waked = h.join().unwrap();
+        assert!(waked.duration_since(start) < Duration::from_millis(20));
+    }
+
+    #[test]
+    fn notify_waiters() {
+        let notify = Arc::new(Notify::new());
+        let handles = (0..10)
+            .map(|_| {
+                let notify = notify.clone();
+                go!(move || notify.notified())
+            })
+            .collect::<Vec<_>>();
+
+        thread::sleep(Duration::from_millis(50));
+        notify.notify_waiters();
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert!(notify.state.lock().unwrap().permit == false);
+    }
+
+    #[test]
+    fn canceled_waiter() {
+        let notify = Arc::new(Notify::new());
+        let notify1 = notify.clone();
+        let notify2 = notify.clone();
+
+        let h1 = go!(move || notify1.notified());
+        thread::sleep(Duration::from_millis(20));
+        let h2 = go!(move || notify2.notified());
+        thread::sleep(Duration::from_millis(20));
+
+        // the canceled waiter is removed, the notification goes to h2
+        unsafe { h1.coroutine().cancel() };
+        h1.join().unwrap_err();
+        notify.notify_one();
+        h2.join().unwrap();
+    }
+
+    #[test]
+    fn notified_timeout() {
+        let notify = Arc::new(Notify::new());
+        let notify2 = notify.clone();
+
+        let h = go!(move || {
+            let now = Instant::now();
+            assert_eq!(notify2.notified_timeout(Duration::from_millis(20)), false);
+            assert!(now.elapsed() >= Duration::from_millis(20));
+            notify2.notified_timeout(Duration::from_secs(10))
+        });
+
+        thread::sleep(Duration::from_millis(50));
+        // the timed out waiter has left the list
+        assert_eq!(notify.state.lock().unwrap().to_wake.len(), 1);
+        notify.notify_one();
+        assert!(h.join().unwrap());
+
+        // in thread context
+        notify.notify_one();
+        assert!(notify.notified_timeout(Duration::from_millis(10)));
+        assert!(!notify.notified_timeout(Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn notify_at_timeout() {
+        let notify = Arc::new(Notify::new());
+        for i in 0..200 {
+            let notify1 = notify.clone();
+            let notify2 = notify.clone();
+            let h = go!(move || notify1.notified_timeout(Duration::from_millis(2)));
+            let n = go!(move || {
+                ::coroutine::sleep(Duration::from_millis(1 + i % 3));
+                notify2.notify_one();
+            });
+            let got = h.join().unwrap();
+            n.join().unwrap();
+
+            // the notification is either taken or stored, never lost
+            let mut state = notify.state.lock().unwrap();
+            assert_eq!(state.permit, !got);
+            assert!(state.to_wake.is_empty());
+            state.permit = false;
+        }
+    }
+}
@@ -3,9 +3,10 @@
 use std::fmt;
 use std::sync::Arc;
 use std::cell::UnsafeCell;
+use std::time::{Duration, Instant};
 use std::ops::{Deref, DerefMut};
 use std::panic::{RefUnwindSafe, UnwindSafe};
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{LockResult, PoisonError, TryLockError, TryLockResult};
 
 use park::ParkError;
@@ -13,7 +14,8 @@ use may_queue::mpsc_list;
 use cancel::trigger_cancel_panic;
 
 use super::poison;
-use super::mutex::{self, Mutex};
+use super::condvar::Condvar;
+use super::mutex::{self, Mutex, MutexGuard};
 use super::blocking::SyncBlocker;
 
 /// A reader-writer lock
@@ -29,6 +31,12 @@ pub struct RwLock<T: ?Sized> {
 
     // the reader mutex that track the reader count
     rlock: Mutex<usize>,
+    // only one upgradable reader at a time
+    ulock: Mutex<()>,
+    // the upgradable reader is waiting for the other readers to leave,
+    // the new readers wait for it on `rcond`
+    upgrading: AtomicBool,
+    rcond: Condvar,
 
     poison: poison::Flag,
     data: UnsafeCell<T>,
@@ -54,12 +62,25 @@ pub struct RwLockWriteGuard<'a, T: ?Sized + 'a> {
 
 // impl<'a, T: ?Sized> !marker::Send for RwLockWriteGuard<'a, T> {}
 
+/// The shared read access that could be upgraded to the write access
+///
+/// it's returned by `RwLock::upgradable_read`, and it's upgraded by
+/// `RwLockUpgradableReadGuard::upgrade` without releasing the lock
+#[must_use]
+pub struct RwLockUpgradableReadGuard<'a, T: ?Sized + 'a> {
+    __read: RwLockReadGuard<'a, T>,
+    __ulock: MutexGuard<'a, ()>,
+}
+
 impl<T> RwLock<T> {
     pub fn new(t: T) -> RwLock<T> {
         RwLock {
             to_wake: mpsc_list::Queue::new(),
             cnt: AtomicUsize::new(0),
             rlock: Mutex::new(0),
+            ulock: Mutex::new(()),
+            upgrading: AtomicBool::new(false),
+            rcond: Condvar::new(),
             poison: poison::Flag::new(),
             data: UnsafeCell::new(t),
         }
@@ -68,7 +89,7 @@ impl<T> RwLock<T> {
 
 impl<T: ?Sized> RwLock<T> {
     // global mutex lock without return a guard
-    fn lock(&self) -> Result<(), ParkError> {
+    fn lock(&self, dur: Option<Duration>) -> Result<(), ParkError> {
         // try lock first
         match self.try_lock() {
             Ok(_) => return Ok(()),
@@ -85,9 +106,21 @@ impl<T: ?Sized> RwLock<T> {
                 .pop()
                 .map_or_else(|| panic!("got null blocker!"), |w| self.unpark_one(w));
         }
-        match cur.park(None) {
+        match cur.park(dur) {
             Ok(_) => Ok(()),
-            Err(ParkError::Timeout) => unreachable!("rwlock timeout"),
+            Err(ParkError::Timeout) => {
+                // we may got the lock just before the timeout
+                if cur.is_unparked() {
+                    return Ok(());
+                }
+                // register, the unparker would pass the lock on for us
+                cur.set_release();
+                // re-check unpark status
+                if cur.is_unparked() && cur.take_release() {
+                    return Ok(());
+                }
+                Err(ParkError::Timeout)
+            }
             Err(ParkError::Canceled) => {
                 // check the unpark status
                 if cur.is_unparked() {
@@ -141,10 +174,40 @@ impl<T: ?Sized> RwLock<T> {
         }
     }
 
+    // wait for the upgrade in progress with the reader mutex locked, or it
+    // could be starved by the new readers
+    fn wait_upgrade(&self, deadline: Option<Instant>) -> Result<(), ParkError> {
+        while self.upgrading.load(Ordering::Acquire) {
+            let dur = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(ParkError::Timeout);
+                    }
+                    Some(deadline - now)
+                }
+                None => None,
+            };
+            match self.rcond.wait_impl(&self.rlock, dur) {
+                Err(ParkError::Canceled) => return Err(ParkError::Canceled),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
     pub fn read(&self) -> LockResult<RwLockReadGuard<T>> {
         let mut r = self.rlock.lock().expect("rwlock read");
+        if self.wait_upgrade(None).is_err() {
+            // don't set the poison flag
+            ::std::mem::forget(r);
+            // release the mutex to let other run
+            mutex::unlock_mutex(&self.rlock);
+            // now we can safely go with the cancel panic
+            trigger_cancel_panic();
+        }
         if *r == 0 {
-            match self.lock() {
+            match self.lock(None) {
                 Err(ParkError::Canceled) => {
                     // don't set the poison flag
                     ::std::mem::forget(r);
@@ -161,6 +224,52 @@ impl<T: ?Sized> RwLock<T> {
         RwLockReadGuard::new(self)
     }
 
+    /// acquire the shared read access, giving up once `dur` has elapsed
+    ///
+    /// a timed out attempt returns `TryLockError::WouldBlock`
+    pub fn read_timeout(&self, dur: Duration) -> TryLockResult<RwLockReadGuard<T>> {
+        let deadline = Instant::now() + dur;
+        let mut r = match self.rlock.lock_timeout(dur) {
+            Ok(r) => r,
+            Err(TryLockError::WouldBlock) => return Err(TryLockError::WouldBlock),
+            Err(TryLockError::Poisoned(_)) => panic!("rwlock read_timeout"),
+        };
+        match self.wait_upgrade(Some(deadline)) {
+            Err(ParkError::Timeout) => return Err(TryLockError::WouldBlock),
+            Err(ParkError::Canceled) => {
+                // don't set the poison flag
+                ::std::mem::forget(r);
+                // release the mutex to let other run
+                mutex::unlock_mutex(&self.rlock);
+                // now we can safely go with the cancel panic
+                trigger_cancel_panic();
+            }
+            _ => {}
+        }
+        if *r == 0 {
+            let now = Instant::now();
+            let left = if deadline > now {
+                deadline - now
+            } else {
+                Duration::from_secs(0)
+            };
+            match self.lock(Some(left)) {
+                Err(ParkError::Timeout) => return Err(TryLockError::WouldBlock),
+                Err(ParkError::Canceled) => {
+                    // don't set the poison flag
+                    ::std::mem::forget(r);
+                    // release the mutex to let other run
+                    mutex::unlock_mutex(&self.rlock);
+                    // now we can safely go with the cancel panic
+                    trigger_cancel_panic();
+                }
+                _ => {}
+            }
+        }
+        *r += 1;
+        Ok(RwLockReadGuard::new(self)?)
+    }
+
     pub fn try_read(&self) -> TryLockResult<RwLockReadGuard<T>> {
         let mut r = match self.rlock.try_lock() {
             Ok(r) => r,
@@ -172,6 +281,9 @@ impl<T: ?Sized> RwLock<T> {
             Err(TryLockError::WouldBlock) => return Err(TryLockError::WouldBlock),
         };
 
+        if self.upgrading.load(Ordering::Acquire) {
+            return Err(TryLockError::WouldBlock);
+        }
         if *r == 0 {
             match self.try_lock() {
                 Err(TryLockError::WouldBlock) => return Err(TryLockError::WouldBlock),
@@ -190,11 +302,51 @@ impl<T: ?Sized> RwLock<T> {
         *r -= 1;
         if *r == 0 {
             self.unlock();
+        } else if *r == 1 && self.upgrading.load(Ordering::Acquire) {
+            // the upgradable reader is the last one
+            self.rcond.notify_all();
+        }
+    }
+
+    /// acquire the shared read access that could be upgraded later
+    ///
+    /// the other readers are not blocked, but there is only one upgradable
+    /// reader at a time, so the upgrades never wait for each other. the
+    /// writers can't get in between the read and the upgrade
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use may::sync::{RwLock, RwLockUpgradableReadGuard};
+    ///
+    /// let cache = RwLock::new(0);
+    /// let r = cache.upgradable_read().unwrap();
+    /// if *r == 0 {
+    ///     let mut w = RwLockUpgradableReadGuard::upgrade(r).unwrap();
+    ///     *w = 42;
+    /// }
+    /// assert_eq!(*cache.read().unwrap(), 42);
+    /// ```
+    pub fn upgradable_read(&self) -> LockResult<RwLockUpgradableReadGuard<T>> {
+        let ulock = match self.ulock.lock() {
+            Ok(g) => g,
+            // the poison is reported by the read guard
+            Err(e) => e.into_inner(),
+        };
+        match self.read() {
+            Ok(g) => Ok(RwLockUpgradableReadGuard {
+                __read: g,
+                __ulock: ulock,
+            }),
+            Err(e) => Err(PoisonError::new(RwLockUpgradableReadGuard {
+                __read: e.into_inner(),
+                __ulock: ulock,
+            })),
         }
     }
 
     pub fn write(&self) -> LockResult<RwLockWriteGuard<T>> {
-        match self.lock() {
+        match self.lock(None) {
             Err(ParkError::Canceled) => {
                 // now we can safely go with the cancel panic
                 trigger_cancel_panic();
@@ -204,6 +356,21 @@ impl<T: ?Sized> RwLock<T> {
         RwLockWriteGuard::new(self)
     }
 
+    /// acquire the exclusive write access, giving up once `dur` has elapsed
+    ///
+    /// a timed out attempt returns `TryLockError::WouldBlock`
+    pub fn write_timeout(&self, dur: Duration) -> TryLockResult<RwLockWriteGuard<T>> {
+        match self.lock(Some(dur)) {
+            Err(ParkError::Timeout) => return Err(TryLockError::WouldBlock),
+            Err(ParkError::Canceled) => {
+                // now we can safely go with the cancel panic
+                trigger_cancel_panic();
+            }
+            _ => {}
+        }
+        Ok(RwLockWriteGuard::new(self)?)
+    }
+
     pub fn try_write(&self) -> TryLockResult<RwLockWriteGuard<T>> {
         match self.try_lock() {
             Err(TryLockError::WouldBlock) => return Err(TryLockError::WouldBlock),
@@ -276,6 +443,42 @@ impl<'rwlock, T: ?Sized> RwLockWriteGuard<'rwlock, T> {
     }
 }
 
+impl<'rwlock, T: ?Sized> RwLockUpgradableReadGuard<'rwlock, T> {
+    /// upgrade to the write access
+    ///
+    /// it waits for the other readers to leave, the new readers wait until
+    /// the write access is released. no writer could get the lock in between
+    pub fn upgrade(s: Self) -> LockResult<RwLockWriteGuard<'rwlock, T>> {
+        let lock = s.__read.__lock;
+        let mut r = lock.rlock.lock().expect("rwlock upgrade");
+        lock.upgrading.store(true, Ordering::Release);
+        while *r > 1 {
+            if lock.rcond.wait_impl(&lock.rlock, None) == Err(ParkError::Canceled) {
+                // let the new readers go on, the read access is released
+                // by the guard while unwinding
+                lock.upgrading.store(false, Ordering::Release);
+                lock.rcond.notify_all();
+                // don't set the poison flag
+                ::std::mem::forget(r);
+                // release the mutex to let other run
+                mutex::unlock_mutex(&lock.rlock);
+                // now we can safely go with the cancel panic
+                trigger_cancel_panic();
+            }
+        }
+        // take the global lock over from the readers
+        *r = 0;
+        lock.upgrading.store(false, Ordering::Release);
+        lock.rcond.notify_all();
+        drop(r);
+
+        let RwLockUpgradableReadGuard { __read, __ulock } = s;
+        ::std::mem::forget(__read);
+        drop(__ulock);
+        RwLockWriteGuard::new(lock)
+    }
+}
+
 impl<'a, T: fmt::Debug> fmt::Debug for RwLockReadGuard<'a, T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("RwLockReadGuard")
@@ -300,6 +503,22 @@ impl<'rwlock, T: ?Sized> Deref for RwLockReadGuard<'rwlock, T> {
     }
 }
 
+impl<'a, T: fmt::Debug> fmt::Debug for RwLockUpgradableReadGuard<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RwLockUpgradableReadGuard")
+            .field("lock", &self.__read.__lock)
+            .finish()
+    }
+}
+
+impl<'rwlock, T: ?Sized> Deref for RwLockUpgradableReadGuard<'rwlock, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.__read
+    }
+}
+
 impl<'rwlock, T: ?Sized> Deref for RwLockWriteGuard<'rwlock, T> {
     type Target = T;
 
@@ -577,6 +796,81 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_rwlock_timeout() {
+        use std::time::Duration;
+
+        let rwlock = Arc::new(RwLock::new(0));
+        let w = rwlock.write().unwrap();
+
+        let r1 = rwlock.clone();
+        let h1 = go!(move || match r1.read_timeout(Duration::from_millis(20)) {
+            Err(TryLockError::WouldBlock) => {}
+            _ => panic!("read_timeout should time out"),
+        });
+        let r2 = rwlock.clone();
+        let h2 = thread::spawn(move || match r2.write_timeout(Duration::from_millis(20)) {
+            Err(TryLockError::WouldBlock) => {}
+            _ => panic!("write_timeout should time out"),
+        });
+        h1.join().unwrap();
+        h2.join().unwrap();
+
+        // the timed out waiters must not block the later ones
+        let r3 = rwlock.clone();
+        let h3 = go!(move || {
+            *r3.write_timeout(Duration::from_secs(10)).unwrap() += 1;
+        });
+        let r4 = rwlock.clone();
+        let h4 = go!(move || {
+            assert!(*r4.read_timeout(Duration::from_secs(10)).unwrap() <= 1);
+        });
+        thread::sleep(Duration::from_millis(20));
+        drop(w);
+        h3.join().unwrap();
+        h4.join().unwrap();
+
+        let r = rwlock.read_timeout(Duration::from_millis(10)).unwrap();
+        let r1 = rwlock.read_timeout(Duration::from_millis(10)).unwrap();
+        assert_eq!(*r + *r1, 2);
+        assert!(rwlock.try_write().is_err());
+        drop(r);
+        drop(r1);
+        assert!(rwlock.try_write().is_ok());
+    }
+
+    #[test]
+    fn test_rwlock_timeout_race() {
+        use std::time::Duration;
+        use coroutine::sleep;
+
+        let rwlock = Arc::new(RwLock::new(0));
+        for i in 0..20 {
+            let w = rwlock.write().unwrap();
+            let mut vec = vec![];
+            for j in 0..4 {
+                let rwlock = rwlock.clone();
+                // the timeouts fire around the time the lock is released
+                let dur = Duration::from_millis(8 + j);
+                vec.push(go!(move || if j % 2 == 0 {
+                    if let Ok(mut g) = rwlock.write_timeout(dur) {
+                        *g += 1;
+                    }
+                } else {
+                    let _ = rwlock.read_timeout(dur);
+                }));
+            }
+            sleep(Duration::from_millis(8 + i % 4));
+            drop(w);
+            for h in vec {
+                h.join().unwrap();
+            }
+            // the counters must be consistent whoever won the race
+            drop(rwlock.try_write().unwrap());
+            drop(rwlock.try_read().unwrap());
+        }
+    }
+
     #[test]
     fn test_rwlock_write_canceled() {
         const N: usize = 10;
@@ -686,4 +980,104 @@ mod tests {
         assert_eq!(a, 10);
         assert_eq!(rx.try_recv().is_err(), true);
     }
+
+    #[test]
+    fn test_rwlock_upgradable_read() {
+        use std::time::Duration;
+        use std::sync::atomic::AtomicBool;
+        use coroutine::sleep;
+        use sync::RwLockUpgradableReadGuard;
+
+        let rwlock = Arc::new(RwLock::new(0));
+        let u = rwlock.upgradable_read().unwrap();
+        // the readers are not blocked, the writers and upgradables are
+        drop(rwlock.try_read().unwrap());
+        assert!(rwlock.try_write().is_err());
+        let r = rwlock.clone();
+        let h = go!(move || *r.upgradable_read().unwrap());
+        thread::sleep(Duration::from_millis(20));
+        assert!(!h.is_done());
+
+        // the upgrade waits for the reader to leave
+        let released = Arc::new(AtomicBool::new(false));
+        let (tx, rx) = channel();
+        let (r, rel) = (rwlock.clone(), released.clone());
+        go!(move || {
+            let g = r.read().unwrap();
+            tx.send(()).unwrap();
+            sleep(Duration::from_millis(50));
+            rel.store(true, Ordering::SeqCst);
+            drop(g);
+        });
+        rx.recv().unwrap();
+        // the new reader comes while upgrading, it waits for the write
+        let r = rwlock.clone();
+        let reader = go!(move || {
+            sleep(Duration::from_millis(20));
+            *r.read().unwrap()
+        });
+
+        let mut w = RwLockUpgradableReadGuard::upgrade(u).unwrap();
+        assert!(released.load(Ordering::SeqCst));
+        *w += 1;
+        thread::sleep(Duration::from_millis(20));
+        drop(w);
+        assert_eq!(reader.join().unwrap(), 1);
+        assert_eq!(h.join().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_rwlock_upgrade_no_writer_between() {
+        use sync::RwLockUpgradableReadGuard;
+
+        const N: usize = 8;
+        const M: usize = 200;
+        let rwlock = Arc::new(RwLock::new(0));
+        let mut vec = vec![];
+        for i in 0..N {
+            let rwlock = rwlock.clone();
+            vec.push(go!(move || for _ in 0..M {
+                match i % 3 {
+                    0 => {
+                        let r = rwlock.upgradable_read().unwrap();
+                        let v = *r;
+                        let mut w = RwLockUpgradableReadGuard::upgrade(r).unwrap();
+                        // nobody could write after the read
+                        assert_eq!(*w, v);
+                        *w += 1;
+                    }
+                    1 => *rwlock.write().unwrap() += 1,
+                    _ => drop(rwlock.read().unwrap()),
+                }
+            }));
+        }
+        for h in vec {
+            h.join().unwrap();
+        }
+        let writers = (0..N).filter(|i| i % 3 != 2).count();
+        assert_eq!(*rwlock.read().unwrap(), writers * M);
+    }
+
+    #[test]
+    fn test_rwlock_upgrade_canceled() {
+        use std::time::Duration;
+        use sync::RwLockUpgradableReadGuard;
+
+        let rwlock = Arc::new(RwLock::new(0));
+        let read = rwlock.read().unwrap();
+        let r = rwlock.clone();
+        let h = go!(move || {
+            let u = r.upgradable_read().unwrap();
+            *RwLockUpgradableReadGuard::upgrade(u).unwrap() += 1;
+        });
+        thread::sleep(Duration::from_millis(20));
+        unsafe { h.coroutine().cancel() };
+        h.join().unwrap_err();
+
+        // the upgrade is given up, the lock is usable
+        drop(rwlock.read().unwrap());
+        drop(rwlock.upgradable_read().unwrap());
+        drop(read);
+        assert_eq!(*rwlock.write().unwrap(), 0);
+    }
 }
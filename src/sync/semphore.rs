@@ -3,6 +3,7 @@ use std::sync::Arc;
 use std::time::Duration;
 use std::sync::atomic::{AtomicIsize, Ordering};
 use super::blocking::SyncBlocker;
+use super::Mutex;
 use park::ParkError;
 use crossbeam::sync::SegQueue;
 use cancel::trigger_cancel_panic;
@@ -38,12 +39,34 @@ use cancel::trigger_cancel_panic;
 /// // wait for the coroutine to start up
 /// sem.wait();
 /// ```
+///
+/// multiple resources can be taken and given back at once by `acquire_n`
+/// and `release_n`. the waiters are served in FIFO order, a waiter that
+/// requests `n` resources collects them one by one, queuing for each of
+/// them like `wait`, and keeps the collected ones, so the single resource
+/// waiters can't starve it. a canceled waiter gives back what it collected.
+/// only one multi resource waiter is collecting at a time, the others wait
+/// for their turn in order
 pub struct Semphore {
     // track how many resources available for the semphore
     // if it's negative means how many threads are waiting for
     cnt: AtomicIsize,
     // the waiting blocker list, must be mpmc
     to_wake: SegQueue<Arc<SyncBlocker>>,
+    // serialize the multi resource waiters
+    multi: Mutex<()>,
+}
+
+// give back the collected resources if the waiter is canceled
+struct Collected<'a> {
+    sem: &'a Semphore,
+    cnt: usize,
+}
+
+impl<'a> Drop for Collected<'a> {
+    fn drop(&mut self) {
+        self.sem.release_n(self.cnt);
+    }
 }
 
 impl Semphore {
@@ -53,6 +76,7 @@ impl Semphore {
         Semphore {
             to_wake: SegQueue::new(),
             cnt: AtomicIsize::new(init as isize),
+            multi: Mutex::new(()),
         }
     }
 
@@ -140,6 +164,47 @@ impl Semphore {
         false
     }
 
+    /// acquire `n` resources at once
+    /// block until all of them are available
+    pub fn acquire_n(&self, n: usize) {
+        if self.try_acquire_n(n) {
+            return;
+        }
+
+        let _g = self.multi.lock().unwrap_or_else(|e| e.into_inner());
+        let mut collected = Collected { sem: self, cnt: 0 };
+        while collected.cnt < n {
+            self.wait();
+            collected.cnt += 1;
+        }
+        collected.cnt = 0;
+    }
+
+    /// return false if would block
+    /// return true if successfully acquire `n` resources at once
+    pub fn try_acquire_n(&self, n: usize) -> bool {
+        assert!(n < ::std::isize::MAX as usize);
+        let n = n as isize;
+        let mut cnt = self.cnt.load(Ordering::Acquire);
+        while cnt >= n {
+            match self.cnt
+                .compare_exchange(cnt, cnt - n, Ordering::SeqCst, Ordering::Relaxed)
+            {
+                Ok(_) => return true,
+                Err(x) => cnt = x,
+            }
+        }
+        false
+    }
+
+    /// give back `n` resources at once
+    /// the same as calling `post` for `n` times
+    pub fn release_n(&self, n: usize) {
+        for _ in 0..n {
+            self.post();
+        }
+    }
+
     /// increment the semphore value
     /// and would wakeup a thread/coroutine that is calling `wait`
     pub fn post(&self) {
@@ -288,6 +353,80 @@ mod tests {
         h2.join().unwrap();
     }
 
+    #[test]
+    fn test_semphore_multi() {
+        let sem = Arc::new(Semphore::new(5));
+        assert!(sem.try_acquire_n(3));
+        assert!(!sem.try_acquire_n(3));
+        assert_eq!(sem.get_value(), 2);
+        sem.release_n(3);
+        assert_eq!(sem.get_value(), 5);
+        assert!(sem.try_acquire_n(0));
+
+        sem.acquire_n(5);
+        assert_eq!(sem.get_value(), 0);
+
+        let sem1 = sem.clone();
+        let h = go!(move || {
+            sem1.acquire_n(3);
+            sem1.release_n(3);
+        });
+        sem.release_n(2);
+        thread::sleep(Duration::from_millis(10));
+        sem.release_n(3);
+        h.join().unwrap();
+        assert_eq!(sem.get_value(), 5);
+    }
+
+    #[test]
+    fn test_semphore_multi_not_starved() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let sem = Arc::new(Semphore::new(2));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        // a stream of single resource users that keep the value below 3
+        let mut vec = vec![];
+        for _ in 0..4 {
+            let sem = sem.clone();
+            let stop = stop.clone();
+            vec.push(go!(move || while !stop.load(Ordering::Relaxed) {
+                sem.wait();
+                ::coroutine::yield_now();
+                sem.post();
+            }));
+        }
+
+        thread::sleep(Duration::from_millis(10));
+        // total is only 2, but the multi waiter would get them all eventually
+        let sem1 = sem.clone();
+        let h = go!(move || {
+            sem1.acquire_n(2);
+            sem1.release_n(2);
+        });
+        h.join().unwrap();
+
+        stop.store(true, Ordering::Relaxed);
+        for h in vec {
+            h.join().unwrap();
+        }
+        assert_eq!(sem.get_value(), 2);
+    }
+
+    #[test]
+    fn test_semphore_multi_canceled() {
+        use sleep::sleep;
+
+        let sem = Arc::new(Semphore::new(1));
+        let sem1 = sem.clone();
+        let h = go!(move || sem1.acquire_n(2));
+        sleep(Duration::from_millis(50));
+        // the collected resource is given back after cancel
+        unsafe { h.coroutine().cancel() };
+        h.join().unwrap_err();
+        assert_eq!(sem.get_value(), 1);
+    }
+
     #[test]
     fn test_semphore_thread_timeout() {
         use sleep::sleep;
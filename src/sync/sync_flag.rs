@@ -0,0 +1,254 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use super::{Blocker, Mutex};
+
+/// SyncFlag primitive
+///
+/// a one-shot latch, `fire` sets the flag and wakes up all the waiters of
+/// `wait` and `wait_timeout`. the flag is never cleared, the waits after
+/// the firing return immediately. `fire` can be called from any thread
+///
+/// # Examples
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate may;
+///
+/// use std::sync::Arc;
+/// use std::time::Duration;
+/// use may::sync::SyncFlag;
+///
+/// fn main() {
+///     let init = Arc::new(SyncFlag::new());
+///     let init2 = init.clone();
+///
+///     go!(move || {
+///         // do the init work
+///         init2.fire();
+///     });
+///
+///     if !init.wait_timeout(Duration::from_secs(10)) {
+///         println!("the init takes too long");
+///     }
+///     // all the later waits see the flag
+///     init.wait();
+/// }
+/// ```
+pub struct SyncFlag {
+    fired: AtomicBool,
+    // the waiters that are not waked yet
+    to_wake: Mutex<VecDeque<Arc<Blocker>>>,
+}
+
+// remove the waiter from the list if it leaves before the flag is fired
+struct WaitGuard<'a> {
+    flag: &'a SyncFlag,
+    blocker: Arc<Blocker>,
+}
+
+impl<'a> WaitGuard<'a> {
+    // remove the waiter from the list, return true if the flag is fired
+    fn remove(&self) -> bool {
+        let mut to_wake = self.flag.to_wake.lock().unwrap_or_else(|e| e.into_inner());
+        // the firing drains the list under the lock
+        if self.flag.is_fired() {
+            return true;
+        }
+        to_wake
+            .iter()
+            .position(|w| Arc::ptr_eq(w, &self.blocker))
+            .map(|i| to_wake.remove(i));
+        false
+    }
+}
+
+impl<'a> Drop for WaitGuard<'a> {
+    fn drop(&mut self) {
+        self.remove();
+    }
+}
+
+impl SyncFlag {
+    /// create a new SyncFlag that is not fired
+    pub fn new() -> Self {
+        SyncFlag {
+            fired: AtomicBool::new(false),
+            to_wake: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// set the flag and wake up all the waiters
+    pub fn fire(&self) {
+        let to_wake = {
+            let mut to_wake = self.to_wake.lock().unwrap();
+            self.fired.store(true, Ordering::Release);
+            ::std::mem::replace(&mut *to_wake, VecDeque::new())
+        };
+
+        for w in to_wake {
+            w.unpark();
+        }
+    }
+
+    /// return true if the flag is fired
+    pub fn is_fired(&self) -> bool {
+        self.fired.load(Ordering::Acquire)
+    }
+
+    /// block until the flag is fired
+    pub fn wait(&self) {
+        self.wait_until(None);
+    }
+
+    /// block until the flag is fired or the timeout expires, return false
+    /// on the timeout
+    ///
+    /// the waiter leaves the list on the timeout, so a later `fire` doesn't
+    /// touch it. a firing that happens just before it leaves is not missed,
+    /// true is returned for it
+    pub fn wait_timeout(&self, dur: Duration) -> bool {
+        self.wait_until(Some(Instant::now() + dur))
+    }
+
+    fn wait_until(&self, deadline: Option<Instant>) -> bool {
+        if self.is_fired() {
+            return true;
+        }
+
+        let blocker = {
+            let mut to_wake = self.to_wake.lock().unwrap();
+            if self.is_fired() {
+                return true;
+            }
+            let blocker = Blocker::current();
+            to_wake.push_back(blocker.clone());
+            blocker
+        };
+
+        let g = WaitGuard {
+            flag: self,
+            blocker: blocker,
+        };
+        while !self.is_fired() {
+            let timeout = match deadline {
+                Some(d) => {
+                    let now = Instant::now();
+                    if now >= d {
+                        return g.remove();
+                    }
+                    Some(d - now)
+                }
+                None => None,
+            };
+            g.blocker.park(timeout).ok();
+        }
+        true
+    }
+}
+
+impl Default for SyncFlag {
+    fn default() -> Self {
+        SyncFlag::new()
+    }
+}
+
+impl fmt::Debug for SyncFlag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SyncFlag {{ fired: {:?} }}", self.is_fired())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+    use super::*;
+
+    #[test]
+    fn wake_all_and_latch() {
+        let flag = Arc::new(SyncFlag::new());
+        let handles = (0..10)
+            .map(|_| {
+                let flag = flag.clone();
+                go!(move || flag.wait())
+            })
+            .collect::<Vec<_>>();
+
+        thread::sleep(Duration::from_millis(50));
+        flag.fire();
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert!(flag.to_wake.lock().unwrap().is_empty());
+
+        // the later waits see the flag
+        flag.wait();
+        assert!(flag.wait_timeout(Duration::from_millis(0)));
+        let flag2 = flag.clone();
+        assert!(go!(move || flag2.wait_timeout(Duration::from_secs(1))).join().unwrap());
+    }
+
+    #[test]
+    fn wait_timeout() {
+        let flag = Arc::new(SyncFlag::new());
+        let flag2 = flag.clone();
+
+        let h = go!(move || {
+            let now = Instant::now();
+            assert_eq!(flag2.wait_timeout(Duration::from_millis(20)), false);
+            assert!(now.elapsed() >= Duration::from_millis(20));
+            flag2.wait_timeout(Duration::from_secs(10))
+        });
+
+        thread::sleep(Duration::from_millis(50));
+        // the timed out waiter has left the list
+        assert_eq!(flag.to_wake.lock().unwrap().len(), 1);
+        flag.fire();
+        assert!(h.join().unwrap());
+
+        // in thread context
+        let flag = SyncFlag::new();
+        assert!(!flag.wait_timeout(Duration::from_millis(10)));
+        assert!(flag.to_wake.lock().unwrap().is_empty());
+        flag.fire();
+        assert!(flag.wait_timeout(Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn fire_at_timeout() {
+        for i in 0..200 {
+            let flag = Arc::new(SyncFlag::new());
+            let flag1 = flag.clone();
+            let flag2 = flag.clone();
+            let h = go!(move || flag1.wait_timeout(Duration::from_millis(2)));
+            let f = go!(move || {
+                ::coroutine::sleep(Duration::from_millis(1 + i % 3));
+                flag2.fire();
+            });
+            let got = h.join().unwrap();
+            f.join().unwrap();
+
+            // the waiter is either waked by the firing or has left the list
+            assert!(flag.to_wake.lock().unwrap().is_empty());
+            assert!(got || flag.is_fired());
+        }
+    }
+
+    #[test]
+    fn canceled_waiter() {
+        let flag = Arc::new(SyncFlag::new());
+        let flag2 = flag.clone();
+
+        let h = go!(move || flag2.wait());
+        thread::sleep(Duration::from_millis(20));
+        unsafe { h.coroutine().cancel() };
+        h.join().unwrap_err();
+        // the canceled waiter is removed
+        assert!(flag.to_wake.lock().unwrap().is_empty());
+        flag.fire();
+    }
+}
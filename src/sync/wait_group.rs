@@ -0,0 +1,218 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use super::{Blocker, Mutex};
+
+struct State {
+    // the number of `done` calls that are still expected
+    cnt: usize,
+    // the waiters that would be waked up when `cnt` drops to zero
+    to_wake: Vec<Arc<Blocker>>,
+}
+
+/// WaitGroup primitive
+///
+/// a WaitGroup waits for a collection of threads/coroutines to finish.
+/// `add` sets the number of the tasks to wait for, then each of the tasks
+/// calls `done` when finished. `wait` blocks until all of them are done
+///
+/// # Examples
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate may;
+///
+/// use std::sync::Arc;
+/// use may::sync::WaitGroup;
+///
+/// fn main() {
+///     let wg = Arc::new(WaitGroup::new());
+///     for _ in 0..10 {
+///         wg.add(1);
+///         let wg = wg.clone();
+///         go!(move || {
+///             // do some work
+///             wg.done();
+///         });
+///     }
+///     wg.wait();
+/// }
+/// ```
+pub struct WaitGroup {
+    state: Mutex<State>,
+}
+
+impl WaitGroup {
+    /// create a WaitGroup with no task to wait for
+    pub fn new() -> Self {
+        WaitGroup {
+            state: Mutex::new(State {
+                cnt: 0,
+                to_wake: Vec::new(),
+            }),
+        }
+    }
+
+    /// add `n` tasks to wait for
+    pub fn add(&self, n: usize) {
+        self.state.lock().unwrap().cnt += n;
+    }
+
+    /// mark one task as finished, wake up all the waiters if it's the last one
+    ///
+    /// panic if there is no task to wait for
+    pub fn done(&self) {
+        let to_wake = {
+            let mut state = self.state.lock().unwrap();
+            assert!(state.cnt > 0, "WaitGroup::done called too many times");
+            state.cnt -= 1;
+            if state.cnt > 0 {
+                return;
+            }
+            ::std::mem::replace(&mut state.to_wake, Vec::new())
+        };
+
+        for w in &to_wake {
+            w.unpark();
+        }
+    }
+
+    /// return the number of the tasks that are not done yet
+    pub fn count(&self) -> usize {
+        self.state.lock().unwrap().cnt
+    }
+
+    // return false if timeout
+    fn wait_timeout_impl(&self, dur: Option<Duration>) -> bool {
+        let deadline = dur.map(|d| Instant::now() + d);
+        loop {
+            let cur = Blocker::current();
+            {
+                let mut state = self.state.lock().unwrap();
+                if state.cnt == 0 {
+                    return true;
+                }
+                state.to_wake.push(cur.clone());
+            }
+
+            let left = match deadline {
+                None => None,
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        Some(Duration::from_secs(0))
+                    } else {
+                        Some(deadline - now)
+                    }
+                }
+            };
+
+            if cur.park(left).is_ok() {
+                continue;
+            }
+
+            // timeout, remove the waiter if it's not taken by `done`
+            // a late `unpark` on the removed blocker is harmless
+            let mut state = self.state.lock().unwrap();
+            if let Some(i) = state.to_wake.iter().position(|w| Arc::ptr_eq(w, &cur)) {
+                state.to_wake.swap_remove(i);
+            }
+            return state.cnt == 0;
+        }
+    }
+
+    /// block until all the tasks are done
+    pub fn wait(&self) {
+        self.wait_timeout_impl(None);
+    }
+
+    /// same as `wait` except that with an extra timeout value
+    /// return false if not all the tasks are done before the timeout
+    pub fn wait_timeout(&self, dur: Duration) -> bool {
+        self.wait_timeout_impl(Some(dur))
+    }
+}
+
+impl Default for WaitGroup {
+    fn default() -> Self {
+        WaitGroup::new()
+    }
+}
+
+impl fmt::Debug for WaitGroup {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "WaitGroup {{ cnt: {} }}", self.count())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use super::*;
+
+    #[test]
+    fn wait_all() {
+        let wg = Arc::new(WaitGroup::new());
+        let mut vec = vec![];
+        for _ in 0..10 {
+            wg.add(1);
+            let wg = wg.clone();
+            vec.push(go!(move || wg.done()));
+        }
+
+        // wait in both coroutine and thread
+        let wg1 = wg.clone();
+        let h = go!(move || wg1.wait());
+        wg.wait();
+        h.join().unwrap();
+        assert_eq!(wg.count(), 0);
+        for h in vec {
+            h.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn wait_timeout() {
+        let wg = Arc::new(WaitGroup::new());
+        wg.add(2);
+        wg.done();
+
+        let wg1 = wg.clone();
+        let h = go!(move || {
+            assert_eq!(wg1.wait_timeout(Duration::from_millis(10)), false);
+        });
+        assert_eq!(wg.wait_timeout(Duration::from_millis(10)), false);
+        h.join().unwrap();
+        assert_eq!(wg.count(), 1);
+        assert_eq!(wg.state.lock().unwrap().to_wake.len(), 0);
+
+        // the late done would not panic, and the later wait succeeds
+        wg.done();
+        assert_eq!(wg.wait_timeout(Duration::from_millis(10)), true);
+        wg.wait();
+    }
+
+    #[test]
+    fn wait_timeout_done_in_time() {
+        let wg = Arc::new(WaitGroup::new());
+        wg.add(1);
+        let wg1 = wg.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            wg1.done();
+        });
+        let wg2 = wg.clone();
+        let h = go!(move || wg2.wait_timeout(Duration::from_secs(10)));
+        assert_eq!(wg.wait_timeout(Duration::from_secs(10)), true);
+        assert_eq!(h.join().unwrap(), true);
+    }
+
+    #[test]
+    #[should_panic]
+    fn done_too_many() {
+        let wg = WaitGroup::new();
+        wg.done();
+    }
+}
@@ -3,11 +3,12 @@ use std::mem;
 use std::cmp;
 use std::thread;
 use std::sync::{Arc, Mutex, RwLock};
-use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::time::Duration;
 use std::collections::{BinaryHeap, HashMap};
 
 use sync::AtomicOption;
+use config::config;
 use may_queue::mpsc_list::Queue as mpsc;
 use may_queue::mpsc_list_v1::Queue as TimeoutList;
 use may_queue::mpsc_list_v1::Entry;
@@ -17,6 +18,29 @@ const NANOS_PER_SEC: u64 = 1_000_000_000;
 
 const HASH_CAP: usize = 1024;
 
+// the timers that are neither fired nor removed, of all the lists
+static PENDING_TIMERS: AtomicUsize = AtomicUsize::new(0);
+
+/// the number of the pending timers reported by `metrics`
+pub fn pending_timers() -> usize {
+    PENDING_TIMERS.load(Ordering::Relaxed)
+}
+
+/// remove a timer that is not fired yet, return false if it's already gone
+///
+/// it's O(1) and the timer is freed at once. only the timer that is the
+/// only one of its list is left to fire, so there is at most one such timer
+/// for each slot distance. it must be called by the thread that fires the
+/// timers
+pub fn remove_timer<T>(h: TimeoutHandle<T>) -> bool {
+    let TimeoutHandle { entry, list } = h;
+    let removed = list.remove(entry).is_some();
+    if removed {
+        PENDING_TIMERS.fetch_sub(1, Ordering::Relaxed);
+    }
+    removed
+}
+
 #[inline]
 fn dur_to_ns(dur: Duration) -> u64 {
     // Note that a duration is a (u64, u32) (seconds, nanoseconds) pair
@@ -25,6 +49,18 @@ fn dur_to_ns(dur: Duration) -> u64 {
         .saturating_add(dur.subsec_nanos() as u64)
 }
 
+// round up the ns to the multiple of the resolution
+#[inline]
+fn round_up(ns: u64, resolution: u64) -> u64 {
+    if resolution <= 1 {
+        return ns;
+    }
+    match ns % resolution {
+        0 => ns,
+        r => ns.saturating_add(resolution - r),
+    }
+}
+
 #[inline]
 pub fn ns_to_dur(ns: u64) -> Duration {
     Duration::new(ns / NANOS_PER_SEC, (ns % NANOS_PER_SEC) as u32)
@@ -48,10 +84,29 @@ pub struct TimeoutData<T> {
     pub data: T, // the data associate with the timeout event
 }
 
+type IntervalList<T> = Arc<TimeoutList<TimeoutData<T>>>;
+
 // timeout handler which can be removed/cancelled
-pub type TimeoutHandle<T> = Entry<TimeoutData<T>>;
+pub struct TimeoutHandle<T> {
+    entry: Entry<TimeoutData<T>>,
+    // the list that the entry is in, to unlink the last entry
+    list: IntervalList<T>,
+}
 
-type IntervalList<T> = Arc<TimeoutList<TimeoutData<T>>>;
+impl<T> TimeoutHandle<T> {
+    // get the internal data mut ref
+    // must make sure it's not fired
+    #[inline]
+    pub unsafe fn get_data(&self) -> &mut TimeoutData<T> {
+        self.entry.get_data()
+    }
+
+    /// judge if the timer is not fired or removed yet
+    #[inline]
+    pub fn is_link(&self) -> bool {
+        self.entry.is_link()
+    }
+}
 
 // this is the data type that used by the binary heap to get the latest timer
 struct IntervalEntry<T> {
@@ -70,7 +125,10 @@ impl<T> IntervalEntry<T> {
         let p = |v: &TimeoutData<T>| v.time <= now;
         loop {
             match self.list.pop_if(&p) {
-                Some(timeout) => f(timeout.data),
+                Some(timeout) => {
+                    PENDING_TIMERS.fetch_sub(1, Ordering::Relaxed);
+                    f(timeout.data)
+                }
                 None => break,
             }
         }
@@ -109,13 +167,20 @@ pub struct TimeOutList<T> {
     interval_map: RwLock<HashMap<u64, IntervalList<T>>>,
     // a priority queue, each element is the head of a mpsc queue
     timer_bh: Mutex<BinaryHeap<IntervalEntry<T>>>,
+    // the timers are rounded up to the multiple of it, in ns
+    resolution: u64,
 }
 
 impl<T> TimeOutList<T> {
     pub fn new() -> Self {
+        TimeOutList::with_resolution(config().get_timer_resolution())
+    }
+
+    pub fn with_resolution(resolution: Duration) -> Self {
         TimeOutList {
             interval_map: RwLock::new(HashMap::with_capacity(HASH_CAP)),
             timer_bh: Mutex::new(BinaryHeap::new()),
+            resolution: dur_to_ns(resolution),
         }
     }
 
@@ -129,14 +194,24 @@ impl<T> TimeOutList<T> {
     // this can be called in any thread
     // return true if we need to recall next expire
     pub fn add_timer(&self, dur: Duration, data: T) -> (TimeoutHandle<T>, bool) {
-        let interval = dur_to_ns(dur);
-        let time = now() + interval; // TODO: deal with overflow?
-                                     //println!("add timer = {:?}", time);
+        self.add_timer_at(now(), dur, data)
+    }
+
+    fn add_timer_at(&self, now: u64, dur: Duration, data: T) -> (TimeoutHandle<T>, bool) {
+        // the timers expire at the slot boundaries, so that the ones in the
+        // same slot are fired by a single wakeup
+        let time = round_up(now.saturating_add(dur_to_ns(dur)), self.resolution);
+        // the list is keyed by the distance from the current slot to the
+        // expiry slot. the later pushed timer of the same list never expires
+        // earlier, so a timer is never held back by the one before it, and
+        // the close intervals still share the list
+        let interval = time - round_up(now, self.resolution);
 
         let timeout = TimeoutData {
             time: time,
             data: data,
         };
+        PENDING_TIMERS.fetch_add(1, Ordering::Relaxed);
 
         let interval_list = {
             // use the read lock protect
@@ -146,15 +221,19 @@ impl<T> TimeOutList<T> {
         };
 
         if let Some(interval_list) = interval_list {
-            let (handle, is_head) = interval_list.push(timeout);
+            let (entry, is_head) = interval_list.push(timeout);
             if is_head {
                 // install the interval list to the binary heap
                 self.install_timer_bh(IntervalEntry {
                     time: time,
                     interval: interval,
-                    list: interval_list,
+                    list: interval_list.clone(),
                 });
             }
+            let handle = TimeoutHandle {
+                entry: entry,
+                list: interval_list,
+            };
             return (handle, is_head);
         }
 
@@ -163,7 +242,7 @@ impl<T> TimeOutList<T> {
         let mut interval_map_w = self.interval_map.write().unwrap();
         // recheck the interval list in case other thread may install it
         if let Some(interval_list) = (*interval_map_w).get(&interval) {
-            let (handle, is_head) = interval_list.push(timeout);
+            let (entry, is_head) = interval_list.push(timeout);
             if is_head {
                 // this rarely happens
                 self.install_timer_bh(IntervalEntry {
@@ -172,11 +251,18 @@ impl<T> TimeOutList<T> {
                     list: interval_list.clone(),
                 });
             }
+            let handle = TimeoutHandle {
+                entry: entry,
+                list: interval_list.clone(),
+            };
             return (handle, is_head);
         }
 
         let interval_list = Arc::new(TimeoutList::<TimeoutData<T>>::new());
-        let ret = interval_list.push(timeout).0;
+        let ret = TimeoutHandle {
+            entry: interval_list.push(timeout).0,
+            list: interval_list.clone(),
+        };
         (*interval_map_w).insert(interval, interval_list.clone());
         // drop the write lock here
         mem::drop(interval_map_w);
@@ -255,6 +341,8 @@ pub struct TimerThread<T> {
     remove_list: mpsc<TimeoutHandle<T>>,
     // the timer thread wakeup handler
     wakeup: AtomicOption<thread::Thread>,
+    // ask the timer thread to exit
+    stopped: AtomicBool,
 }
 
 impl<T> TimerThread<T> {
@@ -263,9 +351,16 @@ impl<T> TimerThread<T> {
             timer_list: TimeOutList::new(),
             remove_list: mpsc::new(),
             wakeup: AtomicOption::none(),
+            stopped: AtomicBool::new(false),
         }
     }
 
+    // let the timer thread return, the pending timers never fire
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Release);
+        self.wakeup.take(Ordering::Relaxed).map(|t| t.unpark());
+    }
+
     pub fn add_timer(&self, dur: Duration, data: T) -> TimeoutHandle<T> {
         let (h, is_recal) = self.timer_list.add_timer(dur, data);
         // wake up the timer thread if it's a new queue
@@ -283,15 +378,15 @@ impl<T> TimerThread<T> {
     // the timer thread function
     pub fn run<F: Fn(T)>(&self, f: &F) {
         let current_thread = thread::current();
-        loop {
+        while !self.stopped.load(Ordering::Acquire) {
             while let Some(h) = self.remove_list.pop() {
-                h.remove();
+                remove_timer(h);
             }
             // we must register the thread handle first
             // or there will be no signal to wakeup the timer thread
             self.wakeup.swap(current_thread.clone(), Ordering::Relaxed);
 
-            if !self.remove_list.is_empty() {
+            if !self.remove_list.is_empty() || self.stopped.load(Ordering::Acquire) {
                 self.wakeup.take(Ordering::Relaxed).map(|t| t.unpark());
             }
 
@@ -333,4 +428,114 @@ mod tests {
 
         thread::sleep(Duration::from_millis(1500));
     }
+
+    #[test]
+    fn timer_resolution() {
+        use std::cell::RefCell;
+
+        let res = 10 * NANOS_PER_MILLI;
+        let list = TimeOutList::with_resolution(Duration::from_millis(10));
+        let start = round_up(now(), res) + 3 * NANOS_PER_MILLI;
+        list.add_timer_at(start, Duration::from_millis(1), 1);
+        list.add_timer_at(start, Duration::from_millis(7), 2);
+        list.add_timer_at(start, Duration::from_millis(25), 3);
+        // the timers of the same slot share the list
+        assert_eq!(list.interval_map.read().unwrap().len(), 2);
+
+        let fired = RefCell::new(Vec::new());
+        let f = |data: usize| fired.borrow_mut().push(data);
+        let next = list.schedule_timer(start, &f).unwrap();
+        assert!(fired.borrow().is_empty());
+        // the timers expire at the slot boundaries
+        assert_eq!((start + next) % res, 0);
+
+        list.schedule_timer(start + 20 * NANOS_PER_MILLI, &f).unwrap();
+        assert_eq!(*fired.borrow(), vec![1, 2]);
+    }
+
+    #[test]
+    fn remove_million_timers() {
+        use std::cell::Cell;
+
+        const N: usize = 1_000_000;
+        let list = TimeOutList::with_resolution(Duration::from_millis(1));
+        let durs = [
+            Duration::from_secs(30),
+            Duration::from_secs(10),
+            Duration::from_millis(1500),
+        ];
+        let mut handles = (0..N)
+            .map(|i| list.add_timer(durs[i % durs.len()], i).0)
+            .collect::<Vec<_>>();
+
+        // the reads complete in any order, remove the latest ones first
+        let mut removed = 0;
+        let half = handles.split_off(N / 2);
+        for h in half.into_iter().rev().chain(handles.into_iter()) {
+            if remove_timer(h) {
+                removed += 1;
+            }
+        }
+        // only the one left alone in each list
+        assert!(removed >= N - durs.len());
+
+        let fired = Cell::new(0);
+        let f = |_: usize| fired.set(fired.get() + 1);
+        assert!(list.schedule_timer(now() + 60 * NANOS_PER_SEC, &f).is_none());
+        assert_eq!(removed + fired.get(), N);
+    }
+
+    #[test]
+    fn fire_in_order_of_expiry() {
+        use std::cell::RefCell;
+
+        let ms = NANOS_PER_MILLI;
+        let list = TimeOutList::with_resolution(Duration::from_millis(10));
+        let start = round_up(now(), 10 * ms);
+        // the intervals round up to the same multiple of the resolution,
+        // but the later one expires a slot earlier
+        list.add_timer_at(start + ms, Duration::from_millis(20), 1);
+        list.add_timer_at(start + 2 * ms, Duration::from_millis(11), 2);
+
+        let fired = RefCell::new(Vec::new());
+        let f = |data: usize| fired.borrow_mut().push(data);
+        // no later than the deadline plus the resolution
+        list.schedule_timer(start + 20 * ms, &f);
+        assert_eq!(*fired.borrow(), vec![2]);
+        list.schedule_timer(start + 30 * ms, &f);
+        assert_eq!(*fired.borrow(), vec![2, 1]);
+    }
+
+    #[test]
+    fn remove_timers_of_distinct_intervals() {
+        use std::cell::Cell;
+
+        // like the timeouts that are computed from a deadline, each one is
+        // a bit shorter than the full timeout
+        const N: usize = 2000;
+        let list = TimeOutList::with_resolution(Duration::from_millis(1));
+        let start = now();
+        let handles = (0..N)
+            .map(|i| {
+                let dur = Duration::from_secs(10) - Duration::new(0, (i * 7919 % 50_000) as u32);
+                list.add_timer_at(start + i as u64 * 7_000, dur, i).0
+            })
+            .collect::<Vec<_>>();
+        // they share the list of the same slot distance
+        let lists = list.interval_map.read().unwrap().len();
+        assert!(lists <= 2, "lists = {}", lists);
+
+        let mut removed = 0;
+        for h in handles {
+            if remove_timer(h) {
+                removed += 1;
+            }
+        }
+        assert!(removed >= N - lists);
+
+        let fired = Cell::new(0);
+        let f = |_: usize| fired.set(fired.get() + 1);
+        assert!(list.schedule_timer(start + 60 * NANOS_PER_SEC, &f).is_none());
+        assert_eq!(removed + fired.get(), N);
+    }
 }
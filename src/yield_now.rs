@@ -3,6 +3,7 @@ use generator::{co_get_yield, co_yield_with};
 use coroutine_impl::{current_cancel_data, is_coroutine};
 use coroutine_impl::{CoroutineImpl, EventResult, EventSource, EventSubscriber};
 use scheduler::get_scheduler;
+use listener::ParkReason;
 
 struct Yield {}
 
@@ -12,6 +13,10 @@ impl EventSource for Yield {
         get_scheduler().schedule(co);
         // println!("yield_out()");
     }
+
+    fn park_reason(&self) -> Option<ParkReason> {
+        Some(ParkReason::Yield)
+    }
 }
 
 /// yield internal `EventSource` ref
@@ -0,0 +1,49 @@
+#[macro_use]
+extern crate may;
+
+use std::thread;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use may::coroutine;
+
+#[test]
+fn blocking_on_one_worker() {
+    // set before the scheduler is started
+    may::config().set_workers(1).set_blocking_workers(16);
+
+    let done = Arc::new(AtomicBool::new(false));
+    let ticks = Arc::new(AtomicUsize::new(0));
+    let ticker = {
+        let done = done.clone();
+        let ticks = ticks.clone();
+        go!(move || while !done.load(Ordering::Relaxed) {
+            coroutine::sleep(Duration::from_millis(10));
+            ticks.fetch_add(1, Ordering::Relaxed);
+        })
+    };
+
+    let start = Instant::now();
+    let handles = (0..64)
+        .map(|i| {
+            go!(move || {
+                coroutine::spawn_blocking(move || {
+                    thread::sleep(Duration::from_millis(100));
+                    i
+                }).join()
+                    .unwrap()
+            })
+        })
+        .collect::<Vec<_>>();
+    for (i, h) in handles.into_iter().enumerate() {
+        assert_eq!(h.join().unwrap(), i);
+    }
+    let elapsed = start.elapsed();
+    done.store(true, Ordering::Relaxed);
+    ticker.join().unwrap();
+
+    // 4 rounds of 16 threads instead of 64 sequential sleeps
+    assert!(elapsed < Duration::from_secs(2));
+    // the only worker keeps running the other coroutines
+    assert!(ticks.load(Ordering::Relaxed) >= 10);
+}
@@ -0,0 +1,27 @@
+// the helpers shared by the integration tests
+
+use std::env;
+use std::process::Command;
+
+// the test that the child process runs
+const CHILD_TEST: &str = "MAY_CHILD_TEST";
+
+/// run `f` in a child process that runs only the `test`
+///
+/// for the tests that crash, abort or check the stderr. the child re-runs
+/// the current test binary and calls `f`, where `None` is returned. the
+/// parent gets whether the child exited successfully and its stderr
+pub fn run_in_child<F: FnOnce()>(test: &str, f: F) -> Option<(bool, String)> {
+    if env::var(CHILD_TEST).map(|t| t == test).unwrap_or(false) {
+        f();
+        return None;
+    }
+
+    let out = Command::new(env::current_exe().unwrap())
+        .args(&["--exact", test, "--nocapture"])
+        .env(CHILD_TEST, test)
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&out.stderr).into_owned();
+    Some((out.status.success(), stderr))
+}
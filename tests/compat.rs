@@ -0,0 +1,65 @@
+#![cfg(feature = "futures")]
+#[macro_use]
+extern crate may;
+extern crate futures;
+
+use std::thread;
+use std::time::Duration;
+use futures::channel::oneshot;
+use may::compat::block_on;
+
+#[test]
+fn wake_from_thread() {
+    let (tx, rx) = oneshot::channel();
+    let h = go!(move || block_on(rx).unwrap());
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(20));
+        tx.send(42).unwrap();
+    });
+    assert_eq!(h.join().unwrap(), 42);
+
+    // in thread context
+    let (tx, rx) = oneshot::channel();
+    thread::spawn(move || tx.send("thread").unwrap());
+    assert_eq!(block_on(rx).unwrap(), "thread");
+}
+
+#[cfg(unix)]
+#[test]
+fn async_echo_client() {
+    use std::io::{Read, Write};
+    use futures::io::{AsyncReadExt, AsyncWriteExt};
+    use may::compat::Compat;
+    use may::net::{TcpListener, TcpStream};
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = go!(move || {
+        let (mut s, _) = listener.accept().unwrap();
+        let mut buf = [0u8; 1024];
+        loop {
+            let n = s.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            // the client has to wait for the echo
+            may::coroutine::sleep(Duration::from_millis(10));
+            s.write_all(&buf[..n]).unwrap();
+        }
+    });
+
+    let client = go!(move || {
+        let mut s = Compat::new(TcpStream::connect(addr).unwrap()).unwrap();
+        for i in 0..10 {
+            let msg = format!("hello {}", i);
+            block_on(s.write_all(msg.as_bytes())).unwrap();
+            let mut buf = vec![0u8; msg.len()];
+            block_on(s.read_exact(&mut buf)).unwrap();
+            assert_eq!(buf, msg.as_bytes());
+        }
+        block_on(s.close()).unwrap();
+    });
+
+    client.join().unwrap();
+    server.join().unwrap();
+}
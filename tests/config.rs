@@ -0,0 +1,65 @@
+#[macro_use]
+extern crate may;
+
+use std::time::{Duration, Instant};
+use may::coroutine;
+use may::coroutine::Builder;
+
+mod common;
+
+#[test]
+fn timer_resolution() {
+    // the resolution is read when the scheduler starts
+    let (ok, stderr) = match common::run_in_child("timer_resolution", check_timer_resolution) {
+        Some(ret) => ret,
+        None => return,
+    };
+    assert!(ok, "{}", stderr);
+}
+
+fn check_timer_resolution() {
+    let config = may::config();
+    assert_eq!(config.get_timer_resolution(), Duration::from_millis(1));
+
+    config.set_timer_resolution(Duration::from_nanos(1500));
+    assert_eq!(config.get_timer_resolution(), Duration::from_micros(2));
+    config.set_timer_resolution(Duration::from_secs(0));
+    assert_eq!(config.get_timer_resolution(), Duration::from_millis(1));
+
+    // set before the scheduler is started
+    config.set_timer_resolution(Duration::from_millis(20));
+    assert_eq!(config.get_timer_resolution(), Duration::from_millis(20));
+    let j = go!(|| {
+        // wake up on a slot boundary first
+        coroutine::sleep(Duration::from_millis(1));
+        (0..5)
+            .map(|_| {
+                let start = Instant::now();
+                coroutine::sleep(Duration::from_millis(1));
+                start.elapsed()
+            })
+            .collect::<Vec<_>>()
+    });
+    // each sleep is rounded up to the next slot boundary
+    for dur in j.join().unwrap() {
+        assert!(dur >= Duration::from_millis(15), "{:?}", dur);
+        assert!(dur < Duration::from_millis(200), "{:?}", dur);
+    }
+}
+
+#[test]
+fn pin_to_started_workers() {
+    // start the scheduler
+    go!(|| {}).join().unwrap();
+    let config = may::config();
+    let workers = config.get_workers();
+
+    // the scheduler keeps the workers that it's started with
+    config.set_workers(workers + 4);
+    let e = go!(Builder::new().pin_to(workers + 2), || {});
+    assert_eq!(e.unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+    assert_eq!(may::scheduler::metrics().workers, workers);
+    let h = go!(Builder::new().pin_to(workers - 1), coroutine::current_worker_id).unwrap();
+    assert_eq!(h.join().unwrap(), Some(workers - 1));
+    config.set_workers(workers);
+}
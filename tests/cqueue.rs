@@ -4,6 +4,7 @@ extern crate may;
 use may::{coroutine, cqueue};
 use cqueue::PollError::*;
 
+use std::cmp;
 use std::time::Duration;
 
 #[test]
@@ -167,6 +168,31 @@ fn cqueue_select() {
     assert_eq!(rx1.recv(), Ok(42));
 }
 
+#[test]
+fn select_after() {
+    use may::sync::mpsc::{after, channel};
+
+    let (tx, rx) = channel();
+
+    // nothing is ready in time, pick the timeout arm
+    let id = select!(
+        _ = rx.recv() => unreachable!("nothing should be received"),
+        _ = after(Duration::from_millis(50)).recv() => {}
+    );
+    assert_eq!(id, 1);
+
+    go!(move || {
+        tx.send(1).unwrap();
+    });
+
+    // the data arrives before the timeout
+    let id = select!(
+        a = rx.recv() => assert_eq!(a, Ok(1)),
+        _ = after(Duration::from_secs(10)).recv() => {}
+    );
+    assert_eq!(id, 0);
+}
+
 #[test]
 fn cqueue_timeout() {
     cqueue::scope(|cqueue| {
@@ -230,3 +256,99 @@ fn cqueue_loop() {
 
     assert_eq!(result, 50);
 }
+
+#[test]
+fn cqueue_fair() {
+    use may::sync::mpsc::channel;
+
+    let (tx1, rx1) = channel();
+    let (tx2, rx2) = channel();
+    for i in 0..50_000 {
+        tx1.send(i).unwrap();
+        tx2.send(i).unwrap();
+    }
+
+    // both sources are always ready, they should take turns
+    let mut count = [0; 2];
+    cqueue::scope(|cqueue| {
+        cqueue_add!(cqueue, 0, _ = rx1.recv() => {});
+        cqueue_add!(cqueue, 1, _ = rx2.recv() => {});
+        // the select coroutines could start at different times
+        let mut started = [false; 2];
+        while !(started[0] && started[1]) {
+            started[cqueue.poll(None).unwrap().token] = true;
+        }
+        for _ in 0..10_000 {
+            let ev = cqueue.poll(None).unwrap();
+            count[ev.token] += 1;
+        }
+        drop(tx1);
+        drop(tx2);
+    });
+
+    assert!(count[0] > 4_500 && count[1] > 4_500, "count={:?}", count);
+}
+
+#[test]
+fn cqueue_priority() {
+    use may::sync::mpsc::channel;
+
+    // the extra of the first event of each source
+    const HELLO: usize = 1;
+
+    let (tx1, rx1) = channel();
+    let (tx2, rx2) = channel();
+    let (tx3, rx3) = channel();
+    for i in 0..100 {
+        tx1.send(i).unwrap();
+        tx2.send(i).unwrap();
+        tx3.send(i).unwrap();
+    }
+    drop(tx1);
+    drop(tx2);
+    drop(tx3);
+
+    let mut tokens = Vec::new();
+    // the messages that are polled before all the sources are started
+    let mut early = [0; 3];
+    cqueue::scope(|cqueue| {
+        let rxs = vec![(0, 0, rx1), (1, 1, rx2), (2, 1, rx3)];
+        for (token, prio, rx) in rxs {
+            unsafe {
+                cqueue.add_with_priority(token, prio, move |es| {
+                    es.send(HELLO);
+                    while rx.recv().is_ok() {
+                        es.send(0);
+                    }
+                });
+            }
+        }
+        // wait until all the sources have pushed an event. the bottom half
+        // of a polled event pushes the next one before the poll returns, so
+        // all of them are ready from then on
+        let mut started = [false; 3];
+        while started.iter().any(|s| !s) {
+            let ev = cqueue.poll(None).unwrap();
+            if ev.extra == HELLO {
+                started[ev.token] = true;
+            } else {
+                early[ev.token] += 1;
+            }
+        }
+
+        while let Ok(ev) = cqueue.poll(None) {
+            tokens.push(ev.token);
+        }
+    });
+
+    let left = [100 - early[0], 100 - early[1], 100 - early[2]];
+    assert_eq!(tokens.len(), left[0] + left[1] + left[2]);
+    // the higher priority ones take turns before the lower one
+    let high = left[1] + left[2];
+    let turns = 2 * cmp::min(left[1], left[2]);
+    for pair in tokens[0..turns].chunks(2) {
+        assert_eq!(pair[0] + pair[1], 3, "early={:?}", early);
+    }
+    assert!(tokens[0..high].iter().all(|&t| t != 0));
+    assert!(tokens[high..].iter().all(|&t| t == 0));
+}
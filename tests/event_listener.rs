@@ -0,0 +1,131 @@
+#[macro_use]
+extern crate may;
+
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use may::coroutine::{self, Builder, CoroutineEventListener, ExitKind, ParkReason};
+use may::net::{TcpListener, TcpStream};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Event {
+    Spawn(Option<String>),
+    Resume,
+    Park(ParkReason),
+    Exit(ExitKind),
+}
+
+type Events = Arc<Mutex<Vec<(usize, Event)>>>;
+
+struct Recorder {
+    events: Events,
+}
+
+impl Recorder {
+    fn push(&self, id: usize, ev: Event) {
+        self.events.lock().unwrap().push((id, ev));
+    }
+}
+
+impl CoroutineEventListener for Recorder {
+    fn on_spawn(&self, id: usize, name: Option<&str>) {
+        self.push(id, Event::Spawn(name.map(|s| s.to_owned())));
+    }
+    fn on_resume(&self, id: usize, worker: Option<usize>) {
+        assert_eq!(worker, coroutine::current_worker_id());
+        self.push(id, Event::Resume);
+    }
+    fn on_park(&self, id: usize, reason: ParkReason) {
+        self.push(id, Event::Park(reason));
+    }
+    fn on_exit(&self, id: usize, kind: ExitKind) {
+        self.push(id, Event::Exit(kind));
+    }
+}
+
+fn events_of(events: &Events, id: usize) -> Vec<Event> {
+    // the exit is reported when the coroutine is dropped after the join
+    for _ in 0..100 {
+        let list = events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|&&(i, _)| i == id)
+            .map(|&(_, ref ev)| ev.clone())
+            .collect::<Vec<_>>();
+        if let Some(&Event::Exit(_)) = list.last() {
+            return list;
+        }
+        coroutine::sleep(Duration::from_millis(1));
+    }
+    panic!("coroutine {} is not finished", id);
+}
+
+// the listener is for the whole process, so it's the only test here
+#[test]
+fn lifecycle_events() {
+    let events = Events::default();
+    may::config().set_event_listener(Recorder {
+        events: events.clone(),
+    });
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = go!(move || {
+        let (mut s, _) = listener.accept().unwrap();
+        let mut buf = [0u8; 4];
+        s.read_exact(&mut buf).unwrap();
+    });
+    let h = go!(Builder::new().name("client".to_owned()), move || {
+        coroutine::sleep(Duration::from_millis(10));
+        coroutine::yield_now();
+        let mut s = TcpStream::connect(addr).unwrap();
+        s.write_all(b"ping").unwrap();
+        // wait for the server to finish
+        server.join().unwrap();
+    }).unwrap();
+    let id = h.coroutine().id();
+    h.join().unwrap();
+
+    let list = events_of(&events, id);
+    assert_eq!(list[0], Event::Spawn(Some("client".to_owned())));
+    assert_eq!(list[1], Event::Resume);
+    assert_eq!(list[2], Event::Park(ParkReason::Timer));
+    assert_eq!(list[3], Event::Resume);
+    assert_eq!(list[4], Event::Park(ParkReason::Yield));
+    assert_eq!(list[5], Event::Resume);
+    assert!(list.contains(&Event::Park(ParkReason::Sync)));
+    assert_eq!(list[list.len() - 2], Event::Resume);
+    assert_eq!(list.last(), Some(&Event::Exit(ExitKind::Finished)));
+    // each resume is followed by a park, but the last one
+    for pair in list[1..list.len() - 2].chunks(2) {
+        assert_eq!(pair[0], Event::Resume);
+        match pair[1] {
+            Event::Park(_) => {}
+            ref ev => panic!("unexpected event {:?}", ev),
+        }
+    }
+
+    // the io wait of the accepting coroutine
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let h = go!(move || listener.accept().map(|_| ()).unwrap());
+    let id = h.coroutine().id();
+    coroutine::sleep(Duration::from_millis(10));
+    TcpStream::connect(addr).unwrap();
+    h.join().unwrap();
+    assert!(events_of(&events, id).contains(&Event::Park(ParkReason::Io)));
+
+    // the panicked and canceled ones
+    let h = go!(|| panic!("exit"));
+    let id = h.coroutine().id();
+    h.join().unwrap_err();
+    assert_eq!(events_of(&events, id).last(), Some(&Event::Exit(ExitKind::Panicked)));
+
+    let h = go!(|| coroutine::park());
+    let id = h.coroutine().id();
+    coroutine::sleep(Duration::from_millis(10));
+    unsafe { h.coroutine().cancel() };
+    h.join().unwrap_err();
+    assert_eq!(events_of(&events, id).last(), Some(&Event::Exit(ExitKind::Canceled)));
+}
@@ -22,6 +22,22 @@ fn panic_coroutine() {
     }
 }
 
+#[test]
+fn panic_payload_message() {
+    let j = go!(|| {
+        let v = 1;
+        assert_eq!(v, 2, "worker {} failed", 7);
+    });
+    let e = j.join().unwrap_err();
+    assert!(!coroutine::is_cancel_panic(&*e));
+    let msg = e.downcast_ref::<String>().unwrap();
+    assert!(msg.contains("worker 7 failed"));
+
+    let j = go!(|| coroutine::park());
+    unsafe { j.coroutine().cancel() };
+    assert!(coroutine::is_cancel_panic(&*j.join().unwrap_err()));
+}
+
 #[test]
 fn cancel_coroutine() {
     let j = go!(move || {
@@ -50,6 +66,64 @@ fn cancel_coroutine() {
     }
 }
 
+#[test]
+fn cancel_guard_region() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use coroutine::CancelGuard;
+
+    let steps = Arc::new(AtomicUsize::new(0));
+    let s = steps.clone();
+    let j = go!(move || {
+        {
+            let _guard = CancelGuard::new();
+            // charge
+            s.fetch_add(1, Ordering::SeqCst);
+            coroutine::sleep(Duration::from_millis(50));
+            // record, nested guards are fine
+            let _inner = CancelGuard::new();
+            coroutine::yield_now();
+            s.fetch_add(1, Ordering::SeqCst);
+        }
+        // the deferred cancel is delivered here
+        coroutine::sleep(Duration::from_secs(100));
+        s.fetch_add(1, Ordering::SeqCst);
+    });
+
+    thread::sleep(Duration::from_millis(10));
+    let now = Instant::now();
+    unsafe { j.coroutine().cancel() };
+    let e = j.join().unwrap_err();
+    assert!(coroutine::is_cancel_panic(&*e));
+    assert_eq!(steps.load(Ordering::SeqCst), 2);
+    assert!(now.elapsed() < Duration::from_secs(10));
+}
+
+#[test]
+fn cancel_cpu_loop() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let started = Arc::new(AtomicBool::new(false));
+    let s = started.clone();
+    let j = go!(move || {
+        let mut n = 0u64;
+        loop {
+            s.store(true, Ordering::Relaxed);
+            n = n.wrapping_add(1);
+            coroutine::check_cancel();
+        }
+    });
+
+    while !started.load(Ordering::Relaxed) {
+        thread::yield_now();
+    }
+    let now = Instant::now();
+    unsafe { j.coroutine().cancel() };
+    assert!(coroutine::is_cancel_panic(&*j.join().unwrap_err()));
+    assert!(now.elapsed() < Duration::from_secs(1));
+}
+
 #[test]
 fn cancel_io_coroutine() {
     let j = go!(move || {
@@ -190,6 +264,56 @@ fn scoped_coroutine() {
     assert_eq!(array[2], 4);
 }
 
+#[test]
+fn scoped_collect() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let done = AtomicUsize::new(0);
+    let ret = coroutine::scope_collect(|scope| {
+        for i in 0..4 {
+            let done = &done;
+            go!(scope, move || {
+                if i == 1 {
+                    panic!("child {} failed", i);
+                }
+                coroutine::sleep(Duration::from_millis(10));
+                done.fetch_add(1, Ordering::Relaxed);
+                i * 10
+            });
+        }
+    });
+
+    // the panic doesn't stop the other children from being joined
+    assert_eq!(done.load(Ordering::Relaxed), 3);
+    assert_eq!(ret.len(), 4);
+    assert_eq!(*ret[0].as_ref().unwrap(), 0);
+    assert!(ret[1].is_err());
+    assert_eq!(*ret[2].as_ref().unwrap(), 20);
+    assert_eq!(*ret[3].as_ref().unwrap(), 30);
+}
+
+#[test]
+fn scoped_collect_panic_in_scope() {
+    use std::panic;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let done = AtomicBool::new(false);
+    let ret = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        coroutine::scope_collect(|scope| {
+            let done = &done;
+            go!(scope, move || {
+                coroutine::sleep(Duration::from_millis(20));
+                done.store(true, Ordering::Relaxed);
+            });
+            panic!("scope failed");
+        })
+    }));
+
+    assert!(ret.is_err());
+    // the child is joined before the panic leaves the scope
+    assert!(done.load(Ordering::Relaxed));
+}
+
 #[test]
 fn yield_from_gen() {
     let mut a = 0;
@@ -309,3 +433,737 @@ fn join_macro() {
     assert_eq!(rx1.try_recv().is_err(), true);
     assert_eq!(rx2.try_recv().is_err(), true);
 }
+
+#[test]
+fn sleep_until_no_drift() {
+    let h = go!(|| {
+        let step = Duration::from_millis(10);
+        let start = Instant::now();
+        let mut deadline = start;
+        for _ in 0..20 {
+            deadline += step;
+            coroutine::sleep_until(deadline);
+            // never wake up early
+            assert!(Instant::now() >= deadline);
+            // the work in each step doesn't shift the next deadline
+            thread::sleep(Duration::from_millis(2));
+        }
+        start.elapsed()
+    });
+    let elapsed = h.join().unwrap();
+    assert!(elapsed >= Duration::from_millis(200));
+    assert!(elapsed < Duration::from_millis(600));
+
+    // a passed deadline returns at once
+    let now = Instant::now();
+    coroutine::sleep_until(now);
+    go!(move || coroutine::sleep_until(now)).join().unwrap();
+}
+
+#[test]
+fn sleep_latency() {
+    let hs = (0..100)
+        .map(|_| {
+            go!(|| {
+                let step = Duration::from_millis(10);
+                let mut late = Duration::from_millis(0);
+                for _ in 0..10 {
+                    let start = Instant::now();
+                    coroutine::sleep(step);
+                    let elapsed = start.elapsed();
+                    // never wake up early
+                    assert!(elapsed >= step);
+                    late += elapsed - step;
+                }
+                late / 10
+            })
+        })
+        .collect::<Vec<_>>();
+    let late = hs.into_iter()
+        .map(|h| h.join().unwrap())
+        .fold(Duration::from_millis(0), |a, b| a + b) / 100;
+    // the resolution is 1ms, leave some room for a busy machine
+    assert!(late < Duration::from_millis(10), "late by {:?}", late);
+}
+
+#[test]
+fn spawn_batch() {
+    let hs = unsafe { coroutine::spawn_batch((0..10000).map(|i| move || i + 1)) };
+    assert_eq!(hs.len(), 10000);
+    for (i, h) in hs.into_iter().enumerate() {
+        assert_eq!(h.join().unwrap(), i + 1);
+    }
+
+    // from a coroutine, and an empty batch
+    let h = go!(|| {
+        let hs = unsafe { coroutine::spawn_batch((0..100).map(|_| coroutine::yield_now)) };
+        let empty = unsafe { coroutine::spawn_batch(Vec::<fn()>::new()) };
+        assert!(empty.is_empty());
+        hs.into_iter().map(|h| h.join().unwrap()).count()
+    });
+    assert_eq!(h.join().unwrap(), 100);
+}
+
+#[test]
+fn interval_no_drift() {
+    let h = go!(|| {
+        let interval = coroutine::interval(Duration::from_millis(10));
+        let start = Instant::now();
+        let mut last = start;
+        for _ in 0..100 {
+            let deadline = interval.tick();
+            // the deadlines are the multiples of the period
+            assert!(deadline > last);
+            assert!(Instant::now() >= deadline);
+            last = deadline;
+            // the work doesn't shift the next tick
+            thread::sleep(Duration::from_millis(3));
+        }
+        start.elapsed()
+    });
+    let elapsed = h.join().unwrap();
+    assert!(elapsed >= Duration::from_millis(1000));
+    assert!(elapsed < Duration::from_millis(1150), "took {:?}", elapsed);
+}
+
+#[test]
+fn interval_missed_ticks() {
+    use coroutine::MissedTickBehavior;
+
+    let period = Duration::from_millis(20);
+    let start = Instant::now();
+
+    // all the missed ticks are fired at once
+    let burst = coroutine::interval_at(start, period);
+    assert_eq!(burst.missed_tick_behavior(), MissedTickBehavior::Burst);
+    coroutine::sleep_until(start + period * 3 + period / 4);
+    for i in 0..5 {
+        assert_eq!(burst.tick(), start + period * i);
+    }
+    assert!(Instant::now() >= start + period * 4);
+
+    // only the latest one
+    let mut skip = coroutine::interval_at(start + period * 5, period);
+    skip.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    coroutine::sleep_until(start + period * 7 + period / 4);
+    assert_eq!(skip.tick(), start + period * 7);
+    assert_eq!(skip.tick(), start + period * 8);
+}
+
+#[test]
+fn interval_cancel() {
+    use std::sync::Arc;
+
+    // a canceled wait doesn't consume the tick
+    let start = Instant::now() + Duration::from_millis(50);
+    let interval = Arc::new(coroutine::interval_at(start, Duration::from_millis(10)));
+    let i = interval.clone();
+    let h = go!(move || i.tick());
+    coroutine::sleep(Duration::from_millis(10));
+    unsafe { h.coroutine().cancel() };
+    assert!(h.join().is_err());
+    assert_eq!(interval.tick(), start);
+
+    // the losing arm of select
+    let start = interval.tick() + Duration::from_millis(10);
+    let id = select!(
+        _ = interval.tick() => {},
+        _ = coroutine::sleep(Duration::from_millis(1)) => {}
+    );
+    assert_eq!(id, 1);
+    assert_eq!(interval.tick(), start);
+}
+
+#[test]
+fn join_timeout() {
+    let mut j = go!(move || {
+        coroutine::sleep(Duration::from_millis(100));
+        42
+    });
+
+    let now = Instant::now();
+    assert!(j.join_timeout(Duration::from_millis(10)).is_err());
+    assert!(now.elapsed() < Duration::from_millis(100));
+    // the coroutine is still running, join it again
+    assert!(!j.is_done());
+    assert_eq!(j.join_timeout(Duration::from_secs(10)).unwrap().unwrap(), 42);
+
+    // join after timeout from a coroutine
+    let mut j = go!(move || {
+        coroutine::sleep(Duration::from_millis(50));
+        "done"
+    });
+    let h = go!(move || {
+        assert!(j.join_timeout(Duration::from_millis(5)).is_err());
+        j.join().unwrap()
+    });
+    assert_eq!(h.join().unwrap(), "done");
+}
+
+#[test]
+fn wait_timeout_then_cancel() {
+    // wait from a thread
+    let mut j = go!(|| coroutine::sleep(Duration::from_secs(1)));
+    let now = Instant::now();
+    assert!(!j.wait_timeout(Duration::from_millis(100)));
+    assert!(now.elapsed() < Duration::from_millis(500));
+    assert!(!j.is_done());
+    unsafe { j.coroutine().cancel() };
+    assert!(j.join().is_err());
+
+    // wait from a coroutine
+    let h = go!(|| {
+        let mut j = go!(|| coroutine::sleep(Duration::from_secs(1)));
+        let now = Instant::now();
+        assert!(!j.wait_timeout(Duration::from_millis(100)));
+        assert!(now.elapsed() < Duration::from_millis(500));
+        unsafe { j.coroutine().cancel() };
+        assert!(j.join().is_err());
+
+        let mut j = go!(|| 1);
+        assert!(j.wait_timeout(Duration::from_secs(10)));
+        assert!(j.is_done());
+        j.join().unwrap()
+    });
+    assert_eq!(h.join().unwrap(), 1);
+}
+
+#[test]
+fn pinned_coroutines() {
+    use std::io::{Read, Write};
+    use coroutine::Builder;
+    use may::net::{TcpListener, TcpStream};
+    use may::sync::mpsc::channel;
+
+    let worker = may::config().get_workers() - 1;
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (tx, rx) = channel();
+
+    let v = (0..100)
+        .map(|i| {
+            let tx = tx.clone();
+            go!(Builder::new().pin_to(worker), move || {
+                let mut ids = vec![coroutine::current_worker_id()];
+                for _ in 0..5 {
+                    coroutine::yield_now();
+                    ids.push(coroutine::current_worker_id());
+                }
+                // back from the timer
+                coroutine::sleep(Duration::from_millis(1));
+                ids.push(coroutine::current_worker_id());
+                if i % 10 == 0 {
+                    // back from the io worker
+                    let mut s = TcpStream::connect(addr).unwrap();
+                    let mut buf = [0u8; 1];
+                    s.read_exact(&mut buf).unwrap();
+                    ids.push(coroutine::current_worker_id());
+                }
+                tx.send(()).unwrap();
+                ids
+            }).unwrap()
+        })
+        .collect::<Vec<_>>();
+
+    for _ in 0..10 {
+        let (mut s, _) = listener.accept().unwrap();
+        coroutine::sleep(Duration::from_millis(5));
+        s.write_all(b"x").unwrap();
+    }
+    for _ in 0..100 {
+        rx.recv().unwrap();
+    }
+    for h in v {
+        for id in h.join().unwrap() {
+            assert_eq!(id, Some(worker));
+        }
+    }
+
+    let e = go!(Builder::new().pin_to(may::config().get_workers()), || {});
+    assert_eq!(e.unwrap_err().kind(), ::std::io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn with_timeout_unwinds() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use may::sync::Mutex;
+
+    struct Resource(Arc<AtomicBool>);
+    impl Drop for Resource {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    let dropped = Arc::new(AtomicBool::new(false));
+    let lock = Arc::new(Mutex::new(0));
+    let guard = lock.lock().unwrap();
+    let (d, l) = (dropped.clone(), lock.clone());
+    let now = Instant::now();
+    let ret = coroutine::with_timeout(Duration::from_millis(50), move || {
+        let _r = Resource(d);
+        coroutine::sleep(Duration::from_millis(10));
+        // block on the lock that is never released
+        *l.lock().unwrap() += 1;
+    });
+    assert!(ret.is_err());
+    assert!(now.elapsed() < Duration::from_secs(5));
+    assert!(dropped.load(Ordering::SeqCst));
+    drop(guard);
+    assert_eq!(*lock.lock().unwrap(), 0);
+
+    // in coroutine context, and the panic is propagated
+    let h = go!(|| {
+        assert_eq!(coroutine::with_timeout(Duration::from_secs(5), || 1).unwrap(), 1);
+        coroutine::with_timeout(Duration::from_secs(5), || panic!("inner"))
+    });
+    let e = h.join().unwrap_err();
+    assert_eq!(e.downcast_ref::<&str>(), Some(&"inner"));
+}
+
+#[test]
+fn with_timeout_blocked_io() {
+    use std::io::Read;
+    use may::net::{TcpListener, TcpStream};
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    // the peer never sends anything
+    let _client = TcpStream::connect(addr).unwrap();
+    let (mut s, _) = listener.accept().unwrap();
+
+    let now = Instant::now();
+    // the read returns the cancel as its error instead of unwinding
+    let ret = coroutine::with_timeout(Duration::from_millis(50), move || {
+        let mut buf = [0u8; 8];
+        s.read(&mut buf)
+    });
+    assert!(ret.is_err());
+    assert!(now.elapsed() < Duration::from_secs(5));
+
+    // the unwrapped cancel error is the timeout as well
+    let _client = TcpStream::connect(addr).unwrap();
+    let (mut s, _) = listener.accept().unwrap();
+    let ret = coroutine::with_timeout(Duration::from_millis(50), move || {
+        let mut buf = [0u8; 8];
+        s.read(&mut buf).unwrap()
+    });
+    assert!(ret.is_err());
+}
+
+#[test]
+fn cancel_unwrapped_io_err() {
+    use std::io::Read;
+    use may::net::{TcpListener, TcpStream};
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let _client = TcpStream::connect(addr).unwrap();
+    let (mut s, _) = listener.accept().unwrap();
+
+    let h = go!(move || {
+        let mut buf = [0u8; 8];
+        // the cancel error is unwrapped into a panic
+        s.read(&mut buf).unwrap();
+    });
+    thread::sleep(Duration::from_millis(20));
+    unsafe { h.coroutine().cancel() };
+    let e = h.join().unwrap_err();
+    assert!(coroutine::is_cancel_panic(&*e));
+}
+
+#[test]
+fn panic_before_seeing_cancel() {
+    let h = go!(|| {
+        // not a cancel point, the cancel is never seen
+        thread::sleep(Duration::from_millis(100));
+        panic!("boom");
+    });
+    thread::sleep(Duration::from_millis(20));
+    unsafe { h.coroutine().cancel() };
+    let e = h.join().unwrap_err();
+    assert!(!coroutine::is_cancel_panic(&*e));
+    assert_eq!(e.downcast_ref::<&str>(), Some(&"boom"));
+}
+
+#[test]
+fn scheduler_metrics() {
+    let before = may::scheduler::metrics();
+    let j = go!(|| coroutine::park());
+    let m = may::scheduler::metrics();
+    assert!(m.spawned_coroutines > before.spawned_coroutines);
+    assert!(m.live_coroutines >= 1);
+    assert!(m.workers >= 1);
+    assert!(m.parked_workers <= m.workers);
+
+    j.coroutine().unpark();
+    j.join().unwrap();
+}
+
+#[test]
+fn pinned_queue_depths() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use coroutine::Builder;
+    use may::scheduler::pinned_queue_depths;
+
+    let worker = may::scheduler::metrics().workers - 1;
+    let (started, stop) = (Arc::new(AtomicBool::new(false)), Arc::new(AtomicBool::new(false)));
+    let (s1, s2) = (started.clone(), stop.clone());
+    // keep the worker busy so that the pinned ones wait in its queue
+    let busy = go!(Builder::new().pin_to(worker), move || {
+        s1.store(true, Ordering::Release);
+        while !s2.load(Ordering::Acquire) {}
+    }).unwrap();
+    while !started.load(Ordering::Acquire) {
+        thread::sleep(Duration::from_millis(1));
+    }
+
+    let hs = (0..5)
+        .map(|_| go!(Builder::new().pin_to(worker), || {}).unwrap())
+        .collect::<Vec<_>>();
+    let depths = pinned_queue_depths();
+    assert_eq!(depths.len(), worker + 1);
+    assert!(depths[worker] >= 5, "{:?}", depths);
+
+    stop.store(true, Ordering::Release);
+    busy.join().unwrap();
+    for h in hs {
+        h.join().unwrap();
+    }
+    assert_eq!(pinned_queue_depths()[worker], 0);
+}
+
+#[test]
+fn scheduler_metrics_counters() {
+    use std::io::{Read, Write};
+    use may::net::{TcpListener, TcpStream};
+
+    const N: usize = 20;
+    let before = may::scheduler::metrics();
+    let hs = (0..N).map(|_| go!(|| yield_now())).collect::<Vec<_>>();
+    for h in hs {
+        h.join().unwrap();
+    }
+    // the coroutines are counted when they are dropped after the join
+    let mut m = may::scheduler::metrics();
+    for _ in 0..100 {
+        if m.finished_coroutines - before.finished_coroutines >= N {
+            break;
+        }
+        coroutine::sleep(Duration::from_millis(1));
+        m = may::scheduler::metrics();
+    }
+    assert!(m.spawned_coroutines - before.spawned_coroutines >= N);
+    assert!(m.finished_coroutines - before.finished_coroutines >= N);
+    assert!(m.resumes - before.resumes >= 2 * N);
+
+    // the sleeping coroutine has a pending timer
+    let h = go!(|| coroutine::sleep(Duration::from_millis(100)));
+    coroutine::sleep(Duration::from_millis(10));
+    assert!(may::scheduler::metrics().timers >= 1);
+    h.join().unwrap();
+
+    // the reader waits for the data in the selector
+    let before = may::scheduler::metrics();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = go!(move || {
+        let (mut s, _) = listener.accept().unwrap();
+        let mut buf = [0u8; 4];
+        s.read_exact(&mut buf).unwrap();
+        s.write_all(&buf).unwrap();
+    });
+    let client = go!(move || {
+        let mut s = TcpStream::connect(addr).unwrap();
+        coroutine::sleep(Duration::from_millis(10));
+        s.write_all(b"ping").unwrap();
+        let mut buf = [0u8; 4];
+        s.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"ping");
+    });
+    server.join().unwrap();
+    client.join().unwrap();
+    assert!(may::scheduler::metrics().io_events > before.io_events);
+}
+
+#[test]
+fn stack_pool_reuse() {
+    use coroutine::Builder;
+
+    // use up some of the stack, the recycled stack must work the same
+    fn deep(n: usize) -> usize {
+        let buf = [n as u8; 512];
+        if n == 0 {
+            buf.iter().map(|&b| b as usize).sum()
+        } else {
+            deep(n - 1) + buf[n % 512] as usize
+        }
+    }
+
+    // a size that no other test uses
+    let size = 0x2a00;
+    let before = may::scheduler::metrics();
+    for i in 0..10 {
+        let h = go!(Builder::new().stack_size(size), move || deep(20 + i)).unwrap();
+        let expected = (1..21 + i).sum::<usize>();
+        assert_eq!(h.join().unwrap(), expected);
+        // the coroutine is recycled after the join is triggered
+        coroutine::sleep(Duration::from_millis(5));
+    }
+    let m = may::scheduler::metrics();
+    assert!(m.stack_pool_misses > before.stack_pool_misses);
+    assert!(m.stack_pool_hits > before.stack_pool_hits);
+
+    may::scheduler::shrink_stack_pool();
+    let h = go!(Builder::new().stack_size(size), || deep(10)).unwrap();
+    assert_eq!(h.join().unwrap(), (1..11).sum::<usize>());
+}
+
+#[test]
+fn priority_not_starved() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use coroutine::{Builder, Priority};
+
+    let stop = Arc::new(AtomicBool::new(false));
+    // keep the workers busy with the high priority coroutines
+    let busy = (0..16)
+        .map(|_| {
+            let stop = stop.clone();
+            go!(Builder::new().priority(Priority::High), move || {
+                while !stop.load(Ordering::Relaxed) {
+                    yield_now();
+                }
+            }).unwrap()
+        })
+        .collect::<Vec<_>>();
+
+    let low = go!(Builder::new().priority(Priority::Low), || 42).unwrap();
+    assert_eq!(low.join().unwrap(), 42);
+
+    stop.store(true, Ordering::Relaxed);
+    for h in busy {
+        h.join().unwrap();
+    }
+}
+
+#[test]
+fn stack_high_water() {
+    use coroutine::Builder;
+
+    // keep the buffer on the stack
+    fn use_stack(n: usize) -> u8 {
+        let buf = [n as u8; 16 * 1024];
+        buf.iter().fold(0, |a, b| a ^ *b)
+    }
+
+    let h = go!(Builder::new().stack_size(0x8000).track_stack(true), || {
+        use_stack(1)
+    }).unwrap();
+    h.wait();
+    let used = h.stack_high_water().unwrap();
+    assert!(used >= 16 * 1024);
+    assert!(used < 0x8000 * std::mem::size_of::<usize>());
+    assert_eq!(h.join().unwrap(), 0);
+
+    // not measured by default
+    let h = go!(|| use_stack(2));
+    h.wait();
+    assert_eq!(h.stack_high_water(), None);
+}
+
+#[test]
+fn coroutine_dump() {
+    use std::io::Read;
+    use coroutine::{Builder, CoroutineState};
+    use may::net::{TcpListener, TcpStream};
+
+    may::config().set_coroutine_registry(true);
+
+    let find = |id: usize| coroutine::dump().into_iter().find(|i| i.id == id);
+
+    // the running coroutine sees itself
+    let (id, info) = go!(Builder::new().name("self".to_owned()), move || {
+        let id = coroutine::current().id();
+        (id, find(id).unwrap())
+    }).unwrap()
+        .join()
+        .unwrap();
+    assert_eq!(info.id, id);
+    assert_eq!(info.name, Some("self".to_owned()));
+    assert_eq!(info.state, CoroutineState::Running);
+    assert!(info.worker.is_some());
+    // removed when done, which may be just after the join
+    let start = Instant::now();
+    while find(id).is_some() && start.elapsed() < Duration::from_secs(1) {
+        thread::yield_now();
+    }
+    assert!(find(id).is_none());
+
+    let parked = go!(|| coroutine::park());
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let reader = go!(move || {
+        let mut s = TcpStream::connect(addr).unwrap();
+        s.read(&mut [0u8; 4]).unwrap()
+    });
+    let (peer, _) = listener.accept().unwrap();
+    thread::sleep(Duration::from_millis(50));
+
+    let info = find(parked.coroutine().id()).unwrap();
+    assert_eq!(info.name, None);
+    assert_eq!(info.state, CoroutineState::Parked);
+    let info = find(reader.coroutine().id()).unwrap();
+    assert_eq!(info.state, CoroutineState::Io);
+
+    parked.coroutine().unpark();
+    parked.join().unwrap();
+    drop(peer);
+    assert_eq!(reader.join().unwrap(), 0);
+
+    may::config().set_coroutine_registry(false);
+    let h = go!(|| coroutine::park());
+    assert!(find(h.coroutine().id()).is_none());
+    h.coroutine().unpark();
+    h.join().unwrap();
+}
+
+#[test]
+fn blocker_park_unpark() {
+    use may::coroutine::ParkError;
+    use may::sync::Blocker;
+
+    let h = go!(|| {
+        let blocker = Blocker::current();
+        // the timeout is a scheduler timer
+        let now = Instant::now();
+        let r = blocker.park(Some(Duration::from_millis(20)));
+        assert_eq!(r, Err(ParkError::Timeout));
+        assert!(now.elapsed() >= Duration::from_millis(20));
+
+        // the unpark before the park is remembered
+        blocker.unpark();
+        blocker.park(Some(Duration::from_secs(10))).unwrap();
+
+        // woken up by a thread
+        let b = blocker.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            b.unpark();
+        });
+        blocker.park(None).unwrap();
+    });
+    h.join().unwrap();
+
+    // parked by a thread and woken up by a coroutine
+    let blocker = Blocker::current();
+    let b = blocker.clone();
+    go!(move || b.unpark());
+    blocker.park(None).unwrap();
+    let r = blocker.park(Some(Duration::from_millis(10)));
+    assert_eq!(r, Err(ParkError::Timeout));
+
+    // the park returns the cancel when it's ignored
+    let h = go!(|| {
+        let blocker = Blocker::new(true);
+        blocker.park(None)
+    });
+    thread::sleep(Duration::from_millis(10));
+    unsafe { h.coroutine().cancel() };
+    assert_eq!(h.join().unwrap(), Err(ParkError::Canceled));
+}
+
+#[cfg(unix)]
+#[test]
+fn select_stream_ready() {
+    use std::io::{Read, Write};
+    use may::net::{TcpListener, TcpStream};
+    use may::sync::mpsc::channel;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (tx, rx) = channel();
+    let h = go!(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut got = Vec::new();
+        loop {
+            let id = select!(
+                _ = stream.wait_read() => {},
+                _ = rx.recv() => {}
+            );
+            if id == 1 {
+                // the canceled wait leaves the stream as it is
+                continue;
+            }
+            let mut buf = [0u8; 16];
+            match stream.read(&mut buf).unwrap() {
+                0 => return got,
+                n => got.extend_from_slice(&buf[0..n]),
+            }
+        }
+    });
+
+    let mut client = TcpStream::connect(addr).unwrap();
+    for i in 0..10u8 {
+        tx.send(()).unwrap();
+        client.write_all(&[i; 4]).unwrap();
+        coroutine::sleep(Duration::from_millis(5));
+    }
+    drop(client);
+
+    let got = h.join().unwrap();
+    let expected = (0..10u8).flat_map(|i| vec![i; 4]).collect::<Vec<_>>();
+    assert_eq!(got, expected);
+}
+
+#[cfg(unix)]
+#[test]
+fn cqueue_stream_ready() {
+    use std::io::{Read, Write};
+    use may::cqueue;
+    use may::net::{TcpListener, TcpStream};
+    use may::sync::mpsc::channel;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (tx, rx) = channel();
+    let h = go!(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut got = Vec::new();
+        let mut msgs = 0;
+        cqueue::scope(|q| {
+            // one select coroutine for all the reads, it finishes on EOF
+            let ready = stream.read_ready_event();
+            cqueue_add!(q, 0, _ = ready.wait().unwrap() => {
+                let mut buf = [0u8; 16];
+                match (&stream).read(&mut buf).unwrap() {
+                    0 => break,
+                    n => got.extend_from_slice(&buf[0..n]),
+                }
+            });
+            cqueue_add!(q, 1, _ = rx.recv().unwrap() => {
+                msgs += 1;
+                if msgs == 10 {
+                    break;
+                }
+            });
+            while q.poll(None).is_ok() {}
+        });
+        (got, msgs)
+    });
+
+    let mut client = TcpStream::connect(addr).unwrap();
+    for i in 0..10u8 {
+        tx.send(()).unwrap();
+        client.write_all(&[i; 4]).unwrap();
+        coroutine::sleep(Duration::from_millis(5));
+    }
+    drop(client);
+
+    let (got, msgs) = h.join().unwrap();
+    let expected = (0..10u8).flat_map(|i| vec![i; 4]).collect::<Vec<_>>();
+    assert_eq!(got, expected);
+    assert_eq!(msgs, 10);
+}
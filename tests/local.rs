@@ -49,3 +49,124 @@ fn coroutine_local_many() {
         assert_eq!(f.load(Ordering::Relaxed), 0);
     });
 }
+
+#[test]
+fn coroutine_local_try_with() {
+    coroutine_local!(static FOO: i32 = 3);
+
+    // not allowed in thread context
+    assert!(FOO.try_with(|f| *f).is_err());
+
+    let v = go!(|| FOO.try_with(|f| *f)).join().unwrap();
+    assert_eq!(v.unwrap(), 3);
+}
+
+#[test]
+fn coroutine_local_drop() {
+    use std::cell::Cell;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+    struct Trace(Cell<usize>);
+    impl Drop for Trace {
+        fn drop(&mut self) {
+            DROPS.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    coroutine_local!(static ID: Trace = Trace(Cell::new(0)));
+
+    let handles = (1..11)
+        .map(|i| {
+            go!(move || {
+                ID.with(|id| id.0.set(i));
+                coroutine::yield_now();
+                // not shared with the other coroutines on the same worker
+                ID.with(|id| assert_eq!(id.0.get(), i));
+            })
+        })
+        .collect::<Vec<_>>();
+    for h in handles {
+        h.join().unwrap();
+    }
+    // dropped when each coroutine is done, which may be just after the join
+    let start = ::std::time::Instant::now();
+    while DROPS.load(Ordering::SeqCst) < 10 && start.elapsed().as_secs() < 1 {
+        ::std::thread::yield_now();
+    }
+    assert_eq!(DROPS.load(Ordering::SeqCst), 10);
+}
+
+#[test]
+fn coroutine_local_migrate() {
+    use std::cell::Cell;
+    use std::time::{Duration, Instant};
+
+    coroutine_local!(static ID: Cell<usize> = Cell::new(0));
+
+    let handles = (1..9)
+        .map(|i| {
+            go!(move || {
+                ID.with(|id| id.set(i));
+                let first = ::std::thread::current().id();
+                let start = Instant::now();
+                let mut moved = false;
+                // run until resumed on another worker
+                while !moved && start.elapsed() < Duration::from_secs(2) {
+                    coroutine::sleep(Duration::from_millis(1));
+                    ID.with(|id| assert_eq!(id.get(), i));
+                    moved = ::std::thread::current().id() != first;
+                }
+                moved
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let moved = handles
+        .into_iter()
+        .map(|h| h.join().unwrap())
+        .filter(|&m| m)
+        .count();
+    if may::config().get_workers() > 1 {
+        assert!(moved > 0);
+    }
+}
+
+#[test]
+fn coroutine_local_cancel_drop() {
+    use std::time::Duration;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+    struct Trace;
+    impl Drop for Trace {
+        fn drop(&mut self) {
+            DROPS.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    coroutine_local! {
+        static A: Trace = Trace;
+        pub static B: Trace = Trace;
+    }
+
+    let h = go!(|| {
+        A.with(|_| ());
+        B.with(|_| ());
+        coroutine::park();
+    });
+    ::std::thread::sleep(Duration::from_millis(20));
+    unsafe { h.coroutine().cancel() };
+    assert!(h.join().is_err());
+
+    // dropped when the cancelled coroutine is done
+    let start = ::std::time::Instant::now();
+    while DROPS.load(Ordering::SeqCst) < 2 && start.elapsed().as_secs() < 1 {
+        ::std::thread::yield_now();
+    }
+    // and never dropped again
+    ::std::thread::sleep(Duration::from_millis(20));
+    assert_eq!(DROPS.load(Ordering::SeqCst), 2);
+}
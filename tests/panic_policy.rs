@@ -0,0 +1,111 @@
+#[macro_use]
+extern crate may;
+
+mod common;
+
+use may::PanicPolicy;
+use may::coroutine::Builder;
+
+#[test]
+fn log_and_continue() {
+    may::config().set_panic_policy(PanicPolicy::LogAndContinue);
+    assert_eq!(may::config().get_panic_policy(), PanicPolicy::LogAndContinue);
+
+    // the panic is still returned by join
+    let h = go!(Builder::new().name("log".to_owned()), || panic!("logged")).unwrap();
+    let e = h.join().unwrap_err();
+    assert_eq!(e.downcast_ref::<&str>(), Some(&"logged"));
+
+    // the cancel is not a panic of the coroutine
+    let h = go!(|| may::coroutine::park());
+    unsafe { h.coroutine().cancel() };
+    assert!(h.join().is_err());
+
+    may::config().set_panic_policy(PanicPolicy::Propagate);
+}
+
+#[test]
+fn abort() {
+    // run the panic in a child process, it must not exit normally
+    let (ok, stderr) = match common::run_in_child("abort", || {
+        may::config().set_panic_policy(PanicPolicy::Abort);
+        go!(Builder::new().name("abort".to_owned()), || panic!("bug")).unwrap();
+        may::coroutine::sleep(::std::time::Duration::from_secs(5));
+    }) {
+        Some(ret) => ret,
+        None => return,
+    };
+    assert!(!ok);
+    assert!(stderr.contains("coroutine 'abort' panicked at 'bug', aborting"));
+}
+
+#[test]
+fn detached_panic() {
+    // capture the stderr of a child process
+    let (ok, stderr) = match common::run_in_child("detached_panic", || {
+        unsafe {
+            Builder::new()
+                .name("detached".to_owned())
+                .spawn_detached(|| panic!("lost"))
+                .unwrap()
+        };
+        may::coroutine::sleep(::std::time::Duration::from_millis(100));
+    }) {
+        Some(ret) => ret,
+        None => return,
+    };
+    // the panic doesn't affect the caller
+    assert!(ok);
+    assert!(stderr.contains("detached coroutine 'detached' (id "));
+    assert!(stderr.contains("lost"));
+}
+
+#[test]
+fn named_panic() {
+    // capture the stderr of a child process
+    let (ok, stderr) = match common::run_in_child("named_panic", || {
+        let h = go!(Builder::new().name("named".to_owned()), || panic!("oops")).unwrap();
+        let id = h.coroutine().id();
+        assert!(h.join().is_err());
+        eprintln!("spawned id {}", id);
+    }) {
+        Some(ret) => ret,
+        None => return,
+    };
+    assert!(ok);
+    let id = stderr
+        .lines()
+        .filter_map(|l| l.split("spawned id ").nth(1))
+        .next()
+        .unwrap();
+    assert!(stderr.contains(&format!("coroutine 'named' (id {}) panicked", id)));
+    // reported once, not once per scheduler thread
+    assert_eq!(stderr.matches("coroutine 'named'").count(), 1, "{}", stderr);
+    assert!(stderr.contains("oops"));
+}
+
+#[test]
+fn abort_ignores_cancel() {
+    // a canceled io that is unwrapped doesn't abort the child
+    let (ok, stderr) = match common::run_in_child("abort_ignores_cancel", || {
+        use std::io::Read;
+        use may::net::{TcpListener, TcpStream};
+
+        may::config().set_panic_policy(PanicPolicy::Abort);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let _client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (mut s, _) = listener.accept().unwrap();
+        let h = go!(move || {
+            let mut buf = [0u8; 8];
+            s.read(&mut buf).unwrap();
+        });
+        may::coroutine::sleep(::std::time::Duration::from_millis(20));
+        unsafe { h.coroutine().cancel() };
+        assert!(may::coroutine::is_cancel_panic(&*h.join().unwrap_err()));
+    }) {
+        Some(ret) => ret,
+        None => return,
+    };
+    assert!(ok, "{}", stderr);
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+}
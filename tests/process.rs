@@ -0,0 +1,97 @@
+#![cfg(unix)]
+#[macro_use]
+extern crate may;
+
+use std::io::{Read, Write};
+use std::time::{Duration, Instant};
+use may::process::{Command, Stdio};
+
+mod common;
+
+#[test]
+fn wait_not_block_worker() {
+    // the worker number is read when the scheduler starts
+    let (ok, stderr) = match common::run_in_child("wait_not_block_worker", || {
+        may::config().set_workers(1);
+        wait_children();
+    }) {
+        Some(ret) => ret,
+        None => return,
+    };
+    assert!(ok, "{}", stderr);
+}
+
+fn wait_children() {
+
+    let start = Instant::now();
+    let handles = (0..100)
+        .map(|_| {
+            go!(|| {
+                let mut child = Command::new("sleep").arg("0.2").spawn().unwrap();
+                assert!(child.wait().unwrap().success());
+            })
+        })
+        .collect::<Vec<_>>();
+    for h in handles {
+        h.join().unwrap();
+    }
+
+    // all the children are waited concurrently on the only worker
+    let elapsed = start.elapsed();
+    assert!(elapsed < Duration::from_millis(500), "elapsed = {:?}", elapsed);
+}
+
+#[test]
+fn stdio_pipes() {
+    let h = go!(|| {
+        let mut child = Command::new("cat")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        let mut stdin = child.stdin.take().unwrap();
+        let writer = go!(move || {
+            stdin.write_all(b"hello world").unwrap();
+        });
+
+        let mut s = String::new();
+        child.stdout.take().unwrap().read_to_string(&mut s).unwrap();
+        writer.join().unwrap();
+        assert_eq!(s, "hello world");
+        assert!(child.wait().unwrap().success());
+    });
+    h.join().unwrap();
+}
+
+#[test]
+fn output() {
+    let h = go!(|| {
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg("echo out; echo err >&2; exit 3")
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(3));
+        assert_eq!(output.stdout, b"out\n");
+        assert_eq!(output.stderr, b"err\n");
+    });
+    h.join().unwrap();
+
+    // also works in thread context
+    let output = Command::new("echo").arg("hello").output().unwrap();
+    assert_eq!(output.stdout, b"hello\n");
+}
+
+#[test]
+fn kill_and_try_wait() {
+    let h = go!(|| {
+        let mut child = Command::new("sleep").arg("10").spawn().unwrap();
+        assert!(child.try_wait().unwrap().is_none());
+        child.kill().unwrap();
+        let status = child.wait().unwrap();
+        assert!(!status.success());
+        assert_eq!(child.try_wait().unwrap(), Some(status));
+    });
+    h.join().unwrap();
+}
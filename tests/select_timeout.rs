@@ -0,0 +1,76 @@
+#[macro_use]
+extern crate may;
+
+use std::thread;
+use std::time::{Duration, Instant};
+use may::coroutine;
+use may::scheduler::metrics;
+use may::sync::mpsc::channel;
+
+// poll the condition for a while, the counters are updated a bit after
+// the coroutines and the timers are done
+fn eventually<F: Fn() -> bool>(f: F) -> bool {
+    let deadline = Instant::now() + Duration::from_secs(1);
+    while !f() {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    true
+}
+
+// the pending timers are counted globally, so it's the only test here
+#[test]
+fn select_timeout() {
+    let timers = metrics().timers;
+
+    // run in a coroutine, the thread context doesn't use the timer list
+    go!(|| {
+        let (tx1, rx1) = channel::<usize>();
+        let (_tx2, rx2) = channel::<usize>();
+
+        // nothing is ever ready, the timeout arm is picked
+        for _ in 0..10 {
+            let now = Instant::now();
+            let id = select!(
+                _ = rx1.recv() => unreachable!("rx1 is never ready"),
+                _ = rx2.recv() => unreachable!("rx2 is never ready"),
+                timeout(Duration::from_millis(50)) => {},
+            );
+            let dur = now.elapsed();
+            assert_eq!(id, 2);
+            assert!(dur >= Duration::from_millis(50), "{:?}", dur);
+            assert!(dur < Duration::from_millis(500), "{:?}", dur);
+        }
+
+        // the events win the race, the timers must not be left behind
+        let n = 10_000;
+        let sender = go!(move || for i in 0..n {
+            tx1.send(i).unwrap();
+            coroutine::yield_now();
+        });
+        let mut sum = 0;
+        for _ in 0..n {
+            let id = select!(
+                a = rx1.recv() => sum += a.unwrap(),
+                _ = rx2.recv() => unreachable!("rx2 is never ready"),
+                timeout(Duration::from_secs(10)) => unreachable!("no timeout")
+            );
+            assert_eq!(id, 0);
+        }
+        sender.join().unwrap();
+        assert_eq!(sum, n * (n - 1) / 2);
+    }).join()
+        .unwrap();
+
+    // a timer that is alone in its list is left to fire, there is one such
+    // list for each slot distance of the 10s timeouts at most
+    assert!(
+        eventually(|| metrics().timers <= timers + 2),
+        "timers: {} -> {}",
+        timers,
+        metrics().timers
+    );
+    assert!(eventually(|| metrics().live_coroutines == 0));
+}
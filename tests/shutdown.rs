@@ -0,0 +1,66 @@
+#[macro_use]
+extern crate may;
+
+use std::io;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use may::coroutine;
+use may::net::TcpListener;
+use may::scheduler::shutdown_with_grace;
+
+#[cfg(target_os = "linux")]
+fn threads() -> usize {
+    std::fs::read_dir("/proc/self/task").unwrap().count()
+}
+
+// the shutdown is for the whole process, so it's the only test here
+#[test]
+fn shutdown_releases_everything() {
+    #[cfg(target_os = "linux")]
+    let before = threads();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    go!(move || loop {
+        // returns when the coroutine is canceled
+        listener.accept().unwrap();
+    });
+    for _ in 0..10 {
+        go!(|| coroutine::park());
+    }
+    // a request in flight is finished in the grace
+    let done = Arc::new(AtomicBool::new(false));
+    let d = done.clone();
+    let request = go!(move || {
+        coroutine::sleep(Duration::from_millis(100));
+        assert!(may::scheduler::is_shutting_down());
+        // no new work in the grace
+        assert!(go!(coroutine::Builder::new(), || {}).is_err());
+        d.store(true, Ordering::Release);
+    });
+    coroutine::sleep(Duration::from_millis(10));
+    assert!(!may::scheduler::is_shutting_down());
+
+    // can't be called from a coroutine
+    let h = go!(|| may::shutdown(Duration::from_secs(1)));
+    let e = h.join().unwrap().unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::Other);
+
+    let start = Instant::now();
+    shutdown_with_grace(Duration::from_millis(500), Duration::from_secs(5)).unwrap();
+    // the parked ones are canceled only after the grace
+    assert!(start.elapsed() >= Duration::from_millis(500));
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert!(done.load(Ordering::Acquire));
+    request.join().unwrap();
+    // the second call is a no-op
+    may::shutdown(Duration::from_secs(5)).unwrap();
+
+    // the listener is dropped with the accepting coroutine
+    std::net::TcpListener::bind(addr).unwrap();
+    // no more spawns
+    assert!(go!(coroutine::Builder::new(), || {}).is_err());
+    #[cfg(target_os = "linux")]
+    assert_eq!(threads(), before);
+}
@@ -0,0 +1,19 @@
+#![cfg(unix)]
+#[macro_use]
+extern crate may;
+
+use std::time::{Duration, Instant};
+use may::os::unix::{signal, SignalKind};
+use may::scheduler::shutdown_with_grace;
+
+// the shutdown is for the whole process, so it's the only test here
+#[test]
+fn signal_driver_is_not_waited() {
+    let _sig = signal(SignalKind::user_defined1()).unwrap();
+    go!(|| {}).join().unwrap();
+
+    let start = Instant::now();
+    shutdown_with_grace(Duration::from_secs(2), Duration::from_secs(5)).unwrap();
+    // there is no coroutine left, the grace is not waited out
+    assert!(start.elapsed() < Duration::from_secs(1));
+}
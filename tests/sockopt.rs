@@ -0,0 +1,114 @@
+#[macro_use]
+extern crate may;
+
+use std::time::Duration;
+use may::net::{TcpKeepalive, TcpListener, TcpStream, UdpSocket};
+
+// set and read back the buffer sizes, the system may round them up
+macro_rules! check_buffer_size {
+    ($s:expr) => {{
+        $s.set_recv_buffer_size(64 * 1024).unwrap();
+        assert!($s.recv_buffer_size().unwrap() >= 64 * 1024);
+        $s.set_send_buffer_size(64 * 1024).unwrap();
+        assert!($s.send_buffer_size().unwrap() >= 64 * 1024);
+    }};
+}
+
+#[test]
+fn tcp_stream_options() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let j = go!(move || {
+        let s = TcpStream::connect(addr).unwrap();
+
+        s.set_nodelay(true).unwrap();
+        assert_eq!(s.nodelay().unwrap(), true);
+
+        s.set_ttl(42).unwrap();
+        assert_eq!(s.ttl().unwrap(), 42);
+
+        s.set_linger(Some(Duration::from_secs(3))).unwrap();
+        assert_eq!(s.linger().unwrap(), Some(Duration::from_secs(3)));
+        s.set_linger(None).unwrap();
+        assert_eq!(s.linger().unwrap(), None);
+
+        check_buffer_size!(s);
+
+        s.set_keepalive(None).unwrap();
+        assert_eq!(s.keepalive().unwrap(), None);
+        let keepalive = TcpKeepalive::new().with_time(Duration::from_secs(30));
+        s.set_keepalive(Some(&keepalive)).unwrap();
+        let got = s.keepalive().unwrap().unwrap();
+        assert_eq!(got.time(), Some(Duration::from_secs(30)));
+
+        let keepalive = keepalive
+            .with_interval(Duration::from_secs(5))
+            .with_retries(3);
+        match s.set_keepalive(Some(&keepalive)) {
+            Ok(()) => {
+                let got = s.keepalive().unwrap().unwrap();
+                assert_eq!(got.interval(), Some(Duration::from_secs(5)));
+                assert_eq!(got.retries(), Some(3));
+            }
+            // not supported on the platform
+            Err(e) => assert_eq!(e.kind(), std::io::ErrorKind::Other),
+        }
+    });
+
+    let _s = listener.accept().unwrap();
+    j.join().unwrap();
+}
+
+#[test]
+fn tcp_listener_options() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.set_ttl(42).unwrap();
+    assert_eq!(listener.ttl().unwrap(), 42);
+    check_buffer_size!(listener);
+}
+
+#[test]
+fn udp_options() {
+    let s = UdpSocket::bind("127.0.0.1:0").unwrap();
+    s.set_ttl(42).unwrap();
+    assert_eq!(s.ttl().unwrap(), 42);
+    check_buffer_size!(s);
+}
+
+#[cfg(unix)]
+#[test]
+fn unix_options() {
+    use may::os::unix::net::{UnixDatagram, UnixListener, UnixStream};
+
+    let (s1, s2) = UnixStream::pair().unwrap();
+    check_buffer_size!(s1);
+    check_buffer_size!(s2);
+
+    let (d1, _d2) = UnixDatagram::pair().unwrap();
+    check_buffer_size!(d1);
+
+    let path = std::env::temp_dir().join(format!("may_sockopt_{}", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).unwrap();
+    check_buffer_size!(listener);
+    drop(listener);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[test]
+fn unix_seqpacket_options() {
+    use may::os::unix::net::{UnixSeqpacket, UnixSeqpacketListener};
+
+    let (s1, s2) = UnixSeqpacket::pair().unwrap();
+    check_buffer_size!(s1);
+    check_buffer_size!(s2);
+
+    let path = std::env::temp_dir().join(format!("may_sockopt_seq_{}", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixSeqpacketListener::bind(&path).unwrap();
+    check_buffer_size!(listener);
+    drop(listener);
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,38 @@
+#![cfg(unix)]
+#[macro_use]
+extern crate may;
+
+mod common;
+
+use may::coroutine::Builder;
+
+// use some stack in each frame
+fn recurse(n: usize) -> usize {
+    let buf = [n as u8; 256];
+    if n == ::std::usize::MAX {
+        return 0;
+    }
+    recurse(n + 1) + buf[n % 256] as usize
+}
+
+#[test]
+fn stack_overflow_reported() {
+    // overflow in a child process, it must die with the report
+    let (ok, stderr) = match common::run_in_child("stack_overflow_reported", || {
+        may::config().set_stack_protection(true);
+        let h = go!(Builder::new().name("deep".to_owned()).stack_size(0x2000), || {
+            recurse(0)
+        }).unwrap();
+        h.join().ok();
+    }) {
+        Some(ret) => ret,
+        None => return,
+    };
+    assert!(!ok);
+    let size = 0x2000 * ::std::mem::size_of::<usize>();
+    assert!(stderr.contains("coroutine 'deep' (id "), "{}", stderr);
+    assert!(stderr.contains(&format!(
+        ") has overflowed its stack, stack size = {} bytes",
+        size
+    )));
+}
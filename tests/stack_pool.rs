@@ -0,0 +1,42 @@
+#[macro_use]
+extern crate may;
+
+use may::coroutine;
+use may::scheduler::metrics;
+
+// spawn the parked coroutines and return the hits and misses of the pool
+fn spawn_parked(n: usize) -> (usize, usize) {
+    let before = metrics();
+    let hs = (0..n).map(|_| go!(|| coroutine::park())).collect::<Vec<_>>();
+    let after = metrics();
+    for h in hs {
+        h.coroutine().unpark();
+        h.join().unwrap();
+    }
+    // wait for the stacks to be back in the pool
+    while metrics().live_coroutines != 0 {
+        coroutine::yield_now();
+    }
+    (
+        after.stack_pool_hits - before.stack_pool_hits,
+        after.stack_pool_misses - before.stack_pool_misses,
+    )
+}
+
+// the pool is created with the scheduler, so it's the only test here
+#[test]
+fn bounded_prealloc_pool() {
+    let config = may::config();
+    config.set_pool_capacity(50).set_prealloc_stacks(10);
+    assert_eq!(config.get_prealloc_stacks(), 10);
+
+    // only the preallocated ones are there at first
+    assert_eq!(spawn_parked(11), (10, 1));
+    // the pool keeps at most the capacity
+    assert_eq!(spawn_parked(60), (11, 49));
+    assert_eq!(spawn_parked(60), (50, 10));
+
+    // capped by the capacity
+    config.set_prealloc_stacks(100);
+    assert_eq!(config.get_prealloc_stacks(), 50);
+}
@@ -0,0 +1,58 @@
+#![cfg(target_os = "linux")]
+#[macro_use]
+extern crate may;
+
+use std::fs::File;
+use std::io::Read;
+use std::ptr;
+use std::time::Duration;
+use may::coroutine;
+
+// the resident pages of the process
+fn rss_pages() -> usize {
+    let mut statm = String::new();
+    File::open("/proc/self/statm")
+        .unwrap()
+        .read_to_string(&mut statm)
+        .unwrap();
+    statm.split_whitespace().nth(1).unwrap().parse().unwrap()
+}
+
+// the config is read when the scheduler starts, so it's the only test here
+#[test]
+fn release_idle_stacks() {
+    // 512k stacks
+    may::config()
+        .set_stack_size(0x10000)
+        .set_stack_idle_release(Duration::from_millis(50));
+
+    // let all the coroutines touch their stacks at the same time
+    let hs = (0..64)
+        .map(|_| {
+            go!(|| {
+                let mut buf = [0u8; 256 * 1024];
+                for i in 0..buf.len() / 4096 {
+                    unsafe { ptr::write_volatile(&mut buf[i * 4096], 1) };
+                }
+                coroutine::park();
+            })
+        })
+        .collect::<Vec<_>>();
+    coroutine::sleep(Duration::from_millis(100));
+    for h in hs {
+        h.coroutine().unpark();
+        h.join().unwrap();
+    }
+
+    // the stacks are in the pool now
+    let busy = rss_pages();
+    coroutine::sleep(Duration::from_millis(300));
+    let idle = rss_pages();
+    // the 16M touched is mostly released
+    assert!(busy - idle > 3000, "busy={} idle={} pages", busy, idle);
+
+    // the pooled coroutines are still reused
+    let before = may::scheduler::metrics().stack_pool_hits;
+    go!(|| {}).join().unwrap();
+    assert_eq!(may::scheduler::metrics().stack_pool_hits, before + 1);
+}
@@ -0,0 +1,49 @@
+#[macro_use]
+extern crate may;
+
+use may::coroutine;
+
+// use some stack in each frame
+fn recurse(n: usize) -> usize {
+    let buf = [n as u8; 256];
+    if n == 0 {
+        return 0;
+    }
+    recurse(n - 1) + buf[n % 256] as usize
+}
+
+#[test]
+fn track_all_stacks() {
+    // the config is global, so this runs in its own test binary
+    may::config().set_track_stack(true);
+    assert!(may::config().get_track_stack());
+
+    let trivial = go!(|| 0);
+    let deep = go!(|| recurse(20));
+    trivial.wait();
+    deep.wait();
+    let small = trivial.stack_high_water().unwrap();
+    let large = deep.stack_high_water().unwrap();
+    assert!(large > small + 20 * 256, "{} {}", large, small);
+    trivial.join().unwrap();
+    deep.join().unwrap();
+
+    let stats = coroutine::stack_stats();
+    assert_eq!(stats.count, 2);
+    assert_eq!(stats.buckets.iter().map(|&(_, n)| n).sum::<usize>(), 2);
+    assert!(stats.buckets.windows(2).all(|w| w[0].0 < w[1].0));
+    // each coroutine falls in the bucket that covers its usage
+    for used in &[small, large] {
+        assert!(stats
+            .buckets
+            .iter()
+            .any(|&(limit, _)| limit >= *used && limit / 2 < *used));
+    }
+
+    // not measured any more
+    may::config().set_track_stack(false);
+    let h = go!(|| 0);
+    h.wait();
+    assert_eq!(h.stack_high_water(), None);
+    assert_eq!(coroutine::stack_stats().count, 2);
+}